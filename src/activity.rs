@@ -3,6 +3,23 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::watch;
 
+/// Source of the current time for [`ActivityTracker`].
+///
+/// Production code uses [`SystemClock`]; tests can supply a clock they
+/// control so idle detection doesn't depend on real elapsed time.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// [`Clock`] backed by [`Instant::now`].
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
 /// Tracks the timestamp of the last terminal activity (PTY output or input).
 ///
 /// Clients can wait for the terminal to become idle — a period of inactivity
@@ -16,6 +33,7 @@ use tokio::sync::watch;
 pub struct ActivityTracker {
     tx: Arc<watch::Sender<Instant>>,
     generation: Arc<AtomicU64>,
+    clock: Arc<dyn Clock>,
 }
 
 impl Default for ActivityTracker {
@@ -27,17 +45,27 @@ impl Default for ActivityTracker {
 impl ActivityTracker {
     /// Create a new tracker seeded with the current instant.
     pub fn new() -> Self {
-        let (tx, _) = watch::channel(Instant::now());
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    /// Create a tracker that reads the time from `clock`.
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        let (tx, _) = watch::channel(clock.now());
         Self {
             tx: Arc::new(tx),
             generation: Arc::new(AtomicU64::new(0)),
+            clock,
         }
     }
 
+    fn elapsed_since(&self, instant: Instant) -> Duration {
+        self.clock.now().saturating_duration_since(instant)
+    }
+
     /// Record activity. Safe to call from blocking threads.
     pub fn touch(&self) {
         self.generation.fetch_add(1, Ordering::Release);
-        self.tx.send_replace(Instant::now());
+        self.tx.send_replace(self.clock.now());
     }
 
     /// Current generation counter value.
@@ -53,7 +81,12 @@ impl ActivityTracker {
 
     /// Return how many milliseconds have elapsed since the last activity.
     pub fn last_activity_ms(&self) -> u64 {
-        self.tx.borrow().elapsed().as_millis() as u64
+        self.quiet_for().as_millis() as u64
+    }
+
+    /// How long it's been since the last activity.
+    pub fn quiet_for(&self) -> Duration {
+        self.elapsed_since(*self.tx.borrow())
     }
 
     /// Wait until `timeout` has elapsed since the last activity.
@@ -82,7 +115,7 @@ impl ActivityTracker {
 
         loop {
             let last = *rx.borrow_and_update();
-            let elapsed = last.elapsed();
+            let elapsed = self.elapsed_since(last);
             if elapsed >= timeout {
                 return self.generation.load(Ordering::Acquire);
             }
@@ -92,7 +125,7 @@ impl ActivityTracker {
                     // Double-check: a touch may have arrived in the tiny window
                     // between sleep completing and us running.
                     let last = *rx.borrow_and_update();
-                    if last.elapsed() >= timeout {
+                    if self.elapsed_since(last) >= timeout {
                         return self.generation.load(Ordering::Acquire);
                     }
                     // Not yet idle — loop again with fresh remaining.
@@ -120,7 +153,7 @@ impl ActivityTracker {
         let mut rx = self.tx.subscribe();
         loop {
             let last = *rx.borrow_and_update();
-            let elapsed = last.elapsed();
+            let elapsed = self.elapsed_since(last);
             // Even if already idle, wait at least `timeout` to confirm.
             let remaining = if elapsed >= timeout {
                 timeout
@@ -130,7 +163,7 @@ impl ActivityTracker {
             tokio::select! {
                 _ = tokio::time::sleep(remaining) => {
                     let last = *rx.borrow_and_update();
-                    if last.elapsed() >= timeout {
+                    if self.elapsed_since(last) >= timeout {
                         return self.generation.load(Ordering::Acquire);
                     }
                     // Activity arrived during sleep — loop again.
@@ -151,16 +184,36 @@ mod tests {
     use super::*;
     use std::time::Duration;
 
-    #[tokio::test]
+    /// Clock that follows tokio's time, which these tests pause: sleeps
+    /// complete instantly and advance the clock by exactly their duration.
+    struct TokioClock;
+
+    impl Clock for TokioClock {
+        fn now(&self) -> Instant {
+            tokio::time::Instant::now().into_std()
+        }
+    }
+
+    fn tracker() -> ActivityTracker {
+        ActivityTracker::with_clock(Arc::new(TokioClock))
+    }
+
+    fn now() -> tokio::time::Instant {
+        tokio::time::Instant::now()
+    }
+
+    async fn sleep_ms(ms: u64) {
+        tokio::time::sleep(Duration::from_millis(ms)).await;
+    }
+
+    #[tokio::test(start_paused = true)]
     async fn touch_updates_state() {
-        let tracker = ActivityTracker::new();
-        let before = Instant::now();
-        tokio::time::sleep(Duration::from_millis(10)).await;
+        let tracker = tracker();
+        let before = tracker.clock.now();
+        sleep_ms(10).await;
         tracker.touch();
-        // The last activity should be after `before`.
-        let mut rx = tracker.tx.subscribe();
-        let last = *rx.borrow_and_update();
-        assert!(last > before);
+        let last = *tracker.subscribe().borrow();
+        assert_eq!(last - before, Duration::from_millis(10));
     }
 
     #[tokio::test]
@@ -173,19 +226,18 @@ mod tests {
         assert_eq!(tracker.generation(), 2);
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn idle_fires_after_timeout() {
-        let tracker = ActivityTracker::new();
+        let tracker = tracker();
         tracker.touch();
-        let start = Instant::now();
+        let start = now();
         tracker.wait_for_idle(Duration::from_millis(50), None).await;
-        let elapsed = start.elapsed();
-        assert!(elapsed >= Duration::from_millis(50));
+        assert!(start.elapsed() >= Duration::from_millis(50));
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn idle_returns_generation() {
-        let tracker = ActivityTracker::new();
+        let tracker = tracker();
         tracker.touch();
         tracker.touch();
         tracker.touch();
@@ -193,137 +245,108 @@ mod tests {
         assert_eq!(gen, 3);
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn activity_resets_timer() {
-        let tracker = ActivityTracker::new();
+        let tracker = tracker();
         tracker.touch(); // gen=1
 
         let t = tracker.clone();
-        // Spawn a task that touches after 20ms, resetting the timer.
-        // The large gap between touch delay (20ms) and timeout (150ms)
-        // ensures the touch fires well before the timeout under any
-        // realistic scheduler load.
         tokio::spawn(async move {
-            tokio::time::sleep(Duration::from_millis(20)).await;
+            sleep_ms(20).await;
             t.touch(); // gen=2
         });
 
-        let start = Instant::now();
+        let start = now();
         let gen = tracker.wait_for_idle(Duration::from_millis(150), None).await;
-        let elapsed = start.elapsed();
-        // The touch at ~20ms resets the timer; total >= 20ms + 150ms = 170ms.
+        // The touch at 20ms resets the timer: idle at 20ms + 150ms.
         assert_eq!(gen, 2, "second touch should have been observed");
-        assert!(
-            elapsed >= Duration::from_millis(150),
-            "Expected >= 150ms (timer should have reset on activity), got {:?}",
-            elapsed
-        );
+        assert!(start.elapsed() >= Duration::from_millis(170));
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn already_idle_returns_immediately() {
-        let tracker = ActivityTracker::new();
+        let tracker = tracker();
         // Don't touch — the seed instant was set at construction time.
-        // Wait long enough that the seed is stale.
-        tokio::time::sleep(Duration::from_millis(60)).await;
+        sleep_ms(60).await;
 
-        let start = Instant::now();
+        let start = now();
         tracker.wait_for_idle(Duration::from_millis(50), None).await;
-        let elapsed = start.elapsed();
-        // Should return almost immediately.
-        assert!(elapsed < Duration::from_millis(10));
+        assert_eq!(start.elapsed(), Duration::ZERO);
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn last_seen_prevents_immediate_return() {
-        let tracker = ActivityTracker::new();
+        let tracker = tracker();
         tracker.touch(); // generation = 1
-        // Wait for idle
-        tokio::time::sleep(Duration::from_millis(60)).await;
+        sleep_ms(60).await;
 
         // Without last_seen: returns immediately
-        let start = Instant::now();
+        let start = now();
         let gen = tracker.wait_for_idle(Duration::from_millis(50), None).await;
-        assert!(start.elapsed() < Duration::from_millis(10));
+        assert_eq!(start.elapsed(), Duration::ZERO);
         assert_eq!(gen, 1);
 
         // With last_seen matching current generation: blocks until new activity
         let t = tracker.clone();
         tokio::spawn(async move {
-            tokio::time::sleep(Duration::from_millis(50)).await;
+            sleep_ms(50).await;
             t.touch(); // generation = 2
         });
 
-        let start = Instant::now();
+        let start = now();
         let gen = tracker.wait_for_idle(Duration::from_millis(30), Some(1)).await;
-        let elapsed = start.elapsed();
-        // Should wait ~50ms for new activity + ~30ms for idle
-        assert!(elapsed >= Duration::from_millis(70));
+        // 50ms for new activity + 30ms for idle
+        assert!(start.elapsed() >= Duration::from_millis(80));
         assert_eq!(gen, 2);
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn last_seen_stale_returns_normally() {
-        let tracker = ActivityTracker::new();
+        let tracker = tracker();
         tracker.touch(); // generation = 1
         tracker.touch(); // generation = 2
-
-        // Wait for idle
-        tokio::time::sleep(Duration::from_millis(60)).await;
+        sleep_ms(60).await;
 
         // last_seen=1 but current generation=2: doesn't block on new activity
-        let start = Instant::now();
+        let start = now();
         let gen = tracker.wait_for_idle(Duration::from_millis(50), Some(1)).await;
-        assert!(start.elapsed() < Duration::from_millis(10));
+        assert_eq!(start.elapsed(), Duration::ZERO);
         assert_eq!(gen, 2);
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn fresh_idle_always_waits() {
-        let tracker = ActivityTracker::new();
-        // Wait for well past the timeout so terminal is "already idle"
-        tokio::time::sleep(Duration::from_millis(120)).await;
+        let tracker = tracker();
+        // Well past the timeout, so the terminal is "already idle"
+        sleep_ms(120).await;
 
-        let start = Instant::now();
+        let start = now();
         tracker.wait_for_fresh_idle(Duration::from_millis(50)).await;
-        let elapsed = start.elapsed();
-        // Should wait at least 50ms even though already idle
-        assert!(
-            elapsed >= Duration::from_millis(45),
-            "Expected >= 45ms, got {:?}",
-            elapsed
-        );
+        // Waits the full timeout even though already idle
+        assert!(start.elapsed() >= Duration::from_millis(50));
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn fresh_idle_resets_on_activity() {
-        let tracker = ActivityTracker::new();
-        tokio::time::sleep(Duration::from_millis(200)).await;
+        let tracker = tracker();
+        sleep_ms(200).await;
 
         let t = tracker.clone();
-        // Touch after 20ms. The large gap between touch delay (20ms) and
-        // timeout (150ms) ensures the touch fires well before the initial
-        // sleep expires under any realistic scheduler load.
         tokio::spawn(async move {
-            tokio::time::sleep(Duration::from_millis(20)).await;
+            sleep_ms(20).await;
             t.touch(); // gen=1
         });
 
-        let start = Instant::now();
+        let start = now();
         let gen = tracker.wait_for_fresh_idle(Duration::from_millis(150)).await;
-        let elapsed = start.elapsed();
-        // The touch at ~20ms resets the timer; total >= 20ms + 150ms = 170ms.
+        // The touch at 20ms resets the timer: idle at 20ms + 150ms.
         assert_eq!(gen, 1, "touch should have been observed");
-        assert!(
-            elapsed >= Duration::from_millis(150),
-            "Expected >= 150ms (timer should have reset on activity), got {:?}",
-            elapsed
-        );
+        assert!(start.elapsed() >= Duration::from_millis(170));
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn multiple_concurrent_waiters() {
-        let tracker = ActivityTracker::new();
+        let tracker = tracker();
         tracker.touch();
 
         let t1 = tracker.clone();
@@ -331,12 +354,12 @@ mod tests {
 
         let (r1, r2) = tokio::join!(
             async move {
-                let start = Instant::now();
+                let start = now();
                 t1.wait_for_idle(Duration::from_millis(50), None).await;
                 start.elapsed()
             },
             async move {
-                let start = Instant::now();
+                let start = now();
                 t2.wait_for_idle(Duration::from_millis(50), None).await;
                 start.elapsed()
             },
@@ -346,19 +369,19 @@ mod tests {
         assert!(r2 >= Duration::from_millis(50));
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn last_activity_ms_zero_at_start() {
-        let tracker = ActivityTracker::new();
-        tokio::time::sleep(Duration::from_millis(10)).await;
+        let tracker = tracker();
+        sleep_ms(10).await;
         tracker.touch();
-        assert!(tracker.last_activity_ms() < 10);
+        assert_eq!(tracker.last_activity_ms(), 0);
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn last_activity_ms_grows_without_touch() {
-        let tracker = ActivityTracker::new();
+        let tracker = tracker();
         tracker.touch();
-        tokio::time::sleep(Duration::from_millis(60)).await;
-        assert!(tracker.last_activity_ms() >= 50);
+        sleep_ms(60).await;
+        assert_eq!(tracker.last_activity_ms(), 60);
     }
 }
@@ -41,10 +41,20 @@ pub enum ApiError {
     MaxSessionsReached,
     /// 500 - Failed to write input to the PTY.
     InputSendFailed,
+    /// 503 - The PTY stopped accepting input and the input queue stayed full.
+    InputTimeout,
     /// 408 - Idle wait exceeded max_wait_ms deadline.
     IdleTimeout,
+    /// 408 - Expected output pattern was not seen before the timeout.
+    ExpectTimeout,
+    /// 408 - The prompt regex did not match a quiet screen before the timeout.
+    PromptTimeout,
+    /// 408 - The session produced no output (or no prompt) before the timeout.
+    ReadyTimeout,
     /// 500 - Failed to create a session (PTY spawn error, etc.).
     SessionCreateFailed(String),
+    /// 503 - No PTY could be opened: out of file descriptors or PTYs.
+    PtyExhausted(String),
     /// 409 - Session name already exists.
     SessionNameConflict(String),
     /// 404 - No sessions exist in the registry.
@@ -55,6 +65,8 @@ pub enum ApiError {
     AlreadyInAltScreen,
     /// 409 - Session is not in alternate screen mode.
     NotInAltScreen,
+    /// 409 - No command boundaries are known (shell doesn't emit OSC 133).
+    NoCommandHistory,
     /// 400 - Invalid tag value.
     InvalidTag(String),
     /// 400 - Invalid session name.
@@ -63,6 +75,8 @@ pub enum ApiError {
     ResourceLimitReached(String),
     /// 403 - WebSocket origin not allowed (CSWSH protection).
     OriginNotAllowed,
+    /// 403 - Output injection is disabled (server runs without `--allow-feed`).
+    FeedDisabled,
     /// 500 - Catch-all internal error.
     InternalError(String),
 }
@@ -86,17 +100,24 @@ impl ApiError {
             ApiError::ParserTimeout => StatusCode::GATEWAY_TIMEOUT,
             ApiError::MaxSessionsReached => StatusCode::SERVICE_UNAVAILABLE,
             ApiError::InputSendFailed => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::InputTimeout => StatusCode::SERVICE_UNAVAILABLE,
             ApiError::IdleTimeout => StatusCode::REQUEST_TIMEOUT,
+            ApiError::ExpectTimeout => StatusCode::REQUEST_TIMEOUT,
+            ApiError::PromptTimeout => StatusCode::REQUEST_TIMEOUT,
+            ApiError::ReadyTimeout => StatusCode::REQUEST_TIMEOUT,
             ApiError::SessionCreateFailed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::PtyExhausted(_) => StatusCode::SERVICE_UNAVAILABLE,
             ApiError::SessionNameConflict(_) => StatusCode::CONFLICT,
             ApiError::NoSessions => StatusCode::NOT_FOUND,
             ApiError::NotFocusable(_) => StatusCode::BAD_REQUEST,
             ApiError::AlreadyInAltScreen => StatusCode::CONFLICT,
             ApiError::NotInAltScreen => StatusCode::CONFLICT,
+            ApiError::NoCommandHistory => StatusCode::CONFLICT,
             ApiError::InvalidTag(_) => StatusCode::BAD_REQUEST,
             ApiError::InvalidSessionName(_) => StatusCode::BAD_REQUEST,
             ApiError::ResourceLimitReached(_) => StatusCode::TOO_MANY_REQUESTS,
             ApiError::OriginNotAllowed => StatusCode::FORBIDDEN,
+            ApiError::FeedDisabled => StatusCode::FORBIDDEN,
             ApiError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -119,17 +140,24 @@ impl ApiError {
             ApiError::ParserTimeout => "parser_timeout",
             ApiError::MaxSessionsReached => "max_sessions_reached",
             ApiError::InputSendFailed => "input_send_failed",
+            ApiError::InputTimeout => "input_timeout",
             ApiError::IdleTimeout => "idle_timeout",
+            ApiError::ExpectTimeout => "expect_timeout",
+            ApiError::PromptTimeout => "prompt_timeout",
+            ApiError::ReadyTimeout => "ready_timeout",
             ApiError::SessionCreateFailed(_) => "session_create_failed",
+            ApiError::PtyExhausted(_) => "pty_exhausted",
             ApiError::SessionNameConflict(_) => "session_name_conflict",
             ApiError::NoSessions => "no_sessions",
             ApiError::NotFocusable(_) => "not_focusable",
             ApiError::AlreadyInAltScreen => "already_in_alt_screen",
             ApiError::NotInAltScreen => "not_in_alt_screen",
+            ApiError::NoCommandHistory => "no_command_history",
             ApiError::InvalidTag(_) => "invalid_tag",
             ApiError::InvalidSessionName(_) => "invalid_session_name",
             ApiError::ResourceLimitReached(_) => "resource_limit_reached",
             ApiError::OriginNotAllowed => "origin_not_allowed",
+            ApiError::FeedDisabled => "feed_disabled",
             ApiError::InternalError(_) => "internal_error",
         }
     }
@@ -156,12 +184,25 @@ impl ApiError {
                 "Maximum number of sessions reached.".to_string()
             }
             ApiError::InputSendFailed => "Failed to send input to terminal.".to_string(),
+            ApiError::InputTimeout => {
+                "Terminal is not accepting input; the input queue is full.".to_string()
+            }
             ApiError::IdleTimeout => {
                 "Terminal did not become idle within the deadline.".to_string()
             }
+            ApiError::ExpectTimeout => {
+                "Pattern did not appear in output within the timeout.".to_string()
+            }
+            ApiError::PromptTimeout => {
+                "Prompt did not appear on a quiet screen within the timeout.".to_string()
+            }
+            ApiError::ReadyTimeout => "Session was not ready within the timeout.".to_string(),
             ApiError::SessionCreateFailed(detail) => {
                 format!("Failed to create session: {}.", detail)
             }
+            ApiError::PtyExhausted(detail) => {
+                format!("Cannot open a PTY, the server is out of file descriptors or PTYs ({}). Raise the open file limit (ulimit -n) or wait for sessions to exit.", detail)
+            }
             ApiError::SessionNameConflict(name) => {
                 format!("Session name already exists: {}.", name)
             }
@@ -175,17 +216,36 @@ impl ApiError {
             ApiError::NotInAltScreen => {
                 "Session is not in alternate screen mode.".to_string()
             }
+            ApiError::NoCommandHistory => {
+                "No command boundaries known. Enable shell integration (OSC 133 prompt marks) in the session's shell.".to_string()
+            }
             ApiError::InvalidTag(detail) => format!("Invalid tag: {}.", detail),
             ApiError::InvalidSessionName(detail) => format!("Invalid session name: {}.", detail),
             ApiError::ResourceLimitReached(detail) => {
                 format!("Resource limit reached: {}.", detail)
             }
             ApiError::OriginNotAllowed => "WebSocket origin not allowed.".to_string(),
+            ApiError::FeedDisabled => {
+                "Output injection is disabled. Start the server with --allow-feed to use /feed.".to_string()
+            }
             ApiError::InternalError(detail) => format!("Internal error: {}.", detail),
         }
     }
 }
 
+impl From<crate::parser::ParserError> for ApiError {
+    fn from(err: crate::parser::ParserError) -> Self {
+        use crate::parser::ParserError;
+        match err {
+            ParserError::QueryTimeout => ApiError::ParserTimeout,
+            ParserError::ChannelFull => ApiError::ChannelFull,
+            ParserError::InvalidQuery(detail) => ApiError::InvalidRequest(detail),
+            ParserError::TaskDied | ParserError::Cancelled => ApiError::ParserUnavailable,
+            ParserError::FeedUnavailable => ApiError::InternalError("parser has no feed channel".into()),
+        }
+    }
+}
+
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         let body = serde_json::json!({
@@ -286,6 +346,13 @@ mod tests {
         assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
     }
 
+    #[tokio::test]
+    async fn input_timeout_status() {
+        let (status, json) = response_parts(ApiError::InputTimeout).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(json["error"]["code"], "input_timeout");
+    }
+
     #[tokio::test]
     async fn session_not_found_status() {
         let (status, _) = response_parts(ApiError::SessionNotFound("x".into())).await;
@@ -298,6 +365,24 @@ mod tests {
         assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
     }
 
+    #[tokio::test]
+    async fn expect_timeout_status() {
+        let (status, _) = response_parts(ApiError::ExpectTimeout).await;
+        assert_eq!(status, StatusCode::REQUEST_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn prompt_timeout_status() {
+        let (status, _) = response_parts(ApiError::PromptTimeout).await;
+        assert_eq!(status, StatusCode::REQUEST_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn ready_timeout_status() {
+        let (status, _) = response_parts(ApiError::ReadyTimeout).await;
+        assert_eq!(status, StatusCode::REQUEST_TIMEOUT);
+    }
+
     // ── Code string tests ──────────────────────────────────────────
 
     #[tokio::test]
@@ -378,6 +463,24 @@ mod tests {
         assert_eq!(json["error"]["code"], "internal_error");
     }
 
+    #[tokio::test]
+    async fn expect_timeout_code() {
+        let (_, json) = response_parts(ApiError::ExpectTimeout).await;
+        assert_eq!(json["error"]["code"], "expect_timeout");
+    }
+
+    #[tokio::test]
+    async fn prompt_timeout_code() {
+        let (_, json) = response_parts(ApiError::PromptTimeout).await;
+        assert_eq!(json["error"]["code"], "prompt_timeout");
+    }
+
+    #[tokio::test]
+    async fn ready_timeout_code() {
+        let (_, json) = response_parts(ApiError::ReadyTimeout).await;
+        assert_eq!(json["error"]["code"], "ready_timeout");
+    }
+
     // ── Message content tests (parameterized variants) ─────────────
 
     #[tokio::test]
@@ -448,6 +551,17 @@ mod tests {
         assert_eq!(json["error"]["code"], "session_create_failed");
     }
 
+    #[tokio::test]
+    async fn pty_exhausted_is_service_unavailable() {
+        let (status, json) =
+            response_parts(ApiError::PtyExhausted("too many open files".into())).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(json["error"]["code"], "pty_exhausted");
+        let msg = json["error"]["message"].as_str().unwrap();
+        assert!(msg.contains("too many open files"));
+        assert!(msg.contains("ulimit"));
+    }
+
     #[tokio::test]
     async fn session_create_failed_includes_detail() {
         let (_, json) =
@@ -601,6 +715,27 @@ mod tests {
         );
     }
 
+    // ── NoCommandHistory error tests ────────────────────────────────
+
+    #[tokio::test]
+    async fn no_command_history_status() {
+        let (status, _) = response_parts(ApiError::NoCommandHistory).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn no_command_history_code() {
+        let (_, json) = response_parts(ApiError::NoCommandHistory).await;
+        assert_eq!(json["error"]["code"], "no_command_history");
+    }
+
+    #[tokio::test]
+    async fn no_command_history_message_suggests_shell_integration() {
+        let (_, json) = response_parts(ApiError::NoCommandHistory).await;
+        let msg = json["error"]["message"].as_str().unwrap();
+        assert!(msg.contains("shell integration"), "message: {msg}");
+    }
+
     // ── InvalidTag error tests ──────────────────────────────────────
 
     #[tokio::test]
@@ -621,4 +756,24 @@ mod tests {
         let msg = json["error"]["message"].as_str().unwrap();
         assert_eq!(msg, "Invalid tag: tag must not be empty.");
     }
+
+    // ── ParserError mapping tests ───────────────────────────────────
+
+    #[tokio::test]
+    async fn parser_errors_map_to_distinct_statuses() {
+        use crate::parser::ParserError;
+        let cases = [
+            (ParserError::QueryTimeout, StatusCode::GATEWAY_TIMEOUT, "parser_timeout"),
+            (ParserError::InvalidQuery("bad".into()), StatusCode::BAD_REQUEST, "invalid_request"),
+            (ParserError::ChannelFull, StatusCode::SERVICE_UNAVAILABLE, "channel_full"),
+            (ParserError::TaskDied, StatusCode::SERVICE_UNAVAILABLE, "parser_unavailable"),
+            (ParserError::Cancelled, StatusCode::SERVICE_UNAVAILABLE, "parser_unavailable"),
+            (ParserError::FeedUnavailable, StatusCode::INTERNAL_SERVER_ERROR, "internal_error"),
+        ];
+        for (err, expected_status, expected_code) in cases {
+            let (status, json) = response_parts(ApiError::from(err)).await;
+            assert_eq!(status, expected_status);
+            assert_eq!(json["error"]["code"], expected_code);
+        }
+    }
 }
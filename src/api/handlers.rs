@@ -14,12 +14,13 @@ use bytes::Bytes;
 use futures::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
 use tokio::sync::broadcast;
+use tracing::Instrument;
 
 use crate::input::Mode;
-use crate::overlay::{BackgroundStyle, Overlay, OverlaySpan, RegionWrite};
+use crate::overlay::{BackgroundStyle, Overlay, OverlayScope, OverlaySpan, RegionWrite};
 use crate::panel::{self, Panel, Position};
 use crate::parser::{
-    events::EventType,
+    events::{EventType, ShutdownReason},
     state::{Format, Query},
 };
 use crate::pty::SpawnCommand;
@@ -48,6 +49,31 @@ const MAX_WS_MESSAGE_SIZE: usize = 1024 * 1024;
 /// Prevents clients from holding connections open indefinitely.
 const MAX_WAIT_CEILING_MS: u64 = 300_000; // 5 minutes
 
+/// Largest raw output log a session may keep.
+const MAX_RAW_LOG_BYTES: usize = 64 * 1024 * 1024;
+
+/// Most events a session's replay journal may keep.
+const MAX_JOURNAL_EVENTS: usize = 100_000;
+
+/// Largest serialized size a session's replay journal may be capped at.
+const MAX_JOURNAL_BYTES: usize = 64 * 1024 * 1024;
+
+/// Shortest heartbeat period a subscriber may ask for.
+const MIN_HEARTBEAT_MS: u64 = 100;
+
+/// Interval for a subscriber's `heartbeat_ms`, or None when heartbeats are
+/// off. The first tick is one full period out.
+fn heartbeat_interval(heartbeat_ms: u64) -> Option<tokio::time::Interval> {
+    if heartbeat_ms == 0 {
+        return None;
+    }
+    let period =
+        std::time::Duration::from_millis(heartbeat_ms.clamp(MIN_HEARTBEAT_MS, MAX_WAIT_CEILING_MS));
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    Some(interval)
+}
+
 /// Pending await_idle state: (request_id, method_name, format, future resolving to generation or None on timeout)
 type PendingIdle = (
     Option<serde_json::Value>,
@@ -71,24 +97,200 @@ pub(super) async fn health() -> Json<HealthResponse> {
     Json(HealthResponse { status: "ok" })
 }
 
+#[derive(Serialize)]
+pub(super) struct VersionResponse {
+    crate_version: &'static str,
+    protocol_version: u32,
+}
+
+pub(super) async fn version() -> Json<VersionResponse> {
+    Json(VersionResponse {
+        crate_version: env!("CARGO_PKG_VERSION"),
+        protocol_version: super::PROTOCOL_VERSION,
+    })
+}
+
+#[derive(Serialize)]
+pub(super) struct CapabilitiesResponse {
+    protocol_version: u32,
+    events: &'static [crate::parser::events::EventType],
+    formats: &'static [crate::parser::state::Format],
+    features: Features,
+}
+
+/// Optional behaviors clients may rely on. Fixed for a given build.
+#[derive(Serialize)]
+pub(super) struct Features {
+    /// `line_coalesce_ms` / `idle_flush_ms` on `subscribe`.
+    line_coalescing: bool,
+    /// A `sync` event with the full screen after each `subscribe`.
+    sync: bool,
+    /// Resuming a WebSocket stream from the replay journal.
+    journal: bool,
+    /// Command boundaries from OSC 133 shell-integration marks.
+    osc_133: bool,
+    /// Mouse input and mouse tracking reports.
+    mouse: bool,
+}
+
+/// What this build supports, so clients can enable features conditionally.
+pub(super) async fn capabilities() -> Json<CapabilitiesResponse> {
+    Json(CapabilitiesResponse {
+        protocol_version: super::PROTOCOL_VERSION,
+        events: crate::parser::events::EventType::ALL,
+        formats: crate::parser::state::Format::ALL,
+        features: Features {
+            line_coalescing: true,
+            sync: true,
+            journal: true,
+            osc_133: true,
+            mouse: false,
+        },
+    })
+}
+
+/// JSON Schema of the events `/ws/json` subscribers receive.
+pub(super) async fn event_schema() -> Json<serde_json::Value> {
+    Json(crate::parser::Parser::event_schema())
+}
+
 pub(super) async fn input(
     State(state): State<AppState>,
     Path(name): Path<String>,
+    headers: axum::http::HeaderMap,
     body: Bytes,
 ) -> Result<StatusCode, ApiError> {
     let session = get_session(&state.sessions, &name)?;
+    // Bodies declared binary are forwarded untouched.
+    let binary = headers
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/octet-stream"));
+    let body = if binary { body } else { session.input_mode.normalize(body) };
+    send_to_input_target(&state, &session, body).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Send end-of-file (Ctrl+D, EOT) so the program sees its stdin close.
+/// Goes where `/input` goes, focused command panel included.
+pub(super) async fn input_eof(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    send_to_input_target(&state, &session, Bytes::from_static(b"\x04")).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn send_to_input_target(state: &AppState, session: &Session, data: Bytes) -> Result<(), ApiError> {
     tokio::time::timeout(
-        std::time::Duration::from_secs(5),
-        session.input_tx.send(body),
+        state.server_config.input_timeout(),
+        session.input_target().send(data),
     )
     .await
-    .map_err(|_| ApiError::InputSendFailed)?
+    .map_err(|_| ApiError::InputTimeout)?
     .map_err(|e| {
         tracing::error!("Failed to send input to PTY: {}", e);
         ApiError::InputSendFailed
     })?;
     session.activity.touch();
-    Ok(StatusCode::NO_CONTENT)
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub(super) struct KeysRequest {
+    keys: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct EncodedKey {
+    key: String,
+    bytes: Vec<u8>,
+}
+
+#[derive(Serialize)]
+pub(super) struct KeysResponse {
+    cursor_key_app_mode: bool,
+    keys: Vec<EncodedKey>,
+}
+
+/// Send named keys (e.g. `"Home"`, `"PageUp"`, `"Ctrl+C"`) to the PTY, or
+/// to the focused command panel like `/input`.
+///
+/// Arrow and Home/End encodings depend on the cursor-key mode the running
+/// program has set, so the parser is consulted before encoding. The response
+/// echoes the exact bytes written, for debugging.
+pub(super) async fn keys(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(req): Json<KeysRequest>,
+) -> Result<Json<KeysResponse>, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    let response = tokio::time::timeout(
+        PARSER_QUERY_TIMEOUT,
+        session.parser.query(Query::InputModes),
+    )
+    .await
+    .map_err(|_| ApiError::ParserTimeout)??;
+    let cursor_key_app_mode = match response {
+        crate::parser::state::QueryResponse::InputModes(m) => m.cursor_key_app_mode,
+        _ => {
+            return Err(ApiError::InternalError(
+                "unexpected parser response".to_string(),
+            ))
+        }
+    };
+
+    // Encode everything up front so an unknown name sends nothing.
+    let mut encoded = Vec::with_capacity(req.keys.len());
+    for key in req.keys {
+        let bytes = crate::input::encode_key(&key, cursor_key_app_mode)
+            .ok_or_else(|| ApiError::InvalidRequest(format!("unknown key '{}'", key)))?;
+        encoded.push(EncodedKey { key, bytes });
+    }
+
+    let data: Vec<u8> = encoded.iter().flat_map(|k| k.bytes.iter().copied()).collect();
+    send_to_input_target(&state, &session, Bytes::from(data)).await?;
+
+    Ok(Json(KeysResponse {
+        cursor_key_app_mode,
+        keys: encoded,
+    }))
+}
+
+#[derive(Deserialize)]
+pub(super) struct TranslateKeyQuery {
+    key: String,
+    #[serde(default)]
+    cursor_key_app_mode: bool,
+}
+
+#[derive(Serialize)]
+pub(super) struct TranslateKeyResponse {
+    key: String,
+    cursor_key_app_mode: bool,
+    hex: String,
+    bytes: Vec<u8>,
+}
+
+/// What `/input/keys` would send for a key, without a session or sending
+/// anything. Uses the same encoder, so the two can't disagree.
+pub(super) async fn translate_key(
+    axum::extract::Query(params): axum::extract::Query<TranslateKeyQuery>,
+) -> Result<Json<TranslateKeyResponse>, ApiError> {
+    let bytes = crate::input::encode_key(&params.key, params.cursor_key_app_mode).ok_or_else(|| {
+        ApiError::InvalidRequest(format!(
+            "unknown key '{}'; known keys: {}, Ctrl+A-Ctrl+Z",
+            params.key,
+            crate::input::NAMED_KEYS.join(", ")
+        ))
+    })?;
+    Ok(Json(TranslateKeyResponse {
+        key: params.key,
+        cursor_key_app_mode: params.cursor_key_app_mode,
+        hex: bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+        bytes,
+    }))
 }
 
 pub(super) async fn ws_raw(
@@ -101,7 +303,51 @@ pub(super) async fn ws_raw(
         ApiError::ResourceLimitReached("too many clients connected to session".into())
     })?;
     Ok(ws.max_message_size(MAX_WS_MESSAGE_SIZE)
-        .on_upgrade(|socket| handle_ws_raw(socket, session, state.shutdown, client_guard)))
+        .on_upgrade(|socket| {
+            let span = session.log_span();
+            handle_ws_raw(socket, session, state.shutdown, client_guard, false).instrument(span)
+        }))
+}
+
+/// Output-only variant of [`ws_raw`]. Inbound data frames are read and
+/// discarded so the socket never stalls, but nothing reaches the PTY.
+pub(super) async fn ws_view(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    let client_guard = session.connect().ok_or_else(|| {
+        ApiError::ResourceLimitReached("too many clients connected to session".into())
+    })?;
+    Ok(ws.max_message_size(MAX_WS_MESSAGE_SIZE)
+        .on_upgrade(|socket| {
+            let span = session.log_span();
+            handle_ws_raw(socket, session, state.shutdown, client_guard, true).instrument(span)
+        }))
+}
+
+/// Close frame for a WebSocket, naming why its session ended once that's
+/// known. On server shutdown the reason ends with the suggested reconnect
+/// delay, as `; retry_after_ms=<n>`.
+fn close_frame(
+    session: Option<&Session>,
+    shutdown: &crate::shutdown::ShutdownCoordinator,
+    shutting_down: bool,
+) -> CloseFrame {
+    let reason = if shutting_down {
+        Some(ShutdownReason::ServerShutdown)
+    } else {
+        session.and_then(Session::shutdown_reason)
+    };
+    let mut reason = reason.map_or_else(|| "session ended".to_string(), |reason| reason.to_string());
+    if let Some(retry_after) = shutdown.retry_after().filter(|_| shutting_down) {
+        reason.push_str(&format!("; retry_after_ms={}", retry_after.as_millis()));
+    }
+    CloseFrame {
+        code: axum::extract::ws::close_code::NORMAL,
+        reason: reason.into(),
+    }
 }
 
 async fn handle_ws_raw(
@@ -109,6 +355,7 @@ async fn handle_ws_raw(
     session: Session,
     shutdown: crate::shutdown::ShutdownCoordinator,
     _client_guard: crate::session::ClientGuard,
+    read_only: bool,
 ) {
     // Register this connection for graceful shutdown tracking.
     // Check borrow immediately after register to handle the case where
@@ -193,6 +440,8 @@ async fn handle_ws_raw(
             // WebSocket input -> PTY
             msg = ws_rx.next() => {
                 match msg {
+                    // View-only clients: drop input, keep pong/close handling.
+                    Some(Ok(Message::Binary(_) | Message::Text(_))) if read_only => continue,
                     Some(Ok(Message::Binary(data))) => {
                         match tokio::time::timeout(
                             std::time::Duration::from_secs(5),
@@ -259,10 +508,7 @@ async fn handle_ws_raw(
     }
 
     // Send close frame with timeout (Phase 2c)
-    let close_frame = CloseFrame {
-        code: axum::extract::ws::close_code::NORMAL,
-        reason: "session ended".into(),
-    };
+    let close_frame = close_frame(Some(&session), &shutdown, *shutdown_rx.borrow());
     let _ = tokio::time::timeout(
         std::time::Duration::from_secs(2),
         ws_tx.send(Message::Close(Some(close_frame))),
@@ -271,17 +517,134 @@ async fn handle_ws_raw(
     // _guard is dropped here, decrementing active connection count
 }
 
+#[derive(Deserialize)]
+pub(super) struct WsJsonQuery {
+    /// `seq` of the last parser event received on a previous connection.
+    since: Option<u64>,
+    /// Format for subscriptions on this connection that don't name one.
+    format: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct WsJsonServerQuery {
+    /// Format for subscriptions on this connection that don't name one.
+    format: Option<String>,
+}
+
+/// Parse an event stream's `?format=`. Checked before the upgrade so a bad
+/// value is a plain 400 rather than a socket that closes.
+fn stream_format(format: Option<&str>) -> Result<Option<Format>, ApiError> {
+    match format {
+        None => Ok(None),
+        Some("plain") => Ok(Some(Format::Plain)),
+        Some("styled") => Ok(Some(Format::Styled)),
+        Some(other) => Err(ApiError::InvalidFormat(format!(
+            "expected 'plain' or 'styled', got '{}'",
+            other.chars().take(32).collect::<String>()
+        ))),
+    }
+}
+
+/// Give a `subscribe` request without a `format` the connection's default.
+fn apply_stream_format(req: &mut super::ws_methods::ServerWsRequest, format: Option<Format>) {
+    let Some(format) = format.filter(|_| req.method == "subscribe") else {
+        return;
+    };
+    let params = req.params.get_or_insert_with(|| serde_json::Value::Object(Default::default()));
+    if let Some(params) = params.as_object_mut() {
+        params.entry("format").or_insert_with(|| serde_json::json!(format));
+    }
+}
+
 pub(super) async fn ws_json(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
     Path(name): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<WsJsonQuery>,
 ) -> Result<impl IntoResponse, ApiError> {
+    let default_format = stream_format(params.format.as_deref())?;
     let session = get_session(&state.sessions, &name)?;
     let client_guard = session.connect().ok_or_else(|| {
         ApiError::ResourceLimitReached("too many clients connected to session".into())
     })?;
-    Ok(ws.max_message_size(MAX_WS_MESSAGE_SIZE)
-        .on_upgrade(|socket| handle_ws_json(socket, session, state.shutdown, client_guard)))
+    Ok(ws
+        .protocols([WsEncoding::JSON_PROTOCOL, WsEncoding::MSGPACK_PROTOCOL])
+        .max_message_size(MAX_WS_MESSAGE_SIZE)
+        .on_upgrade(move |socket| {
+            let span = session.log_span();
+            handle_ws_json(socket, session, state.shutdown, client_guard, params.since, default_format).instrument(span)
+        }))
+}
+
+/// Wire encoding for server-to-client messages on `/sessions/:name/ws/json`.
+///
+/// Negotiated through the `Sec-WebSocket-Protocol` header: `wsh.msgpack`
+/// sends every message as a binary MessagePack frame (maps with named
+/// fields, same shape as the JSON). Anything else, including no subprotocol,
+/// keeps JSON text frames. Client requests are always JSON text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WsEncoding {
+    Json,
+    MsgPack,
+}
+
+impl WsEncoding {
+    const JSON_PROTOCOL: &'static str = "wsh.json";
+    const MSGPACK_PROTOCOL: &'static str = "wsh.msgpack";
+
+    fn from_protocol(protocol: Option<&axum::http::HeaderValue>) -> Self {
+        match protocol.and_then(|p| p.to_str().ok()) {
+            Some(Self::MSGPACK_PROTOCOL) => WsEncoding::MsgPack,
+            _ => WsEncoding::Json,
+        }
+    }
+
+    fn encode<T: serde::Serialize>(self, value: &T) -> Option<Message> {
+        match self {
+            WsEncoding::Json => serde_json::to_string(value)
+                .ok()
+                .map(|json| Message::Text(json.into())),
+            WsEncoding::MsgPack => rmp_serde::to_vec_named(value)
+                .ok()
+                .map(|bytes| Message::Binary(bytes.into())),
+        }
+    }
+}
+
+/// Whether a parser event should be forwarded to a client subscribed to
+/// `subscribed` event types.
+fn subscribed_to(event: &crate::parser::events::Event, subscribed: &[EventType]) -> bool {
+    use crate::parser::events::Event;
+    match event {
+        Event::Line { .. } | Event::Scroll { .. } => subscribed.contains(&EventType::Lines),
+        Event::Cursor { .. } => subscribed.contains(&EventType::Cursor),
+        Event::Mode { .. } | Event::ScrollRegion { .. } | Event::DefaultColors { .. } => {
+            subscribed.contains(&EventType::Mode)
+        }
+        Event::Diff { .. } => subscribed.contains(&EventType::Diffs),
+        Event::Reset { .. } | Event::Sync { .. } | Event::Heartbeat { .. } | Event::End { .. } => true,
+        Event::Idle { .. } | Event::Running { .. } => subscribed.contains(&EventType::Activity),
+        Event::DeviceQuery { .. } => subscribed.contains(&EventType::Queries),
+        Event::ResizeRequest { .. } => subscribed.contains(&EventType::ResizeRequests),
+        Event::Cwd { .. } => subscribed.contains(&EventType::Cwd),
+        Event::Notification { .. } => subscribed.contains(&EventType::Notifications),
+    }
+}
+
+/// The events to send for `event` to a client subscribed to `subscribed`.
+/// A client following lines but not diffs gets a redraw's `Diff` as the
+/// `Line` events it replaced.
+fn events_for_subscriber(
+    event: crate::parser::events::Event,
+    subscribed: &[EventType],
+) -> Vec<crate::parser::events::Event> {
+    if subscribed_to(&event, subscribed) {
+        return vec![event];
+    }
+    match event.diff_as_lines() {
+        Some(lines) if subscribed.contains(&EventType::Lines) => lines,
+        _ => Vec::new(),
+    }
 }
 
 async fn handle_ws_json(
@@ -289,31 +652,43 @@ async fn handle_ws_json(
     session: Session,
     shutdown: crate::shutdown::ShutdownCoordinator,
     _client_guard: crate::session::ClientGuard,
+    mut resume_since: Option<u64>,
+    default_format: Option<Format>,
 ) {
     let (_guard, mut shutdown_rx) = shutdown.register();
     if *shutdown_rx.borrow_and_update() {
         return;
     }
+    let encoding = WsEncoding::from_protocol(socket.protocol());
     let (mut ws_tx, mut ws_rx) = socket.split();
 
     // Send connected message
-    let connected_msg = serde_json::json!({ "connected": true });
-    if ws_tx
-        .send(Message::Text(connected_msg.to_string().into()))
-        .await
-        .is_err()
-    {
+    let connected_msg = serde_json::json!({
+        "connected": true,
+        "protocol_version": super::PROTOCOL_VERSION,
+    });
+    let Some(connected) = encoding.encode(&connected_msg) else {
+        return;
+    };
+    if ws_tx.send(connected).await.is_err() {
         return;
     }
 
     // Mutable subscription state (initially no subscription)
     let mut subscribed_types: Vec<crate::parser::events::EventType> = Vec::new();
 
-    // Subscribe to parser events (stream is always active, filtering is local)
-    let mut events = Box::pin(session.parser.subscribe());
+    // Subscribe to parser events (stream is always active, filtering is local).
+    // Boxed as a trait object so a resumed subscription can replace it.
+    let mut events: std::pin::Pin<
+        Box<dyn futures::Stream<Item = crate::parser::SubscriptionEvent> + Send>,
+    > = Box::pin(session.parser.subscribe());
 
     // Input subscription (lazily created when EventType::Input is subscribed)
     let mut input_rx: Option<tokio::sync::broadcast::Receiver<crate::input::InputEvent>> = None;
+    // Input mode changes, forwarded as `mode` input events alongside input_rx
+    let mut input_mode_rx: Option<tokio::sync::watch::Receiver<crate::input::Mode>> = None;
+    // Focus changes, forwarded as `focus` input events
+    let mut input_focus_rx: Option<tokio::sync::watch::Receiver<Option<String>>> = None;
 
     let mut pending_idle: Option<PendingIdle> = None;
 
@@ -322,6 +697,17 @@ async fn handle_ws_json(
     let mut activity_sub_handle: Option<tokio::task::JoinHandle<()>> = None;
     let mut activity_sub_format = crate::parser::state::Format::default();
 
+    // Application-level heartbeat (off unless the subscriber sets heartbeat_ms),
+    // stamped with the latest parser seq seen on this connection.
+    let mut heartbeat: Option<tokio::time::Interval> = None;
+    let mut last_seq = 0u64;
+
+    // Folds rapid rewrites of the same line into one `line` event.
+    let mut coalescer = crate::parser::coalesce::LineCoalescer::with_idle_flush(
+        std::time::Duration::from_millis(crate::parser::coalesce::DEFAULT_LINE_COALESCE_MS),
+        std::time::Duration::from_millis(crate::parser::coalesce::DEFAULT_IDLE_FLUSH_MS),
+    );
+
     // Ping/pong keepalive
     let mut ping_interval = tokio::time::interval(std::time::Duration::from_secs(30));
     ping_interval.reset();
@@ -344,44 +730,47 @@ async fn handle_ws_json(
         };
     }
 
+    /// Send several events, leaving the main loop like `ws_send!` if any
+    /// send fails.
+    macro_rules! ws_send_events {
+        ($tx:expr, $events:expr) => {
+            let mut failed = false;
+            for event in $events {
+                if let Some(msg) = encoding.encode(&event) {
+                    if !matches!(tokio::time::timeout(WS_SEND_TIMEOUT, $tx.send(msg)).await, Ok(Ok(()))) {
+                        failed = true;
+                        break;
+                    }
+                }
+            }
+            if failed {
+                tracing::debug!("ws_json send failed or timed out, closing");
+                break;
+            }
+        };
+    }
+
     // Main event loop
     loop {
         tokio::select! {
             sub_event = events.next() => {
                 match sub_event {
-                    Some(crate::parser::SubscriptionEvent::Event(event)) if !subscribed_types.is_empty() => {
-                        let should_send = match &event {
-                            crate::parser::events::Event::Line { .. } => {
-                                subscribed_types.contains(&EventType::Lines)
-                            }
-                            crate::parser::events::Event::Cursor { .. } => {
-                                subscribed_types.contains(&EventType::Cursor)
-                            }
-                            crate::parser::events::Event::Mode { .. } => {
-                                subscribed_types.contains(&EventType::Mode)
-                            }
-                            crate::parser::events::Event::Diff { .. } => {
-                                subscribed_types.contains(&EventType::Diffs)
-                            }
-                            crate::parser::events::Event::Reset { .. }
-                            | crate::parser::events::Event::Sync { .. } => true,
-                            crate::parser::events::Event::Idle { .. }
-                            | crate::parser::events::Event::Running { .. } => {
-                                subscribed_types.contains(&EventType::Activity)
-                            }
-                        };
-
-                        if should_send {
-                            if let Ok(json) = serde_json::to_string(&event) {
-                                ws_send!(ws_tx, Message::Text(json.into()));
-                            }
+                    Some(crate::parser::SubscriptionEvent::Event(event)) => {
+                        last_seq = event.seq();
+                        if !subscribed_types.is_empty() {
+                            let now = tokio::time::Instant::now();
+                            let ready: Vec<_> = events_for_subscriber(event, &subscribed_types)
+                                .into_iter()
+                                .flat_map(|event| coalescer.push(event, now))
+                                .collect();
+                            ws_send_events!(ws_tx, ready);
                         }
                     }
                     Some(crate::parser::SubscriptionEvent::Lagged(n)) => {
                         tracing::warn!(skipped = n, "parser event subscriber lagged");
                         let lag_msg = serde_json::json!({"type": "lagged", "skipped": n});
-                        if let Ok(json) = serde_json::to_string(&lag_msg) {
-                            ws_send!(ws_tx, Message::Text(json.into()));
+                        if let Some(msg) = encoding.encode(&lag_msg) {
+                            ws_send!(ws_tx, msg);
                         }
                         // After lag, push a full sync so the client can recover.
                         // Without this, the client has an incomplete view of state.
@@ -397,13 +786,12 @@ async fn handle_ws_json(
                                 screen,
                                 scrollback_lines,
                             };
-                            if let Ok(json) = serde_json::to_string(&sync_event) {
-                                ws_send!(ws_tx, Message::Text(json.into()));
+                            if let Some(msg) = encoding.encode(&sync_event) {
+                                ws_send!(ws_tx, msg);
                             }
                         }
                     }
                     None => break,
-                    _ => {} // No subscription active, discard
                 }
             }
 
@@ -415,8 +803,8 @@ async fn handle_ws_json(
             } => {
                 match input_event {
                     Ok(event) => {
-                        if let Ok(json) = serde_json::to_string(&event) {
-                            ws_send!(ws_tx, Message::Text(json.into()));
+                        if let Some(msg) = encoding.encode(&event) {
+                            ws_send!(ws_tx, msg);
                         }
                     }
                     Err(broadcast::error::RecvError::Closed) => {
@@ -425,10 +813,46 @@ async fn handle_ws_json(
                     Err(broadcast::error::RecvError::Lagged(n)) => {
                         tracing::warn!(skipped = n, "input event subscriber lagged");
                         let lag_msg = serde_json::json!({"type": "input_lagged", "skipped": n});
-                        if let Ok(json) = serde_json::to_string(&lag_msg) {
-                            ws_send!(ws_tx, Message::Text(json.into()));
+                        if let Some(msg) = encoding.encode(&lag_msg) {
+                            ws_send!(ws_tx, msg);
+                        }
+                    }
+                }
+            }
+
+            changed = async {
+                match &mut input_mode_rx {
+                    Some(rx) => rx.changed().await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                match changed {
+                    Ok(()) => {
+                        let mode = *input_mode_rx.as_mut().unwrap().borrow_and_update();
+                        let event = crate::input::InputEvent::Mode { mode };
+                        if let Some(msg) = encoding.encode(&event) {
+                            ws_send!(ws_tx, msg);
+                        }
+                    }
+                    Err(_) => input_mode_rx = None,
+                }
+            }
+
+            changed = async {
+                match &mut input_focus_rx {
+                    Some(rx) => rx.changed().await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                match changed {
+                    Ok(()) => {
+                        let focused = input_focus_rx.as_mut().unwrap().borrow_and_update().clone();
+                        let event = crate::input::InputEvent::Focus { focused };
+                        if let Some(msg) = encoding.encode(&event) {
+                            ws_send!(ws_tx, msg);
                         }
                     }
+                    Err(_) => input_focus_rx = None,
                 }
             }
 
@@ -457,8 +881,8 @@ async fn handle_ws_json(
                                     "generation": generation,
                                 }),
                             );
-                            if let Ok(json) = serde_json::to_string(&resp) {
-                                ws_send!(ws_tx, Message::Text(json.into()));
+                            if let Some(msg) = encoding.encode(&resp) {
+                                ws_send!(ws_tx, msg);
                             }
                         }
                         _ => {
@@ -468,8 +892,8 @@ async fn handle_ws_json(
                                 "parser_error",
                                 "Terminal is idle but screen query failed.",
                             );
-                            if let Ok(json) = serde_json::to_string(&resp) {
-                                ws_send!(ws_tx, Message::Text(json.into()));
+                            if let Some(msg) = encoding.encode(&resp) {
+                                ws_send!(ws_tx, msg);
                             }
                         }
                     }
@@ -481,8 +905,8 @@ async fn handle_ws_json(
                         "idle_timeout",
                         "Terminal did not become idle within the deadline.",
                     );
-                    if let Ok(json) = serde_json::to_string(&resp) {
-                        ws_send!(ws_tx, Message::Text(json.into()));
+                    if let Some(msg) = encoding.encode(&resp) {
+                        ws_send!(ws_tx, msg);
                     }
                 }
             }
@@ -508,8 +932,8 @@ async fn handle_ws_json(
                                 screen,
                                 scrollback_lines,
                             };
-                            if let Ok(json) = serde_json::to_string(&idle_event) {
-                                ws_send!(ws_tx, Message::Text(json.into()));
+                            if let Some(msg) = encoding.encode(&idle_event) {
+                                ws_send!(ws_tx, msg);
                             }
                         }
                     }
@@ -518,8 +942,8 @@ async fn handle_ws_json(
                             seq: 0,
                             generation,
                         };
-                        if let Ok(json) = serde_json::to_string(&running_event) {
-                            ws_send!(ws_tx, Message::Text(json.into()));
+                        if let Some(msg) = encoding.encode(&running_event) {
+                            ws_send!(ws_tx, msg);
                         }
                     }
                     None => {
@@ -529,6 +953,29 @@ async fn handle_ws_json(
                 }
             }
 
+            _ = async {
+                match coalescer.flush_at(tokio::time::Instant::now(), session.activity.quiet_for()) {
+                    Some(at) => tokio::time::sleep_until(at).await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                ws_send_events!(ws_tx, coalescer.flush_due(tokio::time::Instant::now(), session.activity.quiet_for()));
+            }
+
+            _ = async {
+                match &mut heartbeat {
+                    Some(interval) => {
+                        interval.tick().await;
+                    }
+                    None => std::future::pending().await,
+                }
+            } => {
+                let event = crate::parser::events::Event::heartbeat(last_seq);
+                if let Some(msg) = encoding.encode(&event) {
+                    ws_send!(ws_tx, msg);
+                }
+            }
+
             // Ping keepalive
             _ = ping_interval.tick() => {
                 if ping_sent && last_pong.elapsed() > PONG_TIMEOUT {
@@ -554,8 +1001,8 @@ async fn handle_ws_json(
                                     "invalid_request",
                                     "Invalid JSON or missing 'method' field.",
                                 );
-                                if let Ok(json) = serde_json::to_string(&err) {
-                                    ws_send!(ws_tx, Message::Text(json.into()));
+                                if let Some(msg) = encoding.encode(&err) {
+                                    ws_send!(ws_tx, msg);
                                 }
                                 continue;
                             }
@@ -569,15 +1016,25 @@ async fn handle_ws_json(
                                     params.idle_timeout_ms = params.idle_timeout_ms.min(MAX_WAIT_CEILING_MS);
                                     params.interval_ms = params.interval_ms.min(MAX_WAIT_CEILING_MS);
                                     subscribed_types = params.events.clone();
-                                    let sub_format = params.format;
+                                    let sub_format = params.format.or(default_format).unwrap_or_else(|| session.parser.default_format());
+                                    heartbeat = heartbeat_interval(params.heartbeat_ms);
+                                    ws_send_events!(ws_tx, coalescer.flush(tokio::time::Instant::now()));
+                                    coalescer = crate::parser::coalesce::LineCoalescer::with_idle_flush(
+                                        std::time::Duration::from_millis(params.line_coalesce_ms.min(MAX_WAIT_CEILING_MS)),
+                                        std::time::Duration::from_millis(params.idle_flush_ms.min(MAX_WAIT_CEILING_MS)),
+                                    );
 
                                     // Set up input subscription if needed
                                     if subscribed_types.contains(&EventType::Input) {
                                         if input_rx.is_none() {
                                             input_rx = Some(session.input_broadcaster.subscribe());
+                                            input_mode_rx = Some(session.input_mode.subscribe());
+                                            input_focus_rx = Some(session.focus.subscribe());
                                         }
                                     } else {
                                         input_rx = None;
+                                        input_mode_rx = None;
+                                        input_focus_rx = None;
                                     }
 
                                     // Set up activity subscription if requested
@@ -663,24 +1120,67 @@ async fn handle_ws_json(
                                         "subscribe",
                                         serde_json::json!({"events": event_names}),
                                     );
-                                    if let Ok(json) = serde_json::to_string(&resp) {
-                                        ws_send!(ws_tx, Message::Text(json.into()));
+                                    if let Some(msg) = encoding.encode(&resp) {
+                                        ws_send!(ws_tx, msg);
+                                    }
+
+                                    // Resuming a previous connection: replay the missed
+                                    // events instead of a full sync when the journal
+                                    // still covers them.
+                                    let mut resumed = false;
+                                    if let Some(since) = resume_since.take() {
+                                        let (replay, stream) = session.parser.subscribe_since(since);
+                                        if let Some(replay) = replay {
+                                            events = Box::pin(stream);
+                                            if let Some(last) = replay.last() {
+                                                last_seq = last.seq();
+                                            }
+                                            let mut replayed = 0usize;
+                                            for event in replay.into_iter().flat_map(|e| events_for_subscriber(e, &subscribed_types)) {
+                                                if let Some(msg) = encoding.encode(&event) {
+                                                    ws_send!(ws_tx, msg);
+                                                    replayed += 1;
+                                                }
+                                            }
+                                            let resumed_msg = serde_json::json!({"type": "resumed", "since": since, "replayed": replayed});
+                                            if let Some(msg) = encoding.encode(&resumed_msg) {
+                                                ws_send!(ws_tx, msg);
+                                            }
+                                            resumed = true;
+                                        } else {
+                                            let journal = session.parser.journal_status();
+                                            tracing::info!(
+                                                since,
+                                                oldest_seq = ?journal.oldest_seq,
+                                                newest_seq = ?journal.newest_seq,
+                                                misses = journal.misses,
+                                                "resume point not in event journal; sending full sync"
+                                            );
+                                        }
                                     }
 
                                     // Send sync event (with timeout to avoid blocking the loop)
-                                    if let Ok(Ok(crate::parser::state::QueryResponse::Screen(screen))) = tokio::time::timeout(
+                                    if resumed {
+                                        // Client state is already current.
+                                    } else if let Ok(Ok(crate::parser::state::QueryResponse::Screen(screen))) = tokio::time::timeout(
                                         std::time::Duration::from_secs(10),
                                         session.parser.query(crate::parser::state::Query::Screen { format: sub_format }),
                                     ).await {
+                                        let initial_lines = if params.initial_lines {
+                                            crate::parser::events::Event::screen_lines(0, &screen)
+                                        } else {
+                                            Vec::new()
+                                        };
                                         let scrollback_lines = screen.total_lines;
                                         let sync_event = crate::parser::events::Event::Sync {
                                             seq: 0,
                                             screen,
                                             scrollback_lines,
                                         };
-                                        if let Ok(json) = serde_json::to_string(&sync_event) {
-                                            ws_send!(ws_tx, Message::Text(json.into()));
+                                        if let Some(msg) = encoding.encode(&sync_event) {
+                                            ws_send!(ws_tx, msg);
                                         }
+                                        ws_send_events!(ws_tx, initial_lines);
                                     }
 
                                     // Send initial activity state if activity subscription is active
@@ -699,8 +1199,8 @@ async fn handle_ws_json(
                                                     screen,
                                                     scrollback_lines,
                                                 };
-                                                if let Ok(json) = serde_json::to_string(&idle_event) {
-                                                    ws_send!(ws_tx, Message::Text(json.into()));
+                                                if let Some(msg) = encoding.encode(&idle_event) {
+                                                    ws_send!(ws_tx, msg);
                                                 }
                                             }
                                         } else {
@@ -708,8 +1208,8 @@ async fn handle_ws_json(
                                                 seq: 0,
                                                 generation,
                                             };
-                                            if let Ok(json) = serde_json::to_string(&running_event) {
-                                                ws_send!(ws_tx, Message::Text(json.into()));
+                                            if let Some(msg) = encoding.encode(&running_event) {
+                                                ws_send!(ws_tx, msg);
                                             }
                                         }
                                     }
@@ -721,8 +1221,8 @@ async fn handle_ws_json(
                                         "invalid_request",
                                         "Invalid parameters for this method.",
                                     );
-                                    if let Ok(json) = serde_json::to_string(&resp) {
-                                        ws_send!(ws_tx, Message::Text(json.into()));
+                                    if let Some(msg) = encoding.encode(&resp) {
+                                        ws_send!(ws_tx, msg);
                                     }
                                 }
                             }
@@ -759,8 +1259,8 @@ async fn handle_ws_json(
                                             "idle_superseded",
                                             "A new await_idle request superseded this one.",
                                         );
-                                        if let Ok(json) = serde_json::to_string(&resp) {
-                                            ws_send!(ws_tx, Message::Text(json.into()));
+                                        if let Some(msg) = encoding.encode(&resp) {
+                                            ws_send!(ws_tx, msg);
                                         }
                                     }
                                     pending_idle = Some((req.id.clone(), req.method.clone(), format, fut));
@@ -772,8 +1272,8 @@ async fn handle_ws_json(
                                         "invalid_request",
                                         "Invalid parameters for this method.",
                                     );
-                                    if let Ok(json) = serde_json::to_string(&resp) {
-                                        ws_send!(ws_tx, Message::Text(json.into()));
+                                    if let Some(msg) = encoding.encode(&resp) {
+                                        ws_send!(ws_tx, msg);
                                     }
                                 }
                             }
@@ -781,8 +1281,8 @@ async fn handle_ws_json(
                             // Dispatch all other methods
                             let resp = super::ws_methods::dispatch(&req, &session).await;
 
-                            if let Ok(json) = serde_json::to_string(&resp) {
-                                ws_send!(ws_tx, Message::Text(json.into()));
+                            if let Some(msg) = encoding.encode(&resp) {
+                                ws_send!(ws_tx, msg);
                             }
                         }
                     }
@@ -807,10 +1307,7 @@ async fn handle_ws_json(
     }
 
     // Send close frame on any exit path (with timeout to avoid blocking on dead connections)
-    let close_frame = CloseFrame {
-        code: axum::extract::ws::close_code::NORMAL,
-        reason: "session ended".into(),
-    };
+    let close_frame = close_frame(Some(&session), &shutdown, *shutdown_rx.borrow());
     let _ = tokio::time::timeout(
         std::time::Duration::from_secs(2),
         ws_tx.send(Message::Close(Some(close_frame))),
@@ -841,7 +1338,9 @@ impl Drop for ServerWsGuard {
 pub(super) async fn ws_json_server(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<WsJsonServerQuery>,
 ) -> Result<impl IntoResponse, ApiError> {
+    let default_format = stream_format(params.format.as_deref())?;
     // Enforce server-level WS connection limit with a race-free CAS loop.
     loop {
         let current = state.server_ws_count.load(std::sync::atomic::Ordering::Acquire);
@@ -865,8 +1364,8 @@ pub(super) async fn ws_json_server(
     }
     let guard = ServerWsGuard(state.server_ws_count.clone());
     Ok(ws.max_message_size(MAX_WS_MESSAGE_SIZE)
-        .on_upgrade(|socket| async move {
-            handle_ws_json_server(socket, state).await;
+        .on_upgrade(move |socket| async move {
+            handle_ws_json_server(socket, state, default_format).await;
             drop(guard); // explicitly drop after handler completes
         }))
 }
@@ -877,6 +1376,23 @@ struct TaggedSessionEvent {
     event: crate::parser::SubscriptionEvent,
 }
 
+/// Forward parser events tagged with the session's current name. Returns
+/// false once the receiving connection is gone.
+async fn forward_tagged(
+    tx: &tokio::sync::mpsc::Sender<TaggedSessionEvent>,
+    name: &parking_lot::Mutex<String>,
+    events: Vec<crate::parser::events::Event>,
+) -> bool {
+    for event in events {
+        let session = name.lock().clone();
+        let event = crate::parser::SubscriptionEvent::Event(event);
+        if tx.send(TaggedSessionEvent { session, event }).await.is_err() {
+            return false;
+        }
+    }
+    true
+}
+
 /// Tracks a per-session subscription's forwarding task.
 struct SubHandle {
     subscribed_types: Vec<EventType>,
@@ -940,34 +1456,7 @@ fn format_registry_event(
     }
 }
 
-/// Check if a per-session tagged event should be forwarded based on subscription filters.
-fn should_forward_session_event(
-    event: &crate::parser::events::Event,
-    handle: &SubHandle,
-) -> bool {
-    match event {
-        crate::parser::events::Event::Line { .. } => {
-            handle.subscribed_types.contains(&EventType::Lines)
-        }
-        crate::parser::events::Event::Cursor { .. } => {
-            handle.subscribed_types.contains(&EventType::Cursor)
-        }
-        crate::parser::events::Event::Mode { .. } => {
-            handle.subscribed_types.contains(&EventType::Mode)
-        }
-        crate::parser::events::Event::Diff { .. } => {
-            handle.subscribed_types.contains(&EventType::Diffs)
-        }
-        crate::parser::events::Event::Reset { .. }
-        | crate::parser::events::Event::Sync { .. } => true,
-        crate::parser::events::Event::Idle { .. }
-        | crate::parser::events::Event::Running { .. } => {
-            handle.subscribed_types.contains(&EventType::Activity)
-        }
-    }
-}
-
-async fn handle_ws_json_server(socket: WebSocket, state: AppState) {
+async fn handle_ws_json_server(socket: WebSocket, state: AppState, default_format: Option<Format>) {
     let (_guard, mut shutdown_rx) = state.shutdown.register();
     if *shutdown_rx.borrow_and_update() {
         return;
@@ -975,7 +1464,10 @@ async fn handle_ws_json_server(socket: WebSocket, state: AppState) {
     let (mut ws_tx, mut ws_rx) = socket.split();
 
     // Send connected message
-    let connected_msg = serde_json::json!({ "connected": true });
+    let connected_msg = serde_json::json!({
+        "connected": true,
+        "protocol_version": super::PROTOCOL_VERSION,
+    });
     if ws_tx
         .send(Message::Text(connected_msg.to_string().into()))
         .await
@@ -1023,7 +1515,7 @@ async fn handle_ws_json_server(socket: WebSocket, state: AppState) {
             msg = ws_rx.next() => {
                 match msg {
                     Some(Ok(Message::Text(text))) => {
-                        let req = match serde_json::from_str::<super::ws_methods::ServerWsRequest>(&text) {
+                        let mut req = match serde_json::from_str::<super::ws_methods::ServerWsRequest>(&text) {
                             Ok(req) => req,
                             Err(_e) => {
                                 let err = super::ws_methods::WsResponse::protocol_error(
@@ -1037,6 +1529,7 @@ async fn handle_ws_json_server(socket: WebSocket, state: AppState) {
                             }
                         };
 
+                        apply_stream_format(&mut req, default_format);
                         let is_subscribe = req.method == "subscribe";
                         let subscribe_session = req.session.clone();
 
@@ -1060,16 +1553,29 @@ async fn handle_ws_json_server(socket: WebSocket, state: AppState) {
                             if subscribe_ok {
                                 if let Some(session_name) = &subscribe_session {
                                     if let Some(session) = state.sessions.get(session_name) {
-                                        let format = {
+                                        let (format, initial_lines) = {
                                             let params_value = req.params.clone().unwrap_or(serde_json::Value::Object(Default::default()));
                                             serde_json::from_value::<super::ws_methods::SubscribeParams>(params_value)
-                                                .map(|p| p.format)
+                                                .map(|p| (p.format, p.initial_lines))
                                                 .unwrap_or_default()
                                         };
+                                        let format = format.unwrap_or_else(|| session.parser.default_format());
                                         if let Ok(Ok(crate::parser::state::QueryResponse::Screen(screen))) = tokio::time::timeout(
                                             std::time::Duration::from_secs(10),
                                             session.parser.query(crate::parser::state::Query::Screen { format }),
                                         ).await {
+                                            let line_messages: Vec<String> = if initial_lines {
+                                                crate::parser::events::Event::screen_lines(0, &screen)
+                                                    .iter()
+                                                    .filter_map(|event| serde_json::to_value(event).ok())
+                                                    .filter_map(|mut value| {
+                                                        value["session"] = serde_json::json!(session_name);
+                                                        serde_json::to_string(&value).ok()
+                                                    })
+                                                    .collect()
+                                            } else {
+                                                Vec::new()
+                                            };
                                             let scrollback_lines = screen.total_lines;
                                             let sync_event = serde_json::json!({
                                                 "event": "sync",
@@ -1083,6 +1589,18 @@ async fn handle_ws_json_server(socket: WebSocket, state: AppState) {
                                             if let Ok(json) = serde_json::to_string(&sync_event) {
                                                 ws_send!(ws_tx, Message::Text(json.into()));
                                             }
+                                            let mut failed = false;
+                                            for json in line_messages {
+                                                let sent = tokio::time::timeout(WS_SEND_TIMEOUT, ws_tx.send(Message::Text(json.into()))).await;
+                                                if !matches!(sent, Ok(Ok(()))) {
+                                                    failed = true;
+                                                    break;
+                                                }
+                                            }
+                                            if failed {
+                                                tracing::debug!("server ws_json send failed or timed out, closing");
+                                                break;
+                                            }
                                         }
 
                                         // Send initial activity state if activity subscription is active
@@ -1181,21 +1699,32 @@ async fn handle_ws_json_server(socket: WebSocket, state: AppState) {
             Some(tagged) = sub_rx.recv() => {
                 match tagged.event {
                     crate::parser::SubscriptionEvent::Event(ref event) => {
-                        if let Some(handle) = sub_handles.get(&tagged.session) {
-                            if should_forward_session_event(event, handle) {
-                                if let Ok(event_value) = serde_json::to_value(event) {
-                                    let tagged_json = if let serde_json::Value::Object(mut map) = event_value {
-                                        map.insert("session".to_string(), serde_json::json!(tagged.session));
-                                        serde_json::Value::Object(map)
-                                    } else {
-                                        event_value
-                                    };
-                                    if let Ok(json) = serde_json::to_string(&tagged_json) {
-                                        ws_send!(ws_tx, Message::Text(json.into()));
+                        let forwarded = match sub_handles.get(&tagged.session) {
+                            Some(handle) => events_for_subscriber(event.clone(), &handle.subscribed_types),
+                            None => Vec::new(),
+                        };
+                        let mut failed = false;
+                        for event in forwarded {
+                            if let Ok(event_value) = serde_json::to_value(&event) {
+                                let tagged_json = if let serde_json::Value::Object(mut map) = event_value {
+                                    map.insert("session".to_string(), serde_json::json!(tagged.session));
+                                    serde_json::Value::Object(map)
+                                } else {
+                                    event_value
+                                };
+                                if let Ok(json) = serde_json::to_string(&tagged_json) {
+                                    let sent = tokio::time::timeout(WS_SEND_TIMEOUT, ws_tx.send(Message::Text(json.into()))).await;
+                                    if !matches!(sent, Ok(Ok(()))) {
+                                        failed = true;
+                                        break;
                                     }
                                 }
                             }
                         }
+                        if failed {
+                            tracing::debug!("server ws_json send failed or timed out, closing");
+                            break;
+                        }
                     }
                     crate::parser::SubscriptionEvent::Lagged(n) => {
                         tracing::warn!(session = %tagged.session, skipped = n, "parser event subscriber lagged");
@@ -1250,10 +1779,7 @@ async fn handle_ws_json_server(socket: WebSocket, state: AppState) {
     }
 
     // Send close frame on any exit path (with timeout to avoid blocking on dead connections)
-    let close_frame = CloseFrame {
-        code: axum::extract::ws::close_code::NORMAL,
-        reason: "session ended".into(),
-    };
+    let close_frame = close_frame(None, &state.shutdown, *shutdown_rx.borrow());
     let _ = tokio::time::timeout(
         std::time::Duration::from_secs(2),
         ws_tx.send(Message::Close(Some(close_frame))),
@@ -1293,7 +1819,26 @@ async fn handle_server_ws_request(
                 env: Option<std::collections::HashMap<String, String>>,
                 #[serde(default)]
                 tags: Vec<String>,
-            }
+                #[serde(default)]
+                auto_reply_cpr: bool,
+                #[serde(default)]
+                decoding: crate::parser::decode::Decoding,
+                #[serde(default)]
+                login: bool,
+                #[serde(default = "default_reflow_on_resize")]
+                reflow_on_resize: bool,
+                redraw_threshold: Option<usize>,
+                #[serde(default)]
+                normalize_newlines: bool,
+                #[serde(default)]
+                echo_captured_input: bool,
+                #[serde(default)]
+                default_format: crate::parser::state::Format,
+                prompt_pattern: Option<String>,
+                raw_log_bytes: Option<usize>,
+                journal_events: Option<usize>,
+                journal_bytes: Option<usize>,
+            }
             let params: CreateParams = match &req.params {
                 Some(v) => match serde_json::from_value(v.clone()) {
                     Ok(p) => p,
@@ -1314,22 +1859,51 @@ async fn handle_server_ws_request(
                     cwd: None,
                     env: None,
                     tags: vec![],
+                    auto_reply_cpr: false,
+                    decoding: Default::default(),
+                    login: false,
+                    reflow_on_resize: true,
+                    redraw_threshold: None,
+                    normalize_newlines: false,
+                    echo_captured_input: false,
+                    default_format: Default::default(),
+                    prompt_pattern: None,
+                    raw_log_bytes: None,
+                    journal_events: None,
+                    journal_bytes: None,
                 },
             };
+            let prompt_pattern = crate::session::PromptPattern::default();
+            if let Some(pattern) = &params.prompt_pattern {
+                if let Err(e) = prompt_pattern.set(pattern) {
+                    return Some(super::ws_methods::WsResponse::error(
+                        id,
+                        method,
+                        "invalid_request",
+                        &format!("Invalid prompt_pattern: {}.", e),
+                    ));
+                }
+            }
 
             let command = match params.command {
                 Some(cmd) => SpawnCommand::Command {
                     command: cmd,
                     interactive: true,
+                    force_interactive: None,
+                    scrub_env: None,
                 },
                 None => SpawnCommand::Shell {
                     interactive: true,
                     shell: None,
+                    login: params.login,
+                    force_interactive: None,
+                    scrub_env: None,
                 },
             };
 
-            let rows = params.rows.unwrap_or(24).clamp(1, 1000);
-            let cols = params.cols.unwrap_or(80).clamp(1, 1000);
+            let (default_rows, default_cols) = state.server_config.default_size();
+            let rows = params.rows.unwrap_or(default_rows).clamp(1, 1000);
+            let cols = params.cols.unwrap_or(default_cols).clamp(1, 1000);
 
             // Advisory pre-check — see name_available() doc for TOCTOU rationale.
             // The authoritative check is insert_and_get() below.
@@ -1386,19 +1960,26 @@ async fn handle_server_ws_request(
             }
 
             let param_name = params.name;
-            let cwd = params.cwd;
-            let env = params.env;
+            let options = crate::session::SpawnOptions {
+                cwd: params.cwd,
+                env: params.env,
+                decoding: params.decoding,
+                read_buffer_bytes: state.sessions.read_buffer_bytes(),
+                prompt_pattern,
+                raw_log_bytes: params.raw_log_bytes.map_or(crate::broker::DEFAULT_RAW_LOG_LIMIT, |limit| limit.min(MAX_RAW_LOG_BYTES)),
+            };
             let spawn_result = tokio::task::spawn_blocking(move || {
-                Session::spawn_with_options("".to_string(), command, rows, cols, cwd, env)
+                Session::spawn_with_options("".to_string(), command, rows, cols, options)
             }).await;
             let (session, child_exit_rx) = match spawn_result {
                 Ok(Ok(result)) => result,
                 Ok(Err(e)) => {
+                    let e = spawn_error(e, &state.sessions);
                     return Some(super::ws_methods::WsResponse::error(
                         id,
                         method,
-                        "session_create_failed",
-                        &format!("Failed to create session: {}.", e),
+                        e.code(),
+                        &e.message(),
                     ));
                 }
                 Err(e) => {
@@ -1410,6 +1991,14 @@ async fn handle_server_ws_request(
                     ));
                 }
             };
+            session.parser.set_reflow_on_resize(params.reflow_on_resize);
+            if let Some(percent) = params.redraw_threshold {
+                session.parser.set_redraw_threshold(percent);
+            }
+            session.input_mode.set_normalize_newlines(params.normalize_newlines);
+            session.input_mode.set_echo_captured(params.echo_captured_input);
+            session.parser.set_default_format(params.default_format);
+            apply_journal_limits(&session, params.journal_events, params.journal_bytes);
 
             // Set initial tags before registry insertion
             if !initial_tags.is_empty() {
@@ -1420,6 +2009,9 @@ async fn handle_server_ws_request(
                 Ok((assigned_name, _session)) => {
                     // Monitor child exit so the session is auto-removed.
                     state.sessions.monitor_child_exit(assigned_name.clone(), session.client_count.clone(), session.child_exited.clone(), child_exit_rx);
+                    if params.auto_reply_cpr {
+                        session.spawn_cpr_responder();
+                    }
                     let mut tags: Vec<String> = session.tags.read().iter().cloned().collect();
                     tags.sort();
                     return Some(super::ws_methods::WsResponse::success(
@@ -1853,17 +2445,35 @@ async fn handle_server_ws_request(
                 let shared_name = std::sync::Arc::new(parking_lot::Mutex::new(session_name.clone()));
                 let task_name = shared_name.clone();
                 let cancelled = session.cancelled.clone();
+                let mut heartbeat = heartbeat_interval(params.heartbeat_ms);
+                let mut coalescer = crate::parser::coalesce::LineCoalescer::with_idle_flush(
+                    std::time::Duration::from_millis(params.line_coalesce_ms.min(MAX_WAIT_CEILING_MS)),
+                    std::time::Duration::from_millis(params.idle_flush_ms.min(MAX_WAIT_CEILING_MS)),
+                );
+                let activity = session.activity.clone();
                 let task = tokio::spawn(async move {
+                    let mut last_seq = 0u64;
                     loop {
                         tokio::select! {
                             event = events.next() => {
                                 match event {
-                                    Some(e) => {
+                                    Some(crate::parser::SubscriptionEvent::Event(event)) => {
+                                        last_seq = event.seq();
+                                        let ready = coalescer.push(event, tokio::time::Instant::now());
+                                        if !forward_tagged(&tx, &task_name, ready).await {
+                                            break;
+                                        }
+                                    }
+                                    Some(lagged) => {
+                                        let held = coalescer.flush(tokio::time::Instant::now());
+                                        if !forward_tagged(&tx, &task_name, held).await {
+                                            break;
+                                        }
                                         let current_name = task_name.lock().clone();
                                         if tx
                                             .send(TaggedSessionEvent {
                                                 session: current_name,
-                                                event: e,
+                                                event: lagged,
                                             })
                                             .await
                                             .is_err()
@@ -1874,6 +2484,38 @@ async fn handle_server_ws_request(
                                     None => break,
                                 }
                             }
+                            _ = async {
+                                match coalescer.flush_at(tokio::time::Instant::now(), activity.quiet_for()) {
+                                    Some(at) => tokio::time::sleep_until(at).await,
+                                    None => std::future::pending().await,
+                                }
+                            } => {
+                                let held = coalescer.flush_due(tokio::time::Instant::now(), activity.quiet_for());
+                                if !forward_tagged(&tx, &task_name, held).await {
+                                    break;
+                                }
+                            }
+                            _ = async {
+                                match &mut heartbeat {
+                                    Some(interval) => {
+                                        interval.tick().await;
+                                    }
+                                    None => std::future::pending().await,
+                                }
+                            } => {
+                                let current_name = task_name.lock().clone();
+                                let event = crate::parser::events::Event::heartbeat(last_seq);
+                                if tx
+                                    .send(TaggedSessionEvent {
+                                        session: current_name,
+                                        event: crate::parser::SubscriptionEvent::Event(event),
+                                    })
+                                    .await
+                                    .is_err()
+                                {
+                                    break;
+                                }
+                            }
                             _ = cancelled.cancelled() => break,
                         }
                     }
@@ -1886,7 +2528,7 @@ async fn handle_server_ws_request(
                     let activity_tx = sub_tx.clone();
                     let activity_name = shared_name.clone();
                     let activity_parser = session.parser.clone();
-                    let activity_format = params.format;
+                    let activity_format = params.format.unwrap_or_else(|| session.parser.default_format());
                     Some(tokio::spawn(async move {
                         let mut watch_rx = activity.subscribe();
 
@@ -2108,8 +2750,7 @@ pub(super) async fn idle(
                 session.parser.query(Query::Screen { format: params.format }),
             )
             .await
-            .map_err(|_| ApiError::ParserTimeout)?
-            .map_err(|_| ApiError::ParserUnavailable)?;
+            .map_err(|_| ApiError::ParserTimeout)??;
 
             match response {
                 crate::parser::state::QueryResponse::Screen(screen) => {
@@ -2132,6 +2773,159 @@ pub(super) async fn idle(
     }
 }
 
+#[derive(Deserialize)]
+pub(super) struct WaitPromptQuery {
+    /// Overall deadline for the prompt to appear.
+    #[serde(default = "default_max_wait")]
+    timeout_ms: u64,
+    /// Quiet period that must pass before the prompt regex is checked.
+    #[serde(default = "default_prompt_idle")]
+    idle_ms: u64,
+    #[serde(default)]
+    format: Format,
+    /// Regex to use instead of the session's `prompt_pattern`.
+    pattern: Option<String>,
+    /// Generation from an earlier idle or wait-prompt response. When it is
+    /// still current, the server waits for new activity first so the prompt
+    /// left over from the previous command isn't matched.
+    last_generation: Option<u64>,
+}
+
+fn default_prompt_idle() -> u64 {
+    250
+}
+
+/// Text of the cursor row up to the cursor, with the trailing blanks that
+/// screen output trims restored so patterns such as `\$ $` can match.
+fn prompt_line(screen: &crate::parser::state::ScreenResponse) -> String {
+    let col = screen.cursor.col;
+    let text = screen
+        .lines
+        .get(screen.cursor.row)
+        .map(|line| line.text())
+        .unwrap_or_default();
+    let mut before: String = text.chars().take(col).collect();
+    let len = before.chars().count();
+    before.extend(std::iter::repeat_n(' ', col - len));
+    before
+}
+
+/// Wait until the terminal is quiet and the cursor row matches the prompt
+/// regex — a command-completion signal for shells without OSC 133.
+pub(super) async fn wait_prompt(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<WaitPromptQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    let prompt = match params.pattern.as_deref() {
+        Some(pattern) => Some(regex::Regex::new(pattern).map_err(|e| invalid_prompt_pattern(&e))?),
+        None => session.prompt_pattern.get(),
+    }
+    .ok_or_else(|| ApiError::InvalidRequest("no prompt_pattern is set for this session".into()))?;
+    let idle = std::time::Duration::from_millis(params.idle_ms.min(MAX_WAIT_CEILING_MS));
+    let deadline = std::time::Duration::from_millis(params.timeout_ms.min(MAX_WAIT_CEILING_MS));
+
+    let wait = async {
+        let mut last_generation = params.last_generation;
+        loop {
+            let generation = session.activity.wait_for_idle(idle, last_generation).await;
+            let response = tokio::time::timeout(
+                PARSER_QUERY_TIMEOUT,
+                session.parser.query(Query::Screen { format: params.format }),
+            )
+            .await
+            .map_err(|_| ApiError::ParserTimeout)??;
+            let crate::parser::state::QueryResponse::Screen(screen) = response else {
+                return Err(ApiError::ParserUnavailable);
+            };
+            let line = prompt_line(&screen);
+            if let Some(m) = prompt.find(&line) {
+                return Ok(Json(serde_json::json!({
+                    "matched": m.as_str(),
+                    "line": line,
+                    "screen": screen,
+                    "generation": generation,
+                    "last_activity_ms": session.activity.last_activity_ms(),
+                })));
+            }
+            // Quiet but no prompt yet (e.g. a slow command); wait for more output.
+            last_generation = Some(generation);
+        }
+    };
+
+    tokio::time::timeout(deadline, wait)
+        .await
+        .unwrap_or(Err(ApiError::PromptTimeout))
+}
+
+#[derive(Deserialize)]
+pub(super) struct ReadyQuery {
+    /// How long to wait for the session to become ready.
+    #[serde(default = "default_max_wait")]
+    timeout_ms: u64,
+    #[serde(default)]
+    format: Format,
+    /// Regex to use instead of the session's `prompt_pattern`.
+    pattern: Option<String>,
+}
+
+/// Wait until a session is usable: it has written its first output and,
+/// when a prompt regex is known, the cursor row matches it. Returns the
+/// screen at that point so a client can scrape without a further request.
+pub(super) async fn ready(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<ReadyQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    let prompt = match params.pattern.as_deref() {
+        Some(pattern) => Some(regex::Regex::new(pattern).map_err(|e| invalid_prompt_pattern(&e))?),
+        None => session.prompt_pattern.get(),
+    };
+    let deadline = std::time::Duration::from_millis(params.timeout_ms.min(MAX_WAIT_CEILING_MS));
+    let started = std::time::Instant::now();
+
+    let wait = async {
+        session.output_ready.wait().await;
+        let mut activity = session.activity.subscribe();
+        loop {
+            let response = tokio::time::timeout(PARSER_QUERY_TIMEOUT, async {
+                session.parser.barrier().await?;
+                session.parser.query(Query::Screen { format: params.format }).await
+            })
+            .await
+            .map_err(|_| ApiError::ParserTimeout)??;
+            let crate::parser::state::QueryResponse::Screen(screen) = response else {
+                return Err(ApiError::ParserUnavailable);
+            };
+            let matched = match &prompt {
+                None => None,
+                Some(prompt) => match prompt.find(&prompt_line(&screen)) {
+                    Some(m) => Some(m.as_str().to_string()),
+                    // Output so far is a banner or MOTD; wait for more.
+                    None => {
+                        if activity.changed().await.is_err() {
+                            return Err(ApiError::ParserUnavailable);
+                        }
+                        continue;
+                    }
+                },
+            };
+            return Ok(Json(serde_json::json!({
+                "ready": true,
+                "prompt": matched,
+                "waited_ms": started.elapsed().as_millis() as u64,
+                "screen": screen,
+            })));
+        }
+    };
+
+    tokio::time::timeout(deadline, wait)
+        .await
+        .unwrap_or(Err(ApiError::ReadyTimeout))
+}
+
 // Server-level idle query parameters (any session)
 #[derive(Deserialize)]
 pub(super) struct IdleAnyQuery {
@@ -2222,8 +3016,7 @@ pub(super) async fn idle_any(
                 session.parser.query(Query::Screen { format: params.format }),
             )
             .await
-            .map_err(|_| ApiError::ParserTimeout)?
-            .map_err(|_| ApiError::ParserUnavailable)?;
+            .map_err(|_| ApiError::ParserTimeout)??;
 
             match response {
                 crate::parser::state::QueryResponse::Screen(screen) => {
@@ -2255,25 +3048,111 @@ struct EnrichedScreen {
     last_activity_ms: u64,
 }
 
+/// Query the parser for the current screen fingerprint.
+async fn query_screen_hash(session: &Session) -> Result<String, ApiError> {
+    let response = tokio::time::timeout(
+        PARSER_QUERY_TIMEOUT,
+        session.parser.query(Query::ScreenHash),
+    )
+    .await
+    .map_err(|_| ApiError::ParserTimeout)??;
+    match response {
+        crate::parser::state::QueryResponse::ScreenHash(h) => Ok(h.hash),
+        _ => Err(ApiError::InternalError(
+            "unexpected parser response".to_string(),
+        )),
+    }
+}
+
+/// Build the ETag for a screen hash. The format is part of the tag because
+/// plain and styled bodies differ for the same screen.
+fn screen_etag(hash: &str, format: Format) -> String {
+    let format = match format {
+        Format::Plain => "plain",
+        Format::Styled => "styled",
+    };
+    format!("\"{}-{}\"", hash, format)
+}
+
+/// Whether an `If-None-Match` header value matches `etag`.
+fn if_none_match_matches(header: &str, etag: &str) -> bool {
+    header
+        .split(',')
+        .map(|t| t.trim())
+        .any(|t| t == "*" || t == etag || t.strip_prefix("W/") == Some(etag))
+}
+
 pub(super) async fn screen(
     State(state): State<AppState>,
     Path(name): Path<String>,
+    headers: axum::http::HeaderMap,
     axum::extract::Query(params): axum::extract::Query<ScreenQuery>,
-) -> Result<impl IntoResponse, ApiError> {
+) -> Result<axum::response::Response, ApiError> {
     let session = get_session(&state.sessions, &name)?;
+
+    let if_none_match = headers
+        .get(axum::http::header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok());
+
+    // A conditional request only needs the hash; anything else gets the
+    // screen and its hash from one parser query.
+    if let Some(inm) = if_none_match {
+        let etag = screen_etag(&query_screen_hash(&session).await?, params.format);
+        if if_none_match_matches(inm, &etag) {
+            return Ok((StatusCode::NOT_MODIFIED, [(axum::http::header::ETAG, etag)]).into_response());
+        }
+    }
+
     let response = tokio::time::timeout(
         PARSER_QUERY_TIMEOUT,
-        session.parser.query(Query::Screen { format: params.format }),
+        session.parser.query(Query::ScreenWithHash { format: params.format }),
     )
     .await
-    .map_err(|_| ApiError::ParserTimeout)?
-    .map_err(|_| ApiError::ParserUnavailable)?;
+    .map_err(|_| ApiError::ParserTimeout)??;
+    let crate::parser::state::QueryResponse::ScreenWithHash(response) = response else {
+        return Err(ApiError::InternalError(
+            "unexpected parser response".to_string(),
+        ));
+    };
 
     let last_activity_ms = session.activity.last_activity_ms();
-    Ok(Json(EnrichedScreen {
-        screen: response,
+    let body = Json(EnrichedScreen {
+        screen: crate::parser::state::QueryResponse::Screen(response.screen),
         last_activity_ms,
-    }))
+    });
+    let etag = screen_etag(&response.hash, params.format);
+    Ok(([(axum::http::header::ETAG, etag)], body).into_response())
+}
+
+#[derive(Deserialize)]
+pub(super) struct ExpectQuery {
+    pattern: String,
+    #[serde(default)]
+    regex: bool,
+    #[serde(default = "default_max_wait")]
+    timeout_ms: u64,
+}
+
+/// Block until a pattern appears in new output (see
+/// [`Session::wait_for_output`]).
+pub(super) async fn expect(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<ExpectQuery>,
+) -> Result<Json<crate::session::OutputMatch>, ApiError> {
+    use crate::session::ExpectError;
+
+    let session = get_session(&state.sessions, &name)?;
+    let timeout = std::time::Duration::from_millis(params.timeout_ms.min(MAX_WAIT_CEILING_MS));
+    session
+        .wait_for_output(&params.pattern, params.regex, timeout)
+        .await
+        .map(Json)
+        .map_err(|e| match e {
+            ExpectError::InvalidPattern(detail) => ApiError::InvalidRequest(detail),
+            ExpectError::Timeout => ApiError::ExpectTimeout,
+            ExpectError::Closed => ApiError::SessionNotFound(name.clone()),
+        })
 }
 
 #[derive(Deserialize)]
@@ -2284,6 +3163,8 @@ pub(super) struct ScrollbackQuery {
     offset: usize,
     #[serde(default = "default_limit")]
     limit: usize,
+    #[serde(default)]
+    from_end: bool,
 }
 
 fn default_limit() -> usize {
@@ -2303,15 +3184,295 @@ pub(super) async fn scrollback(
             format: params.format,
             offset: params.offset,
             limit,
+            from_end: params.from_end,
         }),
     )
     .await
-    .map_err(|_| ApiError::ParserTimeout)?
-    .map_err(|_| ApiError::ParserUnavailable)?;
+    .map_err(|_| ApiError::ParserTimeout)??;
 
     Ok(Json(response))
 }
 
+#[derive(Deserialize)]
+pub(super) struct LineQuery {
+    #[serde(default)]
+    format: Format,
+}
+
+/// One line by absolute index (scrollback then screen), as numbered by
+/// `/scrollback` offsets.
+pub(super) async fn scrollback_line(
+    State(state): State<AppState>,
+    Path((name, index)): Path<(String, usize)>,
+    axum::extract::Query(params): axum::extract::Query<LineQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    let response = tokio::time::timeout(
+        PARSER_QUERY_TIMEOUT,
+        session.parser.query(Query::Line { index, format: params.format }),
+    )
+    .await
+    .map_err(|_| ApiError::ParserTimeout)??;
+
+    Ok(Json(response))
+}
+
+/// Output of the most recent command, delimited by OSC 133 prompt marks.
+pub(super) async fn last_output(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    let response = tokio::time::timeout(
+        PARSER_QUERY_TIMEOUT,
+        session.parser.query(Query::LastCommandOutput),
+    )
+    .await
+    .map_err(|_| ApiError::ParserTimeout)??;
+
+    match response {
+        crate::parser::state::QueryResponse::LastCommandOutput(Some(output)) => Ok(Json(output)),
+        crate::parser::state::QueryResponse::LastCommandOutput(None) => Err(ApiError::NoCommandHistory),
+        _ => Err(ApiError::InternalError("unexpected parser response".to_string())),
+    }
+}
+
+/// Just the cursor, for clients that poll it between screen fetches.
+pub(super) async fn cursor(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<crate::parser::state::Cursor>, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    let response = tokio::time::timeout(PARSER_QUERY_TIMEOUT, session.parser.query(Query::Cursor))
+        .await
+        .map_err(|_| ApiError::ParserTimeout)??;
+
+    match response {
+        crate::parser::state::QueryResponse::Cursor(c) => Ok(Json(c.cursor)),
+        _ => Err(ApiError::InternalError("unexpected parser response".to_string())),
+    }
+}
+
+#[derive(Deserialize)]
+pub(super) struct DumpQuery {
+    #[serde(default)]
+    format: Format,
+    #[serde(default = "default_dump_scrollback_limit")]
+    scrollback_limit: usize,
+}
+
+fn default_dump_scrollback_limit() -> usize {
+    1000
+}
+
+#[derive(Serialize)]
+pub(super) struct DumpBody {
+    #[serde(flatten)]
+    dump: crate::parser::state::DumpResponse,
+    input_mode: Mode,
+}
+
+/// Screen, scrollback, modes and metadata in a single snapshot.
+pub(super) async fn dump(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<DumpQuery>,
+) -> Result<Json<DumpBody>, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    let response = tokio::time::timeout(
+        PARSER_QUERY_TIMEOUT,
+        session.parser.query(Query::Dump {
+            format: params.format,
+            scrollback_limit: params.scrollback_limit.min(10_000),
+        }),
+    )
+    .await
+    .map_err(|_| ApiError::ParserTimeout)??;
+
+    match response {
+        crate::parser::state::QueryResponse::Dump(dump) => Ok(Json(DumpBody {
+            dump,
+            input_mode: session.input_mode.get(),
+        })),
+        _ => Err(ApiError::InternalError("unexpected parser response".to_string())),
+    }
+}
+
+#[derive(Deserialize)]
+pub(super) struct FullTextQuery {
+    #[serde(default)]
+    format: Format,
+}
+
+/// Scrollback and screen as one string, for reading the whole transcript
+/// without paginating.
+pub(super) async fn full_text(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<FullTextQuery>,
+) -> Result<Json<crate::parser::state::FullTextResponse>, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    let response = tokio::time::timeout(
+        PARSER_QUERY_TIMEOUT,
+        session.parser.query(Query::FullText { format: params.format }),
+    )
+    .await
+    .map_err(|_| ApiError::ParserTimeout)??;
+
+    match response {
+        crate::parser::state::QueryResponse::FullText(text) => Ok(Json(text)),
+        _ => Err(ApiError::InternalError("unexpected parser response".to_string())),
+    }
+}
+
+/// Raw PTY output retained for export, oldest bytes first.
+///
+/// The body is the byte stream as the program wrote it. `x-wsh-raw-offset`
+/// gives the position of its first byte in the session's output (non-zero
+/// once older bytes were dropped) and `x-wsh-raw-total` the bytes seen so far.
+pub(super) async fn raw_log(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    let snapshot = session.raw_log.snapshot();
+    Ok((
+        [
+            ("content-type", "application/octet-stream".to_string()),
+            ("x-wsh-raw-offset", snapshot.offset.to_string()),
+            ("x-wsh-raw-total", snapshot.total.to_string()),
+        ],
+        snapshot.bytes,
+    ))
+}
+
+#[derive(Deserialize)]
+pub(super) struct LogsQuery {
+    /// Return only the newest `limit` lines.
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub(super) struct LogsResponse {
+    /// Whether the session is capturing log lines.
+    capture_logs: bool,
+    /// Most lines the session keeps.
+    capacity: usize,
+    /// Lines dropped to stay within `capacity`.
+    dropped: u64,
+    lines: Vec<crate::session_log::LogLine>,
+}
+
+/// Recent log lines for one session, oldest first. Best-effort: only
+/// events logged while doing work for the session are captured.
+pub(super) async fn session_logs(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<LogsQuery>,
+) -> Result<Json<LogsResponse>, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    Ok(Json(LogsResponse {
+        capture_logs: session.logs.enabled(),
+        capacity: crate::session_log::LOG_CAPACITY,
+        dropped: session.logs.dropped(),
+        lines: session.logs.lines(params.limit),
+    }))
+}
+
+/// Stop reading the session's PTY until `/resume`. Output the program
+/// writes meanwhile waits in the kernel's PTY buffer; once that fills, the
+/// program blocks on its next write.
+pub(super) async fn session_pause(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    session.read_pause.pause();
+    session.log_span().in_scope(|| tracing::debug!("PTY reading paused"));
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Start reading the PTY again; output held back by `/pause` follows in
+/// order.
+pub(super) async fn session_resume(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    session.read_pause.resume();
+    session.log_span().in_scope(|| tracing::debug!("PTY reading resumed"));
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub(super) struct ClearQuery {
+    #[serde(default)]
+    scope: crate::parser::state::ClearScope,
+}
+
+/// Erase the scrollback, the screen, or both without restarting the
+/// session. Output already sent to the parser is cleared too.
+pub(super) async fn clear(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<ClearQuery>,
+) -> Result<StatusCode, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    tokio::time::timeout(PARSER_QUERY_TIMEOUT, async {
+        session.parser.barrier().await?;
+        session.parser.clear(params.scope).await
+    })
+    .await
+    .map_err(|_| ApiError::ParserTimeout)??;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub(super) struct RestoreQuery {
+    #[serde(default)]
+    quiet: bool,
+}
+
+/// Parse previously captured output (typically a `/raw-log` export) into the
+/// session's terminal state. The bytes go to the parser only, never to the
+/// program. With `quiet`, subscribers get one `sync` at the end instead of
+/// a line-by-line replay.
+pub(super) async fn restore(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<RestoreQuery>,
+    body: Bytes,
+) -> Result<StatusCode, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    tokio::time::timeout(PARSER_QUERY_TIMEOUT, session.parser.restore(body, params.quiet))
+        .await
+        .map_err(|_| ApiError::ParserTimeout)??;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Inject bytes into the session's output as if the program had written
+/// them: the parser, `/ws/raw` clients, attached terminals and the raw log
+/// all see them, the program never does. This is the output side; `/input`
+/// is what writes to the program. Disabled unless the server runs with
+/// `--allow-feed`.
+pub(super) async fn feed(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    body: Bytes,
+) -> Result<StatusCode, ApiError> {
+    if !state.server_config.allow_feed() {
+        return Err(ApiError::FeedDisabled);
+    }
+    let session = get_session(&state.sessions, &name)?;
+    let fed = tokio::time::timeout(PARSER_QUERY_TIMEOUT, session.feed_output(body))
+        .await
+        .map_err(|_| ApiError::ParserTimeout)?;
+    if !fed {
+        return Err(ApiError::ParserUnavailable);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
 
 // Overlay request/response types
 #[derive(Deserialize)]
@@ -2326,6 +3487,10 @@ pub(super) struct CreateOverlayRequest {
     spans: Vec<OverlaySpan>,
     #[serde(default)]
     focusable: bool,
+    #[serde(default)]
+    wrap: bool,
+    #[serde(default)]
+    scope: OverlayScope,
 }
 
 #[derive(Serialize)]
@@ -2367,7 +3532,7 @@ pub(super) async fn overlay_create(
 ) -> Result<(StatusCode, Json<CreateOverlayResponse>), ApiError> {
     let session = get_session(&state.sessions, &name)?;
     let current_mode = *session.screen_mode.read();
-    let id = session.overlays.create(req.x, req.y, req.z, req.width, req.height, req.background, req.spans, req.focusable, current_mode)
+    let id = session.overlays.create_scoped(req.x, req.y, req.z, req.width, req.height, req.background, req.spans, req.focusable, req.wrap, current_mode, req.scope)
         .map_err(|e| ApiError::ResourceLimitReached(e.to_string()))?;
     let _ = session.visual_update_tx.send(crate::protocol::VisualUpdate::OverlaysChanged);
     Ok((StatusCode::CREATED, Json(CreateOverlayResponse { id })))
@@ -2379,7 +3544,7 @@ pub(super) async fn overlay_list(
 ) -> Result<Json<Vec<Overlay>>, ApiError> {
     let session = get_session(&state.sessions, &name)?;
     let mode = *session.screen_mode.read();
-    Ok(Json(session.overlays.list_by_mode(mode)))
+    Ok(Json(session.overlays.list_remote_by_mode(mode)))
 }
 
 pub(super) async fn overlay_get(
@@ -2488,6 +3653,8 @@ pub(super) struct CreatePanelRequest {
     spans: Vec<OverlaySpan>,
     #[serde(default)]
     focusable: bool,
+    #[serde(default)]
+    command: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -2528,6 +3695,21 @@ pub(super) async fn panel_create(
         .map_err(|e| ApiError::ResourceLimitReached(e.to_string()))?;
     panel::reconfigure_layout(&session.panels, &session.terminal_size, &session.pty, &session.parser)
         .await;
+    if let Some(command) = req.command {
+        if let Err(e) = panel::start_panel_command(
+            &session.panels,
+            &id,
+            &command,
+            &session.terminal_size,
+            &session.visual_update_tx,
+            &session.cancelled,
+        ) {
+            session.panels.delete(&id);
+            panel::reconfigure_layout(&session.panels, &session.terminal_size, &session.pty, &session.parser)
+                .await;
+            return Err(ApiError::InternalError(format!("failed to start panel command: {e}")));
+        }
+    }
     let _ = session.visual_update_tx.send(crate::protocol::VisualUpdate::PanelsChanged);
     Ok((StatusCode::CREATED, Json(CreatePanelResponse { id })))
 }
@@ -2541,6 +3723,14 @@ pub(super) async fn panel_list(
     Ok(Json(session.panels.list_by_mode(mode)))
 }
 
+pub(super) async fn panel_layout(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<panel::LayoutRects>, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    Ok(Json(panel::current_layout(&session.panels, &session.terminal_size)))
+}
+
 pub(super) async fn panel_get(
     State(state): State<AppState>,
     Path((name, id)): Path<(String, String)>,
@@ -2687,6 +3877,27 @@ pub(super) struct InputModeResponse {
     mode: Mode,
 }
 
+#[derive(Serialize)]
+pub(super) struct PromptStatusResponse {
+    input_mode: Mode,
+    clients: usize,
+    alternate_active: bool,
+}
+
+/// A few flags for a shell prompt to show, read without touching the
+/// parser queue so it stays cheap enough to call on every prompt.
+pub(super) async fn prompt_status(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<PromptStatusResponse>, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    Ok(Json(PromptStatusResponse {
+        input_mode: session.input_mode.get(),
+        clients: session.clients(),
+        alternate_active: session.parser.alternate_active(),
+    }))
+}
+
 // Input mode handlers
 pub(super) async fn input_mode_get(
     State(state): State<AppState>,
@@ -2738,7 +3949,7 @@ pub(super) async fn input_focus(
     let is_focusable = if let Some(overlay) = session.overlays.get(&req.id) {
         overlay.focusable
     } else if let Some(panel) = session.panels.get(&req.id) {
-        panel.focusable
+        panel.accepts_focus()
     } else {
         return Err(ApiError::InvalidRequest(format!(
             "no overlay or panel with id '{}'",
@@ -2754,6 +3965,24 @@ pub(super) async fn input_focus(
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// Give a panel input focus, so input sent to the session goes to the
+/// panel's command (or to input subscribers, for plain focusable panels).
+pub(super) async fn panel_focus(
+    State(state): State<AppState>,
+    Path((name, id)): Path<(String, String)>,
+) -> Result<StatusCode, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    let panel = session
+        .panels
+        .get(&id)
+        .ok_or_else(|| ApiError::PanelNotFound(id.clone()))?;
+    if !panel.accepts_focus() {
+        return Err(ApiError::NotFocusable(id));
+    }
+    session.focus.focus(id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub(super) async fn input_unfocus(
     State(state): State<AppState>,
     Path(name): Path<String>,
@@ -2801,6 +4030,43 @@ pub(super) struct CreateSessionRequest {
     pub env: Option<std::collections::HashMap<String, String>>,
     #[serde(default)]
     pub tags: Vec<String>,
+    /// Answer cursor position reports on the program's behalf.
+    #[serde(default)]
+    pub auto_reply_cpr: bool,
+    /// How PTY output bytes are decoded: `utf8`, `latin1`, or `passthrough`.
+    #[serde(default)]
+    pub decoding: crate::parser::decode::Decoding,
+    /// Start the shell as a login shell. Ignored when `command` is set.
+    #[serde(default)]
+    pub login: bool,
+    /// Re-wrap soft-wrapped lines when the terminal is resized. When false,
+    /// rows are cut off or padded instead.
+    #[serde(default = "default_reflow_on_resize")]
+    pub reflow_on_resize: bool,
+    /// Send output that rewrites at least this percentage of the screen's
+    /// rows as one `Diff` event. Off unless set.
+    pub redraw_threshold: Option<usize>,
+    /// Translate lone `\n` to `\r` in text input.
+    #[serde(default)]
+    pub normalize_newlines: bool,
+    /// In capture mode, draw captured keystrokes on the screen.
+    #[serde(default)]
+    pub echo_captured_input: bool,
+    /// Format of event streams whose `subscribe` doesn't name one.
+    #[serde(default)]
+    pub default_format: crate::parser::state::Format,
+    /// Regex matched against the cursor row by `wait-prompt`.
+    pub prompt_pattern: Option<String>,
+    /// How many bytes of raw output `/raw-log` keeps.
+    pub raw_log_bytes: Option<usize>,
+    /// How many recent events are kept for resuming a WebSocket stream.
+    pub journal_events: Option<usize>,
+    /// Cap on the serialized size of those events; 0 for none.
+    pub journal_bytes: Option<usize>,
+}
+
+fn default_reflow_on_resize() -> bool {
+    true
 }
 
 #[derive(Serialize)]
@@ -2813,6 +4079,28 @@ pub(super) struct SessionInfo {
     pub clients: usize,
     pub tags: Vec<String>,
     pub last_activity_ms: u64,
+    pub normalize_newlines: bool,
+    /// Whether captured keystrokes are drawn on the screen.
+    pub echo_captured_input: bool,
+    /// Format of event streams whose `subscribe` doesn't name one.
+    pub default_format: crate::parser::state::Format,
+    /// Bytes read from the PTY since the session started.
+    pub bytes_read: u64,
+    /// Bytes written to the PTY since the session started.
+    pub bytes_written: u64,
+    /// Share of the output broadcast buffer the slowest streaming client
+    /// hasn't read yet (0.0 to 1.0).
+    pub output_pressure: f32,
+    /// Prompt regex used by `wait-prompt`, if one is set.
+    pub prompt_pattern: Option<String>,
+    /// Retention size of the raw output log, in bytes.
+    pub raw_log_bytes: usize,
+    /// Range of event `seq`s a WebSocket resume can replay, and limits.
+    pub journal: crate::parser::journal::JournalStatus,
+    /// Whether log lines are captured for `/logs`.
+    pub capture_logs: bool,
+    /// Whether reading the PTY is paused by `/pause`.
+    pub paused: bool,
 }
 
 fn build_session_info(session: &crate::session::Session) -> SessionInfo {
@@ -2828,6 +4116,17 @@ fn build_session_info(session: &crate::session::Session) -> SessionInfo {
         clients: session.clients(),
         tags,
         last_activity_ms: session.activity.last_activity_ms(),
+        normalize_newlines: session.input_mode.normalize_newlines(),
+        echo_captured_input: session.input_mode.echo_captured(),
+        default_format: session.parser.default_format(),
+        bytes_read: session.io_counters.bytes_read(),
+        bytes_written: session.io_counters.bytes_written(),
+        output_pressure: crate::broker::output_pressure(&session.output_rx),
+        prompt_pattern: session.prompt_pattern.as_str(),
+        raw_log_bytes: session.raw_log.limit(),
+        journal: session.parser.journal_status(),
+        capture_logs: session.logs.enabled(),
+        paused: session.read_pause.is_paused(),
     }
 }
 
@@ -2841,6 +4140,20 @@ pub(super) struct UpdateSessionRequest {
     /// Tags to remove (optional)
     #[serde(default)]
     pub remove_tags: Vec<String>,
+    /// Turn newline normalization of text input on or off (optional)
+    pub normalize_newlines: Option<bool>,
+    /// Turn echoing of captured input on or off (optional)
+    pub echo_captured_input: Option<bool>,
+    /// Replace the prompt regex; an empty string clears it (optional)
+    pub prompt_pattern: Option<String>,
+    /// Change the raw output log's retention size (optional)
+    pub raw_log_bytes: Option<usize>,
+    /// Change how many events the replay journal keeps (optional)
+    pub journal_events: Option<usize>,
+    /// Change the replay journal's byte cap; 0 removes it (optional)
+    pub journal_bytes: Option<usize>,
+    /// Turn capture of the session's log lines on or off (optional)
+    pub capture_logs: Option<bool>,
 }
 
 #[derive(Deserialize)]
@@ -2885,15 +4198,21 @@ pub(super) async fn session_create(
         Some(cmd) => SpawnCommand::Command {
             command: cmd,
             interactive: true,
+            force_interactive: None,
+            scrub_env: None,
         },
         None => SpawnCommand::Shell {
             interactive: true,
             shell: None,
+            login: req.login,
+            force_interactive: None,
+            scrub_env: None,
         },
     };
 
-    let rows = req.rows.unwrap_or(24).clamp(1, 1000);
-    let cols = req.cols.unwrap_or(80).clamp(1, 1000);
+    let (default_rows, default_cols) = state.server_config.default_size();
+    let rows = req.rows.unwrap_or(default_rows).clamp(1, 1000);
+    let cols = req.cols.unwrap_or(default_cols).clamp(1, 1000);
 
     // Advisory pre-check — see name_available() doc for TOCTOU rationale.
     // The authoritative check is insert_and_get() below.
@@ -2911,14 +4230,33 @@ pub(super) async fn session_create(
     // Under load, fork() on a large-RSS process can take hundreds of ms,
     // so we run it on the blocking thread pool to avoid stalling the
     // async executor.
-    let cwd = req.cwd;
-    let env = req.env;
+    let prompt_pattern = crate::session::PromptPattern::default();
+    if let Some(pattern) = &req.prompt_pattern {
+        prompt_pattern.set(pattern).map_err(|e| invalid_prompt_pattern(&e))?;
+    }
+
+    let options = crate::session::SpawnOptions {
+        cwd: req.cwd,
+        env: req.env,
+        decoding: req.decoding,
+        read_buffer_bytes: state.sessions.read_buffer_bytes(),
+        prompt_pattern,
+        raw_log_bytes: req.raw_log_bytes.map_or(crate::broker::DEFAULT_RAW_LOG_LIMIT, |limit| limit.min(MAX_RAW_LOG_BYTES)),
+    };
     let (session, child_exit_rx) = tokio::task::spawn_blocking(move || {
-        Session::spawn_with_options("".to_string(), command, rows, cols, cwd, env)
+        Session::spawn_with_options("".to_string(), command, rows, cols, options)
     })
     .await
     .map_err(|e| ApiError::SessionCreateFailed(e.to_string()))?
-    .map_err(|e| ApiError::SessionCreateFailed(e.to_string()))?;
+    .map_err(|e| spawn_error(e, &state.sessions))?;
+    session.parser.set_reflow_on_resize(req.reflow_on_resize);
+    if let Some(percent) = req.redraw_threshold {
+        session.parser.set_redraw_threshold(percent);
+    }
+    session.input_mode.set_normalize_newlines(req.normalize_newlines);
+    session.input_mode.set_echo_captured(req.echo_captured_input);
+    session.parser.set_default_format(req.default_format);
+    apply_journal_limits(&session, req.journal_events, req.journal_bytes);
 
     // Validate and set initial tags before inserting into registry,
     // so that insert_and_get() properly indexes them.
@@ -2945,6 +4283,9 @@ pub(super) async fn session_create(
 
     // Monitor child exit so the session is auto-removed when the process dies.
     state.sessions.monitor_child_exit(assigned_name.clone(), session.client_count.clone(), session.child_exited.clone(), child_exit_rx);
+    if req.auto_reply_cpr {
+        session.spawn_cpr_responder();
+    }
 
     Ok((
         StatusCode::CREATED,
@@ -2965,6 +4306,11 @@ pub(super) async fn session_update(
     Path(name): Path<String>,
     Json(req): Json<UpdateSessionRequest>,
 ) -> Result<Json<SessionInfo>, ApiError> {
+    // Reject a bad prompt regex before any other change is applied.
+    if let Some(pattern) = req.prompt_pattern.as_deref().filter(|p| !p.is_empty()) {
+        regex::Regex::new(pattern).map_err(|e| invalid_prompt_pattern(&e))?;
+    }
+
     // Handle rename if requested
     let current_name = if let Some(new_name) = req.name {
         state.sessions.rename(&name, &new_name).map_err(|e| match e {
@@ -2997,9 +4343,55 @@ pub(super) async fn session_update(
     }
 
     let session = get_session(&state.sessions, &current_name)?;
+    if let Some(enabled) = req.normalize_newlines {
+        session.input_mode.set_normalize_newlines(enabled);
+    }
+    if let Some(enabled) = req.echo_captured_input {
+        session.input_mode.set_echo_captured(enabled);
+    }
+    if let Some(pattern) = &req.prompt_pattern {
+        session.prompt_pattern.set(pattern).map_err(|e| invalid_prompt_pattern(&e))?;
+    }
+    if let Some(limit) = req.raw_log_bytes {
+        session.raw_log.set_limit(limit.min(MAX_RAW_LOG_BYTES));
+    }
+    apply_journal_limits(&session, req.journal_events, req.journal_bytes);
+    if let Some(enabled) = req.capture_logs {
+        session.logs.set_enabled(enabled);
+    }
     Ok(Json(build_session_info(&session)))
 }
 
+/// Resize the session's replay journal, clamped to the server's maximums.
+fn apply_journal_limits(session: &crate::session::Session, events: Option<usize>, bytes: Option<usize>) {
+    if let Some(events) = events {
+        session.parser.set_journal_capacity(events.min(MAX_JOURNAL_EVENTS));
+    }
+    if let Some(bytes) = bytes {
+        session.parser.set_journal_max_bytes(bytes.min(MAX_JOURNAL_BYTES));
+    }
+}
+
+/// Map a failed session spawn to an API error. Running out of file
+/// descriptors or PTYs gets its own 503 so operators know to raise limits
+/// rather than hunt for a bug.
+fn spawn_error(err: crate::pty::PtyError, sessions: &crate::session::SessionRegistry) -> ApiError {
+    if err.is_resource_exhausted() {
+        tracing::error!(
+            sessions = sessions.len(),
+            "cannot open a PTY, out of file descriptors or PTYs (raise ulimit -n): {}",
+            err
+        );
+        ApiError::PtyExhausted(err.to_string())
+    } else {
+        ApiError::SessionCreateFailed(err.to_string())
+    }
+}
+
+fn invalid_prompt_pattern(e: &regex::Error) -> ApiError {
+    ApiError::InvalidRequest(format!("invalid prompt_pattern: {}", e))
+}
+
 pub(super) async fn session_kill(
     State(state): State<AppState>,
     Path(name): Path<String>,
@@ -3088,6 +4480,11 @@ pub(super) async fn server_persist_get(
     (StatusCode::OK, Json(serde_json::json!({"persistent": persistent})))
 }
 
+/// Estimated scrollback memory across sessions, against the budget.
+pub(super) async fn server_scrollback(State(state): State<AppState>) -> Json<crate::session::ScrollbackUsage> {
+    Json(state.sessions.scrollback_usage())
+}
+
 pub(super) async fn server_persist_set(
     State(state): State<AppState>,
     Json(body): Json<serde_json::Value>,
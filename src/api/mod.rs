@@ -24,6 +24,11 @@ use crate::shutdown::ShutdownCoordinator;
 
 use handlers::*;
 
+/// Version of the HTTP/WebSocket wire format, reported by `GET /version`
+/// and in the `connected` frame of `/ws/json`. Bump whenever an event or
+/// message schema changes.
+pub const PROTOCOL_VERSION: u32 = 11;
+
 /// Configuration controlling server lifecycle behavior.
 ///
 /// In ephemeral mode (default, `persistent = false`) the server shuts down
@@ -31,15 +36,63 @@ use handlers::*;
 /// stays alive indefinitely, waiting for new sessions to be created.
 pub struct ServerConfig {
     persistent: AtomicBool,
+    /// (rows, cols) for sessions created without explicit dimensions.
+    default_size: (u16, u16),
+    /// How long an input request waits for room in a full input queue.
+    input_timeout: std::time::Duration,
+    /// Whether `POST /sessions/:name/feed` may inject output.
+    allow_feed: bool,
 }
 
+/// Default for [`ServerConfig::input_timeout`].
+pub const DEFAULT_INPUT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
 impl ServerConfig {
     pub fn new(persistent: bool) -> Self {
         Self {
             persistent: AtomicBool::new(persistent),
+            default_size: (24, 80),
+            input_timeout: DEFAULT_INPUT_TIMEOUT,
+            allow_feed: false,
         }
     }
 
+    /// Set how long `/input` waits for a stalled PTY writer before failing
+    /// with `503 input_timeout`.
+    pub fn with_input_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.input_timeout = timeout;
+        self
+    }
+
+    /// How long an input request waits for room in a full input queue.
+    pub fn input_timeout(&self) -> std::time::Duration {
+        self.input_timeout
+    }
+
+    /// Set the size used when a create-session request omits `rows`/`cols`.
+    pub fn with_default_size(mut self, rows: u16, cols: u16) -> Self {
+        self.default_size = (rows, cols);
+        self
+    }
+
+    /// (rows, cols) for sessions created without explicit dimensions.
+    pub fn default_size(&self) -> (u16, u16) {
+        self.default_size
+    }
+
+    /// Allow `POST /sessions/:name/feed` to inject output into sessions.
+    /// Off by default: injected output can forge anything a program could
+    /// draw, including prompts.
+    pub fn with_allow_feed(mut self, allow: bool) -> Self {
+        self.allow_feed = allow;
+        self
+    }
+
+    /// Whether `POST /sessions/:name/feed` is enabled.
+    pub fn allow_feed(&self) -> bool {
+        self.allow_feed
+    }
+
     pub fn is_persistent(&self) -> bool {
         self.persistent.load(Ordering::Acquire)
     }
@@ -117,16 +170,35 @@ pub fn router(state: AppState, config: RouterConfig) -> Router {
     );
     let session_routes = Router::new()
         .route("/input", post(input))
+        .route("/input/keys", post(keys))
+        .route("/input/eof", post(input_eof))
         .route("/input/mode", get(input_mode_get))
         .route("/input/capture", post(input_capture))
         .route("/input/release", post(input_release))
         .route("/input/focus", get(input_focus_get).post(input_focus))
         .route("/input/unfocus", post(input_unfocus))
         .route("/idle", get(idle))
+        .route("/prompt-status", get(prompt_status))
+        .route("/wait-prompt", get(wait_prompt))
+        .route("/ready", get(ready))
+        .route("/expect", get(expect))
         .route("/ws/raw", get(ws_raw))
+        .route("/ws/view", get(ws_view))
         .route("/ws/json", get(ws_json))
         .route("/screen", get(screen))
+        .route("/cursor", get(cursor))
         .route("/scrollback", get(scrollback))
+        .route("/scrollback/{index}", get(scrollback_line))
+        .route("/last-output", get(last_output))
+        .route("/dump", get(dump))
+        .route("/text", get(full_text))
+        .route("/clear", post(clear))
+        .route("/restore", post(restore))
+        .route("/feed", post(feed))
+        .route("/raw-log", get(raw_log))
+        .route("/logs", get(session_logs))
+        .route("/pause", post(session_pause))
+        .route("/resume", post(session_resume))
         .route(
             "/overlay",
             get(overlay_list)
@@ -157,6 +229,8 @@ pub fn router(state: AppState, config: RouterConfig) -> Router {
         )
         .route("/panel/{id}/spans", post(panel_update_spans))
         .route("/panel/{id}/write", post(panel_region_write))
+        .route("/panel/{id}/focus", post(panel_focus))
+        .route("/layout", get(panel_layout))
         .route("/screen_mode", get(screen_mode_get))
         .route("/screen_mode/enter_alt", post(enter_alt_screen))
         .route("/screen_mode/exit_alt", post(exit_alt_screen));
@@ -175,6 +249,7 @@ pub fn router(state: AppState, config: RouterConfig) -> Router {
         .route("/sessions/{name}/detach", post(session_detach))
         .route("/idle", get(idle_any))
         .route("/server/persist", get(server_persist_get).put(server_persist_set))
+        .route("/server/scrollback", get(server_scrollback))
         .route("/ws/json", get(ws_json_server));
 
     let protected = Router::new()
@@ -226,10 +301,16 @@ pub fn router(state: AppState, config: RouterConfig) -> Router {
     let router = Router::new()
         .route("/", get(|| async { Redirect::temporary("/ui") }))
         .route("/health", get(health))
+        .route("/version", get(version))
+        .route("/capabilities", get(capabilities))
+        .route("/schema/events", get(event_schema))
+        .route("/keys/translate", get(translate_key))
         .route("/openapi.yaml", get(openapi_spec))
         .route("/docs", get(docs_index))
         .merge(protected)
         .nest("/ui", ui)
+        // Unknown paths get the same JSON error envelope as handler errors.
+        .fallback(|| async { error::ApiError::NotFound })
         .layer(DefaultBodyLimit::max(1024 * 1024)) // 1 MB
         .layer(SetResponseHeaderLayer::overriding(
             HeaderName::from_static("x-frame-options"),
@@ -293,6 +374,7 @@ mod tests {
             child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
             input_tx,
             output_rx: broker.sender(),
+            output_mirror: broker.mirror(),
             shutdown: ShutdownCoordinator::new(),
             parser,
             overlays: OverlayStore::new(),
@@ -310,6 +392,13 @@ mod tests {
             visual_update_tx: tokio::sync::broadcast::channel::<crate::protocol::VisualUpdate>(16).0,
             screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(crate::overlay::ScreenMode::Normal)),
             cancelled: tokio_util::sync::CancellationToken::new(),
+            io_counters: crate::session::IoCounters::default(),
+            prompt_pattern: crate::session::PromptPattern::default(),
+            raw_log: crate::broker::RawLog::default(),
+            logs: crate::session_log::SessionLog::default(),
+            read_pause: crate::session::ReadPause::default(),
+            output_ready: crate::session::OutputReady::default(),
+            output_feed: crate::session::OutputFeed::default(),
         };
         let registry = crate::session::SessionRegistry::new();
         registry.insert(Some("test".into()), session).unwrap();
@@ -341,6 +430,96 @@ mod tests {
         assert_eq!(json["status"], "ok");
     }
 
+    #[tokio::test]
+    async fn test_version_endpoint() {
+        let (state, _input_rx, _name) = create_test_state();
+        let app = router(state, RouterConfig::default());
+
+        let response = app
+            .oneshot(Request::builder().uri("/version").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["crate_version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(json["protocol_version"], PROTOCOL_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_capabilities_endpoint() {
+        let (state, _input_rx, _name) = create_test_state();
+        let app = router(state, RouterConfig::default());
+
+        let response = app
+            .oneshot(Request::builder().uri("/capabilities").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["protocol_version"], PROTOCOL_VERSION);
+        assert_eq!(json["events"].as_array().unwrap().len(), 12);
+        assert!(json["events"].as_array().unwrap().contains(&serde_json::json!("cwd")));
+        assert_eq!(json["formats"], serde_json::json!(["plain", "styled"]));
+        assert_eq!(json["features"]["journal"], true);
+        assert_eq!(json["features"]["mouse"], false);
+    }
+
+    #[tokio::test]
+    async fn test_event_schema_endpoint() {
+        let (state, _input_rx, _name) = create_test_state();
+        let app = router(state, RouterConfig::default());
+
+        let response = app
+            .oneshot(Request::builder().uri("/schema/events").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["title"], "Event");
+        assert!(json["oneOf"].is_array());
+    }
+
+    #[tokio::test]
+    async fn test_translate_key_endpoint() {
+        let (state, _input_rx, _name) = create_test_state();
+        let app = router(state, RouterConfig::default());
+
+        let get = |uri: &'static str| app.clone().oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap());
+        let response = get("/keys/translate?key=Ctrl%2BC").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["hex"], "03");
+        assert_eq!(json["bytes"], serde_json::json!([3]));
+
+        let response = get("/keys/translate?key=Home&cursor_key_app_mode=true").await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["hex"], "1b4f48");
+
+        let response = get("/keys/translate?key=Hyper").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let message = json["error"]["message"].as_str().unwrap();
+        assert!(message.contains("PageDown") && message.contains("Ctrl+A"), "{message}");
+    }
+
     #[tokio::test]
     async fn test_input_endpoint_success() {
         let (state, _input_rx, _name) = create_test_state();
@@ -360,6 +539,221 @@ mod tests {
         assert_eq!(response.status(), StatusCode::NO_CONTENT);
     }
 
+    #[tokio::test]
+    async fn test_input_eof_sends_eot_even_when_captured() {
+        let (state, mut input_rx, _name) = create_test_state();
+        let app = router(state, RouterConfig::default());
+
+        let post = |uri: &'static str| {
+            app.clone()
+                .oneshot(Request::builder().method("POST").uri(uri).body(Body::empty()).unwrap())
+        };
+        // Capture only holds back local keystrokes; API input still goes through.
+        assert_eq!(post("/sessions/test/input/capture").await.unwrap().status(), StatusCode::NO_CONTENT);
+        assert_eq!(post("/sessions/test/input/eof").await.unwrap().status(), StatusCode::NO_CONTENT);
+        assert_eq!(input_rx.recv().await.unwrap(), Bytes::from_static(b"\x04"));
+
+        assert_eq!(post("/sessions/missing/input/eof").await.unwrap().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_session_logs_capture_and_toggle() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let (state, _input_rx, _name) = create_test_state();
+        let session = state.sessions.get("test").unwrap();
+        let app = router(state, RouterConfig::default());
+        let _subscriber = tracing::subscriber::set_default(
+            tracing_subscriber::registry().with(crate::session_log::layer()),
+        );
+
+        session.log_span().in_scope(|| tracing::debug!(rows = 30, "resized"));
+        tracing::info!("not for any session");
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("PATCH")
+                    .uri("/sessions/test")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"capture_logs": false}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["capture_logs"], false);
+        session.log_span().in_scope(|| tracing::info!("while capture is off"));
+
+        let response = app
+            .oneshot(Request::builder().uri("/sessions/test/logs").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["capture_logs"], false);
+        assert_eq!(json["dropped"], 0);
+        let lines = json["lines"].as_array().unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["level"], "DEBUG");
+        assert_eq!(lines[0]["message"], "resized rows=30");
+    }
+
+    #[tokio::test]
+    async fn test_ready_times_out_before_any_output() {
+        let (state, _input_rx, _name) = create_test_state();
+        let app = router(state, RouterConfig::default());
+        let get = |uri: &'static str| {
+            app.clone().oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        };
+
+        let response = get("/sessions/test/ready?timeout_ms=50").await.unwrap();
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+
+        let response = get("/sessions/test/ready?pattern=(").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_session_pause_and_resume() {
+        let (state, _input_rx, _name) = create_test_state();
+        let app = router(state, RouterConfig::default());
+
+        let paused = |method: &'static str, uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .clone()
+                    .oneshot(Request::builder().method(method).uri(uri).body(Body::empty()).unwrap())
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::NO_CONTENT);
+                let response = app
+                    .oneshot(Request::builder().uri("/sessions/test").body(Body::empty()).unwrap())
+                    .await
+                    .unwrap();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                json["paused"].clone()
+            }
+        };
+        assert_eq!(paused("POST", "/sessions/test/pause").await, true);
+        assert_eq!(paused("POST", "/sessions/test/resume").await, false);
+    }
+
+    #[tokio::test]
+    async fn test_input_newline_normalization() {
+        let (state, mut input_rx, _name) = create_test_state();
+        let app = router(state, RouterConfig::default());
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("PATCH")
+                    .uri("/sessions/test")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"normalize_newlines": true}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["normalize_newlines"], true);
+
+        for (content_type, expected) in [
+            ("text/plain", &b"ls\recho\r\n"[..]),
+            ("application/octet-stream", &b"ls\necho\r\n"[..]),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri("/sessions/test/input")
+                        .header("content-type", content_type)
+                        .body(Body::from("ls\necho\r\n"))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NO_CONTENT);
+            assert_eq!(input_rx.recv().await.unwrap().as_ref(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_patch_prompt_pattern_validates_sets_and_clears() {
+        let (state, _input_rx, _name) = create_test_state();
+        let app = router(state, RouterConfig::default());
+
+        for (body, status, expected) in [
+            (r#"{"prompt_pattern": "[$#] $"}"#, StatusCode::OK, serde_json::json!("[$#] $")),
+            (r#"{"prompt_pattern": "(", "name": "renamed"}"#, StatusCode::BAD_REQUEST, serde_json::Value::Null),
+            (r#"{"prompt_pattern": ""}"#, StatusCode::OK, serde_json::Value::Null),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method("PATCH")
+                        .uri("/sessions/test")
+                        .header("content-type", "application/json")
+                        .body(Body::from(body))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), status, "{body}");
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            if status == StatusCode::OK {
+                assert_eq!(json["prompt_pattern"], expected);
+            } else {
+                assert_eq!(json["error"]["code"], "invalid_request");
+            }
+        }
+        // The rejected request must not have renamed the session.
+        let response = app
+            .oneshot(Request::builder().uri("/sessions/test").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_input_endpoint_times_out_when_queue_full() {
+        let (mut state, _input_rx, _name) = create_test_state();
+        state.server_config = Arc::new(
+            ServerConfig::new(false).with_input_timeout(std::time::Duration::from_millis(100)),
+        );
+        // Fill the queue; the receiver is never drained.
+        let session = state.sessions.get("test").unwrap();
+        while session.input_tx.try_send(Bytes::from_static(b"x")).is_ok() {}
+        let app = router(state, RouterConfig::default());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/sessions/test/input")
+                    .body(Body::from("stuck"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"]["code"], "input_timeout");
+    }
+
     #[tokio::test]
     async fn test_input_endpoint_forwards_to_channel() {
         let (state, mut input_rx, _name) = create_test_state();
@@ -438,6 +832,11 @@ mod tests {
             .await
             .unwrap();
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"]["code"], "not_found");
 
         // Web UI is served under /ui (SPA fallback)
         let response = app
@@ -530,6 +929,7 @@ mod tests {
                     underline: false,
                 }],
                 false,
+                false,
                 crate::overlay::ScreenMode::Normal,
             ).unwrap();
         }
@@ -557,6 +957,53 @@ mod tests {
         assert_eq!(json[0]["y"], 2);
     }
 
+    #[tokio::test]
+    async fn test_overlay_scope_filters_api_list() {
+        let (state, _input_rx, _name) = create_test_state();
+
+        for scope in ["local_only", "remote_only"] {
+            let body = serde_json::json!({
+                "x": 0, "y": 0, "width": 10, "height": 1,
+                "spans": [{ "text": scope }],
+                "scope": scope,
+            });
+            let response = router(state.clone(), RouterConfig::default())
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri("/sessions/test/overlay")
+                        .header("content-type", "application/json")
+                        .body(Body::from(serde_json::to_string(&body).unwrap()))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::CREATED);
+        }
+
+        let response = router(state.clone(), RouterConfig::default())
+            .oneshot(
+                Request::builder()
+                    .uri("/sessions/test/overlay")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json.len(), 1);
+        assert_eq!(json[0]["scope"], "remote_only");
+
+        // The local-only overlay is still drawn for attached terminals.
+        let session = state.sessions.get("test").unwrap();
+        let local = session.overlays.list_local_by_mode(crate::overlay::ScreenMode::Normal);
+        assert_eq!(local.len(), 1);
+        assert_eq!(local[0].scope, crate::overlay::OverlayScope::LocalOnly);
+    }
+
     #[tokio::test]
     async fn test_overlay_delete() {
         let (state, _input_rx, _name) = create_test_state();
@@ -564,7 +1011,7 @@ mod tests {
         // Create an overlay
         let id = {
             let session = state.sessions.get("test").unwrap();
-            session.overlays.create(0, 0, None, 80, 1, None, vec![], false, crate::overlay::ScreenMode::Normal).unwrap()
+            session.overlays.create(0, 0, None, 80, 1, None, vec![], false, false, crate::overlay::ScreenMode::Normal).unwrap()
         };
 
         let app = router(state, RouterConfig::default());
@@ -607,6 +1054,36 @@ mod tests {
         assert_eq!(json["mode"], "passthrough");
     }
 
+    #[tokio::test]
+    async fn test_prompt_status() {
+        let (state, _input_rx, _name) = create_test_state();
+        let session = state.sessions.get("test").unwrap();
+        session.input_mode.capture();
+        let _client = session.connect().unwrap();
+        let app = router(state, RouterConfig::default());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/sessions/test/prompt-status")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"input_mode": "capture", "clients": 1, "alternate_active": false})
+        );
+    }
+
     #[tokio::test]
     async fn test_input_capture_and_release() {
         let (state, _input_rx, _name) = create_test_state();
@@ -1723,6 +2200,7 @@ mod tests {
                     id: None, fg: None, bg: None, bold: false, italic: false, underline: false,
                 }],
                 false,
+                false,
                 crate::overlay::ScreenMode::Normal,
             ).unwrap();
         }
@@ -1737,6 +2215,7 @@ mod tests {
                     id: None, fg: None, bg: None, bold: false, italic: false, underline: false,
                 }],
                 false,
+                false,
                 crate::overlay::ScreenMode::Alt,
             ).unwrap();
         }
@@ -116,13 +116,40 @@ pub struct SubscribeParams {
     pub events: Vec<EventType>,
     #[serde(default = "default_interval")]
     pub interval_ms: u64,
-    #[serde(default)]
-    pub format: Format,
+    /// Event format; the session's default format when omitted.
+    pub format: Option<Format>,
     /// When > 0, the server will emit `idle` and `running` events whenever the
     /// terminal transitions between active and idle states. The value specifies
     /// the idle timeout in milliseconds.
     #[serde(default, alias = "quiesce_ms")]
     pub idle_timeout_ms: u64,
+    /// When > 0, the server emits a `heartbeat` event every this many
+    /// milliseconds so the stream never goes silent. Off by default.
+    #[serde(default)]
+    pub heartbeat_ms: u64,
+    /// After a `line` event, further updates to the same line within this
+    /// many milliseconds are folded into one event carrying the latest
+    /// content (e.g. `\r` progress bars). 0 (the default) sends every
+    /// update.
+    #[serde(default = "default_line_coalesce")]
+    pub line_coalesce_ms: u64,
+    /// Send a folded line early once the terminal has been quiet for this
+    /// many milliseconds, rather than holding it for the rest of
+    /// `line_coalesce_ms`. 0 always waits out the window.
+    #[serde(default = "default_idle_flush")]
+    pub idle_flush_ms: u64,
+    /// Follow the initial `sync` with one `line` event per screen row, for
+    /// clients that only handle `line` events.
+    #[serde(default)]
+    pub initial_lines: bool,
+}
+
+fn default_line_coalesce() -> u64 {
+    crate::parser::coalesce::DEFAULT_LINE_COALESCE_MS
+}
+
+fn default_idle_flush() -> u64 {
+    crate::parser::coalesce::DEFAULT_IDLE_FLUSH_MS
 }
 
 /// Parameters for the `await_idle` WebSocket method.
@@ -166,6 +193,17 @@ pub struct ScrollbackParams {
     pub offset: usize,
     #[serde(default = "default_scrollback_limit")]
     pub limit: usize,
+    /// Count `offset` back from the newest line instead of the oldest.
+    #[serde(default)]
+    pub from_end: bool,
+}
+
+/// Parameters for the `get_line` method.
+#[derive(Debug, Deserialize)]
+pub struct LineParams {
+    pub index: usize,
+    #[serde(default)]
+    pub format: Format,
 }
 
 fn default_scrollback_limit() -> usize {
@@ -177,6 +215,10 @@ fn default_scrollback_limit() -> usize {
 pub struct ResizeParams {
     pub cols: u16,
     pub rows: u16,
+    /// Return the resized screen in this format, captured before any
+    /// further output is parsed.
+    #[serde(default)]
+    pub format: Option<Format>,
 }
 
 /// Parameters for the `send_input` method.
@@ -219,6 +261,12 @@ pub struct CreateOverlayParams {
     pub spans: Vec<OverlaySpan>,
     #[serde(default)]
     pub focusable: bool,
+    /// Wrap span text at the overlay's right edge.
+    #[serde(default)]
+    pub wrap: bool,
+    /// Which clients render the overlay.
+    #[serde(default)]
+    pub scope: crate::overlay::OverlayScope,
 }
 
 /// Parameters for replacing an overlay's spans.
@@ -265,6 +313,9 @@ pub struct CreatePanelParams {
     pub spans: Vec<OverlaySpan>,
     #[serde(default)]
     pub focusable: bool,
+    /// Command whose live screen fills the panel.
+    #[serde(default)]
+    pub command: Option<String>,
 }
 
 /// Parameters for fully replacing a panel.
@@ -397,7 +448,7 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
             let is_focusable = if let Some(overlay) = session.overlays.get(&params.id) {
                 overlay.focusable
             } else if let Some(panel) = session.panels.get(&params.id) {
-                panel.focusable
+                panel.accepts_focus()
             } else {
                 return WsResponse::error(
                     id,
@@ -427,7 +478,7 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
         }
         "list_overlays" => {
             let mode = *session.screen_mode.read();
-            let overlays = session.overlays.list_by_mode(mode);
+            let overlays = session.overlays.list_remote_by_mode(mode);
             WsResponse::success(id, method, serde_json::to_value(&overlays).unwrap())
         }
         "clear_overlays" => {
@@ -442,7 +493,7 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
                 Err(e) => return e,
             };
             let current_mode = *session.screen_mode.read();
-            let overlay_id = match session.overlays.create(params.x, params.y, params.z, params.width, params.height, params.background, params.spans, params.focusable, current_mode) {
+            let overlay_id = match session.overlays.create_scoped(params.x, params.y, params.z, params.width, params.height, params.background, params.spans, params.focusable, params.wrap, current_mode, params.scope) {
                 Ok(id) => id,
                 Err(e) => return WsResponse::error(id, method, "resource_limit_reached", e),
             };
@@ -550,6 +601,78 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
                 ),
             }
         }
+        "get_screen_hash" => {
+            match tokio::time::timeout(
+                PARSER_QUERY_TIMEOUT,
+                session.parser.query(Query::ScreenHash),
+            ).await {
+                Ok(Ok(resp)) => WsResponse::success(
+                    id,
+                    method,
+                    serde_json::to_value(&resp).unwrap(),
+                ),
+                Ok(Err(_)) => WsResponse::error(
+                    id,
+                    method,
+                    "parser_unavailable",
+                    "Terminal parser is unavailable.",
+                ),
+                Err(_) => WsResponse::error(
+                    id,
+                    method,
+                    "parser_timeout",
+                    "Parser query timed out.",
+                ),
+            }
+        }
+        "get_scroll_region" => {
+            match tokio::time::timeout(
+                PARSER_QUERY_TIMEOUT,
+                session.parser.query(Query::ScrollRegion),
+            ).await {
+                Ok(Ok(resp)) => WsResponse::success(
+                    id,
+                    method,
+                    serde_json::to_value(&resp).unwrap(),
+                ),
+                Ok(Err(_)) => WsResponse::error(
+                    id,
+                    method,
+                    "parser_unavailable",
+                    "Terminal parser is unavailable.",
+                ),
+                Err(_) => WsResponse::error(
+                    id,
+                    method,
+                    "parser_timeout",
+                    "Parser query timed out.",
+                ),
+            }
+        }
+        "get_default_colors" => {
+            match tokio::time::timeout(
+                PARSER_QUERY_TIMEOUT,
+                session.parser.query(Query::DefaultColors),
+            ).await {
+                Ok(Ok(resp)) => WsResponse::success(
+                    id,
+                    method,
+                    serde_json::to_value(&resp).unwrap(),
+                ),
+                Ok(Err(_)) => WsResponse::error(
+                    id,
+                    method,
+                    "parser_unavailable",
+                    "Terminal parser is unavailable.",
+                ),
+                Err(_) => WsResponse::error(
+                    id,
+                    method,
+                    "parser_timeout",
+                    "Parser query timed out.",
+                ),
+            }
+        }
         "get_scrollback" => {
             let params: ScrollbackParams = match parse_params(req) {
                 Ok(p) => p,
@@ -561,6 +684,38 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
                     format: params.format,
                     offset: params.offset,
                     limit: params.limit.min(10_000),
+                    from_end: params.from_end,
+                }),
+            ).await {
+                Ok(Ok(resp)) => WsResponse::success(
+                    id,
+                    method,
+                    serde_json::to_value(&resp).unwrap(),
+                ),
+                Ok(Err(_)) => WsResponse::error(
+                    id,
+                    method,
+                    "parser_unavailable",
+                    "Terminal parser is unavailable.",
+                ),
+                Err(_) => WsResponse::error(
+                    id,
+                    method,
+                    "parser_timeout",
+                    "Parser query timed out.",
+                ),
+            }
+        }
+        "get_line" => {
+            let params: LineParams = match parse_params(req) {
+                Ok(p) => p,
+                Err(e) => return e,
+            };
+            match tokio::time::timeout(
+                PARSER_QUERY_TIMEOUT,
+                session.parser.query(Query::Line {
+                    index: params.index,
+                    format: params.format,
                 }),
             ).await {
                 Ok(Ok(resp)) => WsResponse::success(
@@ -568,6 +723,9 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
                     method,
                     serde_json::to_value(&resp).unwrap(),
                 ),
+                Ok(Err(crate::parser::ParserError::InvalidQuery(detail))) => {
+                    WsResponse::error(id, method, "invalid_request", &detail)
+                }
                 Ok(Err(_)) => WsResponse::error(
                     id,
                     method,
@@ -588,7 +746,7 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
                 Err(e) => return e,
             };
             let bytes = match params.encoding {
-                InputEncoding::Utf8 => bytes::Bytes::from(params.data),
+                InputEncoding::Utf8 => session.input_mode.normalize(bytes::Bytes::from(params.data)),
                 InputEncoding::Base64 => {
                     use base64::Engine;
                     match base64::engine::general_purpose::STANDARD.decode(&params.data) {
@@ -606,7 +764,7 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
             };
             match tokio::time::timeout(
                 std::time::Duration::from_secs(5),
-                session.input_tx.send(bytes),
+                session.input_target().send(bytes),
             ).await {
                 Ok(Ok(())) => {
                     session.activity.touch();
@@ -633,20 +791,45 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
             };
             let rows = params.rows.clamp(1, 1000);
             let cols = params.cols.clamp(1, 1000);
-            if let Err(e) = session.pty.lock().resize(rows, cols) {
-                tracing::warn!(?e, "failed to resize PTY via WS");
-            }
-            if let Err(e) = session.parser.resize(cols as usize, rows as usize).await {
-                tracing::warn!(?e, "failed to resize parser via WS");
+            let Some(format) = params.format else {
+                if let Err(e) = session.resize(rows, cols).await {
+                    tracing::warn!(?e, "failed to resize parser via WS");
+                }
+                return WsResponse::success(id, method, serde_json::json!({}));
+            };
+            let result = tokio::time::timeout(
+                PARSER_QUERY_TIMEOUT,
+                session.resize_and_screen(rows, cols, format),
+            ).await;
+            match result {
+                Ok(Ok(screen)) => WsResponse::success(
+                    id,
+                    method,
+                    serde_json::to_value(&screen).unwrap(),
+                ),
+                Ok(Err(_)) => WsResponse::error(
+                    id,
+                    method,
+                    "parser_unavailable",
+                    "Terminal parser is unavailable.",
+                ),
+                Err(_) => WsResponse::error(
+                    id,
+                    method,
+                    "parser_timeout",
+                    "Parser query timed out.",
+                ),
             }
-            session.terminal_size.set(rows, cols);
-            WsResponse::success(id, method, serde_json::json!({}))
         }
         "list_panels" => {
             let mode = *session.screen_mode.read();
             let panels = session.panels.list_by_mode(mode);
             WsResponse::success(id, method, serde_json::to_value(&panels).unwrap())
         }
+        "get_layout" => {
+            let layout = crate::panel::current_layout(&session.panels, &session.terminal_size);
+            WsResponse::success(id, method, serde_json::to_value(&layout).unwrap())
+        }
         "clear_panels" => {
             session.panels.clear();
             session.focus.unfocus();
@@ -679,6 +862,31 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
                 &session.parser,
             )
             .await;
+            if let Some(command) = params.command {
+                if let Err(e) = crate::panel::start_panel_command(
+                    &session.panels,
+                    &panel_id,
+                    &command,
+                    &session.terminal_size,
+                    &session.visual_update_tx,
+                    &session.cancelled,
+                ) {
+                    session.panels.delete(&panel_id);
+                    crate::panel::reconfigure_layout(
+                        &session.panels,
+                        &session.terminal_size,
+                        &session.pty,
+                        &session.parser,
+                    )
+                    .await;
+                    return WsResponse::error(
+                        id,
+                        method,
+                        "internal_error",
+                        &format!("Internal error: failed to start panel command: {e}."),
+                    );
+                }
+            }
             let _ = session.visual_update_tx.send(crate::protocol::VisualUpdate::PanelsChanged);
             WsResponse::success(id, method, serde_json::json!({ "id": panel_id }))
         }
@@ -1131,6 +1339,16 @@ mod tests {
         assert_eq!(params.encoding, InputEncoding::Base64);
     }
 
+    #[test]
+    fn deserialize_scrollback_params_from_end() {
+        let params: ScrollbackParams = serde_json::from_value(json!({})).unwrap();
+        assert!(!params.from_end);
+        let params: ScrollbackParams =
+            serde_json::from_value(json!({ "limit": 10, "from_end": true })).unwrap();
+        assert!(params.from_end);
+        assert_eq!(params.limit, 10);
+    }
+
     #[test]
     fn await_idle_params_defaults_max_wait() {
         let json = r#"{"timeout_ms": 500}"#;
@@ -1151,7 +1369,15 @@ mod tests {
         assert_eq!(params.events[1], EventType::Cursor);
         assert_eq!(params.events[2], EventType::Diffs);
         assert_eq!(params.interval_ms, 200);
-        assert_eq!(params.format, Format::Plain);
+        assert_eq!(params.format, Some(Format::Plain));
+        assert_eq!(params.heartbeat_ms, 0);
+    }
+
+    #[test]
+    fn deserialize_subscribe_params_with_heartbeat() {
+        let raw = json!({ "events": [], "heartbeat_ms": 15000 });
+        let params: SubscribeParams = serde_json::from_value(raw).unwrap();
+        assert_eq!(params.heartbeat_ms, 15_000);
     }
 
     // -----------------------------------------------------------------------
@@ -1181,6 +1407,7 @@ mod tests {
             child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
             input_tx,
             output_rx: broker.sender(),
+            output_mirror: broker.mirror(),
             shutdown: ShutdownCoordinator::new(),
             parser,
             overlays: OverlayStore::new(),
@@ -1195,6 +1422,13 @@ mod tests {
             visual_update_tx: tokio::sync::broadcast::channel::<crate::protocol::VisualUpdate>(16).0,
             screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(crate::overlay::ScreenMode::Normal)),
             cancelled: tokio_util::sync::CancellationToken::new(),
+            io_counters: crate::session::IoCounters::default(),
+            prompt_pattern: crate::session::PromptPattern::default(),
+            raw_log: crate::broker::RawLog::default(),
+            logs: crate::session_log::SessionLog::default(),
+            read_pause: crate::session::ReadPause::default(),
+            output_ready: crate::session::OutputReady::default(),
+            output_feed: crate::session::OutputFeed::default(),
         };
         (session, input_rx, parser_tx)
     }
@@ -1287,7 +1521,7 @@ mod tests {
     #[tokio::test]
     async fn dispatch_clear_overlays() {
         let (session, _rx, _parser_tx) = create_test_session();
-        session.overlays.create(0, 0, None, 80, 1, None, vec![], false, crate::overlay::ScreenMode::Normal).unwrap();
+        session.overlays.create(0, 0, None, 80, 1, None, vec![], false, false, crate::overlay::ScreenMode::Normal).unwrap();
         assert_eq!(session.overlays.list().len(), 1);
 
         let req = WsRequest {
@@ -1342,6 +1576,57 @@ mod tests {
         assert!(json["result"]["lines"].is_array());
     }
 
+    #[tokio::test]
+    async fn dispatch_get_line() {
+        let (session, _rx, _parser_tx) = create_test_session();
+        let req = WsRequest {
+            id: None,
+            method: "get_line".to_string(),
+            params: Some(serde_json::json!({"index": 0, "format": "plain"})),
+        };
+        let json = serde_json::to_value(dispatch(&req, &session).await).unwrap();
+        assert_eq!(json["result"]["index"], 0);
+        assert_eq!(json["result"]["line"], "");
+
+        let req = WsRequest {
+            id: None,
+            method: "get_line".to_string(),
+            params: Some(serde_json::json!({"index": 100000})),
+        };
+        let json = serde_json::to_value(dispatch(&req, &session).await).unwrap();
+        assert_eq!(json["error"]["code"], "invalid_request");
+    }
+
+    #[tokio::test]
+    async fn dispatch_get_scroll_region() {
+        let (session, _rx, parser_tx) = create_test_session();
+        parser_tx.send(Bytes::from("\x1b[2;20r")).await.unwrap();
+        session.parser.barrier().await.unwrap();
+        let req = WsRequest {
+            id: None,
+            method: "get_scroll_region".to_string(),
+            params: None,
+        };
+        let json = serde_json::to_value(dispatch(&req, &session).await).unwrap();
+        assert_eq!(json["result"]["top"], 1);
+        assert_eq!(json["result"]["bottom"], 19);
+    }
+
+    #[tokio::test]
+    async fn dispatch_get_default_colors() {
+        let (session, _rx, parser_tx) = create_test_session();
+        parser_tx.send(Bytes::from("\x1b]11;#1e1e2e\x07")).await.unwrap();
+        session.parser.barrier().await.unwrap();
+        let req = WsRequest {
+            id: None,
+            method: "get_default_colors".to_string(),
+            params: None,
+        };
+        let json = serde_json::to_value(dispatch(&req, &session).await).unwrap();
+        assert_eq!(json["result"]["fg"], serde_json::Value::Null);
+        assert_eq!(json["result"]["bg"], serde_json::json!({"rgb": {"r": 30, "g": 30, "b": 46}}));
+    }
+
     #[tokio::test]
     async fn dispatch_send_input_utf8() {
         let (session, mut rx, _parser_tx) = create_test_session();
@@ -1408,6 +1693,24 @@ mod tests {
         assert_eq!(cols, 120);
     }
 
+    #[tokio::test]
+    async fn dispatch_resize_with_format_returns_resized_screen() {
+        let (session, _rx, parser_tx) = create_test_session();
+        parser_tx.send(Bytes::from("hello")).await.unwrap();
+        session.parser.barrier().await.unwrap();
+        let req = WsRequest {
+            id: None,
+            method: "resize".to_string(),
+            params: Some(json!({"cols": 100, "rows": 30, "format": "plain"})),
+        };
+        let resp = dispatch(&req, &session).await;
+        let json = serde_json::to_value(&resp).unwrap();
+        assert_eq!(json["result"]["cols"], 100);
+        assert_eq!(json["result"]["rows"], 30);
+        assert_eq!(json["result"]["lines"][0], "hello");
+        assert_eq!(session.terminal_size.get(), (30, 100));
+    }
+
     #[tokio::test]
     async fn dispatch_resize_clamps_to_min_1() {
         let (session, _rx, _parser_tx) = create_test_session();
@@ -1448,7 +1751,7 @@ mod tests {
         let id = session.overlays.create(5, 10, None, 80, 1, None, vec![crate::overlay::OverlaySpan {
             text: "Test".to_string(),
             id: None, fg: None, bg: None, bold: false, italic: false, underline: false,
-        }], false, crate::overlay::ScreenMode::Normal).unwrap();
+        }], false, false, crate::overlay::ScreenMode::Normal).unwrap();
         let req = WsRequest {
             id: None,
             method: "get_overlay".to_string(),
@@ -1479,7 +1782,7 @@ mod tests {
         let id = session.overlays.create(0, 0, None, 80, 1, None, vec![crate::overlay::OverlaySpan {
             text: "Old".to_string(),
             id: None, fg: None, bg: None, bold: false, italic: false, underline: false,
-        }], false, crate::overlay::ScreenMode::Normal).unwrap();
+        }], false, false, crate::overlay::ScreenMode::Normal).unwrap();
         let req = WsRequest {
             id: None,
             method: "update_overlay".to_string(),
@@ -1495,7 +1798,7 @@ mod tests {
     #[tokio::test]
     async fn dispatch_patch_overlay() {
         let (session, _rx, _parser_tx) = create_test_session();
-        let id = session.overlays.create(0, 0, None, 80, 1, None, vec![], false, crate::overlay::ScreenMode::Normal).unwrap();
+        let id = session.overlays.create(0, 0, None, 80, 1, None, vec![], false, false, crate::overlay::ScreenMode::Normal).unwrap();
         let req = WsRequest {
             id: None,
             method: "patch_overlay".to_string(),
@@ -1512,7 +1815,7 @@ mod tests {
     #[tokio::test]
     async fn dispatch_delete_overlay() {
         let (session, _rx, _parser_tx) = create_test_session();
-        let id = session.overlays.create(0, 0, None, 80, 1, None, vec![], false, crate::overlay::ScreenMode::Normal).unwrap();
+        let id = session.overlays.create(0, 0, None, 80, 1, None, vec![], false, false, crate::overlay::ScreenMode::Normal).unwrap();
         let req = WsRequest {
             id: None,
             method: "delete_overlay".to_string(),
@@ -1554,6 +1857,30 @@ mod tests {
         assert_eq!(json["result"], serde_json::json!([]));
     }
 
+    #[tokio::test]
+    async fn dispatch_get_layout() {
+        let (session, _rx, _parser_tx) = create_test_session();
+        session.terminal_size.set(24, 80);
+        let req = WsRequest {
+            id: Some(json!(1)),
+            method: "create_panel".to_string(),
+            params: Some(json!({"position": "bottom", "height": 2})),
+        };
+        dispatch(&req, &session).await;
+
+        let req = WsRequest {
+            id: Some(json!(2)),
+            method: "get_layout".to_string(),
+            params: None,
+        };
+        let json = serde_json::to_value(dispatch(&req, &session).await).unwrap();
+        let result = &json["result"];
+        assert_eq!(result["pty"], json!({"row": 0, "col": 0, "width": 80, "height": 22}));
+        assert_eq!(result["panels"][0]["row"], 22);
+        assert_eq!(result["panels"][0]["height"], 2);
+        assert_eq!(result["hidden_panels"], json!([]));
+    }
+
     #[tokio::test]
     async fn dispatch_create_panel() {
         let (session, _rx, _parser_tx) = create_test_session();
@@ -1801,7 +2128,7 @@ mod tests {
                 id: Some("lbl".to_string()),
                 fg: None, bg: None, bold: false, italic: false, underline: false,
             },
-        ], false, crate::overlay::ScreenMode::Normal).unwrap();
+        ], false, false, crate::overlay::ScreenMode::Normal).unwrap();
         let req = WsRequest {
             id: Some(json!(1)),
             method: "update_overlay_spans".to_string(),
@@ -1833,7 +2160,7 @@ mod tests {
     #[tokio::test]
     async fn dispatch_overlay_region_write() {
         let (session, _rx, _parser_tx) = create_test_session();
-        let oid = session.overlays.create(0, 0, None, 80, 1, None, vec![], false, crate::overlay::ScreenMode::Normal).unwrap();
+        let oid = session.overlays.create(0, 0, None, 80, 1, None, vec![], false, false, crate::overlay::ScreenMode::Normal).unwrap();
         let req = WsRequest {
             id: Some(json!(2)),
             method: "overlay_region_write".to_string(),
@@ -1959,7 +2286,7 @@ mod tests {
                 id: Some("title".to_string()),
                 fg: None, bg: None, bold: false, italic: false, underline: false,
             },
-        ], false, crate::overlay::ScreenMode::Normal).unwrap();
+        ], false, false, crate::overlay::ScreenMode::Normal).unwrap();
         let req = WsRequest {
             id: Some(json!(5)),
             method: "batch_update".to_string(),
@@ -2057,7 +2384,7 @@ mod tests {
                 id: Some("a".to_string()),
                 fg: None, bg: None, bold: false, italic: false, underline: false,
             },
-        ], false, crate::overlay::ScreenMode::Normal).unwrap();
+        ], false, false, crate::overlay::ScreenMode::Normal).unwrap();
         let req = WsRequest {
             id: None,
             method: "batch_update".to_string(),
@@ -2078,7 +2405,7 @@ mod tests {
     #[tokio::test]
     async fn dispatch_batch_update_writes_only() {
         let (session, _rx, _parser_tx) = create_test_session();
-        let oid = session.overlays.create(0, 0, None, 80, 1, None, vec![], false, crate::overlay::ScreenMode::Normal).unwrap();
+        let oid = session.overlays.create(0, 0, None, 80, 1, None, vec![], false, false, crate::overlay::ScreenMode::Normal).unwrap();
         let req = WsRequest {
             id: None,
             method: "batch_update".to_string(),
@@ -2116,7 +2443,7 @@ mod tests {
     #[tokio::test]
     async fn dispatch_focus_focusable_overlay() {
         let (session, _rx, _parser_tx) = create_test_session();
-        let oid = session.overlays.create(0, 0, None, 80, 1, None, vec![], true, crate::overlay::ScreenMode::Normal).unwrap();
+        let oid = session.overlays.create(0, 0, None, 80, 1, None, vec![], true, false, crate::overlay::ScreenMode::Normal).unwrap();
         let req = WsRequest {
             id: None,
             method: "focus".to_string(),
@@ -2131,7 +2458,7 @@ mod tests {
     #[tokio::test]
     async fn dispatch_focus_non_focusable_overlay() {
         let (session, _rx, _parser_tx) = create_test_session();
-        let oid = session.overlays.create(0, 0, None, 80, 1, None, vec![], false, crate::overlay::ScreenMode::Normal).unwrap();
+        let oid = session.overlays.create(0, 0, None, 80, 1, None, vec![], false, false, crate::overlay::ScreenMode::Normal).unwrap();
         let req = WsRequest {
             id: None,
             method: "focus".to_string(),
@@ -2181,7 +2508,7 @@ mod tests {
     #[tokio::test]
     async fn dispatch_unfocus() {
         let (session, _rx, _parser_tx) = create_test_session();
-        let oid = session.overlays.create(0, 0, None, 80, 1, None, vec![], true, crate::overlay::ScreenMode::Normal).unwrap();
+        let oid = session.overlays.create(0, 0, None, 80, 1, None, vec![], true, false, crate::overlay::ScreenMode::Normal).unwrap();
         session.focus.focus(oid.clone());
         assert_eq!(session.focus.focused(), Some(oid));
 
@@ -2199,7 +2526,7 @@ mod tests {
     #[tokio::test]
     async fn dispatch_get_focus_after_focus() {
         let (session, _rx, _parser_tx) = create_test_session();
-        let oid = session.overlays.create(0, 0, None, 80, 1, None, vec![], true, crate::overlay::ScreenMode::Normal).unwrap();
+        let oid = session.overlays.create(0, 0, None, 80, 1, None, vec![], true, false, crate::overlay::ScreenMode::Normal).unwrap();
         session.focus.focus(oid.clone());
 
         let req = WsRequest {
@@ -2346,13 +2673,13 @@ mod tests {
         let (session, _rx, _parser_tx) = create_test_session();
 
         // Create overlay in normal mode
-        session.overlays.create(0, 0, None, 80, 1, None, vec![], false, crate::overlay::ScreenMode::Normal).unwrap();
+        session.overlays.create(0, 0, None, 80, 1, None, vec![], false, false, crate::overlay::ScreenMode::Normal).unwrap();
 
         // Switch to alt mode
         *session.screen_mode.write() = crate::overlay::ScreenMode::Alt;
 
         // Create overlay in alt mode
-        session.overlays.create(0, 0, None, 80, 1, None, vec![], false, crate::overlay::ScreenMode::Alt).unwrap();
+        session.overlays.create(0, 0, None, 80, 1, None, vec![], false, false, crate::overlay::ScreenMode::Alt).unwrap();
 
         // list_overlays should only return alt-mode overlays
         let req = WsRequest {
@@ -2484,11 +2811,11 @@ mod tests {
         *session.screen_mode.write() = crate::overlay::ScreenMode::Alt;
 
         // Create alt-mode overlay and panel
-        session.overlays.create(0, 0, None, 80, 1, None, vec![], false, crate::overlay::ScreenMode::Alt).unwrap();
+        session.overlays.create(0, 0, None, 80, 1, None, vec![], false, false, crate::overlay::ScreenMode::Alt).unwrap();
         session.panels.create(crate::panel::Position::Bottom, 1, None, None, vec![], false, crate::overlay::ScreenMode::Alt).unwrap();
 
         // Also create normal-mode elements (should survive)
-        session.overlays.create(0, 0, None, 80, 1, None, vec![], false, crate::overlay::ScreenMode::Normal).unwrap();
+        session.overlays.create(0, 0, None, 80, 1, None, vec![], false, false, crate::overlay::ScreenMode::Normal).unwrap();
         session.panels.create(crate::panel::Position::Top, 1, None, None, vec![], false, crate::overlay::ScreenMode::Normal).unwrap();
 
         assert_eq!(session.overlays.list().len(), 2);
@@ -1,14 +1,36 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
 use bytes::Bytes;
-use tokio::sync::broadcast;
+use parking_lot::Mutex;
+use tokio::sync::{broadcast, mpsc};
 
 pub const BROADCAST_CAPACITY: usize = 64;
 
+/// Per-subscriber buffer for the lossless mirror. A subscriber that lets it
+/// fill is cut off (see [`MirrorError::Overflowed`]); the PTY reader never
+/// waits for it.
+pub const MIRROR_CAPACITY: usize = 256;
+
+/// Default for [`RawLog`] retention: off. The log isn't counted in the
+/// scrollback memory budget, so sessions opt in with a size.
+pub const DEFAULT_RAW_LOG_LIMIT: usize = 0;
+
 /// Distributes PTY output to streaming API clients via a broadcast channel.
 ///
-/// The broadcast channel is lossy by design: if a subscriber falls behind,
-/// it receives a `Lagged` error and must re-query state. This is fine for
-/// streaming clients (WebSocket, socket) because they can recover by
-/// re-fetching the current screen.
+/// PTY output has two kinds of consumers:
+///
+/// - **Lossy** (this broadcast): WebSocket clients and `wait_for_output`
+///   style waiters. If a subscriber falls behind, it receives a `Lagged`
+///   error and must re-query state. This is fine because they can recover
+///   by re-fetching the current screen.
+/// - **Lossless** ([`OutputMirror`]): the socket clients that mirror the
+///   session to a local terminal's stdout. A dropped chunk there corrupts
+///   the user's terminal (half an escape sequence, missing lines), so these
+///   subscribers get every byte in order until they fall a whole queue
+///   behind. Then they are cut off and resync from the screen instead of
+///   continuing with a gap. Neither kind ever slows the PTY reader.
 ///
 /// # Parser channel is NOT here
 ///
@@ -20,19 +42,24 @@ pub const BROADCAST_CAPACITY: usize = 64;
 #[derive(Clone)]
 pub struct Broker {
     tx: broadcast::Sender<Bytes>,
+    mirror: OutputMirror,
 }
 
 impl Broker {
     pub fn new() -> Self {
         let (tx, _) = broadcast::channel(BROADCAST_CAPACITY);
-        Self { tx }
+        Self {
+            tx,
+            mirror: OutputMirror::new(),
+        }
     }
 
     /// Publish PTY output to streaming clients.
     ///
     /// This is non-blocking and lossy: lagged subscribers get dropped messages.
     /// The parser receives data through a separate bounded channel with
-    /// backpressure (see session.rs), NOT through this broadcast.
+    /// backpressure (see session.rs), NOT through this broadcast. Lossless
+    /// subscribers are fed separately through [`Broker::mirror`].
     pub fn publish(&self, data: Bytes) {
         let _ = self.tx.send(data);
     }
@@ -44,6 +71,61 @@ impl Broker {
     pub fn sender(&self) -> broadcast::Sender<Bytes> {
         self.tx.clone()
     }
+
+    /// The lossless fan-out sharing this broker's output.
+    pub fn mirror(&self) -> OutputMirror {
+        self.mirror.clone()
+    }
+
+    /// How close the slowest streaming subscriber is to lagging; see
+    /// [`output_pressure`].
+    pub fn pressure(&self) -> f32 {
+        output_pressure(&self.tx)
+    }
+
+    /// Forward published output into `sink` from a spawned task, for
+    /// embedders that want it in their own channel or writer rather than a
+    /// `broadcast::Receiver`.
+    ///
+    /// Like any streaming subscriber the sink is lossy: if it falls behind by
+    /// more than [`BROADCAST_CAPACITY`] chunks the missed ones are skipped.
+    /// Use [`Broker::mirror`] when every byte matters. The task ends when a
+    /// send to the sink fails (e.g. its receiving half was dropped) or the
+    /// broker goes away, closing the sink in the latter case.
+    pub fn pipe_to<S>(&self, sink: S) -> tokio::task::JoinHandle<()>
+    where
+        S: futures::Sink<Bytes> + Send + Unpin + 'static,
+    {
+        use futures::SinkExt;
+
+        let mut rx = self.subscribe();
+        let mut sink = sink;
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(data) => {
+                        if sink.send(data).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::debug!(skipped = n, "output sink lagged");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        let _ = sink.close().await;
+                        return;
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// Fraction of [`BROADCAST_CAPACITY`] taken by chunks the slowest subscriber
+/// of `tx` hasn't read yet, from 0.0 to 1.0. At 1.0 the next publish makes
+/// that subscriber lag. Zero with no subscribers.
+pub fn output_pressure(tx: &broadcast::Sender<Bytes>) -> f32 {
+    (tx.len() as f32 / BROADCAST_CAPACITY as f32).min(1.0)
 }
 
 impl Default for Broker {
@@ -52,9 +134,206 @@ impl Default for Broker {
     }
 }
 
+/// Lossless fan-out of PTY output over one bounded mpsc channel per
+/// subscriber.
+///
+/// Unlike the broadcast in [`Broker`], a subscriber never silently skips
+/// chunks: it sees every byte in order, or its stream ends with
+/// [`MirrorError::Overflowed`] right after the last chunk it was sent. The
+/// sender never waits, so a stalled subscriber can't stall the PTY reader
+/// (and with it the child and every other client). A subscriber leaves by
+/// dropping its receiver.
+#[derive(Clone, Default)]
+pub struct OutputMirror {
+    subscribers: Arc<Mutex<Vec<MirrorSender>>>,
+}
+
+struct MirrorSender {
+    tx: mpsc::Sender<Bytes>,
+    overflowed: Arc<AtomicBool>,
+}
+
+/// Why a [`MirrorReceiver`] stopped yielding output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MirrorError {
+    /// The subscriber fell [`MIRROR_CAPACITY`] chunks behind and was cut
+    /// off. Everything before the cut was delivered; the caller should
+    /// resync from the parser's screen and subscribe again.
+    Overflowed,
+    /// The session's output ended.
+    Closed,
+}
+
+/// Receiving half of an [`OutputMirror`] subscription.
+pub struct MirrorReceiver {
+    rx: mpsc::Receiver<Bytes>,
+    overflowed: Arc<AtomicBool>,
+}
+
+impl MirrorReceiver {
+    /// The next chunk, or why there are no more. Cancellation-safe.
+    pub async fn recv(&mut self) -> Result<Bytes, MirrorError> {
+        match self.rx.recv().await {
+            Some(data) => Ok(data),
+            None if self.overflowed.load(Ordering::Acquire) => Err(MirrorError::Overflowed),
+            None => Err(MirrorError::Closed),
+        }
+    }
+}
+
+impl OutputMirror {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe(&self) -> MirrorReceiver {
+        let (tx, rx) = mpsc::channel(MIRROR_CAPACITY);
+        let overflowed = Arc::new(AtomicBool::new(false));
+        self.subscribers.lock().push(MirrorSender {
+            tx,
+            overflowed: overflowed.clone(),
+        });
+        MirrorReceiver { rx, overflowed }
+    }
+
+    /// Deliver `data` to every subscriber without waiting.
+    ///
+    /// A subscriber whose buffer is full is dropped with its overflow flag
+    /// set, so its receiver drains what it already has and then reports
+    /// [`MirrorError::Overflowed`]. Subscribers that have gone away are
+    /// pruned.
+    pub fn send(&self, data: Bytes) {
+        self.subscribers.lock().retain(|sub| match sub.tx.try_send(data.clone()) {
+            Ok(()) => true,
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                sub.overflowed.store(true, Ordering::Release);
+                false
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => false,
+        });
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.lock().len()
+    }
+}
+
+/// The most recent PTY output bytes, exactly as read, for export.
+///
+/// Unlike scrollback this is the stream before terminal interpretation:
+/// escape sequences and all. Only the last `limit` bytes are kept; older
+/// ones are dropped from the front, so the log may start mid-sequence.
+#[derive(Clone)]
+pub struct RawLog {
+    inner: Arc<Mutex<RawLogState>>,
+}
+
+struct RawLogState {
+    buf: VecDeque<u8>,
+    limit: usize,
+    /// Bytes appended over the log's lifetime, including dropped ones.
+    total: u64,
+}
+
+/// A copy of a [`RawLog`]'s contents.
+pub struct RawLogSnapshot {
+    pub bytes: Bytes,
+    /// Position of the first byte of `bytes` in the session's output.
+    pub offset: u64,
+    /// Bytes of output seen so far; `offset + bytes.len()`.
+    pub total: u64,
+}
+
+impl RawLog {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(RawLogState {
+                buf: VecDeque::new(),
+                limit,
+                total: 0,
+            })),
+        }
+    }
+
+    pub fn append(&self, data: &[u8]) {
+        let mut state = self.inner.lock();
+        state.total += data.len() as u64;
+        let keep = data.len().min(state.limit);
+        state.buf.extend(&data[data.len() - keep..]);
+        state.trim();
+    }
+
+    pub fn snapshot(&self) -> RawLogSnapshot {
+        let state = self.inner.lock();
+        let (front, back) = state.buf.as_slices();
+        let mut bytes = Vec::with_capacity(state.buf.len());
+        bytes.extend_from_slice(front);
+        bytes.extend_from_slice(back);
+        RawLogSnapshot {
+            offset: state.total - bytes.len() as u64,
+            total: state.total,
+            bytes: Bytes::from(bytes),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.inner.lock().limit
+    }
+
+    /// Change the retention size. Shrinking drops the oldest bytes now;
+    /// 0 stops recording.
+    pub fn set_limit(&self, limit: usize) {
+        let mut state = self.inner.lock();
+        state.limit = limit;
+        state.trim();
+    }
+}
+
+impl RawLogState {
+    fn trim(&mut self) {
+        let excess = self.buf.len().saturating_sub(self.limit);
+        self.buf.drain(..excess);
+        if self.limit == 0 {
+            self.buf.shrink_to_fit();
+        }
+    }
+}
+
+impl Default for RawLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_RAW_LOG_LIMIT)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use futures::StreamExt;
+
+    #[test]
+    fn raw_log_keeps_the_newest_bytes() {
+        let log = RawLog::new(8);
+        log.append(b"\x1b[1mab");
+        log.append(b"cdefgh");
+        let snap = log.snapshot();
+        assert_eq!(&snap.bytes[..], b"abcdefgh");
+        assert_eq!((snap.offset, snap.total), (4, 12));
+
+        // A chunk larger than the limit keeps only its tail.
+        log.append(b"0123456789");
+        assert_eq!(&log.snapshot().bytes[..], b"23456789");
+
+        log.set_limit(3);
+        let snap = log.snapshot();
+        assert_eq!(&snap.bytes[..], b"789");
+        assert_eq!((snap.offset, snap.total), (19, 22));
+
+        log.set_limit(0);
+        log.append(b"more");
+        let snap = log.snapshot();
+        assert!(snap.bytes.is_empty());
+        assert_eq!((snap.offset, snap.total), (26, 26));
+    }
 
     #[tokio::test]
     async fn test_new_creates_broker() {
@@ -150,4 +429,123 @@ mod tests {
         let received = rx.recv().await.expect("should receive message from clone");
         assert_eq!(received, Bytes::from("from clone"));
     }
+
+    #[tokio::test]
+    async fn test_pressure_tracks_slowest_subscriber() {
+        let broker = Broker::new();
+        broker.publish(Bytes::from("unheard"));
+        assert_eq!(broker.pressure(), 0.0);
+
+        let mut fast = broker.subscribe();
+        let mut slow = broker.subscribe();
+        for _ in 0..BROADCAST_CAPACITY / 4 {
+            broker.publish(Bytes::from("x"));
+        }
+        while fast.try_recv().is_ok() {}
+        assert_eq!(broker.pressure(), 0.25);
+
+        while slow.try_recv().is_ok() {}
+        assert_eq!(broker.pressure(), 0.0);
+
+        for _ in 0..BROADCAST_CAPACITY * 2 {
+            broker.publish(Bytes::from("x"));
+        }
+        assert_eq!(broker.pressure(), 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_mirror_delivers_every_message_to_slow_subscriber() {
+        let broker = Broker::new();
+        let mut lossy = broker.subscribe();
+        let mut lossless = broker.mirror().subscribe();
+        let count = BROADCAST_CAPACITY * 2;
+
+        // Nothing reads until the broadcast buffer has overflowed.
+        for i in 0..count {
+            let data = Bytes::from(i.to_string());
+            broker.publish(data.clone());
+            broker.mirror().send(data);
+        }
+        for i in 0..count {
+            assert_eq!(lossless.recv().await, Ok(Bytes::from(i.to_string())));
+        }
+
+        // The broadcast subscriber fell behind and lost output.
+        assert!(matches!(
+            lossy.recv().await,
+            Err(broadcast::error::RecvError::Lagged(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_mirror_cuts_off_full_subscriber_without_waiting() {
+        let mirror = OutputMirror::new();
+        let mut stalled = mirror.subscribe();
+        let mut draining = mirror.subscribe();
+
+        for i in 0..=MIRROR_CAPACITY {
+            mirror.send(Bytes::from(i.to_string()));
+            assert_eq!(draining.recv().await, Ok(Bytes::from(i.to_string())));
+        }
+        assert_eq!(mirror.subscriber_count(), 1);
+
+        // Everything sent before the cut arrives, in order, then the overflow.
+        for i in 0..MIRROR_CAPACITY {
+            assert_eq!(stalled.recv().await, Ok(Bytes::from(i.to_string())));
+        }
+        assert_eq!(stalled.recv().await, Err(MirrorError::Overflowed));
+    }
+
+    #[tokio::test]
+    async fn test_mirror_prunes_dropped_subscribers() {
+        let mirror = Broker::new().mirror();
+        let rx = mirror.subscribe();
+        let mut kept = mirror.subscribe();
+        assert_eq!(mirror.subscriber_count(), 2);
+        drop(rx);
+
+        mirror.send(Bytes::from("x"));
+        assert_eq!(mirror.subscriber_count(), 1);
+        assert_eq!(kept.recv().await, Ok(Bytes::from("x")));
+    }
+
+    #[tokio::test]
+    async fn test_mirror_reports_closed_when_output_ends() {
+        let mirror = OutputMirror::new();
+        let mut rx = mirror.subscribe();
+        mirror.send(Bytes::from("last"));
+        drop(mirror);
+        assert_eq!(rx.recv().await, Ok(Bytes::from("last")));
+        assert_eq!(rx.recv().await, Err(MirrorError::Closed));
+    }
+
+    #[tokio::test]
+    async fn test_pipe_to_forwards_until_sink_closes() {
+        let broker = Broker::new();
+        let (sink, mut out) = futures::channel::mpsc::channel::<Bytes>(8);
+        let task = broker.pipe_to(sink);
+
+        broker.publish(Bytes::from("one"));
+        broker.publish(Bytes::from("two"));
+        assert_eq!(out.next().await.unwrap(), Bytes::from("one"));
+        assert_eq!(out.next().await.unwrap(), Bytes::from("two"));
+
+        // The next send fails once the receiving side is gone.
+        drop(out);
+        broker.publish(Bytes::from("three"));
+        tokio::time::timeout(std::time::Duration::from_secs(1), task)
+            .await
+            .expect("forwarding task should end")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pipe_to_closes_sink_when_broker_dropped() {
+        let broker = Broker::new();
+        let (sink, mut out) = futures::channel::mpsc::channel::<Bytes>(8);
+        let task = broker.pipe_to(sink);
+        drop(broker);
+        task.await.unwrap();
+        assert!(out.next().await.is_none());
+    }
 }
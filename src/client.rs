@@ -3,6 +3,9 @@
 //! Provides a thin CLI client that connects to the server's Unix socket,
 //! sends control frames (CreateSession / AttachSession), and then enters
 //! a streaming I/O proxy loop forwarding stdin/stdout over the socket.
+//!
+//! With the `http-client` feature, [`http`] is a typed client for the
+//! HTTP/WebSocket API instead, for programs embedding wsh.
 
 use std::io;
 use std::path::Path;
@@ -15,6 +18,9 @@ use crate::overlay::{self, Overlay};
 use crate::panel::{self, Panel};
 use crate::protocol::*;
 
+#[cfg(feature = "http-client")]
+pub mod http;
+
 /// Render the panel sync update, writing ANSI escape sequences to `w`.
 ///
 /// Handles scroll region transitions carefully: DECSTBM (`\x1b[r`) moves the
@@ -291,9 +297,10 @@ impl Client {
         }
     }
 
-    /// Request the server to shut down gracefully.
-    pub async fn shutdown_server(&mut self) -> io::Result<()> {
-        let msg = ShutdownServerMsg {};
+    /// Request the server to shut down gracefully. `restart` tells it
+    /// clients should expect it back shortly.
+    pub async fn shutdown_server(&mut self, restart: bool) -> io::Result<()> {
+        let msg = ShutdownServerMsg { restart };
         let frame = Frame::control(FrameType::ShutdownServer, &msg)
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
         frame.write_to(&mut self.stream).await?;
@@ -323,6 +330,26 @@ impl Client {
     /// - Handles SIGWINCH signals and sends Resize frames
     /// - Exits on stdin EOF or server disconnect
     pub async fn run_streaming(self) -> io::Result<()> {
+        self.stream_terminal(std::io::stdin(), std::io::stdout()).await
+    }
+
+    /// [`Client::run_streaming`] with `input` in place of stdin and `output`
+    /// in place of stdout, for driving the local-terminal path (keystrokes,
+    /// Ctrl+\ detach) from tests without a tty.
+    #[cfg(any(test, feature = "test-support"))]
+    pub async fn run_streaming_with<R, W>(self, input: R, output: W) -> io::Result<()>
+    where
+        R: std::io::Read + std::os::unix::io::AsRawFd + Send + 'static,
+        W: std::io::Write + Send + 'static,
+    {
+        self.stream_terminal(input, output).await
+    }
+
+    async fn stream_terminal<R, W>(self, mut input: R, output: W) -> io::Result<()>
+    where
+        R: std::io::Read + std::os::unix::io::AsRawFd + Send + 'static,
+        W: std::io::Write + Send + 'static,
+    {
         let (reader, writer) = tokio::io::split(self.stream);
 
         // Channel for stdin data from the blocking reader
@@ -348,12 +375,8 @@ impl Client {
 
         // Spawn stdin reader in a blocking thread.
         let stdin_handle = tokio::task::spawn_blocking(move || {
-            use std::io::Read;
-            use std::os::unix::io::AsRawFd;
-
             let _cancel_rd = cancel_rd; // keep alive; closed on exit
-            let stdin = std::io::stdin();
-            let stdin_fd = stdin.as_raw_fd();
+            let stdin_fd = input.as_raw_fd();
             let mut buf = [0u8; 4096];
             loop {
                 let mut pfds = [
@@ -371,7 +394,7 @@ impl Client {
                 if pfds[0].revents & libc::POLLIN == 0 {
                     continue;
                 }
-                match stdin.lock().read(&mut buf) {
+                match input.read(&mut buf) {
                     Ok(0) => break,
                     Ok(n) => {
                         let data = Bytes::copy_from_slice(&buf[..n]);
@@ -402,8 +425,12 @@ impl Client {
             }
         });
 
-        let mut stdout = std::io::stdout();
-        let result = streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut stdout).await;
+        let mut output = ThreadedOutput::spawn(output);
+        let result = streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut output).await;
+
+        // Let everything queued for the terminal land before the caller
+        // restores it (leaves raw mode / the alternate screen).
+        output.finish().await;
 
         // Close the cancel pipe write end — poll() in the reader wakes
         // instantly with POLLHUP and the reader exits. Then we join it
@@ -418,19 +445,107 @@ impl Client {
 
 }
 
+/// Chunks of terminal output queued for the writer thread before the
+/// streaming loop has to wait for it.
+const OUTPUT_QUEUE_CAPACITY: usize = 256;
+
+/// `io::Write` adapter that mirrors output to the local terminal from a
+/// dedicated thread.
+///
+/// Writing straight to a slow terminal blocked the streaming loop, which
+/// also stalled stdin forwarding and reading frames from the server. Writes
+/// are buffered here and each [`ThreadedOutput::send`] hands the buffer to a
+/// single writer thread over a bounded channel, so bytes reach the terminal
+/// in order. When the terminal is a full queue behind, `send` waits for room
+/// without tying up a runtime worker.
+struct ThreadedOutput {
+    buf: Vec<u8>,
+    tx: Option<tokio::sync::mpsc::Sender<Vec<u8>>>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl ThreadedOutput {
+    fn spawn<W: std::io::Write + Send + 'static>(mut out: W) -> Self {
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<Vec<u8>>(OUTPUT_QUEUE_CAPACITY);
+        let handle = std::thread::spawn(move || {
+            let mut healthy = true;
+            while let Some(chunk) = rx.blocking_recv() {
+                // Keep draining after a write error so the sender never
+                // waits on a dead terminal.
+                if !healthy {
+                    continue;
+                }
+                healthy = out.write_all(&chunk).is_ok();
+                // Coalesce whatever else is queued into a single flush.
+                while healthy {
+                    match rx.try_recv() {
+                        Ok(chunk) => healthy = out.write_all(&chunk).is_ok(),
+                        Err(_) => break,
+                    }
+                }
+                healthy = healthy && out.flush().is_ok();
+            }
+        });
+        Self {
+            buf: Vec::new(),
+            tx: Some(tx),
+            handle: Some(handle),
+        }
+    }
+
+    /// Hand the buffered bytes to the writer thread.
+    async fn send(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::take(&mut self.buf);
+        match &self.tx {
+            Some(tx) => tx
+                .send(chunk)
+                .await
+                .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "output thread exited")),
+            None => Err(io::Error::new(io::ErrorKind::BrokenPipe, "output closed")),
+        }
+    }
+
+    /// Send pending bytes and wait for the writer thread to drain them.
+    async fn finish(mut self) {
+        let _ = self.send().await;
+        drop(self.tx.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = tokio::task::spawn_blocking(move || handle.join()).await;
+        }
+    }
+}
+
+impl std::io::Write for ThreadedOutput {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    /// Bytes stay buffered until [`ThreadedOutput::send`], which the
+    /// streaming loop awaits after each update.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 /// The main streaming loop, factored out of `run_streaming` for testability.
 ///
 /// Reads stdin data from `stdin_rx`, reads frames from the server via `reader`,
 /// writes frames to the server via `writer`, and handles resize signals from
 /// `sigwinch_rx`. Terminal output (PTY data, overlays, panels) is written to
-/// `output`, which is `stdout` in production and a buffer in tests.
+/// `output`, which wraps `stdout` in production and a buffer in tests.
 async fn streaming_loop(
     reader: ReadHalf<UnixStream>,
     mut writer: WriteHalf<UnixStream>,
     stdin_rx: &mut tokio::sync::mpsc::Receiver<Bytes>,
     sigwinch_rx: &mut tokio::sync::mpsc::Receiver<(u16, u16)>,
-    output: &mut impl std::io::Write,
+    output: &mut ThreadedOutput,
 ) -> io::Result<()> {
+    use std::io::Write as _;
+
     // BufReader preserves partially-read bytes across select! cancellation,
     // making Frame::read_from cancellation-safe. Without this, if a select!
     // branch cancels read_from between the header and payload reads, the
@@ -507,7 +622,7 @@ async fn streaming_loop(
                                 } else {
                                     let _ = output.write_all(&frame.payload);
                                 }
-                                let _ = output.flush();
+                                let _ = output.send().await;
                             }
                             FrameType::OverlaySync => {
                                 if let Ok(msg) = frame.parse_json::<OverlaySyncMsg>() {
@@ -519,7 +634,7 @@ async fn streaming_loop(
                                     let _ = output.write_all(overlay::render_all_overlays(&msg.overlays).as_bytes());
                                     let _ = output.write_all(overlay::restore_cursor().as_bytes());
                                     let _ = output.write_all(overlay::end_sync().as_bytes());
-                                    let _ = output.flush();
+                                    let _ = output.send().await;
                                     cached_overlays = msg.overlays;
                                 }
                             }
@@ -533,6 +648,7 @@ async fn streaming_loop(
                                         term_rows,
                                         term_cols,
                                     );
+                                    let _ = output.send().await;
                                     cached_panels = msg.panels;
                                 }
                             }
@@ -586,7 +702,7 @@ async fn streaming_loop(
             let _ = output.write_all(panel::erase_all_panels(&layout, term_cols).as_bytes());
             let _ = output.write_all(panel::reset_scroll_region().as_bytes());
         }
-        let _ = output.flush();
+        let _ = output.send().await;
     }
 
     // Ensure the writer half is cleanly shut down
@@ -616,7 +732,7 @@ mod tests {
 
         tokio::spawn(async move {
             let cancel = tokio_util::sync::CancellationToken::new();
-            let shutdown_request = tokio_util::sync::CancellationToken::new();
+            let shutdown_request = crate::shutdown::ShutdownRequest::new();
             server::serve(sessions, &socket_path, cancel, token, shutdown_request).await.unwrap();
         });
 
@@ -841,7 +957,8 @@ mod tests {
 
         // Spawn the streaming loop
         let loop_handle = tokio::spawn(async move {
-            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink()).await
+            let mut output = ThreadedOutput::spawn(std::io::sink());
+            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut output).await
         });
 
         // Send data through stdin channel
@@ -886,7 +1003,8 @@ mod tests {
 
         // Spawn the streaming loop
         let loop_handle = tokio::spawn(async move {
-            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink()).await
+            let mut output = ThreadedOutput::spawn(std::io::sink());
+            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut output).await
         });
 
         // Send a PtyOutput frame from the "server"
@@ -912,7 +1030,8 @@ mod tests {
         let (sigwinch_tx, mut sigwinch_rx) = tokio::sync::mpsc::channel::<(u16, u16)>(4);
 
         let loop_handle = tokio::spawn(async move {
-            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink()).await
+            let mut output = ThreadedOutput::spawn(std::io::sink());
+            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut output).await
         });
 
         // Send a resize signal
@@ -1063,7 +1182,8 @@ mod tests {
         let (_sigwinch_tx, mut sigwinch_rx) = tokio::sync::mpsc::channel::<(u16, u16)>(4);
 
         let loop_handle = tokio::spawn(async move {
-            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink()).await
+            let mut output = ThreadedOutput::spawn(std::io::sink());
+            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut output).await
         });
 
         // Send Ctrl+\ twice in quick succession
@@ -1112,7 +1232,8 @@ mod tests {
         let (_sigwinch_tx, mut sigwinch_rx) = tokio::sync::mpsc::channel::<(u16, u16)>(4);
 
         let loop_handle = tokio::spawn(async move {
-            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink()).await
+            let mut output = ThreadedOutput::spawn(std::io::sink());
+            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut output).await
         });
 
         // Send a single Ctrl+\ — should be forwarded immediately (no delay)
@@ -1151,7 +1272,8 @@ mod tests {
         let (_sigwinch_tx, mut sigwinch_rx) = tokio::sync::mpsc::channel::<(u16, u16)>(4);
 
         let loop_handle = tokio::spawn(async move {
-            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink()).await
+            let mut output = ThreadedOutput::spawn(std::io::sink());
+            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut output).await
         });
 
         // Send Ctrl+\ followed by 'a'
@@ -1199,7 +1321,8 @@ mod tests {
         let (_sigwinch_tx, mut sigwinch_rx) = tokio::sync::mpsc::channel::<(u16, u16)>(4);
 
         let loop_handle = tokio::spawn(async move {
-            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink()).await
+            let mut output = ThreadedOutput::spawn(std::io::sink());
+            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut output).await
         });
 
         // Send Ctrl+\ then immediately close stdin
@@ -1243,6 +1366,7 @@ mod tests {
             region_writes: vec![],
             visible: true,
             focusable: false,
+            command: None,
             screen_mode: crate::overlay::ScreenMode::Normal,
         }
     }
@@ -1357,8 +1481,10 @@ mod tests {
         let output_buf_clone = output_buf.clone();
 
         let loop_handle = tokio::spawn(async move {
-            let mut out = output_buf_clone;
-            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut out).await
+            let mut out = ThreadedOutput::spawn(output_buf_clone);
+            let result = streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut out).await;
+            out.finish().await;
+            result
         });
 
         // Send a PanelSync frame with empty panels (simulates server visual
@@ -1387,4 +1513,104 @@ mod tests {
             output,
         );
     }
+
+    /// Writer that records bytes into a shared buffer, optionally slowly.
+    struct SharedWriter {
+        data: std::sync::Arc<parking_lot::Mutex<Vec<u8>>>,
+        delay: std::time::Duration,
+    }
+
+    impl std::io::Write for SharedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            std::thread::sleep(self.delay);
+            self.data.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn threaded_output_preserves_order_without_blocking_on_slow_writer() {
+        use std::io::Write;
+
+        let data = std::sync::Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let mut output = ThreadedOutput::spawn(SharedWriter {
+            data: data.clone(),
+            delay: std::time::Duration::from_millis(20),
+        });
+
+        let start = std::time::Instant::now();
+        let mut expected = Vec::new();
+        for i in 0..20 {
+            let chunk = format!("chunk {i};");
+            output.write_all(chunk.as_bytes()).unwrap();
+            output.send().await.unwrap();
+            expected.extend_from_slice(chunk.as_bytes());
+        }
+        // 20 writes at 20ms each would take 400ms if done inline.
+        assert!(
+            start.elapsed() < std::time::Duration::from_millis(200),
+            "send should hand off to the writer thread, took {:?}",
+            start.elapsed()
+        );
+
+        output.finish().await;
+        assert_eq!(*data.lock(), expected);
+    }
+
+    /// Writer whose first write waits until the test lets it through.
+    struct GatedWriter {
+        gate: Option<std::sync::mpsc::Receiver<()>>,
+        data: std::sync::Arc<parking_lot::Mutex<Vec<u8>>>,
+    }
+
+    impl std::io::Write for GatedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if let Some(gate) = self.gate.take() {
+                let _ = gate.recv();
+            }
+            self.data.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn threaded_output_waits_for_room_without_blocking_the_runtime() {
+        use std::io::Write;
+
+        let (open_gate, gate) = std::sync::mpsc::channel();
+        let data = std::sync::Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let mut output = ThreadedOutput::spawn(GatedWriter {
+            gate: Some(gate),
+            data: data.clone(),
+        });
+
+        // One chunk stuck in the writer plus a full queue: the last send
+        // has to wait. The test runs on a single-threaded runtime, so this
+        // task only gets to run again if that wait yields.
+        let sender = tokio::spawn(async move {
+            for _ in 0..OUTPUT_QUEUE_CAPACITY + 2 {
+                output.write_all(b"x").unwrap();
+                output.send().await.unwrap();
+            }
+            output
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!sender.is_finished(), "send should wait for room in the queue");
+
+        open_gate.send(()).unwrap();
+        let output = tokio::time::timeout(std::time::Duration::from_secs(5), sender)
+            .await
+            .expect("send should resume once the writer drains")
+            .unwrap();
+        output.finish().await;
+        assert_eq!(data.lock().len(), OUTPUT_QUEUE_CAPACITY + 2);
+    }
 }
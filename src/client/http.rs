@@ -0,0 +1,279 @@
+//! Typed async client for the wsh HTTP/WebSocket API.
+//!
+//! Responses are decoded into the server's own wire types
+//! ([`ScreenResponse`], [`ScrollbackResponse`], [`Event`]), so a change to
+//! the API shows up as a compile error here rather than a parse failure in
+//! every embedder.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), wsh::client::http::Error> {
+//! use futures::StreamExt;
+//! use wsh::client::http::Client;
+//! use wsh::parser::events::EventType;
+//! use wsh::parser::state::Format;
+//!
+//! let client = Client::connect("http://127.0.0.1:8080").await?;
+//! let screen = client.screen("dev", Format::Plain).await?;
+//! println!("{} rows", screen.rows);
+//!
+//! let mut events = client.subscribe_events("dev", &[EventType::Lines], Format::Plain).await?;
+//! client.send_keys("dev", &["Ctrl+L"]).await?;
+//! while let Some(event) = events.next().await {
+//!     println!("{:?}", event?);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use futures::stream::BoxStream;
+use futures::{SinkExt, StreamExt};
+use reqwest::{StatusCode, Url};
+use serde::de::DeserializeOwned;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::parser::events::{Event, EventType};
+use crate::parser::state::{Format, ScreenResponse, ScrollbackResponse};
+
+/// Errors returned by [`Client`].
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid server URL: {0}")]
+    InvalidUrl(String),
+
+    #[error("HTTP request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("WebSocket error: {0}")]
+    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+
+    /// The server answered with an error body (see `docs/api/errors.md`).
+    #[error("{code}: {message}")]
+    Api {
+        status: u16,
+        code: String,
+        message: String,
+    },
+
+    /// The server sent something this client doesn't understand.
+    #[error("unexpected response: {0}")]
+    Protocol(String),
+}
+
+impl Error {
+    /// The HTTP status, for errors the server reported.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::Api { status, .. } => StatusCode::from_u16(*status).ok(),
+            Error::Http(e) => e.status(),
+            _ => None,
+        }
+    }
+}
+
+/// Client for one wsh server. Cheap to clone; clones share connections.
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base: Url,
+    token: Option<String>,
+}
+
+impl Client {
+    /// Connect to the server at `url` (e.g. `http://127.0.0.1:8080`),
+    /// checking that it answers `/health`.
+    pub async fn connect(url: &str) -> Result<Self, Error> {
+        Self::connect_with_token(url, None).await
+    }
+
+    /// Like [`Client::connect`], authenticating every request with `token`.
+    pub async fn connect_with_token(url: &str, token: Option<String>) -> Result<Self, Error> {
+        let base = Url::parse(url).map_err(|e| Error::InvalidUrl(e.to_string()))?;
+        if !matches!(base.scheme(), "http" | "https") || base.cannot_be_a_base() {
+            return Err(Error::InvalidUrl(format!("expected an http(s) URL, got '{url}'")));
+        }
+        let client = Self {
+            http: reqwest::Client::new(),
+            base,
+            token,
+        };
+        let response = client.request(reqwest::Method::GET, &["health"]).send().await?;
+        check_status(response).await?;
+        Ok(client)
+    }
+
+    /// The session's visible screen.
+    pub async fn screen(&self, session: &str, format: Format) -> Result<ScreenResponse, Error> {
+        let request = self
+            .request(reqwest::Method::GET, &["sessions", session, "screen"])
+            .query(&[("format", format_param(format))]);
+        self.send_json(request).await
+    }
+
+    /// Up to `limit` scrollback lines starting `offset` lines from the
+    /// oldest.
+    pub async fn scrollback(
+        &self,
+        session: &str,
+        format: Format,
+        offset: usize,
+        limit: usize,
+    ) -> Result<ScrollbackResponse, Error> {
+        let request = self
+            .request(reqwest::Method::GET, &["sessions", session, "scrollback"])
+            .query(&[("format", format_param(format))])
+            .query(&[("offset", offset), ("limit", limit)]);
+        self.send_json(request).await
+    }
+
+    /// Send named keys (e.g. `"Enter"`, `"Ctrl+C"`, `"Up"`), encoded for
+    /// the program's current cursor-key mode. An unknown name sends
+    /// nothing.
+    pub async fn send_keys(&self, session: &str, keys: &[&str]) -> Result<(), Error> {
+        let response = self
+            .request(reqwest::Method::POST, &["sessions", session, "input", "keys"])
+            .json(&serde_json::json!({ "keys": keys }))
+            .send()
+            .await?;
+        check_status(response).await?;
+        Ok(())
+    }
+
+    /// Subscribe to the session's events over its JSON WebSocket. The
+    /// stream starts with a `Sync` of the current screen and ends when
+    /// the server closes the socket. Messages that aren't terminal events
+    /// (method responses, input and overlay notices) are skipped.
+    pub async fn subscribe_events(
+        &self,
+        session: &str,
+        events: &[EventType],
+        format: Format,
+    ) -> Result<BoxStream<'static, Result<Event, Error>>, Error> {
+        let mut url = self.url(&["sessions", session, "ws", "json"]);
+        let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+        url.set_scheme(scheme)
+            .map_err(|()| Error::InvalidUrl(format!("cannot use '{scheme}' with {url}")))?;
+        let mut request = url.as_str().into_client_request()?;
+        if let Some(token) = &self.token {
+            let value = format!("Bearer {token}")
+                .parse()
+                .map_err(|_| Error::InvalidUrl("token is not a valid header value".to_string()))?;
+            request.headers_mut().insert("authorization", value);
+        }
+        let (socket, _) = tokio_tungstenite::connect_async(request).await?;
+        let (mut tx, mut rx) = socket.split();
+
+        let subscribe = serde_json::json!({
+            "id": 1,
+            "method": "subscribe",
+            "params": { "events": events, "format": format },
+        });
+        tx.send(Message::Text(subscribe.to_string().into())).await?;
+
+        // Wait for the subscribe response; the `connected` greeting comes
+        // first.
+        loop {
+            let text = match rx.next().await {
+                Some(Ok(Message::Text(text))) => text,
+                Some(Ok(Message::Close(frame))) => {
+                    let reason = frame.map_or_else(String::new, |f| f.reason.to_string());
+                    return Err(Error::Protocol(format!("socket closed before subscribing: {reason}")));
+                }
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(e.into()),
+                None => return Err(Error::Protocol("socket closed before subscribing".to_string())),
+            };
+            let value: serde_json::Value =
+                serde_json::from_str(&text).map_err(|e| Error::Protocol(e.to_string()))?;
+            if value.get("method").and_then(|m| m.as_str()) != Some("subscribe") {
+                continue;
+            }
+            if let Some(error) = value.get("error") {
+                return Err(api_error(400, error));
+            }
+            break;
+        }
+
+        // Keep the write half alive with the stream so the socket stays
+        // open while it's read.
+        let stream = futures::stream::unfold((rx, tx), |(mut rx, tx)| async move {
+            loop {
+                match rx.next().await? {
+                    Ok(Message::Text(text)) => {
+                        let Ok(value) = serde_json::from_str::<serde_json::Value>(&text) else {
+                            continue;
+                        };
+                        if value.get("event").is_none() {
+                            continue;
+                        }
+                        if let Ok(event) = serde_json::from_value::<Event>(value) {
+                            return Some((Ok(event), (rx, tx)));
+                        }
+                    }
+                    Ok(Message::Close(_)) => return None,
+                    Ok(_) => continue,
+                    Err(e) => return Some((Err(e.into()), (rx, tx))),
+                }
+            }
+        });
+        Ok(stream.boxed())
+    }
+
+    /// `base` with `segments` appended, each percent-encoded.
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base.clone();
+        // `connect` rejected URLs that can't be a base.
+        url.path_segments_mut()
+            .expect("base URL has a path")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    fn request(&self, method: reqwest::Method, segments: &[&str]) -> reqwest::RequestBuilder {
+        let request = self.http.request(method, self.url(segments));
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn send_json<T: DeserializeOwned>(&self, request: reqwest::RequestBuilder) -> Result<T, Error> {
+        let response = check_status(request.send().await?).await?;
+        Ok(response.json().await?)
+    }
+}
+
+fn format_param(format: Format) -> &'static str {
+    match format {
+        Format::Plain => "plain",
+        Format::Styled => "styled",
+    }
+}
+
+/// Turn an error status into [`Error::Api`], using the server's error body
+/// when it has one.
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response, Error> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body: serde_json::Value = response.json().await.unwrap_or_default();
+    Err(match body.get("error") {
+        Some(error) => api_error(status.as_u16(), error),
+        None => Error::Api {
+            status: status.as_u16(),
+            code: "http_error".to_string(),
+            message: status.canonical_reason().unwrap_or("request failed").to_string(),
+        },
+    })
+}
+
+fn api_error(status: u16, error: &serde_json::Value) -> Error {
+    let field = |name: &str| error.get(name).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    Error::Api {
+        status,
+        code: field("code"),
+        message: field("message"),
+    }
+}
@@ -22,6 +22,11 @@ pub enum InputEvent {
     Mode {
         mode: Mode,
     },
+    /// The element receiving input changed; `focused` is `None` when input
+    /// goes back to the session's PTY.
+    Focus {
+        focused: Option<String>,
+    },
 }
 
 /// Broadcaster for input events
@@ -51,10 +56,6 @@ impl InputBroadcaster {
         });
     }
 
-    pub fn broadcast_mode(&self, mode: Mode) {
-        let _ = self.tx.send(InputEvent::Mode { mode });
-    }
-
     pub fn subscribe(&self) -> broadcast::Receiver<InputEvent> {
         self.tx.subscribe()
     }
@@ -117,22 +118,6 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_broadcast_mode() {
-        let broadcaster = InputBroadcaster::new();
-        let mut rx = broadcaster.subscribe();
-
-        broadcaster.broadcast_mode(Mode::Capture);
-
-        let event = rx.try_recv().unwrap();
-        match event {
-            InputEvent::Mode { mode } => {
-                assert_eq!(mode, Mode::Capture);
-            }
-            _ => panic!("Expected Mode event"),
-        }
-    }
-
     #[test]
     fn test_input_event_serialization() {
         let event = InputEvent::Input {
@@ -166,14 +151,14 @@ mod tests {
         let broadcaster2 = broadcaster1.clone();
         let mut rx = broadcaster1.subscribe();
 
-        broadcaster2.broadcast_mode(Mode::Capture);
+        broadcaster2.broadcast_input(b"a", Mode::Capture, None);
 
         let event = rx.try_recv().unwrap();
         match event {
-            InputEvent::Mode { mode } => {
+            InputEvent::Input { mode, .. } => {
                 assert_eq!(mode, Mode::Capture);
             }
-            _ => panic!("Expected Mode event"),
+            _ => panic!("Expected Input event"),
         }
     }
 }
@@ -1,48 +1,65 @@
 use std::sync::Arc;
-use parking_lot::RwLock;
+use tokio::sync::watch;
 
 /// Tracks which overlay/panel currently has input focus.
 ///
 /// At most one element has focus at a time. Focus requires input capture
 /// mode to be active -- the FocusTracker doesn't enforce this itself;
 /// the API layer checks capture mode before routing input.
+///
+/// Focus changes are published to subscribers (see
+/// [`FocusTracker::subscribe`]), whichever transport made them.
 #[derive(Clone)]
 pub struct FocusTracker {
-    inner: Arc<RwLock<Option<String>>>,
+    inner: Arc<watch::Sender<Option<String>>>,
 }
 
 impl FocusTracker {
     pub fn new() -> Self {
         Self {
-            inner: Arc::new(RwLock::new(None)),
+            inner: Arc::new(watch::channel(None).0),
         }
     }
 
     /// Set focus to a specific element by ID.
     pub fn focus(&self, id: String) {
-        let mut inner = self.inner.write();
-        *inner = Some(id);
+        self.set(Some(id));
     }
 
     /// Remove focus from any element.
     pub fn unfocus(&self) {
-        let mut inner = self.inner.write();
-        *inner = None;
+        self.set(None);
+    }
+
+    fn set(&self, focused: Option<String>) {
+        self.inner.send_if_modified(|current| {
+            let changed = *current != focused;
+            *current = focused;
+            changed
+        });
     }
 
     /// Get the currently focused element's ID, if any.
     pub fn focused(&self) -> Option<String> {
-        let inner = self.inner.read();
-        inner.clone()
+        self.inner.borrow().clone()
     }
 
     /// Clear focus only if the given ID currently has focus.
     /// Used when an element is deleted -- only unfocus if it was the focused one.
     pub fn clear_if_focused(&self, id: &str) {
-        let mut inner = self.inner.write();
-        if inner.as_deref() == Some(id) {
-            *inner = None;
-        }
+        self.inner.send_if_modified(|current| {
+            let matches = current.as_deref() == Some(id);
+            if matches {
+                *current = None;
+            }
+            matches
+        });
+    }
+
+    /// Subscribe to focus changes. Subscribers are only notified when the
+    /// focused element actually changes.
+    pub fn subscribe(&self) -> watch::Receiver<Option<String>> {
+        self.inner.subscribe()
     }
 }
 
@@ -128,4 +145,25 @@ mod tests {
         let tracker = FocusTracker::default();
         assert!(tracker.focused().is_none());
     }
+
+    #[test]
+    fn test_subscribe_notified_only_on_change() {
+        let tracker = FocusTracker::new();
+        let mut rx = tracker.subscribe();
+
+        tracker.unfocus();
+        assert!(!rx.has_changed().unwrap());
+
+        tracker.focus("panel-1".to_string());
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(*rx.borrow_and_update(), Some("panel-1".to_string()));
+
+        tracker.focus("panel-1".to_string());
+        tracker.clear_if_focused("other");
+        assert!(!rx.has_changed().unwrap());
+
+        tracker.clear_if_focused("panel-1");
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(*rx.borrow_and_update(), None);
+    }
 }
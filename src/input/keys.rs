@@ -36,6 +36,73 @@ pub fn is_ctrl_backslash(data: &[u8]) -> bool {
     data == [0x1c]
 }
 
+/// Every name [`encode_key`] accepts, apart from `"Ctrl+<letter>"`.
+pub const NAMED_KEYS: &[&str] = &[
+    "Enter", "Tab", "Escape", "Backspace", "Space", "ArrowUp", "ArrowDown", "ArrowRight", "ArrowLeft",
+    "Home", "End", "Insert", "Delete", "PageUp", "PageDown", "F1", "F2", "F3", "F4", "F5", "F6", "F7",
+    "F8", "F9", "F10", "F11", "F12",
+];
+
+/// Encodes a named key into the bytes a terminal would send for it.
+///
+/// `app_cursor` is the terminal's cursor-key mode (DECCKM). In application
+/// mode arrows and Home/End use SS3 (`ESC O x`) instead of CSI (`ESC [ x`),
+/// which full-screen programs like vim and less rely on.
+///
+/// Names follow [`parse_key`] (`"ArrowUp"`, `"Home"`, `"Backspace"`, ...),
+/// plus `"Delete"`, `"Insert"`, `"PageUp"`, `"PageDown"`, `"Space"`,
+/// `"F1"`-`"F12"`, and `"Ctrl+<letter>"`. Returns `None` for unknown names.
+pub fn encode_key(name: &str, app_cursor: bool) -> Option<Vec<u8>> {
+    let cursor = |c: u8| -> Vec<u8> {
+        if app_cursor {
+            vec![0x1b, b'O', c]
+        } else {
+            vec![0x1b, b'[', c]
+        }
+    };
+    let tilde = |n: &str| -> Vec<u8> { format!("\x1b[{}~", n).into_bytes() };
+
+    let bytes = match name {
+        "Enter" => vec![0x0d],
+        "Tab" => vec![0x09],
+        "Escape" => vec![0x1b],
+        "Backspace" => vec![0x7f],
+        "Space" => vec![b' '],
+        "ArrowUp" => cursor(b'A'),
+        "ArrowDown" => cursor(b'B'),
+        "ArrowRight" => cursor(b'C'),
+        "ArrowLeft" => cursor(b'D'),
+        "Home" => cursor(b'H'),
+        "End" => cursor(b'F'),
+        "Insert" => tilde("2"),
+        "Delete" => tilde("3"),
+        "PageUp" => tilde("5"),
+        "PageDown" => tilde("6"),
+        "F1" => b"\x1bOP".to_vec(),
+        "F2" => b"\x1bOQ".to_vec(),
+        "F3" => b"\x1bOR".to_vec(),
+        "F4" => b"\x1bOS".to_vec(),
+        "F5" => tilde("15"),
+        "F6" => tilde("17"),
+        "F7" => tilde("18"),
+        "F8" => tilde("19"),
+        "F9" => tilde("20"),
+        "F10" => tilde("21"),
+        "F11" => tilde("23"),
+        "F12" => tilde("24"),
+        _ => {
+            let letter = name
+                .strip_prefix("Ctrl+")
+                .or_else(|| name.strip_prefix("ctrl+"))?;
+            match letter.as_bytes() {
+                [c] if c.is_ascii_alphabetic() => vec![c.to_ascii_lowercase() - b'a' + 1],
+                _ => return None,
+            }
+        }
+    };
+    Some(bytes)
+}
+
 /// Parses raw bytes into a structured key event.
 ///
 /// # Key parsing rules:
@@ -56,24 +123,30 @@ pub fn is_ctrl_backslash(data: &[u8]) -> bool {
 /// - ESC [ D -> "ArrowLeft"
 /// - ESC [ H -> "Home"
 /// - ESC [ F -> "End"
+/// - ESC O A/B/C/D/H/F -> same keys in application cursor mode
+/// - ESC [ 2~ / 3~ / 5~ / 6~ -> "Insert" / "Delete" / "PageUp" / "PageDown"
 /// - Unknown -> key: None
 pub fn parse_key(data: &[u8]) -> ParsedKey {
     if data.is_empty() {
         return ParsedKey::new(None);
     }
 
-    // Check for escape sequences (ESC [ ...)
-    if data.len() >= 3 && data[0] == 0x1b && data[1] == b'[' {
-        let key = match data[2] {
-            b'A' => Some("ArrowUp".to_string()),
-            b'B' => Some("ArrowDown".to_string()),
-            b'C' => Some("ArrowRight".to_string()),
-            b'D' => Some("ArrowLeft".to_string()),
-            b'H' => Some("Home".to_string()),
-            b'F' => Some("End".to_string()),
+    // Check for escape sequences (ESC [ ... or ESC O ...)
+    if data.len() >= 3 && data[0] == 0x1b && (data[1] == b'[' || data[1] == b'O') {
+        let key = match &data[2..] {
+            b"A" => Some("ArrowUp"),
+            b"B" => Some("ArrowDown"),
+            b"C" => Some("ArrowRight"),
+            b"D" => Some("ArrowLeft"),
+            b"H" => Some("Home"),
+            b"F" => Some("End"),
+            b"2~" if data[1] == b'[' => Some("Insert"),
+            b"3~" if data[1] == b'[' => Some("Delete"),
+            b"5~" if data[1] == b'[' => Some("PageUp"),
+            b"6~" if data[1] == b'[' => Some("PageDown"),
             _ => None,
         };
-        return ParsedKey::new(key);
+        return ParsedKey::new(key.map(str::to_string));
     }
 
     // Single byte handling
@@ -156,6 +229,53 @@ mod tests {
         assert!(result.modifiers.is_empty());
     }
 
+    #[test]
+    fn test_encode_home_end_follow_cursor_key_mode() {
+        assert_eq!(encode_key("Home", false).unwrap(), b"\x1b[H");
+        assert_eq!(encode_key("End", false).unwrap(), b"\x1b[F");
+        assert_eq!(encode_key("Home", true).unwrap(), b"\x1bOH");
+        assert_eq!(encode_key("End", true).unwrap(), b"\x1bOF");
+        assert_eq!(encode_key("ArrowUp", true).unwrap(), b"\x1bOA");
+        assert_eq!(encode_key("ArrowUp", false).unwrap(), b"\x1b[A");
+    }
+
+    #[test]
+    fn test_encode_editing_keys() {
+        assert_eq!(encode_key("Backspace", false).unwrap(), b"\x7f");
+        assert_eq!(encode_key("Delete", false).unwrap(), b"\x1b[3~");
+        assert_eq!(encode_key("PageUp", false).unwrap(), b"\x1b[5~");
+        assert_eq!(encode_key("PageDown", true).unwrap(), b"\x1b[6~");
+        assert_eq!(encode_key("F12", false).unwrap(), b"\x1b[24~");
+    }
+
+    #[test]
+    fn test_encode_ctrl_and_unknown() {
+        assert_eq!(encode_key("Ctrl+C", false).unwrap(), vec![0x03]);
+        assert_eq!(encode_key("ctrl+a", false).unwrap(), vec![0x01]);
+        assert_eq!(encode_key("Ctrl+1", false), None);
+        assert_eq!(encode_key("Hyper", false), None);
+    }
+
+    #[test]
+    fn test_named_keys_all_encode() {
+        for name in NAMED_KEYS {
+            assert!(encode_key(name, false).is_some(), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_encode_parse_round_trip() {
+        for name in [
+            "ArrowUp", "ArrowDown", "ArrowLeft", "ArrowRight", "Home", "End",
+            "Insert", "Delete", "PageUp", "PageDown", "Backspace", "Enter", "Tab",
+        ] {
+            for app in [false, true] {
+                let bytes = encode_key(name, app).unwrap();
+                assert_eq!(parse_key(&bytes).key.as_deref(), Some(name), "{name} app={app}");
+            }
+        }
+    }
+
     #[test]
     fn test_parse_ctrl_backslash() {
         // Ctrl+\ is 0x1c
@@ -5,5 +5,5 @@ pub mod mode;
 
 pub use events::{InputBroadcaster, InputEvent};
 pub use focus::FocusTracker;
-pub use keys::{is_ctrl_backslash, parse_key, ParsedKey};
+pub use keys::{encode_key, is_ctrl_backslash, parse_key, ParsedKey, NAMED_KEYS};
 pub use mode::{InputMode, Mode};
@@ -33,9 +33,11 @@
 //! **Do not re-add auto-cleanup.** This has been evaluated and reverted.
 //! If you believe the tradeoffs have changed, discuss before implementing.
 
+use bytes::Bytes;
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use parking_lot::RwLock;
+use tokio::sync::watch;
 
 /// The current input routing mode.
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
@@ -52,33 +54,104 @@ pub enum Mode {
 ///
 /// This struct provides a way to control input routing from multiple threads.
 /// It defaults to passthrough mode where input flows to both API subscribers
-/// and the PTY.
+/// and the PTY. Mode changes are published to subscribers (see
+/// [`InputMode::subscribe`]), whichever transport made them.
 #[derive(Clone)]
 pub struct InputMode {
-    inner: Arc<RwLock<Mode>>,
+    inner: Arc<watch::Sender<Mode>>,
+    /// Translate lone `\n` to `\r` in text input (see [`InputMode::normalize`]).
+    normalize_newlines: Arc<AtomicBool>,
+    /// Show captured input on the session's screen (see [`InputMode::echo`]).
+    echo_captured: Arc<AtomicBool>,
 }
 
 impl InputMode {
     /// Creates a new InputMode in the default Passthrough state.
     pub fn new() -> Self {
         Self {
-            inner: Arc::new(RwLock::new(Mode::default())),
+            inner: Arc::new(watch::channel(Mode::default()).0),
+            normalize_newlines: Arc::new(AtomicBool::new(false)),
+            echo_captured: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    pub fn normalize_newlines(&self) -> bool {
+        self.normalize_newlines.load(Ordering::Relaxed)
+    }
+
+    pub fn set_normalize_newlines(&self, enabled: bool) {
+        self.normalize_newlines.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Applies newline normalization to text input from the API, if enabled.
+    ///
+    /// Programs in raw mode submit on `\r` (what the Enter key sends), so a
+    /// `\n` not preceded by `\r` becomes `\r`. Only call this for input
+    /// known to be text; binary input must be sent unchanged.
+    pub fn normalize(&self, data: Bytes) -> Bytes {
+        if !self.normalize_newlines() || !data.contains(&b'\n') {
+            return data;
+        }
+        let out: Vec<u8> = data
+            .iter()
+            .enumerate()
+            .map(|(i, &byte)| match byte {
+                b'\n' if i == 0 || data[i - 1] != b'\r' => b'\r',
+                _ => byte,
+            })
+            .collect();
+        Bytes::from(out)
+    }
+
+    /// Whether input captured from the local terminal is drawn on the
+    /// session's screen. Off by default: the screen then shows text the
+    /// program never received.
+    pub fn echo_captured(&self) -> bool {
+        self.echo_captured.load(Ordering::Relaxed)
+    }
+
+    pub fn set_echo_captured(&self, enabled: bool) {
+        self.echo_captured.store(enabled, Ordering::Relaxed);
+    }
+
+    /// What to draw for captured keystrokes: about what a tty in canonical
+    /// mode echoes. Enter starts a new line and Backspace erases the
+    /// previous character; everything else is drawn as is.
+    pub fn echo(data: &[u8]) -> Bytes {
+        let mut out = Vec::with_capacity(data.len());
+        for &byte in data {
+            match byte {
+                b'\r' => out.extend_from_slice(b"\r\n"),
+                0x7f | 0x08 => out.extend_from_slice(b"\x08 \x08"),
+                _ => out.push(byte),
+            }
+        }
+        Bytes::from(out)
+    }
+
     /// Gets the current mode.
     pub fn get(&self) -> Mode {
-        *self.inner.read()
+        *self.inner.borrow()
     }
 
-    /// Sets the mode to Capture.
+    /// Sets the mode to Capture. Subscribers are only notified if the mode
+    /// actually changed.
     pub fn capture(&self) {
-        *self.inner.write() = Mode::Capture;
+        self.set(Mode::Capture);
     }
 
-    /// Sets the mode to Passthrough.
+    /// Sets the mode to Passthrough. Subscribers are only notified if the
+    /// mode actually changed.
     pub fn release(&self) {
-        *self.inner.write() = Mode::Passthrough;
+        self.set(Mode::Passthrough);
+    }
+
+    fn set(&self, mode: Mode) {
+        self.inner.send_if_modified(|current| {
+            let changed = *current != mode;
+            *current = mode;
+            changed
+        });
     }
 
     /// Toggles the mode: Passthrough → Capture, Capture → Passthrough.
@@ -86,15 +159,23 @@ impl InputMode {
     /// Used by the local terminal user (Ctrl+\).
     /// Returns the new mode after toggling.
     pub fn toggle(&self) -> Mode {
-        let mut guard = self.inner.write();
-        let new_mode = match *guard {
-            Mode::Passthrough => Mode::Capture,
-            Mode::Capture => Mode::Passthrough,
-        };
-        *guard = new_mode;
+        let mut new_mode = Mode::default();
+        self.inner.send_modify(|current| {
+            new_mode = match *current {
+                Mode::Passthrough => Mode::Capture,
+                Mode::Capture => Mode::Passthrough,
+            };
+            *current = new_mode;
+        });
         new_mode
     }
 
+    /// Subscribe to mode changes from any transport (HTTP, WebSocket, MCP,
+    /// Ctrl+\ on the local terminal).
+    pub fn subscribe(&self) -> watch::Receiver<Mode> {
+        self.inner.subscribe()
+    }
+
     /// Returns true if the current mode is Capture.
     pub fn is_capture(&self) -> bool {
         self.get() == Mode::Capture
@@ -111,6 +192,27 @@ impl Default for InputMode {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_normalize_translates_lone_newlines_only_when_enabled() {
+        let input_mode = InputMode::new();
+        let text = Bytes::from_static(b"ls\nprintf 'a\\n'\r\n\n");
+        assert_eq!(input_mode.normalize(text.clone()), text);
+
+        input_mode.set_normalize_newlines(true);
+        assert_eq!(
+            input_mode.normalize(text),
+            Bytes::from_static(b"ls\rprintf 'a\\n'\r\n\r")
+        );
+        // Clones share the setting.
+        assert!(input_mode.clone().normalize_newlines());
+    }
+
+    #[test]
+    fn test_echo_draws_enter_and_backspace_like_a_tty() {
+        assert_eq!(InputMode::echo(b"lx\x7fs\r"), Bytes::from_static(b"lx\x08 \x08s\r\n"));
+        assert!(!InputMode::new().echo_captured());
+    }
+
     #[test]
     fn test_default_mode_is_passthrough() {
         let input_mode = InputMode::new();
@@ -162,6 +264,40 @@ mod tests {
         assert_eq!(input_mode.get(), Mode::Passthrough);
     }
 
+    #[test]
+    fn test_toggle_notifies_subscriber() {
+        let input_mode = InputMode::new();
+        let mut rx = input_mode.subscribe();
+        assert!(!rx.has_changed().unwrap());
+
+        input_mode.toggle();
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(*rx.borrow_and_update(), Mode::Capture);
+
+        input_mode.toggle();
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(*rx.borrow_and_update(), Mode::Passthrough);
+    }
+
+    #[test]
+    fn test_capture_release_notify_only_on_change() {
+        let input_mode = InputMode::new();
+        let mut rx = input_mode.subscribe();
+
+        input_mode.release();
+        assert!(!rx.has_changed().unwrap(), "already passthrough");
+
+        input_mode.capture();
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(*rx.borrow_and_update(), Mode::Capture);
+
+        input_mode.capture();
+        assert!(!rx.has_changed().unwrap(), "already captured");
+
+        input_mode.release();
+        assert_eq!(*rx.borrow_and_update(), Mode::Passthrough);
+    }
+
     #[test]
     fn test_is_capture() {
         let input_mode = InputMode::new();
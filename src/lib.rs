@@ -11,5 +11,6 @@ pub mod protocol;
 pub mod pty;
 pub mod server;
 pub mod session;
+pub mod session_log;
 pub mod shutdown;
 pub mod terminal;
@@ -17,13 +17,13 @@ use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 use thiserror::Error;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
 use wsh::{
     api, client, protocol,
     protocol::{AttachSessionMsg, ScrollbackRequest},
     server,
     session::SessionRegistry,
-    shutdown::ShutdownCoordinator,
+    shutdown::{ShutdownCoordinator, ShutdownRequest},
     terminal,
 };
 
@@ -77,6 +77,14 @@ struct Cli {
     /// Path to the Unix domain socket (overrides -L)
     #[arg(long, global = true)]
     socket: Option<PathBuf>,
+
+    /// Initial terminal rows (overrides auto-detection, e.g. under a pipe or CI)
+    #[arg(long, env = "WSH_ROWS", global = true, value_parser = clap::value_parser!(u16).range(1..=1000))]
+    rows: Option<u16>,
+
+    /// Initial terminal columns (overrides auto-detection, e.g. under a pipe or CI)
+    #[arg(long, env = "WSH_COLS", global = true, value_parser = clap::value_parser!(u16).range(1..=1000))]
+    cols: Option<u16>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -107,6 +115,55 @@ enum Commands {
         /// Rate limit in requests per second (disabled if omitted)
         #[arg(long)]
         rate_limit: Option<u32>,
+
+        /// How long input requests wait for a stalled PTY before failing
+        /// with 503 (milliseconds)
+        #[arg(long, default_value_t = 2000, value_parser = clap::value_parser!(u64).range(1..))]
+        input_timeout_ms: u64,
+
+        /// Largest chunk each session's PTY reader takes at once
+        /// (1024-1048576). Larger values mean fewer, bigger frames under
+        /// heavy output
+        #[arg(
+            long,
+            env = "WSH_READ_BUFFER_BYTES",
+            default_value_t = wsh::session::DEFAULT_READ_BUFFER_BYTES,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1024..=1048576)
+        )]
+        read_buffer_bytes: usize,
+
+        /// Cap on the estimated scrollback memory of all sessions together,
+        /// in bytes. Over it, the least recently active sessions lose their
+        /// oldest scrollback first (no cap if omitted)
+        #[arg(long, env = "WSH_SCROLLBACK_BUDGET_BYTES")]
+        scrollback_budget_bytes: Option<usize>,
+
+        /// Resume journal size, in events, for sessions that have been
+        /// quiet for a few seconds. With --journal-max-events, each
+        /// session's journal follows its activity instead of a fixed size
+        #[arg(long, env = "WSH_JOURNAL_MIN_EVENTS", requires = "journal_max_events")]
+        journal_min_events: Option<usize>,
+
+        /// Resume journal size, in events, for sessions producing output
+        #[arg(long, env = "WSH_JOURNAL_MAX_EVENTS", requires = "journal_min_events")]
+        journal_max_events: Option<usize>,
+
+        /// Reconnect delay suggested to WebSocket clients when the server
+        /// stops for good (signal, `wsh stop`, ephemeral exit), in
+        /// milliseconds
+        #[arg(long, default_value_t = 30000)]
+        drain_retry_after_ms: u64,
+
+        /// Reconnect delay suggested to WebSocket clients on
+        /// `wsh stop --restart`, in milliseconds
+        #[arg(long, default_value_t = 1000)]
+        restart_retry_after_ms: u64,
+
+        /// Enable `POST /sessions/:name/feed`, which injects bytes into a
+        /// session's output as if the program had written them (for
+        /// testing renderers and reproducing parser bugs)
+        #[arg(long)]
+        allow_feed: bool,
     },
 
     /// Attach to an existing session on the server
@@ -175,7 +232,12 @@ enum Commands {
     },
 
     /// Stop the running wsh server
-    Stop {},
+    Stop {
+        /// Tell connected clients the server is being restarted, so they
+        /// reconnect after a short delay rather than a long one
+        #[arg(long)]
+        restart: bool,
+    },
 
     /// Start an MCP server over stdio (for AI hosts like Claude Desktop)
     Mcp {
@@ -237,6 +299,7 @@ fn resolve_token(bind: &SocketAddr, user_token: &Option<String>) -> Option<Strin
 #[tokio::main]
 async fn main() -> Result<(), WshError> {
     let cli = Cli::parse();
+    terminal::install_panic_hook();
 
     // MCP mode: tracing must use stderr since stdout is for MCP protocol
     let is_mcp = matches!(cli.command, Some(Commands::Mcp { .. }));
@@ -252,11 +315,23 @@ async fn main() -> Result<(), WshError> {
     let server_name = cli.server_name.clone();
 
     match cli.command {
-        Some(Commands::Server { bind, token, ephemeral, max_sessions, cors_origins, rate_limit }) => {
-            run_server(bind, token, socket, ephemeral, max_sessions, server_name, cors_origins, rate_limit).await
+        Some(Commands::Server { bind, token, ephemeral, max_sessions, cors_origins, rate_limit, input_timeout_ms, read_buffer_bytes, scrollback_budget_bytes, journal_min_events, journal_max_events, drain_retry_after_ms, restart_retry_after_ms, allow_feed }) => {
+            let input_timeout = std::time::Duration::from_millis(input_timeout_ms);
+            let journal_keepalive = journal_min_events.zip(journal_max_events).map(|(min, max)| {
+                wsh::session::JournalKeepalive {
+                    idle_events: min,
+                    active_events: max.max(min),
+                    idle_after: wsh::session::DEFAULT_JOURNAL_IDLE_AFTER,
+                }
+            });
+            let retry_after = RetryAfter {
+                drain: std::time::Duration::from_millis(drain_retry_after_ms),
+                restart: std::time::Duration::from_millis(restart_retry_after_ms),
+            };
+            run_server(bind, token, socket, ephemeral, max_sessions, server_name, cors_origins, rate_limit, (cli.rows, cli.cols), input_timeout, read_buffer_bytes, scrollback_budget_bytes, journal_keepalive, retry_after, allow_feed).await
         }
         Some(Commands::Attach { name, scrollback, alt_screen }) => {
-            run_attach(name, scrollback, socket, alt_screen, server_name).await
+            run_attach(name, scrollback, socket, alt_screen, server_name, (cli.rows, cli.cols)).await
         }
         Some(Commands::List {}) => {
             run_list(socket, server_name).await
@@ -276,8 +351,8 @@ async fn main() -> Result<(), WshError> {
         Some(Commands::Tag { name, add, remove }) => {
             run_tag(name, add, remove, socket, server_name).await
         }
-        Some(Commands::Stop {}) => {
-            run_stop(socket, server_name).await
+        Some(Commands::Stop { restart }) => {
+            run_stop(socket, server_name, restart).await
         }
         Some(Commands::Mcp { bind, token }) => {
             run_mcp(bind, socket, token, server_name).await
@@ -290,10 +365,10 @@ async fn main() -> Result<(), WshError> {
 
 fn init_tracing() {
     tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(
+        .with(tracing_subscriber::fmt::layer().with_filter(tracing_subscriber::EnvFilter::new(
             std::env::var("RUST_LOG").unwrap_or_else(|_| "wsh=info,tower_http=info".into()),
-        ))
-        .with(tracing_subscriber::fmt::layer())
+        )))
+        .with(wsh::session_log::layer())
         .init();
 }
 
@@ -303,16 +378,25 @@ fn init_tracing() {
 /// to stderr to avoid corrupting the protocol stream.
 fn init_tracing_stderr() {
     tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(
-            std::env::var("RUST_LOG").unwrap_or_else(|_| "wsh=info".into()),
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr).with_filter(
+            tracing_subscriber::EnvFilter::new(std::env::var("RUST_LOG").unwrap_or_else(|_| "wsh=info".into())),
         ))
-        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .with(wsh::session_log::layer())
         .init();
 }
 
 // ── Server mode ────────────────────────────────────────────────────
 
+/// Reconnect delays suggested to WebSocket clients when the server stops.
+struct RetryAfter {
+    /// The server is going away for good.
+    drain: std::time::Duration,
+    /// The server was stopped with `wsh stop --restart`.
+    restart: std::time::Duration,
+}
+
 /// Run the wsh server daemon: HTTP/WS + Unix socket, no local terminal.
+#[allow(clippy::too_many_arguments)]
 async fn run_server(
     bind: SocketAddr,
     token: Option<String>,
@@ -322,6 +406,13 @@ async fn run_server(
     server_name: String,
     cors_origins: Vec<String>,
     rate_limit: Option<u32>,
+    (rows, cols): (Option<u16>, Option<u16>),
+    input_timeout: std::time::Duration,
+    read_buffer_bytes: usize,
+    scrollback_budget_bytes: Option<usize>,
+    journal_keepalive: Option<wsh::session::JournalKeepalive>,
+    retry_after: RetryAfter,
+    allow_feed: bool,
 ) -> Result<(), WshError> {
     tracing::info!(instance = %server_name, "wsh server starting");
 
@@ -340,8 +431,26 @@ async fn run_server(
         }
         None => SessionRegistry::new(),
     };
+    sessions.set_read_buffer_bytes(read_buffer_bytes);
+    if let Some(budget) = scrollback_budget_bytes {
+        tracing::info!(bytes = budget, "scrollback budget configured");
+        sessions.set_scrollback_budget(Some(budget));
+        sessions.spawn_scrollback_enforcer(wsh::session::SCROLLBACK_BUDGET_INTERVAL);
+    }
+    if let Some(keepalive) = journal_keepalive {
+        tracing::info!(idle_events = keepalive.idle_events, active_events = keepalive.active_events, "activity-sized journals configured");
+        sessions.set_journal_keepalive(Some(keepalive));
+        sessions.spawn_journal_keepalive(wsh::session::JOURNAL_KEEPALIVE_INTERVAL);
+    }
     let shutdown = ShutdownCoordinator::new();
-    let server_config = std::sync::Arc::new(api::ServerConfig::new(persistent));
+    // Headless: there's no local terminal to detect, so API-created sessions
+    // default to the explicit --rows/--cols (or 24x80).
+    let server_config = std::sync::Arc::new(
+        api::ServerConfig::new(persistent)
+            .with_default_size(rows.unwrap_or(24), cols.unwrap_or(80))
+            .with_input_timeout(input_timeout)
+            .with_allow_feed(allow_feed),
+    );
     let state = api::AppState {
         sessions: sessions.clone(),
         shutdown: shutdown.clone(),
@@ -423,7 +532,7 @@ async fn run_server(
     let socket_sessions = sessions.clone();
     let socket_cancel = tokio_util::sync::CancellationToken::new();
     let socket_cancel_clone = socket_cancel.clone();
-    let shutdown_request = tokio_util::sync::CancellationToken::new();
+    let shutdown_request = ShutdownRequest::new();
     let shutdown_request_clone = shutdown_request.clone();
     let socket_handle = tokio::spawn(async move {
         if let Err(e) = server::serve(socket_sessions, &socket_path, socket_cancel_clone, socket_token, shutdown_request_clone).await {
@@ -525,7 +634,7 @@ async fn run_server(
                 tracing::debug!("ephemeral shutdown triggered");
             }
         }
-        _ = shutdown_request.cancelled() => {
+        _ = shutdown_request.requested() => {
             tracing::info!("shutdown requested via 'wsh stop'");
         }
     }
@@ -544,8 +653,10 @@ async fn run_server(
         tracing::debug!(path = %socket_path_for_cleanup.display(), "removed socket file");
     }
 
-    // 2. Signal existing WS handlers to close
-    shutdown.shutdown();
+    // 2. Signal existing WS handlers to close, telling clients when to
+    //    come back
+    let retry_after = if shutdown_request.is_restart() { retry_after.restart } else { retry_after.drain };
+    shutdown.shutdown_with_retry_after(retry_after);
 
     // 3. Wait for all WS connections to close (with timeout)
     let shutdown_result = tokio::time::timeout(
@@ -1047,7 +1158,7 @@ async fn run_default(cli: Cli) -> Result<(), WshError> {
         }
     };
 
-    let (rows, cols) = terminal::terminal_size().unwrap_or((24, 80));
+    let (rows, cols) = terminal::initial_size(cli.rows, cli.cols);
     tracing::debug!(rows, cols, "terminal size");
 
     // Determine what command to pass to the server
@@ -1110,6 +1221,7 @@ async fn run_attach(
     socket: Option<PathBuf>,
     alt_screen: bool,
     server_name: String,
+    (rows_override, cols_override): (Option<u16>, Option<u16>),
 ) -> Result<(), WshError> {
     let socket_path = resolve_socket_path(socket, &server_name);
 
@@ -1125,7 +1237,7 @@ async fn run_attach(
         },
     };
 
-    let (rows, cols) = terminal::terminal_size().unwrap_or((24, 80));
+    let (rows, cols) = terminal::initial_size(rows_override, cols_override);
 
     let mut c = client::Client::connect(&socket_path).await.map_err(|e| {
         eprintln!("wsh attach: failed to connect to server at {}: {}", socket_path.display(), e);
@@ -1211,8 +1323,8 @@ async fn run_list(socket: Option<PathBuf>, server_name: String) -> Result<(), Ws
         println!("No active sessions.");
     } else {
         println!(
-            "{:<20} {:<8} {:<20} {:<12} {:<8} {}",
-            "NAME", "PID", "COMMAND", "SIZE", "CLIENTS", "TAGS"
+            "{:<20} {:<8} {:<20} {:<12} {:<8} TAGS",
+            "NAME", "PID", "COMMAND", "SIZE", "CLIENTS"
         );
         for s in &sessions {
             let pid_str = match s.pid {
@@ -1314,7 +1426,7 @@ async fn run_tag(
     Ok(())
 }
 
-async fn run_stop(socket: Option<PathBuf>, server_name: String) -> Result<(), WshError> {
+async fn run_stop(socket: Option<PathBuf>, server_name: String, restart: bool) -> Result<(), WshError> {
     let socket_path = resolve_socket_path(socket, &server_name);
     let mut c = match client::Client::connect(&socket_path).await {
         Ok(c) => c,
@@ -1336,7 +1448,7 @@ async fn run_stop(socket: Option<PathBuf>, server_name: String) -> Result<(), Ws
         }
     };
 
-    if let Err(e) = c.shutdown_server().await {
+    if let Err(e) = c.shutdown_server(restart).await {
         eprintln!("wsh stop: {}", e);
         std::process::exit(1);
     }
@@ -140,10 +140,15 @@ impl WshMcpServer {
             Some(cmd) => SpawnCommand::Command {
                 command: cmd,
                 interactive: true,
+                force_interactive: None,
+                scrub_env: None,
             },
             None => SpawnCommand::Shell {
                 interactive: true,
                 shell: None,
+                login: params.login,
+                force_interactive: None,
+                scrub_env: None,
             },
         };
 
@@ -176,11 +181,15 @@ impl WshMcpServer {
         })?;
 
         // spawn_with_options calls fork()/exec() — run on blocking pool.
-        let cwd = params.cwd;
-        let env = params.env;
+        let options = crate::session::SpawnOptions {
+            cwd: params.cwd,
+            env: params.env,
+            read_buffer_bytes: self.state.sessions.read_buffer_bytes(),
+            ..Default::default()
+        };
         let (session, child_exit_rx) =
             tokio::task::spawn_blocking(move || {
-                Session::spawn_with_options("".to_string(), command, rows, cols, cwd, env)
+                Session::spawn_with_options("".to_string(), command, rows, cols, options)
             })
             .await
             .map_err(|e| ErrorData::internal_error(format!("spawn task failed: {e}"), None))?
@@ -470,7 +479,7 @@ impl WshMcpServer {
         let session = self.get_session(&params.session)?;
 
         let data = match params.encoding {
-            Encoding::Utf8 => Bytes::from(params.input.into_bytes()),
+            Encoding::Utf8 => session.input_mode.normalize(Bytes::from(params.input.into_bytes())),
             Encoding::Base64 => {
                 use base64::Engine;
                 let decoded = base64::engine::general_purpose::STANDARD
@@ -488,7 +497,7 @@ impl WshMcpServer {
         let len = data.len();
         tokio::time::timeout(
             Duration::from_secs(5),
-            session.input_tx.send(data),
+            session.input_target().send(data),
         )
         .await
         .map_err(|_| ErrorData::internal_error("input send timed out", None))?
@@ -547,6 +556,7 @@ impl WshMcpServer {
                 format,
                 offset: params.offset,
                 limit,
+                from_end: false,
             })
             .await
             .map_err(|e| {
@@ -679,7 +689,7 @@ impl WshMcpServer {
 
         // LIST mode
         if params.list {
-            let overlays = session.overlays.list_by_mode(current_mode);
+            let overlays = session.overlays.list_remote_by_mode(current_mode);
             let result = serde_json::json!({
                 "overlays": overlays,
             });
@@ -773,6 +783,7 @@ impl WshMcpServer {
                     background,
                     spans.unwrap_or_default(),
                     params.focusable,
+                    params.wrap,
                     current_mode,
                 ).map_err(|e| ErrorData::invalid_params(e, None))?;
 
@@ -970,6 +981,30 @@ impl WshMcpServer {
                 )
                 .await;
 
+                if let Some(command) = params.command.as_deref() {
+                    if let Err(e) = crate::panel::start_panel_command(
+                        &session.panels,
+                        &id,
+                        command,
+                        &session.terminal_size,
+                        &session.visual_update_tx,
+                        &session.cancelled,
+                    ) {
+                        session.panels.delete(&id);
+                        crate::panel::reconfigure_layout(
+                            &session.panels,
+                            &session.terminal_size,
+                            &session.pty,
+                            &session.parser,
+                        )
+                        .await;
+                        return Err(ErrorData::internal_error(
+                            format!("failed to start panel command: {e}"),
+                            None,
+                        ));
+                    }
+                }
+
                 let _ = session
                     .visual_update_tx
                     .send(crate::protocol::VisualUpdate::PanelsChanged);
@@ -1087,7 +1122,7 @@ impl WshMcpServer {
             let is_focusable = if let Some(overlay) = session.overlays.get(id) {
                 overlay.focusable
             } else if let Some(panel) = session.panels.get(id) {
-                panel.focusable
+                panel.accepts_focus()
             } else {
                 return Err(ErrorData::invalid_params(
                     format!("no overlay or panel with id '{id}'"),
@@ -173,6 +173,7 @@ pub async fn read_resource(
                     format: Format::Styled,
                     offset: 0,
                     limit: 100,
+                    from_end: false,
                 })
                 .await
                 .map_err(|e| {
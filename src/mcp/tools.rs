@@ -34,6 +34,11 @@ pub struct CreateSessionParams {
     #[serde(default)]
     #[schemars(description = "Tags to assign to the session at creation time.")]
     pub tags: Vec<String>,
+
+    /// Start the shell as a login shell so profile files are sourced.
+    #[serde(default)]
+    #[schemars(description = "Start the shell as a login shell so ~/.profile, ~/.bash_profile or ~/.zprofile are sourced. Ignored when command is set.")]
+    pub login: bool,
 }
 
 /// Parameters for the `wsh_list_sessions` tool.
@@ -281,6 +286,11 @@ pub struct OverlayParams {
     #[schemars(description = "Whether this overlay can receive input focus. Defaults to false.")]
     pub focusable: bool,
 
+    /// Wrap span text at the overlay's right edge onto the next row. Defaults to false.
+    #[serde(default)]
+    #[schemars(description = "Wrap span text at the overlay's right edge onto the next row, so one span can cover a multi-line region. Only used when creating. Defaults to false.")]
+    pub wrap: bool,
+
     /// If true, list all overlays for the current screen mode instead of creating/updating.
     #[serde(default)]
     #[schemars(description = "If true, list all overlays for the current screen mode. All other parameters are ignored.")]
@@ -335,6 +345,10 @@ pub struct PanelParams {
     #[schemars(description = "Whether this panel can receive input focus. Defaults to false.")]
     pub focusable: bool,
 
+    /// Command whose live output fills the panel. Only used when creating.
+    #[schemars(description = "Shell command whose live screen fills the panel (e.g. 'watch df -h'). It runs in its own terminal sized to the panel and is stopped when the panel is deleted. Only used when creating.")]
+    pub command: Option<String>,
+
     /// If true, list all panels for the current screen mode instead of creating/updating.
     #[serde(default)]
     #[schemars(description = "If true, list all panels for the current screen mode. All other parameters are ignored.")]
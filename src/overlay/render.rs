@@ -163,6 +163,7 @@ pub fn render_overlay(overlay: &Overlay) -> String {
 
     // Step 2: Render spans
     let mut current_row = overlay.y;
+    let mut col: u16 = 0;
     result.push_str(&cursor_position(current_row, overlay.x));
 
     for span in &overlay.spans {
@@ -172,13 +173,25 @@ pub fn render_overlay(overlay: &Overlay) -> String {
             if i > 0 {
                 // Newline encountered, move to next row
                 current_row = current_row.saturating_add(1);
+                col = 0;
                 result.push_str(&cursor_position(current_row, overlay.x));
             }
 
-            // Create a temporary span for this line segment
-            if !line.is_empty() {
+            let mut rest = *line;
+            while !rest.is_empty() {
+                if wrap_width(overlay).is_some_and(|w| col >= w) {
+                    // Right edge reached, continue on the next row
+                    current_row = current_row.saturating_add(1);
+                    col = 0;
+                    result.push_str(&cursor_position(current_row, overlay.x));
+                }
+                let room = wrap_width(overlay).map_or(usize::MAX, |w| usize::from(w - col));
+                let (chunk, tail) = split_at_char(rest, room);
+                rest = tail;
+
+                // Create a temporary span for this line segment
                 let line_span = OverlaySpan {
-                    text: line.to_string(),
+                    text: chunk.to_string(),
                     id: None,
                     fg: span.fg.clone(),
                     bg: span.bg.clone(),
@@ -187,8 +200,9 @@ pub fn render_overlay(overlay: &Overlay) -> String {
                     underline: span.underline,
                 };
                 result.push_str(&render_span_style(&line_span));
-                result.push_str(line);
+                result.push_str(chunk);
                 result.push_str(reset());
+                col = col.saturating_add(chunk.chars().count().min(u16::MAX as usize) as u16);
             }
         }
     }
@@ -221,12 +235,14 @@ pub fn end_sync() -> &'static str {
 
 /// Returns `(row, col, width)` for each visual line of an overlay.
 ///
-/// Replicates the newline-splitting logic from `render_overlay` but only computes
-/// geometry. Uses `len()` for width (ASCII approximation).
+/// Replicates the newline-splitting (and, for wrapping overlays, edge-wrapping)
+/// logic from `render_overlay` but only computes geometry. Uses `len()` for
+/// width (ASCII approximation).
 pub fn overlay_line_extents(overlay: &Overlay) -> Vec<(u16, u16, u16)> {
     let mut extents = Vec::new();
     let mut current_row = overlay.y;
     let mut current_width: u16 = 0;
+    let mut col: u16 = 0;
     let mut line_started = false;
 
     for span in &overlay.spans {
@@ -239,10 +255,23 @@ pub fn overlay_line_extents(overlay: &Overlay) -> Vec<(u16, u16, u16)> {
                 }
                 current_row = current_row.saturating_add(1);
                 current_width = 0;
+                col = 0;
                 line_started = false;
             }
-            if !line.is_empty() {
-                current_width = current_width.saturating_add(line.len().min(u16::MAX as usize) as u16);
+            let mut rest = *line;
+            while !rest.is_empty() {
+                if wrap_width(overlay).is_some_and(|w| col >= w) {
+                    // Wrap boundary: flush like a newline
+                    extents.push((current_row, overlay.x, current_width));
+                    current_row = current_row.saturating_add(1);
+                    current_width = 0;
+                    col = 0;
+                }
+                let room = wrap_width(overlay).map_or(usize::MAX, |w| usize::from(w - col));
+                let (chunk, tail) = split_at_char(rest, room);
+                rest = tail;
+                current_width = current_width.saturating_add(chunk.len().min(u16::MAX as usize) as u16);
+                col = col.saturating_add(chunk.chars().count().min(u16::MAX as usize) as u16);
                 line_started = true;
             }
         }
@@ -256,6 +285,17 @@ pub fn overlay_line_extents(overlay: &Overlay) -> Vec<(u16, u16, u16)> {
     extents
 }
 
+/// Column count at which span text wraps, if the overlay wraps at all.
+fn wrap_width(overlay: &Overlay) -> Option<u16> {
+    (overlay.wrap && overlay.width > 0).then_some(overlay.width)
+}
+
+/// Splits `s` after at most `n` characters.
+fn split_at_char(s: &str, n: usize) -> (&str, &str) {
+    let idx = s.char_indices().nth(n).map_or(s.len(), |(i, _)| i);
+    s.split_at(idx)
+}
+
 /// Generates ANSI sequences to erase a single overlay by overwriting with spaces.
 ///
 /// Uses the overlay's explicit `width` and `height` dimensions to erase the full
@@ -305,7 +345,7 @@ pub fn render_all_overlays(overlays: &[Overlay]) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use super::super::types::{BackgroundStyle, ScreenMode};
+    use super::super::types::{BackgroundStyle, OverlayScope, ScreenMode};
 
     #[test]
     fn test_render_plain_text() {
@@ -415,7 +455,9 @@ mod tests {
             }],
             region_writes: vec![],
             focusable: false,
+            wrap: false,
             screen_mode: ScreenMode::Normal,
+            scope: OverlayScope::LocalAndRemote,
         };
         let result = render_overlay(&overlay);
         // y=5, x=10 (0-indexed) -> row=6, col=11 (1-indexed)
@@ -532,7 +574,9 @@ mod tests {
             }],
             region_writes: vec![],
             focusable: false,
+            wrap: false,
             screen_mode: ScreenMode::Normal,
+            scope: OverlayScope::LocalAndRemote,
         }];
         let result = render_all_overlays(&overlays);
         assert!(
@@ -625,7 +669,9 @@ mod tests {
             }],
             region_writes: vec![],
             focusable: false,
+            wrap: false,
             screen_mode: ScreenMode::Normal,
+            scope: OverlayScope::LocalAndRemote,
         };
         let extents = overlay_line_extents(&overlay);
         assert_eq!(extents, vec![(3, 5, 5)]);
@@ -652,7 +698,9 @@ mod tests {
             }],
             region_writes: vec![],
             focusable: false,
+            wrap: false,
             screen_mode: ScreenMode::Normal,
+            scope: OverlayScope::LocalAndRemote,
         };
         let extents = overlay_line_extents(&overlay);
         assert_eq!(extents, vec![(0, 0, 2), (1, 0, 3), (2, 0, 1)]);
@@ -690,7 +738,9 @@ mod tests {
             ],
             region_writes: vec![],
             focusable: false,
+            wrap: false,
             screen_mode: ScreenMode::Normal,
+            scope: OverlayScope::LocalAndRemote,
         };
         let extents = overlay_line_extents(&overlay);
         // Two spans on same line: width = 2 + 2 = 4
@@ -729,7 +779,9 @@ mod tests {
             ],
             region_writes: vec![],
             focusable: false,
+            wrap: false,
             screen_mode: ScreenMode::Normal,
+            scope: OverlayScope::LocalAndRemote,
         };
         let extents = overlay_line_extents(&overlay);
         // First span: "ab\n" -> line "ab" (width 2), then newline
@@ -737,6 +789,64 @@ mod tests {
         assert_eq!(extents, vec![(0, 0, 2), (1, 0, 2)]);
     }
 
+    fn wrapping_overlay(text: &str) -> Overlay {
+        Overlay {
+            id: "t".to_string(),
+            x: 2,
+            y: 3,
+            z: 0,
+            width: 4,
+            height: 2,
+            background: None,
+            spans: vec![OverlaySpan {
+                text: text.to_string(),
+                id: None,
+                fg: Some(Color::Named(NamedColor::Red)),
+                bg: None,
+                bold: true,
+                italic: false,
+                underline: false,
+            }],
+            region_writes: vec![],
+            focusable: false,
+            wrap: true,
+            screen_mode: ScreenMode::Normal,
+            scope: OverlayScope::LocalAndRemote,
+        }
+    }
+
+    #[test]
+    fn test_render_wrapping_overlay_continues_on_next_row() {
+        let result = render_overlay(&wrapping_overlay("abcdef"));
+        // Same style on both rows; the second row starts back at x.
+        let style = "\x1b[1m\x1b[31m";
+        assert_eq!(
+            result,
+            format!("\x1b[4;3H{style}abcd\x1b[0m\x1b[5;3H{style}ef\x1b[0m"),
+        );
+    }
+
+    #[test]
+    fn test_render_wrapping_overlay_exact_fit_does_not_add_row() {
+        let result = render_overlay(&wrapping_overlay("abcd"));
+        assert!(!result.contains("\x1b[5;3H"), "got: {result:?}");
+    }
+
+    #[test]
+    fn test_render_non_wrapping_overlay_runs_past_edge() {
+        let mut overlay = wrapping_overlay("abcdef");
+        overlay.wrap = false;
+        let result = render_overlay(&overlay);
+        assert!(result.contains("abcdef"));
+        assert!(!result.contains("\x1b[5;3H"));
+    }
+
+    #[test]
+    fn test_overlay_line_extents_wrapping() {
+        let extents = overlay_line_extents(&wrapping_overlay("abcdef\ngh"));
+        assert_eq!(extents, vec![(3, 2, 4), (4, 2, 2), (5, 2, 2)]);
+    }
+
     #[test]
     fn test_erase_overlay_output() {
         let overlay = Overlay {
@@ -758,7 +868,9 @@ mod tests {
             }],
             region_writes: vec![],
             focusable: false,
+            wrap: false,
             screen_mode: ScreenMode::Normal,
+            scope: OverlayScope::LocalAndRemote,
         };
         let result = erase_overlay(&overlay);
         // Should erase full width=10 rectangle at (3,5) -> \x1b[4;6H then 10 spaces
@@ -786,7 +898,9 @@ mod tests {
             }],
             region_writes: vec![],
             focusable: false,
+            wrap: false,
             screen_mode: ScreenMode::Normal,
+            scope: OverlayScope::LocalAndRemote,
         };
         let result = erase_overlay(&overlay);
         let spaces = " ".repeat(20);
@@ -822,7 +936,9 @@ mod tests {
             spans: vec![],
             region_writes: vec![],
             focusable: false,
+            wrap: false,
             screen_mode: ScreenMode::Normal,
+            scope: OverlayScope::LocalAndRemote,
         };
         let result = render_overlay(&overlay);
         // Should contain background color
@@ -854,7 +970,9 @@ mod tests {
                 underline: false,
             }],
             focusable: false,
+            wrap: false,
             screen_mode: ScreenMode::Normal,
+            scope: OverlayScope::LocalAndRemote,
         };
         let result = render_overlay(&overlay);
         // Region write at (1, 5) within overlay at (10, 5)
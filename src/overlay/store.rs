@@ -3,7 +3,7 @@ use std::sync::Arc;
 use parking_lot::RwLock;
 use uuid::Uuid;
 
-use super::types::{BackgroundStyle, Overlay, OverlayId, OverlaySpan, RegionWrite, ScreenMode};
+use super::types::{BackgroundStyle, Overlay, OverlayId, OverlayScope, OverlaySpan, RegionWrite, ScreenMode};
 
 const MAX_OVERLAYS: usize = 256;
 const MAX_SPANS_PER_OVERLAY: usize = 4096;
@@ -67,7 +67,27 @@ impl OverlayStore {
         background: Option<BackgroundStyle>,
         spans: Vec<OverlaySpan>,
         focusable: bool,
+        wrap: bool,
         screen_mode: ScreenMode,
+    ) -> Result<OverlayId, &'static str> {
+        self.create_scoped(x, y, z, width, height, background, spans, focusable, wrap, screen_mode, OverlayScope::default())
+    }
+
+    /// Like [`create`](Self::create), but restricts which clients render the overlay.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_scoped(
+        &self,
+        x: u16,
+        y: u16,
+        z: Option<i32>,
+        width: u16,
+        height: u16,
+        background: Option<BackgroundStyle>,
+        spans: Vec<OverlaySpan>,
+        focusable: bool,
+        wrap: bool,
+        screen_mode: ScreenMode,
+        scope: OverlayScope,
     ) -> Result<OverlayId, &'static str> {
         let mut inner = self.inner.write();
         if inner.overlays.len() >= MAX_OVERLAYS {
@@ -95,7 +115,9 @@ impl OverlayStore {
             spans,
             region_writes: vec![],
             focusable,
+            wrap,
             screen_mode,
+            scope,
         };
         inner.overlays.insert(id.clone(), overlay);
         Ok(id)
@@ -292,6 +314,20 @@ impl OverlayStore {
         overlays
     }
 
+    /// List overlays for a screen mode that the local terminal should draw.
+    pub fn list_local_by_mode(&self, mode: ScreenMode) -> Vec<Overlay> {
+        let mut overlays = self.list_by_mode(mode);
+        overlays.retain(|o| o.scope.is_local());
+        overlays
+    }
+
+    /// List overlays for a screen mode that API clients should see.
+    pub fn list_remote_by_mode(&self, mode: ScreenMode) -> Vec<Overlay> {
+        let mut overlays = self.list_by_mode(mode);
+        overlays.retain(|o| o.scope.is_remote());
+        overlays
+    }
+
     /// Delete all overlays for a specific screen mode
     pub fn delete_by_mode(&self, mode: ScreenMode) {
         let mut inner = self.inner.write();
@@ -319,14 +355,14 @@ mod tests {
     #[test]
     fn test_create_overlay() {
         let store = OverlayStore::new();
-        let id = store.create(0, 0, None, 80, 1, None, vec![], false, ScreenMode::Normal).unwrap();
+        let id = store.create(0, 0, None, 80, 1, None, vec![], false, false, ScreenMode::Normal).unwrap();
         assert!(!id.is_empty());
     }
 
     #[test]
     fn test_get_overlay() {
         let store = OverlayStore::new();
-        let id = store.create(5, 10, Some(50), 80, 1, None, vec![], false, ScreenMode::Normal).unwrap();
+        let id = store.create(5, 10, Some(50), 80, 1, None, vec![], false, false, ScreenMode::Normal).unwrap();
         let overlay = store.get(&id).unwrap();
         assert_eq!(overlay.x, 5);
         assert_eq!(overlay.y, 10);
@@ -336,9 +372,9 @@ mod tests {
     #[test]
     fn test_list_overlays_sorted_by_z() {
         let store = OverlayStore::new();
-        store.create(0, 0, Some(100), 80, 1, None, vec![], false, ScreenMode::Normal).unwrap();
-        store.create(0, 0, Some(50), 80, 1, None, vec![], false, ScreenMode::Normal).unwrap();
-        store.create(0, 0, Some(75), 80, 1, None, vec![], false, ScreenMode::Normal).unwrap();
+        store.create(0, 0, Some(100), 80, 1, None, vec![], false, false, ScreenMode::Normal).unwrap();
+        store.create(0, 0, Some(50), 80, 1, None, vec![], false, false, ScreenMode::Normal).unwrap();
+        store.create(0, 0, Some(75), 80, 1, None, vec![], false, false, ScreenMode::Normal).unwrap();
 
         let list = store.list();
         assert_eq!(list.len(), 3);
@@ -350,7 +386,7 @@ mod tests {
     #[test]
     fn test_delete_overlay() {
         let store = OverlayStore::new();
-        let id = store.create(0, 0, None, 80, 1, None, vec![], false, ScreenMode::Normal).unwrap();
+        let id = store.create(0, 0, None, 80, 1, None, vec![], false, false, ScreenMode::Normal).unwrap();
         assert!(store.delete(&id));
         assert!(store.get(&id).is_none());
     }
@@ -358,8 +394,8 @@ mod tests {
     #[test]
     fn test_clear_overlays() {
         let store = OverlayStore::new();
-        store.create(0, 0, None, 80, 1, None, vec![], false, ScreenMode::Normal).unwrap();
-        store.create(0, 0, None, 80, 1, None, vec![], false, ScreenMode::Normal).unwrap();
+        store.create(0, 0, None, 80, 1, None, vec![], false, false, ScreenMode::Normal).unwrap();
+        store.create(0, 0, None, 80, 1, None, vec![], false, false, ScreenMode::Normal).unwrap();
         store.clear();
         assert!(store.list().is_empty());
     }
@@ -367,8 +403,8 @@ mod tests {
     #[test]
     fn test_auto_increment_z() {
         let store = OverlayStore::new();
-        let id1 = store.create(0, 0, None, 80, 1, None, vec![], false, ScreenMode::Normal).unwrap();
-        let id2 = store.create(0, 0, None, 80, 1, None, vec![], false, ScreenMode::Normal).unwrap();
+        let id1 = store.create(0, 0, None, 80, 1, None, vec![], false, false, ScreenMode::Normal).unwrap();
+        let id2 = store.create(0, 0, None, 80, 1, None, vec![], false, false, ScreenMode::Normal).unwrap();
         let o1 = store.get(&id1).unwrap();
         let o2 = store.get(&id2).unwrap();
         assert!(o2.z > o1.z);
@@ -397,7 +433,7 @@ mod tests {
                 underline: false,
             },
         ];
-        let oid = store.create(0, 0, None, 80, 1, None, spans, false, ScreenMode::Normal).unwrap();
+        let oid = store.create(0, 0, None, 80, 1, None, spans, false, false, ScreenMode::Normal).unwrap();
 
         // Update only the "value" span
         let updates = vec![OverlaySpan {
@@ -449,7 +485,7 @@ mod tests {
             italic: false,
             underline: false,
         }];
-        let oid = store.create(0, 0, None, 80, 1, None, spans, false, ScreenMode::Normal).unwrap();
+        let oid = store.create(0, 0, None, 80, 1, None, spans, false, false, ScreenMode::Normal).unwrap();
 
         // Update with a span ID that doesn't match anything
         let updates = vec![OverlaySpan {
@@ -471,7 +507,7 @@ mod tests {
     #[test]
     fn test_region_write_stores_writes() {
         let store = OverlayStore::new();
-        let oid = store.create(0, 0, None, 80, 10, None, vec![], false, ScreenMode::Normal).unwrap();
+        let oid = store.create(0, 0, None, 80, 10, None, vec![], false, false, ScreenMode::Normal).unwrap();
 
         let writes = vec![
             RegionWrite {
@@ -510,7 +546,7 @@ mod tests {
     #[test]
     fn test_region_write_replaces_previous() {
         let store = OverlayStore::new();
-        let oid = store.create(0, 0, None, 80, 10, None, vec![], false, ScreenMode::Normal).unwrap();
+        let oid = store.create(0, 0, None, 80, 10, None, vec![], false, false, ScreenMode::Normal).unwrap();
 
         let writes1 = vec![RegionWrite {
             row: 0,
@@ -564,9 +600,9 @@ mod tests {
     #[test]
     fn test_list_by_mode_filters_correctly() {
         let store = OverlayStore::new();
-        store.create(0, 0, None, 80, 1, None, vec![], false, ScreenMode::Normal).unwrap();
-        store.create(0, 0, None, 80, 1, None, vec![], false, ScreenMode::Normal).unwrap();
-        store.create(0, 0, None, 80, 1, None, vec![], false, ScreenMode::Alt).unwrap();
+        store.create(0, 0, None, 80, 1, None, vec![], false, false, ScreenMode::Normal).unwrap();
+        store.create(0, 0, None, 80, 1, None, vec![], false, false, ScreenMode::Normal).unwrap();
+        store.create(0, 0, None, 80, 1, None, vec![], false, false, ScreenMode::Alt).unwrap();
 
         let normal = store.list_by_mode(ScreenMode::Normal);
         let alt = store.list_by_mode(ScreenMode::Alt);
@@ -574,12 +610,45 @@ mod tests {
         assert_eq!(alt.len(), 1);
     }
 
+    #[test]
+    fn test_local_and_remote_scope_is_listed_for_both() {
+        let store = OverlayStore::new();
+        let id = store.create(0, 0, None, 80, 1, None, vec![], false, false, ScreenMode::Normal).unwrap();
+        assert_eq!(store.get(&id).unwrap().scope, OverlayScope::LocalAndRemote);
+        assert_eq!(store.list_local_by_mode(ScreenMode::Normal).len(), 1);
+        assert_eq!(store.list_remote_by_mode(ScreenMode::Normal).len(), 1);
+    }
+
+    #[test]
+    fn test_remote_only_scope_is_hidden_locally() {
+        let store = OverlayStore::new();
+        let id = store
+            .create_scoped(0, 0, None, 80, 1, None, vec![], false, false, ScreenMode::Normal, OverlayScope::RemoteOnly)
+            .unwrap();
+        assert!(store.list_local_by_mode(ScreenMode::Normal).is_empty());
+        let remote = store.list_remote_by_mode(ScreenMode::Normal);
+        assert_eq!(remote.len(), 1);
+        assert_eq!(remote[0].id, id);
+    }
+
+    #[test]
+    fn test_local_only_scope_is_hidden_remotely() {
+        let store = OverlayStore::new();
+        let id = store
+            .create_scoped(0, 0, None, 80, 1, None, vec![], false, false, ScreenMode::Normal, OverlayScope::LocalOnly)
+            .unwrap();
+        assert!(store.list_remote_by_mode(ScreenMode::Normal).is_empty());
+        let local = store.list_local_by_mode(ScreenMode::Normal);
+        assert_eq!(local.len(), 1);
+        assert_eq!(local[0].id, id);
+    }
+
     #[test]
     fn test_delete_by_mode_removes_only_matching() {
         let store = OverlayStore::new();
-        store.create(0, 0, None, 80, 1, None, vec![], false, ScreenMode::Normal).unwrap();
-        store.create(0, 0, None, 80, 1, None, vec![], false, ScreenMode::Alt).unwrap();
-        store.create(0, 0, None, 80, 1, None, vec![], false, ScreenMode::Alt).unwrap();
+        store.create(0, 0, None, 80, 1, None, vec![], false, false, ScreenMode::Normal).unwrap();
+        store.create(0, 0, None, 80, 1, None, vec![], false, false, ScreenMode::Alt).unwrap();
+        store.create(0, 0, None, 80, 1, None, vec![], false, false, ScreenMode::Alt).unwrap();
 
         store.delete_by_mode(ScreenMode::Alt);
         assert_eq!(store.list().len(), 1);
@@ -592,7 +661,7 @@ mod tests {
         let bg = BackgroundStyle {
             bg: Color::Named(NamedColor::Blue),
         };
-        let id = store.create(0, 0, None, 40, 10, Some(bg), vec![], false, ScreenMode::Normal).unwrap();
+        let id = store.create(0, 0, None, 40, 10, Some(bg), vec![], false, false, ScreenMode::Normal).unwrap();
         let overlay = store.get(&id).unwrap();
         assert!(overlay.background.is_some());
         assert_eq!(overlay.background.unwrap().bg, Color::Named(NamedColor::Blue));
@@ -601,7 +670,7 @@ mod tests {
     #[test]
     fn test_move_to_with_background() {
         let store = OverlayStore::new();
-        let id = store.create(0, 0, None, 80, 1, None, vec![], false, ScreenMode::Normal).unwrap();
+        let id = store.create(0, 0, None, 80, 1, None, vec![], false, false, ScreenMode::Normal).unwrap();
         assert!(store.get(&id).unwrap().background.is_none());
 
         let bg = BackgroundStyle {
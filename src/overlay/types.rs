@@ -17,6 +17,36 @@ pub fn is_normal_mode(mode: &ScreenMode) -> bool {
     matches!(mode, ScreenMode::Normal)
 }
 
+/// Which clients an overlay is rendered for.
+///
+/// "Local" is the terminal attached to the session through `wsh` itself;
+/// "remote" is everything reading overlays over the HTTP/WebSocket/MCP APIs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlayScope {
+    #[default]
+    LocalAndRemote,
+    RemoteOnly,
+    LocalOnly,
+}
+
+impl OverlayScope {
+    /// Whether overlays with this scope are drawn on the local terminal.
+    pub fn is_local(self) -> bool {
+        !matches!(self, OverlayScope::RemoteOnly)
+    }
+
+    /// Whether overlays with this scope are returned to API clients.
+    pub fn is_remote(self) -> bool {
+        !matches!(self, OverlayScope::LocalOnly)
+    }
+}
+
+/// Helper for serde `skip_serializing_if` on `OverlayScope` fields.
+pub fn is_local_and_remote(scope: &OverlayScope) -> bool {
+    matches!(scope, OverlayScope::LocalAndRemote)
+}
+
 /// Unique identifier for an overlay
 pub type OverlayId = String;
 
@@ -63,8 +93,15 @@ pub struct Overlay {
     pub region_writes: Vec<RegionWrite>,
     #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub focusable: bool,
+    /// Wrap span text at the overlay's right edge (`x + width`) onto the next
+    /// row instead of running past it, so one span can cover a multi-line
+    /// region with a uniform style.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub wrap: bool,
     #[serde(default, skip_serializing_if = "is_normal_mode")]
     pub screen_mode: ScreenMode,
+    #[serde(default, skip_serializing_if = "is_local_and_remote")]
+    pub scope: OverlayScope,
 }
 
 /// A styled text span within an overlay
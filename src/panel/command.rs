@@ -0,0 +1,395 @@
+//! Panels whose content is the live screen of a separate command.
+//!
+//! A command panel runs its own PTY and parser, sized to the panel's rows and
+//! the terminal's columns. Whenever the command's screen changes, the screen
+//! is converted to spans and stored on the panel, and socket clients repaint
+//! it from the next PanelSync like any other panel. This gives dashboard
+//! layouts with independent panes (e.g. `watch df -h` in a bottom panel).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::{FutureExt, StreamExt};
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
+
+use crate::overlay::{Color, NamedColor, OverlaySpan};
+use crate::parser::state::{self, FormattedLine, Query, QueryResponse};
+use crate::parser::Parser;
+use crate::protocol::VisualUpdate;
+use crate::pty::{Pty, PtyError, SpawnCommand};
+use crate::terminal::TerminalSize;
+
+use super::store::PanelStore;
+use super::types::PanelId;
+
+/// Screen changes arriving within this window are folded into one refresh.
+const REFRESH_DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// Scrollback kept by the command's parser. Only the screen is shown.
+const SCROLLBACK_LIMIT: usize = 100;
+
+/// A running panel command. Dropping it kills the command and stops
+/// refreshing the panel.
+pub struct PanelCommand {
+    pty: Arc<parking_lot::Mutex<Pty>>,
+    parser: Parser,
+    input_tx: mpsc::Sender<Bytes>,
+    pid: Option<u32>,
+    exited: Arc<AtomicBool>,
+    cancel: CancellationToken,
+}
+
+impl PanelCommand {
+    /// Start `command` in a `rows` x `cols` PTY, mirroring its screen into
+    /// panel `panel_id`.
+    ///
+    /// `cancel` stops the command too; pass the session's cancellation token
+    /// so commands die with their session.
+    pub fn spawn(
+        command: &str,
+        rows: u16,
+        cols: u16,
+        panel_id: PanelId,
+        panels: PanelStore,
+        visual_update_tx: broadcast::Sender<VisualUpdate>,
+        cancel: &CancellationToken,
+    ) -> Result<Self, PtyError> {
+        let rows = rows.max(1);
+        let cols = cols.max(1);
+        let mut pty = Pty::spawn(
+            rows,
+            cols,
+            SpawnCommand::Command { command: command.to_string(), interactive: false, force_interactive: None, scrub_env: None },
+        )?;
+        let reader = pty.take_reader()?;
+        let writer = pty.take_writer()?;
+        let child = pty.take_child();
+        let pid = child.as_ref().and_then(|c| c.process_id());
+        let exited = Arc::new(AtomicBool::new(false));
+
+        let (parser_tx, parser_rx) = mpsc::channel::<Bytes>(256);
+        let parser = Parser::spawn(parser_rx, cols as usize, rows as usize, SCROLLBACK_LIMIT);
+
+        // PTY reader: feeds the parser, then reaps the child on EOF.
+        let exited_clone = exited.clone();
+        tokio::task::spawn_blocking(move || {
+            use std::io::Read;
+            let mut reader = reader;
+            let mut buf = [0u8; 4096];
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        if parser_tx.blocking_send(Bytes::copy_from_slice(&buf[..n])).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+            if let Some(mut child) = child {
+                let _ = child.wait();
+            }
+            exited_clone.store(true, Ordering::Release);
+        });
+
+        // PTY writer: input routed to the panel while it has focus.
+        let (input_tx, mut input_rx) = mpsc::channel::<Bytes>(64);
+        tokio::task::spawn_blocking(move || {
+            use std::io::Write;
+            let mut writer = writer;
+            while let Some(data) = input_rx.blocking_recv() {
+                if writer.write_all(&data).is_err() {
+                    break;
+                }
+                let _ = writer.flush();
+            }
+        });
+
+        let cancel = cancel.child_token();
+        tokio::spawn(refresh_loop(
+            parser.clone(),
+            panel_id,
+            panels,
+            visual_update_tx,
+            cancel.clone(),
+        ));
+
+        Ok(Self {
+            pty: Arc::new(parking_lot::Mutex::new(pty)),
+            parser,
+            input_tx,
+            pid,
+            exited,
+            cancel,
+        })
+    }
+
+    /// Channel that writes to the command's PTY.
+    pub fn input_sender(&self) -> mpsc::Sender<Bytes> {
+        self.input_tx.clone()
+    }
+
+    /// Resize the command's PTY and parser to the panel's new dimensions.
+    pub async fn resize(&self, rows: u16, cols: u16) {
+        let rows = rows.max(1);
+        let cols = cols.max(1);
+        if let Err(e) = self.pty.lock().resize(rows, cols) {
+            tracing::warn!(?e, "failed to resize panel command PTY");
+        }
+        if let Err(e) = self.parser.resize(cols as usize, rows as usize).await {
+            tracing::warn!(?e, "failed to resize panel command parser");
+        }
+    }
+}
+
+impl Drop for PanelCommand {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+        // Skip the signal once the child is reaped; its PID may be reused.
+        if self.exited.load(Ordering::Acquire) {
+            return;
+        }
+        if let Some(pid) = self.pid.filter(|&p| p != 0 && p <= i32::MAX as u32) {
+            // The child leads its own process group (portable_pty calls
+            // setsid), so this also reaches anything it started.
+            #[cfg(unix)]
+            unsafe {
+                libc::kill(-(pid as i32), libc::SIGKILL);
+            }
+        }
+    }
+}
+
+/// Start `command` for an existing panel, sized to the panel's height and
+/// the terminal's width.
+pub fn start_panel_command(
+    panels: &PanelStore,
+    panel_id: &str,
+    command: &str,
+    terminal_size: &TerminalSize,
+    visual_update_tx: &broadcast::Sender<VisualUpdate>,
+    cancel: &CancellationToken,
+) -> Result<(), PtyError> {
+    let Some(panel) = panels.get(panel_id) else {
+        return Ok(());
+    };
+    let (_, cols) = terminal_size.get();
+    let handle = PanelCommand::spawn(
+        command,
+        panel.height,
+        cols,
+        panel_id.to_string(),
+        panels.clone(),
+        visual_update_tx.clone(),
+        cancel,
+    )?;
+    panels.set_command(panel_id, command.to_string(), handle);
+    Ok(())
+}
+
+async fn refresh_loop(
+    parser: Parser,
+    panel_id: PanelId,
+    panels: PanelStore,
+    visual_update_tx: broadcast::Sender<VisualUpdate>,
+    cancel: CancellationToken,
+) {
+    let mut events = Box::pin(parser.subscribe());
+    // Output produced before subscribing has no event of its own.
+    refresh(&parser, &panel_id, &panels, &visual_update_tx).await;
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            event = events.next() => {
+                if event.is_none() {
+                    break;
+                }
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = tokio::time::sleep(REFRESH_DEBOUNCE) => {}
+                }
+                while let Some(Some(_)) = events.next().now_or_never() {}
+                if !refresh(&parser, &panel_id, &panels, &visual_update_tx).await {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Copy the command's screen into the panel. Returns false once the panel
+/// is gone.
+async fn refresh(
+    parser: &Parser,
+    panel_id: &str,
+    panels: &PanelStore,
+    visual_update_tx: &broadcast::Sender<VisualUpdate>,
+) -> bool {
+    let Ok(Ok(QueryResponse::Screen(screen))) = tokio::time::timeout(
+        Duration::from_secs(5),
+        parser.query(Query::Screen { format: state::Format::Styled }),
+    )
+    .await
+    else {
+        return true;
+    };
+    match panels.update(panel_id, screen_spans(&screen.lines)) {
+        Ok(true) => {
+            let _ = visual_update_tx.send(VisualUpdate::PanelsChanged);
+            true
+        }
+        Ok(false) => false,
+        Err(e) => {
+            tracing::debug!(panel = panel_id, error = e, "panel command screen not stored");
+            true
+        }
+    }
+}
+
+/// Convert screen lines to panel spans, one row per line.
+pub fn screen_spans(lines: &[FormattedLine]) -> Vec<OverlaySpan> {
+    let mut spans = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            spans.push(plain_span("\n".to_string()));
+        }
+        match line {
+            FormattedLine::Plain(text) => {
+                if !text.is_empty() {
+                    spans.push(plain_span(text.clone()));
+                }
+            }
+            FormattedLine::Styled(styled) => {
+                spans.extend(styled.iter().filter(|s| !s.text.is_empty()).map(|s| OverlaySpan {
+                    text: s.text.clone(),
+                    id: None,
+                    fg: s.style.fg.as_ref().map(overlay_color),
+                    bg: s.style.bg.as_ref().map(overlay_color),
+                    bold: s.style.bold,
+                    italic: s.style.italic,
+                    underline: s.style.underline,
+                }));
+            }
+        }
+    }
+    spans
+}
+
+fn plain_span(text: String) -> OverlaySpan {
+    OverlaySpan {
+        text,
+        id: None,
+        fg: None,
+        bg: None,
+        bold: false,
+        italic: false,
+        underline: false,
+    }
+}
+
+/// Map a terminal color onto the overlay palette: the 16 ANSI colors become
+/// named colors (bright variants fold onto their base color) and the rest
+/// of the 256-color palette becomes RGB.
+fn overlay_color(color: &state::Color) -> Color {
+    match *color {
+        state::Color::Rgb { r, g, b } => Color::Rgb { r, g, b },
+        state::Color::Indexed(i) if i < 16 => Color::Named(match i % 8 {
+            0 => NamedColor::Black,
+            1 => NamedColor::Red,
+            2 => NamedColor::Green,
+            3 => NamedColor::Yellow,
+            4 => NamedColor::Blue,
+            5 => NamedColor::Magenta,
+            6 => NamedColor::Cyan,
+            _ => NamedColor::White,
+        }),
+        state::Color::Indexed(i) if i < 232 => {
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            let i = i - 16;
+            Color::Rgb { r: level(i / 36), g: level(i / 6 % 6), b: level(i % 6) }
+        }
+        state::Color::Indexed(i) => {
+            let v = 8 + (i - 232) * 10;
+            Color::Rgb { r: v, g: v, b: v }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::state::{Span, Style};
+
+    #[test]
+    fn screen_spans_separates_rows_and_keeps_style() {
+        let lines = vec![
+            FormattedLine::Styled(vec![Span {
+                text: "ok".to_string(),
+                style: Style { fg: Some(state::Color::Indexed(2)), bold: true, ..Default::default() },
+            }]),
+            FormattedLine::Plain(String::new()),
+            FormattedLine::Plain("done".to_string()),
+        ];
+        let spans = screen_spans(&lines);
+        let text: String = spans.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(text, "ok\n\ndone");
+        assert_eq!(spans[0].fg, Some(Color::Named(NamedColor::Green)));
+        assert!(spans[0].bold);
+    }
+
+    #[test]
+    fn overlay_color_maps_palette() {
+        assert_eq!(overlay_color(&state::Color::Indexed(9)), Color::Named(NamedColor::Red));
+        assert_eq!(overlay_color(&state::Color::Indexed(16)), Color::Rgb { r: 0, g: 0, b: 0 });
+        assert_eq!(overlay_color(&state::Color::Indexed(231)), Color::Rgb { r: 255, g: 255, b: 255 });
+        assert_eq!(overlay_color(&state::Color::Indexed(232)), Color::Rgb { r: 8, g: 8, b: 8 });
+        assert_eq!(
+            overlay_color(&state::Color::Rgb { r: 1, g: 2, b: 3 }),
+            Color::Rgb { r: 1, g: 2, b: 3 }
+        );
+    }
+
+    #[tokio::test]
+    async fn command_output_fills_panel_and_follows_resize() {
+        let panels = PanelStore::new();
+        let id = panels
+            .create(super::super::Position::Bottom, 3, None, None, vec![], false, crate::overlay::ScreenMode::Normal)
+            .unwrap();
+        let (visual_tx, mut visual_rx) = broadcast::channel(16);
+        let command = PanelCommand::spawn(
+            "echo from-panel; stty size; sleep 5",
+            3,
+            40,
+            id.clone(),
+            panels.clone(),
+            visual_tx,
+            &CancellationToken::new(),
+        )
+        .unwrap();
+
+        let text_of = |panels: &PanelStore| -> String {
+            panels.get(&id).unwrap().spans.iter().map(|s| s.text.clone()).collect()
+        };
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while !text_of(&panels).contains("3 40") {
+            assert!(tokio::time::Instant::now() < deadline, "panel never filled: {:?}", text_of(&panels));
+            let _ = tokio::time::timeout(Duration::from_millis(200), visual_rx.recv()).await;
+        }
+        assert!(text_of(&panels).starts_with("from-panel"));
+
+        command.resize(4, 20).await;
+        let Ok(QueryResponse::Screen(screen)) =
+            command.parser.query(Query::Screen { format: state::Format::Plain }).await
+        else {
+            panic!("expected screen response");
+        };
+        assert_eq!((screen.rows, screen.cols), (4, 20));
+
+        // Dropping the command stops it; the panel keeps its last screen.
+        drop(command);
+        assert!(text_of(&panels).contains("from-panel"));
+    }
+}
@@ -9,15 +9,23 @@ use crate::parser::Parser;
 use crate::pty::Pty;
 use crate::terminal::TerminalSize;
 
-use super::layout::compute_layout;
+use super::layout::{compute_layout, LayoutRects};
 use super::store::PanelStore;
 
+/// Screen rectangles of the PTY viewport and each visible panel, computed
+/// the same way `reconfigure_layout()` sizes the PTY.
+pub fn current_layout(panels: &PanelStore, terminal_size: &TerminalSize) -> LayoutRects {
+    let (term_rows, term_cols) = terminal_size.get();
+    compute_layout(&panels.list(), term_rows, term_cols).rects()
+}
+
 /// Reconfigure the terminal layout after panel changes.
 ///
 /// This function:
 /// 1. Computes the new layout from all panels and terminal size
 /// 2. Updates panel visibility in the store
 /// 3. Resizes the PTY and parser to match the new viewport
+/// 4. Resizes each visible command panel's PTY to the panel's rows
 ///
 /// Call this after any panel create, delete, or height/position/z change.
 /// Also called on outer terminal resize (SIGWINCH).
@@ -51,6 +59,14 @@ pub async fn reconfigure_layout(
     {
         tracing::error!(?e, "failed to resize parser");
     }
+
+    for (id, command) in panels.commands() {
+        if let Some(panel) = all_panels.iter().find(|p| p.id == id) {
+            if !layout.hidden_panels.contains(&id) {
+                command.resize(panel.height, term_cols).await;
+            }
+        }
+    }
 }
 
 /// Notify that a single panel's content has changed (spans only).
@@ -1,3 +1,7 @@
+use serde::Serialize;
+
+use crate::overlay::BackgroundStyle;
+
 use super::types::{Panel, PanelId, Position};
 
 /// Computed screen layout based on active panels and terminal dimensions.
@@ -19,6 +23,76 @@ pub struct Layout {
     pub pty_cols: u16,
 }
 
+/// A rectangle on the terminal grid (0-indexed).
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct Rect {
+    pub row: u16,
+    pub col: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+/// Where a visible panel is drawn.
+#[derive(Debug, Clone, Serialize)]
+pub struct PanelRect {
+    pub id: PanelId,
+    pub position: Position,
+    pub z: i32,
+    #[serde(flatten)]
+    pub rect: Rect,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<BackgroundStyle>,
+}
+
+/// A [`Layout`] as rectangles, for clients that draw panels themselves.
+#[derive(Debug, Clone, Serialize)]
+pub struct LayoutRects {
+    /// Terminal size the layout was computed for.
+    pub rows: u16,
+    pub cols: u16,
+    /// Area left to the PTY.
+    pub pty: Rect,
+    /// Visible panels, top panels first, each group from the edge inward.
+    pub panels: Vec<PanelRect>,
+    pub hidden_panels: Vec<PanelId>,
+}
+
+impl Layout {
+    /// Panel and PTY rectangles, using the same placement as
+    /// [`render_all_panels`](super::render::render_all_panels).
+    pub fn rects(&self) -> LayoutRects {
+        let cols = self.pty_cols;
+        let top_height: u16 = self.top_panels.iter().map(|p| p.height).sum();
+        let bottom_height: u16 = self.bottom_panels.iter().map(|p| p.height).sum();
+
+        let mut panels = Vec::with_capacity(self.top_panels.len() + self.bottom_panels.len());
+        let mut place = |group: &[Panel], mut row: u16| {
+            for panel in group {
+                panels.push(PanelRect {
+                    id: panel.id.clone(),
+                    position: panel.position.clone(),
+                    z: panel.z,
+                    rect: Rect { row, col: 0, width: cols, height: panel.height },
+                    background: panel.background.clone(),
+                });
+                row = row.saturating_add(panel.height);
+            }
+        };
+        place(&self.top_panels, 0);
+        // scroll_region_bottom is 1-indexed, so it is also the 0-indexed
+        // row just below the PTY area.
+        place(&self.bottom_panels, self.scroll_region_bottom);
+
+        LayoutRects {
+            rows: top_height + self.pty_rows + bottom_height,
+            cols,
+            pty: Rect { row: top_height, col: 0, width: cols, height: self.pty_rows },
+            panels,
+            hidden_panels: self.hidden_panels.clone(),
+        }
+    }
+}
+
 /// Compute the screen layout given all panels and the terminal dimensions.
 ///
 /// Panels are allocated greedily by z-index (highest first = highest priority).
@@ -36,8 +110,8 @@ pub fn compute_layout(panels: &[Panel], terminal_rows: u16, terminal_cols: u16)
         .collect();
 
     // Sort by z descending (highest priority first)
-    top_panels.sort_by(|a, b| b.z.cmp(&a.z));
-    bottom_panels.sort_by(|a, b| b.z.cmp(&a.z));
+    top_panels.sort_by_key(|p| std::cmp::Reverse(p.z));
+    bottom_panels.sort_by_key(|p| std::cmp::Reverse(p.z));
 
     let mut remaining_rows = terminal_rows;
     let mut visible_top: Vec<Panel> = Vec::new();
@@ -51,7 +125,7 @@ pub fn compute_layout(panels: &[Panel], terminal_rows: u16, terminal_cols: u16)
         .chain(bottom_panels.iter())
         .cloned()
         .collect();
-    all_panels.sort_by(|a, b| b.z.cmp(&a.z));
+    all_panels.sort_by_key(|p| std::cmp::Reverse(p.z));
 
     for panel in &all_panels {
         if remaining_rows == 0 {
@@ -74,8 +148,8 @@ pub fn compute_layout(panels: &[Panel], terminal_rows: u16, terminal_cols: u16)
     }
 
     // Re-sort visible panels: top by z descending, bottom by z descending
-    visible_top.sort_by(|a, b| b.z.cmp(&a.z));
-    visible_bottom.sort_by(|a, b| b.z.cmp(&a.z));
+    visible_top.sort_by_key(|p| std::cmp::Reverse(p.z));
+    visible_bottom.sort_by_key(|p| std::cmp::Reverse(p.z));
 
     let top_height: u16 = visible_top.iter().map(|p| p.height).sum();
     let bottom_height: u16 = visible_bottom.iter().map(|p| p.height).sum();
@@ -121,6 +195,7 @@ mod tests {
             region_writes: vec![],
             visible: true,
             focusable: false,
+            command: None,
             screen_mode: ScreenMode::Normal,
         }
     }
@@ -290,9 +365,38 @@ mod tests {
             region_writes: vec![],
             visible: true,
             focusable: false,
+            command: None,
             screen_mode: ScreenMode::Normal,
         }];
         let layout = compute_layout(&panels, 24, 80);
         assert_eq!(layout.bottom_panels[0].spans[0].text, "hello");
     }
+
+    #[test]
+    fn test_rects_place_panels_around_pty() {
+        let panels = vec![
+            make_panel("t1", Position::Top, 2, 10),
+            make_panel("t2", Position::Top, 1, 5),
+            make_panel("b1", Position::Bottom, 3, 7),
+            make_panel("big", Position::Bottom, 30, 1),
+        ];
+        let rects = compute_layout(&panels, 24, 80).rects();
+
+        assert_eq!((rects.rows, rects.cols), (24, 80));
+        assert_eq!(rects.pty, Rect { row: 3, col: 0, width: 80, height: 18 });
+        let placed: Vec<(&str, Rect)> = rects.panels.iter().map(|p| (p.id.as_str(), p.rect)).collect();
+        assert_eq!(
+            placed,
+            vec![
+                ("t1", Rect { row: 0, col: 0, width: 80, height: 2 }),
+                ("t2", Rect { row: 2, col: 0, width: 80, height: 1 }),
+                ("b1", Rect { row: 21, col: 0, width: 80, height: 3 }),
+            ]
+        );
+        assert_eq!(rects.hidden_panels, vec!["big".to_string()]);
+
+        let json = serde_json::to_value(&rects.panels[0]).unwrap();
+        assert_eq!(json["row"], 0);
+        assert_eq!(json["position"], "top");
+    }
 }
@@ -1,11 +1,13 @@
+pub mod command;
 pub mod coordinator;
 pub mod layout;
 pub mod render;
 pub mod store;
 pub mod types;
 
-pub use coordinator::{flush_panel_content, reconfigure_layout};
-pub use layout::{compute_layout, Layout};
+pub use command::{start_panel_command, PanelCommand};
+pub use coordinator::{current_layout, flush_panel_content, reconfigure_layout};
+pub use layout::{compute_layout, Layout, LayoutRects, PanelRect, Rect};
 pub use render::{
     erase_all_panels, render_all_panels, render_panel, reset_scroll_region, set_scroll_region,
 };
@@ -278,6 +278,7 @@ mod tests {
             region_writes: vec![],
             visible: true,
             focusable: false,
+            command: None,
             screen_mode: ScreenMode::Normal,
         }
     }
@@ -304,6 +305,7 @@ mod tests {
             region_writes: vec![],
             visible: true,
             focusable: false,
+            command: None,
             screen_mode: ScreenMode::Normal,
         };
         let result = render_panel(&panel, 23, 10);
@@ -326,6 +328,7 @@ mod tests {
             region_writes: vec![],
             visible: true,
             focusable: false,
+            command: None,
             screen_mode: ScreenMode::Normal,
         };
         let result = render_panel(&panel, 0, 10);
@@ -349,6 +352,7 @@ mod tests {
             region_writes: vec![],
             visible: true,
             focusable: false,
+            command: None,
             screen_mode: ScreenMode::Normal,
         };
         let result = render_panel(&panel, 0, 20);
@@ -377,6 +381,7 @@ mod tests {
             region_writes: vec![],
             visible: true,
             focusable: false,
+            command: None,
             screen_mode: ScreenMode::Normal,
         };
         let result = render_panel(&panel, 23, 10);
@@ -435,6 +440,7 @@ mod tests {
             visible: true,
             region_writes: vec![],
             focusable: false,
+            command: None,
             screen_mode: ScreenMode::Normal,
         };
         let result = render_panel(&panel, 23, 10);
@@ -462,6 +468,7 @@ mod tests {
                 underline: false,
             }],
             focusable: false,
+            command: None,
             screen_mode: ScreenMode::Normal,
         };
         let result = render_panel(&panel, 10, 20);
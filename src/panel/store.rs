@@ -5,6 +5,7 @@ use uuid::Uuid;
 
 use crate::overlay::{BackgroundStyle, OverlaySpan, RegionWrite, ScreenMode};
 
+use super::command::PanelCommand;
 use super::types::{Panel, PanelId, Position};
 
 const MAX_PANELS: usize = 256;
@@ -44,6 +45,9 @@ pub struct PanelStore {
 
 struct StoreInner {
     panels: HashMap<PanelId, Panel>,
+    /// Running commands of command panels, dropped (and so stopped) with
+    /// their panel.
+    commands: HashMap<PanelId, Arc<PanelCommand>>,
     next_z: i32,
 }
 
@@ -52,6 +56,7 @@ impl PanelStore {
         Self {
             inner: Arc::new(RwLock::new(StoreInner {
                 panels: HashMap::new(),
+                commands: HashMap::new(),
                 next_z: 0,
             })),
         }
@@ -93,6 +98,7 @@ impl PanelStore {
             region_writes: vec![],
             visible: true,
             focusable,
+            command: None,
             screen_mode,
         };
         inner.panels.insert(id.clone(), panel);
@@ -225,9 +231,35 @@ impl PanelStore {
         }
     }
 
+    /// Attach a running command to a panel, replacing any previous one.
+    ///
+    /// Returns false (and drops the command, stopping it) if the panel no
+    /// longer exists.
+    pub fn set_command(&self, id: &str, command: String, handle: PanelCommand) -> bool {
+        let mut inner = self.inner.write();
+        let Some(panel) = inner.panels.get_mut(id) else {
+            return false;
+        };
+        panel.command = Some(command);
+        inner.commands.insert(id.to_string(), Arc::new(handle));
+        true
+    }
+
+    /// The running command of a command panel.
+    pub fn command(&self, id: &str) -> Option<Arc<PanelCommand>> {
+        self.inner.read().commands.get(id).cloned()
+    }
+
+    /// Running commands of command panels, by panel ID.
+    pub fn commands(&self) -> Vec<(PanelId, Arc<PanelCommand>)> {
+        let inner = self.inner.read();
+        inner.commands.iter().map(|(id, c)| (id.clone(), c.clone())).collect()
+    }
+
     /// Delete a panel by ID, returns true if it existed
     pub fn delete(&self, id: &str) -> bool {
         let mut inner = self.inner.write();
+        inner.commands.remove(id);
         inner.panels.remove(id).is_some()
     }
 
@@ -255,12 +287,15 @@ impl PanelStore {
     pub fn delete_by_mode(&self, mode: ScreenMode) {
         let mut inner = self.inner.write();
         inner.panels.retain(|_, p| p.screen_mode != mode);
+        let StoreInner { panels, commands, .. } = &mut *inner;
+        commands.retain(|id, _| panels.contains_key(id));
     }
 
     /// Clear all panels
     pub fn clear(&self) {
         let mut inner = self.inner.write();
         inner.panels.clear();
+        inner.commands.clear();
     }
 }
 
@@ -31,10 +31,22 @@ pub struct Panel {
     pub visible: bool,
     #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub focusable: bool,
+    /// Command whose live screen fills the panel. Its spans are replaced by
+    /// the server whenever the command's output changes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
     #[serde(default, skip_serializing_if = "is_normal_mode")]
     pub screen_mode: ScreenMode,
 }
 
+impl Panel {
+    /// Whether the panel can take input focus. Command panels always can,
+    /// since focused input is written to their command.
+    pub fn accepts_focus(&self) -> bool {
+        self.focusable || self.command.is_some()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -76,6 +88,7 @@ mod tests {
             region_writes: vec![],
             visible: true,
             focusable: false,
+            command: None,
             screen_mode: ScreenMode::Normal,
         };
         let json = serde_json::to_string(&panel).unwrap();
@@ -101,6 +114,7 @@ mod tests {
             region_writes: vec![],
             visible: false,
             focusable: false,
+            command: None,
             screen_mode: ScreenMode::Normal,
         };
         let json = serde_json::to_string(&panel).unwrap();
@@ -0,0 +1,237 @@
+//! Per-subscriber coalescing of `Line` events.
+//!
+//! Progress bars redraw one line with `\r` many times a second, and the
+//! parser emits a `Line` event for every redraw. A subscriber only needs the
+//! latest content, so after a line is sent, further updates to it within the
+//! window are held and only the newest is sent when the window closes.
+//!
+//! Held lines are flushed before any other event is passed through, so a
+//! subscriber still sees events in `seq` order. They're also flushed early
+//! once the terminal goes quiet, so the last update of a burst isn't held
+//! for the rest of the window.
+
+use std::collections::HashMap;
+
+use tokio::time::{Duration, Instant};
+
+use super::events::Event;
+
+/// Default coalescing window for `Line` events: off, so every update is
+/// sent unless a subscriber opts in.
+pub const DEFAULT_LINE_COALESCE_MS: u64 = 0;
+
+/// Default quiet period after which held lines are flushed early.
+pub const DEFAULT_IDLE_FLUSH_MS: u64 = 10;
+
+pub struct LineCoalescer {
+    window: Duration,
+    idle: Duration,
+    /// Held line events, keyed by line index.
+    pending: HashMap<usize, Event>,
+    /// When each line index was last sent.
+    last_sent: HashMap<usize, Instant>,
+    deadline: Option<Instant>,
+}
+
+impl LineCoalescer {
+    /// A zero `window` disables coalescing.
+    pub fn new(window: Duration) -> Self {
+        Self::with_idle_flush(window, Duration::ZERO)
+    }
+
+    /// Like [`LineCoalescer::new`], but held lines are also released once
+    /// the terminal has been quiet for `idle`. A zero `idle` always waits
+    /// for the window to close.
+    pub fn with_idle_flush(window: Duration, idle: Duration) -> Self {
+        Self {
+            window,
+            idle,
+            pending: HashMap::new(),
+            last_sent: HashMap::new(),
+            deadline: None,
+        }
+    }
+
+    /// Feed an event, returning the events to send now, in order.
+    pub fn push(&mut self, event: Event, now: Instant) -> Vec<Event> {
+        if self.window.is_zero() {
+            return vec![event];
+        }
+        let Event::Line { index, .. } = event else {
+            let mut out = self.flush(now);
+            out.push(event);
+            return out;
+        };
+
+        let recent = self
+            .last_sent
+            .get(&index)
+            .filter(|sent| now.duration_since(**sent) < self.window);
+        match recent {
+            Some(&sent) => {
+                self.pending.insert(index, event);
+                let due = sent + self.window;
+                self.deadline = Some(self.deadline.map_or(due, |d| d.min(due)));
+                Vec::new()
+            }
+            // Other lines are held: wait with them so order is kept.
+            None if !self.pending.is_empty() => {
+                self.pending.insert(index, event);
+                Vec::new()
+            }
+            None => {
+                self.last_sent.insert(index, now);
+                vec![event]
+            }
+        }
+    }
+
+    /// When held lines are due, if any are held.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// When held lines are due, given the terminal has been quiet for
+    /// `quiet` as of `now`: the window closing or the idle period passing,
+    /// whichever comes first.
+    pub fn flush_at(&self, now: Instant, quiet: Duration) -> Option<Instant> {
+        let deadline = self.deadline?;
+        if self.idle.is_zero() {
+            return Some(deadline);
+        }
+        Some(deadline.min(now + self.idle.saturating_sub(quiet)))
+    }
+
+    /// Release held lines if they're due at `now` (see
+    /// [`LineCoalescer::flush_at`]). Output since the wakeup was scheduled
+    /// can make the terminal busy again, in which case nothing is released.
+    pub fn flush_due(&mut self, now: Instant, quiet: Duration) -> Vec<Event> {
+        let idle = !self.idle.is_zero() && quiet >= self.idle;
+        match self.deadline {
+            Some(deadline) if deadline <= now || idle => self.flush(now),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Release all held lines, in `seq` order.
+    pub fn flush(&mut self, now: Instant) -> Vec<Event> {
+        self.deadline = None;
+        let window = self.window;
+        self.last_sent
+            .retain(|_, sent| now.duration_since(*sent) < window);
+        let mut out: Vec<Event> = self.pending.drain().map(|(_, e)| e).collect();
+        out.sort_by_key(Event::seq);
+        for event in &out {
+            if let Event::Line { index, .. } = event {
+                self.last_sent.insert(*index, now);
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::state::FormattedLine;
+
+    fn line(seq: u64, index: usize, text: &str) -> Event {
+        Event::Line {
+            seq,
+            index,
+            total_lines: 24,
+            line: FormattedLine::Plain(text.to_string()),
+        }
+    }
+
+    fn seqs(events: &[Event]) -> Vec<u64> {
+        events.iter().map(Event::seq).collect()
+    }
+
+    #[test]
+    fn zero_window_passes_everything_through() {
+        let mut c = LineCoalescer::new(Duration::ZERO);
+        let now = Instant::now();
+        assert_eq!(seqs(&c.push(line(1, 0, "a"), now)), vec![1]);
+        assert_eq!(seqs(&c.push(line(2, 0, "b"), now)), vec![2]);
+        assert!(c.deadline().is_none());
+    }
+
+    #[test]
+    fn rapid_rewrites_of_one_line_send_first_and_latest() {
+        let window = Duration::from_millis(50);
+        let mut c = LineCoalescer::new(window);
+        let start = Instant::now();
+
+        assert_eq!(seqs(&c.push(line(1, 3, "10%"), start)), vec![1]);
+        for seq in 2..100 {
+            assert!(c.push(line(seq, 3, "..."), start).is_empty());
+        }
+        assert_eq!(c.deadline(), Some(start + window));
+
+        let flushed = c.flush(start + window);
+        assert_eq!(seqs(&flushed), vec![99]);
+        assert!(c.deadline().is_none());
+    }
+
+    #[test]
+    fn other_events_flush_held_lines_first() {
+        let mut c = LineCoalescer::new(Duration::from_millis(50));
+        let now = Instant::now();
+        c.push(line(1, 0, "a"), now);
+        assert!(c.push(line(2, 0, "b"), now).is_empty());
+        // A different line waits behind the held one to keep seq order.
+        assert!(c.push(line(3, 1, "c"), now).is_empty());
+
+        let cursor = Event::Cursor { seq: 4, row: 1, col: 1, visible: true };
+        assert_eq!(seqs(&c.push(cursor, now)), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn held_line_is_flushed_at_idle_before_window_closes() {
+        let window = Duration::from_millis(50);
+        let idle = Duration::from_millis(10);
+        let mut c = LineCoalescer::with_idle_flush(window, idle);
+        let start = Instant::now();
+
+        // A burst of rewrites leaves the latest one held.
+        c.push(line(1, 0, "1%"), start);
+        for seq in 2..20 {
+            assert!(c.push(line(seq, 0, "..."), start).is_empty());
+        }
+        assert_eq!(c.flush_at(start, Duration::ZERO), Some(start + idle));
+
+        // Still busy: nothing is released yet.
+        assert!(c.flush_due(start + idle, Duration::from_millis(5)).is_empty());
+        assert_eq!(
+            c.flush_at(start + idle, Duration::from_millis(5)),
+            Some(start + idle + Duration::from_millis(5)),
+        );
+
+        // Quiet for the idle period, well before the window closes.
+        let flushed = c.flush_due(start + Duration::from_millis(15), idle);
+        assert_eq!(seqs(&flushed), vec![19]);
+        assert!(c.flush_at(start + Duration::from_millis(15), idle).is_none());
+    }
+
+    #[test]
+    fn zero_idle_waits_for_the_window() {
+        let window = Duration::from_millis(50);
+        let mut c = LineCoalescer::new(window);
+        let start = Instant::now();
+        c.push(line(1, 0, "a"), start);
+        c.push(line(2, 0, "b"), start);
+        assert_eq!(c.flush_at(start, Duration::from_secs(1)), Some(start + window));
+        assert!(c.flush_due(start, Duration::from_secs(1)).is_empty());
+        assert_eq!(seqs(&c.flush_due(start + window, Duration::ZERO)), vec![2]);
+    }
+
+    #[test]
+    fn line_is_sent_immediately_once_window_has_passed() {
+        let window = Duration::from_millis(50);
+        let mut c = LineCoalescer::new(window);
+        let start = Instant::now();
+        c.push(line(1, 0, "a"), start);
+        assert_eq!(seqs(&c.push(line(2, 0, "b"), start + window)), vec![2]);
+    }
+}
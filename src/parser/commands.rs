@@ -0,0 +1,280 @@
+//! Command boundaries from OSC 133 shell-integration marks.
+//!
+//! Shells with semantic-prompt support emit `ESC ] 133 ; X ST` around each
+//! prompt and command:
+//!
+//! - `A`: prompt starts
+//! - `B`: prompt ends, the user's command line starts
+//! - `C`: command submitted, its output starts
+//! - `D [; exit]`: command finished
+//!
+//! The detector finds these marks in PTY output and the tracker turns them
+//! into command records. Positions are kept as absolute line numbers (lines
+//! ever trimmed from scrollback plus the index in the current buffer), so
+//! records survive scrollback trimming. Shells that don't emit OSC 133 simply
+//! produce no records.
+
+use std::collections::VecDeque;
+
+use super::escape::{EscapeScanner, Sequence};
+
+/// Longest OSC body tracked; OSC 133 marks are tiny.
+const MAX_OSC_LEN: usize = 64;
+
+/// Number of finished commands kept.
+pub const COMMAND_HISTORY_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptMark {
+    PromptStart,
+    CommandStart,
+    CommandExecuted,
+    CommandFinished { exit_code: Option<i32> },
+}
+
+/// Incremental scanner for OSC 133 marks in PTY output.
+///
+/// Marks may be split across chunks; partial state carries over to the next
+/// `feed` call.
+pub struct PromptMarkDetector {
+    scanner: EscapeScanner,
+}
+
+impl PromptMarkDetector {
+    pub fn new() -> Self {
+        Self {
+            scanner: EscapeScanner::new(MAX_OSC_LEN),
+        }
+    }
+
+    /// Scan `bytes` and return each mark found, paired with the offset just
+    /// past its terminator.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<(usize, PromptMark)> {
+        let mut found = Vec::new();
+        for (i, &byte) in bytes.iter().enumerate() {
+            if let Some(Sequence::Osc(body)) = self.scanner.step(byte) {
+                // Longer OSCs (e.g. window titles) are skipped by the scanner.
+                if let Some(mark) = parse_mark(body) {
+                    found.push((i + 1, mark));
+                }
+            }
+        }
+        found
+    }
+}
+
+impl Default for PromptMarkDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parse_mark(body: &[u8]) -> Option<PromptMark> {
+    let rest = body.strip_prefix(b"133;")?;
+    let mut parts = rest.split(|&b| b == b';');
+    let mark = match parts.next()? {
+        b"A" => PromptMark::PromptStart,
+        b"B" => PromptMark::CommandStart,
+        b"C" => PromptMark::CommandExecuted,
+        b"D" => PromptMark::CommandFinished {
+            exit_code: parts
+                .next()
+                .and_then(|code| std::str::from_utf8(code).ok())
+                .and_then(|code| code.parse().ok()),
+        },
+        _ => return None,
+    };
+    Some(mark)
+}
+
+/// Absolute position: `line` counts lines trimmed from scrollback too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: u64,
+    pub col: usize,
+}
+
+/// One command, from prompt to finish. Fields fill in as marks arrive.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandRecord {
+    /// Where the command line starts (`B`).
+    pub input: Option<Position>,
+    /// Where output starts (`C`).
+    pub output: Option<Position>,
+    /// Where the command finished (`D`); `None` while it's running.
+    pub end: Option<Position>,
+    pub command_text: Option<String>,
+    pub exit_code: Option<i32>,
+}
+
+/// Builds command records from prompt marks.
+#[derive(Default)]
+pub struct CommandTracker {
+    finished: VecDeque<CommandRecord>,
+    current: Option<CommandRecord>,
+}
+
+impl CommandTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn prompt_start(&mut self, pos: Position) {
+        // A new prompt without `D` means the command ended unreported.
+        self.finish(pos, None);
+        self.current = Some(CommandRecord::default());
+    }
+
+    pub fn command_start(&mut self, pos: Position) {
+        self.current.get_or_insert_with(CommandRecord::default).input = Some(pos);
+    }
+
+    /// Position where the command line began, if known, for extracting the
+    /// command text before calling [`CommandTracker::command_executed`].
+    pub fn input_position(&self) -> Option<Position> {
+        self.current.as_ref().and_then(|c| c.input)
+    }
+
+    pub fn command_executed(&mut self, pos: Position, command_text: Option<String>) {
+        let current = self.current.get_or_insert_with(CommandRecord::default);
+        current.output = Some(pos);
+        current.command_text = command_text;
+    }
+
+    /// Finish the running command. Shells also send `D` before the very
+    /// first prompt, when no command has run; that's ignored.
+    pub fn command_finished(&mut self, pos: Position, exit_code: Option<i32>) {
+        self.finish(pos, exit_code);
+    }
+
+    fn finish(&mut self, pos: Position, exit_code: Option<i32>) {
+        let Some(mut command) = self.current.take() else {
+            return;
+        };
+        if command.output.is_none() {
+            return;
+        }
+        command.end = Some(pos);
+        command.exit_code = exit_code;
+        if self.finished.len() == COMMAND_HISTORY_CAPACITY {
+            self.finished.pop_front();
+        }
+        self.finished.push_back(command);
+    }
+
+    /// Finished commands, oldest first, followed by the running one (if its
+    /// output has started).
+    pub fn commands(&self) -> impl Iterator<Item = &CommandRecord> {
+        self.finished
+            .iter()
+            .chain(self.current.iter().filter(|c| c.output.is_some()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marks(input: &[u8]) -> Vec<PromptMark> {
+        PromptMarkDetector::new()
+            .feed(input)
+            .into_iter()
+            .map(|(_, m)| m)
+            .collect()
+    }
+
+    fn pos(line: u64, col: usize) -> Position {
+        Position { line, col }
+    }
+
+    #[test]
+    fn detects_marks_with_either_terminator() {
+        assert_eq!(
+            marks(b"\x1b]133;A\x07$ \x1b]133;B\x1b\\ls\r\n\x1b]133;C\x07"),
+            vec![
+                PromptMark::PromptStart,
+                PromptMark::CommandStart,
+                PromptMark::CommandExecuted,
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_exit_code() {
+        assert_eq!(
+            marks(b"\x1b]133;D;127\x07\x1b]133;D\x07\x1b]133;D;x\x07"),
+            vec![
+                PromptMark::CommandFinished { exit_code: Some(127) },
+                PromptMark::CommandFinished { exit_code: None },
+                PromptMark::CommandFinished { exit_code: None },
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_other_osc_and_extra_params() {
+        assert!(marks(b"\x1b]0;title\x07\x1b]7;file:///tmp\x07").is_empty());
+        assert_eq!(marks(b"\x1b]133;A;aid=42\x07"), vec![PromptMark::PromptStart]);
+    }
+
+    #[test]
+    fn detects_mark_split_across_chunks() {
+        let mut detector = PromptMarkDetector::new();
+        assert!(detector.feed(b"out\x1b]13").is_empty());
+        let found = detector.feed(b"3;D;0\x07next");
+        assert_eq!(found, vec![(6, PromptMark::CommandFinished { exit_code: Some(0) })]);
+    }
+
+    #[test]
+    fn tracker_records_full_cycle() {
+        let mut tracker = CommandTracker::new();
+        // Shells emit D before the first prompt; nothing has run yet.
+        tracker.command_finished(pos(0, 0), Some(0));
+        tracker.prompt_start(pos(0, 0));
+        tracker.command_start(pos(0, 2));
+        assert_eq!(tracker.input_position(), Some(pos(0, 2)));
+        tracker.command_executed(pos(1, 0), Some("ls".into()));
+
+        let running: Vec<_> = tracker.commands().cloned().collect();
+        assert_eq!(running.len(), 1);
+        assert_eq!(running[0].end, None);
+
+        tracker.command_finished(pos(3, 0), Some(2));
+        tracker.prompt_start(pos(3, 0));
+        let done: Vec<_> = tracker.commands().cloned().collect();
+        assert_eq!(
+            done,
+            vec![CommandRecord {
+                input: Some(pos(0, 2)),
+                output: Some(pos(1, 0)),
+                end: Some(pos(3, 0)),
+                command_text: Some("ls".into()),
+                exit_code: Some(2),
+            }]
+        );
+    }
+
+    #[test]
+    fn prompt_without_finish_closes_command() {
+        let mut tracker = CommandTracker::new();
+        tracker.prompt_start(pos(0, 0));
+        tracker.command_executed(pos(1, 0), None);
+        tracker.prompt_start(pos(4, 0));
+        let commands: Vec<_> = tracker.commands().collect();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].end, Some(pos(4, 0)));
+        assert_eq!(commands[0].exit_code, None);
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut tracker = CommandTracker::new();
+        for i in 0..(COMMAND_HISTORY_CAPACITY as u64 + 10) {
+            tracker.prompt_start(pos(i, 0));
+            tracker.command_executed(pos(i, 2), None);
+            tracker.command_finished(pos(i, 4), Some(0));
+        }
+        assert_eq!(tracker.commands().count(), COMMAND_HISTORY_CAPACITY);
+        assert_eq!(tracker.commands().next().unwrap().output, Some(pos(10, 2)));
+    }
+}
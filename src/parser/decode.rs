@@ -0,0 +1,133 @@
+//! Turning raw PTY bytes into text for the terminal emulator.
+//!
+//! Most programs emit UTF-8, but legacy programs in non-UTF-8 locales send
+//! latin1 (e.g. box drawing from old curses apps). Lossy UTF-8 decoding turns
+//! every such byte into U+FFFD, so the decoding mode is configurable.
+
+use serde::{Deserialize, Serialize};
+
+/// How PTY bytes are turned into characters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Decoding {
+    /// UTF-8; invalid bytes become U+FFFD.
+    #[default]
+    Utf8,
+    /// Each byte maps 1:1 to the code point with the same value.
+    Latin1,
+    /// UTF-8 where valid; invalid bytes map 1:1 like [`Decoding::Latin1`]
+    /// instead of being replaced.
+    Passthrough,
+}
+
+/// Longest UTF-8 prefix that can still be completed by the next chunk.
+const MAX_PENDING: usize = 3;
+
+/// Stateful decoder. UTF-8 sequences split across chunks are held back until
+/// the rest arrives rather than being decoded as invalid, so a chunk ending
+/// in a lead byte is only decoded once the next chunk shows what follows.
+pub struct Decoder {
+    mode: Decoding,
+    pending: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn new(mode: Decoding) -> Self {
+        Self {
+            mode,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn decode(&mut self, bytes: &[u8]) -> String {
+        if self.mode == Decoding::Latin1 {
+            return latin1(bytes);
+        }
+
+        let input = if self.pending.is_empty() {
+            bytes.to_vec()
+        } else {
+            let mut joined = std::mem::take(&mut self.pending);
+            joined.extend_from_slice(bytes);
+            joined
+        };
+
+        let mut out = String::with_capacity(input.len());
+        let mut rest = input.as_slice();
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    out.push_str(valid);
+                    break;
+                }
+                Err(e) => {
+                    let (valid, after) = rest.split_at(e.valid_up_to());
+                    out.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                    match e.error_len() {
+                        Some(len) => {
+                            let (invalid, tail) = after.split_at(len);
+                            match self.mode {
+                                Decoding::Passthrough => out.push_str(&latin1(invalid)),
+                                _ => out.push(char::REPLACEMENT_CHARACTER),
+                            }
+                            rest = tail;
+                        }
+                        // Truncated sequence at the end of the chunk.
+                        None if after.len() <= MAX_PENDING => {
+                            self.pending.extend_from_slice(after);
+                            break;
+                        }
+                        None => unreachable!("incomplete UTF-8 sequences are at most 3 bytes"),
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| char::from(b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf8_replaces_invalid_bytes() {
+        let mut decoder = Decoder::new(Decoding::Utf8);
+        assert_eq!(decoder.decode(b"a\xc4b\xff"), "a\u{fffd}b\u{fffd}");
+    }
+
+    #[test]
+    fn latin1_maps_bytes_to_code_points() {
+        let mut decoder = Decoder::new(Decoding::Latin1);
+        assert_eq!(decoder.decode(b"caf\xe9 \xb0"), "caf\u{e9} \u{b0}");
+        // UTF-8 input is decoded byte by byte too.
+        assert_eq!(decoder.decode("é".as_bytes()), "\u{c3}\u{a9}");
+    }
+
+    #[test]
+    fn passthrough_keeps_utf8_and_maps_invalid_bytes() {
+        let mut decoder = Decoder::new(Decoding::Passthrough);
+        assert_eq!(decoder.decode(b"\xc3\xa9 \xe9!"), "\u{e9} \u{e9}!");
+    }
+
+    #[test]
+    fn split_sequence_is_completed_by_next_chunk() {
+        for mode in [Decoding::Utf8, Decoding::Passthrough] {
+            let mut decoder = Decoder::new(mode);
+            let bytes = "→x".as_bytes();
+            assert_eq!(decoder.decode(&bytes[..2]), "");
+            assert_eq!(decoder.decode(&bytes[2..]), "→x");
+        }
+    }
+
+    #[test]
+    fn deserializes_snake_case() {
+        let mode: Decoding = serde_json::from_str("\"latin1\"").unwrap();
+        assert_eq!(mode, Decoding::Latin1);
+        assert_eq!(Decoding::default(), Decoding::Utf8);
+    }
+}
@@ -0,0 +1,172 @@
+//! Detection of terminal queries that expect a reply from the terminal.
+//!
+//! Programs like to ask the terminal about itself (`ESC [ c` primary device
+//! attributes, `ESC [ 6 n` cursor position report, DECRQSS, ...) and then
+//! block on stdin until the answer arrives. The parser never writes to the
+//! PTY, so without a client answering these programs hang. The detector
+//! spots the queries in PTY output so they can be surfaced as events.
+
+use serde::{Deserialize, Serialize};
+
+use super::escape::{EscapeScanner, Sequence};
+
+/// Longest CSI parameter string or DCS body tracked before giving up.
+const MAX_SEQUENCE_LEN: usize = 64;
+
+/// Which query the program sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceQueryKind {
+    /// `CSI c` / `CSI 0 c` (DA1).
+    PrimaryAttributes,
+    /// `CSI > c` / `CSI > 0 c` (DA2).
+    SecondaryAttributes,
+    /// `CSI 5 n` (DSR operating status).
+    Status,
+    /// `CSI 6 n` (DSR cursor position report).
+    CursorPosition,
+    /// `DCS $ q Pt ST` (DECRQSS); the requested setting is in `setting`.
+    StatusString,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceQuery {
+    pub kind: DeviceQueryKind,
+    /// DECRQSS setting name (e.g. `m`, `r`, ` q`); `None` for other kinds.
+    pub setting: Option<String>,
+}
+
+/// Reply a terminal sends for a cursor position report: 1-based `row;col`.
+pub fn cursor_position_report(row: usize, col: usize) -> Vec<u8> {
+    format!("\x1b[{};{}R", row + 1, col + 1).into_bytes()
+}
+
+/// Incremental scanner for device queries in PTY output.
+///
+/// Sequences may be split across chunks; the partial state is carried over
+/// to the next `feed` call.
+pub struct DeviceQueryDetector {
+    scanner: EscapeScanner,
+}
+
+impl DeviceQueryDetector {
+    pub fn new() -> Self {
+        Self {
+            scanner: EscapeScanner::new(MAX_SEQUENCE_LEN),
+        }
+    }
+
+    /// Scan `bytes` and return each query found, paired with the offset just
+    /// past its final byte.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<(usize, DeviceQuery)> {
+        let mut found = Vec::new();
+        for (i, &byte) in bytes.iter().enumerate() {
+            let query = match self.scanner.step(byte) {
+                Some(Sequence::Csi { params, intermediates: [], final_byte }) => classify_csi(params, final_byte),
+                Some(Sequence::Dcs(body)) => classify_dcs(body),
+                _ => None,
+            };
+            if let Some(query) = query {
+                found.push((i + 1, query));
+            }
+        }
+        found
+    }
+}
+
+impl Default for DeviceQueryDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn classify_csi(params: &[u8], final_byte: u8) -> Option<DeviceQuery> {
+    let kind = match (final_byte, params) {
+        (b'c', b"" | b"0") => DeviceQueryKind::PrimaryAttributes,
+        (b'c', b">" | b">0") => DeviceQueryKind::SecondaryAttributes,
+        (b'n', b"5") => DeviceQueryKind::Status,
+        (b'n', b"6") => DeviceQueryKind::CursorPosition,
+        _ => return None,
+    };
+    Some(DeviceQuery { kind, setting: None })
+}
+
+fn classify_dcs(body: &[u8]) -> Option<DeviceQuery> {
+    let setting = body.strip_prefix(b"$q")?;
+    Some(DeviceQuery {
+        kind: DeviceQueryKind::StatusString,
+        setting: Some(String::from_utf8_lossy(setting).into_owned()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(input: &[u8]) -> Vec<DeviceQueryKind> {
+        DeviceQueryDetector::new()
+            .feed(input)
+            .into_iter()
+            .map(|(_, q)| q.kind)
+            .collect()
+    }
+
+    #[test]
+    fn detects_device_attribute_queries() {
+        assert_eq!(kinds(b"\x1b[c"), vec![DeviceQueryKind::PrimaryAttributes]);
+        assert_eq!(kinds(b"\x1b[0c"), vec![DeviceQueryKind::PrimaryAttributes]);
+        assert_eq!(kinds(b"\x1b[>c"), vec![DeviceQueryKind::SecondaryAttributes]);
+        assert_eq!(kinds(b"\x1b[>0c"), vec![DeviceQueryKind::SecondaryAttributes]);
+    }
+
+    #[test]
+    fn detects_status_reports() {
+        assert_eq!(kinds(b"\x1b[5n"), vec![DeviceQueryKind::Status]);
+        assert_eq!(kinds(b"\x1b[6n"), vec![DeviceQueryKind::CursorPosition]);
+    }
+
+    #[test]
+    fn ignores_replies_and_other_sequences() {
+        // A DA1 reply and ordinary SGR/cursor movement are not queries.
+        assert!(kinds(b"\x1b[?62;22c\x1b[1;31mred\x1b[0m\x1b[2J\x1b[5;1H").is_empty());
+        assert!(kinds(b"plain text c n").is_empty());
+    }
+
+    #[test]
+    fn reports_offset_after_query() {
+        let found = DeviceQueryDetector::new().feed(b"ab\x1b[6ncd");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, 6);
+    }
+
+    #[test]
+    fn detects_query_split_across_chunks() {
+        let mut detector = DeviceQueryDetector::new();
+        assert!(detector.feed(b"hello\x1b").is_empty());
+        assert!(detector.feed(b"[").is_empty());
+        let found = detector.feed(b"6nworld");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, 2);
+        assert_eq!(found[0].1.kind, DeviceQueryKind::CursorPosition);
+    }
+
+    #[test]
+    fn detects_decrqss_with_setting() {
+        let found = DeviceQueryDetector::new().feed(b"\x1bP$qm\x1b\\");
+        assert_eq!(
+            found[0].1,
+            DeviceQuery {
+                kind: DeviceQueryKind::StatusString,
+                setting: Some("m".to_string()),
+            }
+        );
+        // Other DCS strings (e.g. sixel, tmux passthrough) are not queries.
+        assert!(kinds(b"\x1bPq#0;2;0;0;0\x1b\\").is_empty());
+    }
+
+    #[test]
+    fn cursor_position_report_is_one_based() {
+        assert_eq!(cursor_position_report(0, 0), b"\x1b[1;1R");
+        assert_eq!(cursor_position_report(4, 9), b"\x1b[5;10R");
+    }
+}
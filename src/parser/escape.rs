@@ -0,0 +1,307 @@
+//! Incremental escape-sequence scanner shared by the parser's detectors.
+//!
+//! avt interprets the output but doesn't report most of what it saw, so the
+//! detectors (alternate screen, device queries, prompt marks, metadata,
+//! scroll margins) pick out the sequences they care about themselves. They
+//! all see the same stream, split at arbitrary points, so they share one
+//! state machine that follows the VT500 parser closely enough to agree with
+//! avt about where each sequence starts and ends:
+//!
+//! - `ESC`, `CSI`, `OSC`, `DCS`, and `SOS`/`PM`/`APC` strings (skipped),
+//!   introduced by 7-bit `ESC x` or by their UTF-8 encoded C1 controls.
+//! - Strings end with ST (`ESC \` or C1 ST) or BEL. BEL isn't a valid DCS
+//!   terminator, but some programs use it.
+//! - CAN and SUB abort a sequence; ESC aborts it and starts a new one.
+//! - Sequences longer than the scanner's limit are skipped whole.
+
+/// A complete sequence. Slices borrow the scanner until the next byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sequence<'a> {
+    /// `ESC [intermediates] final`, other than the introducers below.
+    Esc { intermediates: &'a [u8], final_byte: u8 },
+    /// `CSI [params] [intermediates] final`. `params` keeps any private
+    /// marker (`?`, `>`, ...) as its first byte.
+    Csi { params: &'a [u8], intermediates: &'a [u8], final_byte: u8 },
+    /// `OSC body ST`.
+    Osc(&'a [u8]),
+    /// `DCS body ST`, with the DCS parameters and final byte left in `body`.
+    Dcs(&'a [u8]),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StringKind {
+    Osc,
+    Dcs,
+    /// SOS, PM and APC: consumed but never reported.
+    Ignored,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScanState {
+    Ground,
+    Esc,
+    Csi,
+    String(StringKind),
+    /// Seen ESC inside a string; `\` completes ST.
+    StringEsc(StringKind),
+}
+
+/// Sequences may be split across chunks; feed bytes one at a time with
+/// [`EscapeScanner::step`] and the state carries over.
+pub struct EscapeScanner {
+    state: ScanState,
+    /// CSI parameters or string body collected so far.
+    buf: Vec<u8>,
+    intermediates: Vec<u8>,
+    /// Longest parameter string or body kept.
+    max_len: usize,
+    /// The current sequence is malformed or too long and won't be reported.
+    ignore: bool,
+    /// Seen 0xC2, the lead byte of a UTF-8 encoded C1 control.
+    c1_lead: bool,
+}
+
+impl EscapeScanner {
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            state: ScanState::Ground,
+            buf: Vec::new(),
+            intermediates: Vec::new(),
+            max_len,
+            ignore: false,
+            c1_lead: false,
+        }
+    }
+
+    /// Whether the scanner is between sequences, so the next byte is text
+    /// unless it starts one.
+    pub fn is_ground(&self) -> bool {
+        self.state == ScanState::Ground && !self.c1_lead
+    }
+
+    /// Scan one byte, returning the sequence it completes, if any.
+    pub fn step(&mut self, byte: u8) -> Option<Sequence<'_>> {
+        if std::mem::take(&mut self.c1_lead) {
+            if matches!(byte, 0x90 | 0x98 | 0x9b..=0x9f) {
+                // A C1 control is ESC followed by the control minus 0x40.
+                self.advance(0x1b);
+                return self.advance(byte - 0x40);
+            }
+            self.advance(0xc2);
+        }
+        if byte == 0xc2 {
+            self.c1_lead = true;
+            return None;
+        }
+        self.advance(byte)
+    }
+
+    fn advance(&mut self, byte: u8) -> Option<Sequence<'_>> {
+        match byte {
+            0x18 | 0x1a => {
+                self.state = ScanState::Ground;
+                return None;
+            }
+            0x1b => {
+                self.state = match self.state {
+                    ScanState::String(kind) => ScanState::StringEsc(kind),
+                    _ => self.enter(ScanState::Esc),
+                };
+                return None;
+            }
+            _ => {}
+        }
+        match self.state {
+            ScanState::Ground => None,
+            ScanState::Esc => match byte {
+                b'[' => {
+                    self.state = self.enter(ScanState::Csi);
+                    None
+                }
+                b']' => self.enter_string(StringKind::Osc),
+                b'P' => self.enter_string(StringKind::Dcs),
+                b'X' | b'^' | b'_' => self.enter_string(StringKind::Ignored),
+                0x20..=0x2f => {
+                    self.push_intermediate(byte);
+                    None
+                }
+                0x30..=0x7e => {
+                    self.state = ScanState::Ground;
+                    Some(Sequence::Esc { intermediates: &self.intermediates, final_byte: byte })
+                }
+                // C0 controls are executed without leaving the sequence.
+                0x00..=0x1f => None,
+                _ => {
+                    self.state = ScanState::Ground;
+                    None
+                }
+            },
+            ScanState::Csi => match byte {
+                0x30..=0x3f => {
+                    // Parameters after an intermediate make it malformed.
+                    if !self.intermediates.is_empty() || self.buf.len() == self.max_len {
+                        self.ignore = true;
+                    } else {
+                        self.buf.push(byte);
+                    }
+                    None
+                }
+                0x20..=0x2f => {
+                    self.push_intermediate(byte);
+                    None
+                }
+                0x40..=0x7e => {
+                    self.state = ScanState::Ground;
+                    (!self.ignore).then_some(Sequence::Csi {
+                        params: &self.buf,
+                        intermediates: &self.intermediates,
+                        final_byte: byte,
+                    })
+                }
+                _ => None,
+            },
+            ScanState::String(kind) => match byte {
+                0x07 => self.finish_string(kind),
+                // Other C0 controls are ignored inside strings.
+                0x00..=0x1f => None,
+                _ if self.buf.len() < self.max_len => {
+                    self.buf.push(byte);
+                    None
+                }
+                _ => {
+                    self.ignore = true;
+                    None
+                }
+            },
+            ScanState::StringEsc(kind) => {
+                if byte == b'\\' {
+                    self.finish_string(kind)
+                } else {
+                    // ESC aborted the string and starts a new sequence.
+                    self.state = self.enter(ScanState::Esc);
+                    self.advance(byte)
+                }
+            }
+        }
+    }
+
+    /// Clear what the previous sequence collected.
+    fn enter(&mut self, state: ScanState) -> ScanState {
+        self.buf.clear();
+        self.intermediates.clear();
+        self.ignore = false;
+        state
+    }
+
+    fn enter_string(&mut self, kind: StringKind) -> Option<Sequence<'_>> {
+        self.state = self.enter(ScanState::String(kind));
+        None
+    }
+
+    fn push_intermediate(&mut self, byte: u8) {
+        if self.intermediates.len() < self.max_len {
+            self.intermediates.push(byte);
+        } else {
+            self.ignore = true;
+        }
+    }
+
+    fn finish_string(&mut self, kind: StringKind) -> Option<Sequence<'_>> {
+        self.state = ScanState::Ground;
+        if self.ignore {
+            return None;
+        }
+        match kind {
+            StringKind::Osc => Some(Sequence::Osc(&self.buf)),
+            StringKind::Dcs => Some(Sequence::Dcs(&self.buf)),
+            StringKind::Ignored => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A sequence found, as owned bytes: (offset just past it, kind,
+    /// payload, intermediates, final byte).
+    type Found = (usize, char, Vec<u8>, Vec<u8>, u8);
+
+    fn scan(scanner: &mut EscapeScanner, bytes: &[u8]) -> Vec<Found> {
+        let mut found = Vec::new();
+        for (i, &byte) in bytes.iter().enumerate() {
+            let entry = match scanner.step(byte) {
+                Some(Sequence::Esc { intermediates, final_byte }) => ('e', vec![], intermediates.to_vec(), final_byte),
+                Some(Sequence::Csi { params, intermediates, final_byte }) => {
+                    ('c', params.to_vec(), intermediates.to_vec(), final_byte)
+                }
+                Some(Sequence::Osc(body)) => ('o', body.to_vec(), vec![], 0),
+                Some(Sequence::Dcs(body)) => ('d', body.to_vec(), vec![], 0),
+                None => continue,
+            };
+            found.push((i + 1, entry.0, entry.1, entry.2, entry.3));
+        }
+        found
+    }
+
+    fn scan_all(bytes: &[u8]) -> Vec<Found> {
+        scan(&mut EscapeScanner::new(64), bytes)
+    }
+
+    #[test]
+    fn reports_each_kind_with_its_end() {
+        let found = scan_all(b"a\x1b[?25hb\x1b]0;title\x07\x1bP$qm\x1b\\\x1b(B\x1b[1 q");
+        assert_eq!(
+            found,
+            vec![
+                (7, 'c', b"?25".to_vec(), vec![], b'h'),
+                (18, 'o', b"0;title".to_vec(), vec![], 0),
+                (25, 'd', b"$qm".to_vec(), vec![], 0),
+                (28, 'e', vec![], b"(".to_vec(), b'B'),
+                (33, 'c', b"1".to_vec(), b" ".to_vec(), b'q'),
+            ]
+        );
+    }
+
+    #[test]
+    fn sequences_split_across_chunks() {
+        let mut scanner = EscapeScanner::new(64);
+        assert!(scan(&mut scanner, b"text\x1b").is_empty());
+        assert!(scan(&mut scanner, b"]7;file:").is_empty());
+        assert!(scan(&mut scanner, b"//h/tmp\x1b").is_empty());
+        let found = scan(&mut scanner, b"\\\xc2");
+        assert_eq!(found[0].2, b"7;file://h/tmp");
+        assert_eq!(scan(&mut scanner, b"\x9b6n"), vec![(3, 'c', b"6".to_vec(), vec![], b'n')]);
+    }
+
+    #[test]
+    fn recognizes_utf8_c1_controls() {
+        let found = scan_all("\u{9b}?1049h\u{9d}2;t\u{9c}\u{90}$qr\u{9c}".as_bytes());
+        let kinds: Vec<char> = found.iter().map(|f| f.1).collect();
+        assert_eq!(kinds, vec!['c', 'o', 'd']);
+        assert_eq!(found[1].2, b"2;t");
+        // Other characters starting with 0xC2 are text, or part of a body.
+        assert!(scan_all("\u{a9}[6n".as_bytes()).is_empty());
+        assert_eq!(scan_all("\x1b]2;\u{a9}\x07".as_bytes())[0].2, "2;\u{a9}".as_bytes());
+    }
+
+    #[test]
+    fn aborted_and_skipped_sequences() {
+        // CAN and SUB abort; ESC restarts.
+        assert!(scan_all(b"\x1b[6\x18n\x1b]0;t\x1a\x07").is_empty());
+        assert_eq!(scan_all(b"\x1b]0;t\x1b[5n")[0].1, 'c');
+        // SOS/PM/APC bodies are skipped.
+        assert!(scan_all(b"\x1b_[6n\x1b\\\x1b^[6n\x07\x1bX]0;t\x07").is_empty());
+        // Parameters after an intermediate make the CSI malformed.
+        assert!(scan_all(b"\x1b[1$5r").is_empty());
+        // C0 controls inside a CSI are executed, not part of it.
+        assert_eq!(scan_all(b"\x1b[6\rn")[0].2, b"6");
+    }
+
+    #[test]
+    fn skips_overlong_sequences_whole() {
+        let mut scanner = EscapeScanner::new(4);
+        assert!(scan(&mut scanner, b"\x1b[1;2;3r\x1b]0;long title\x07").is_empty());
+        assert_eq!(scan(&mut scanner, b"\x1b[1;2r").len(), 1);
+    }
+}
@@ -1,8 +1,9 @@
 use serde::{Deserialize, Serialize};
 
-use super::state::{FormattedLine, ScreenResponse};
+use super::device_query::DeviceQueryKind;
+use super::state::{Color, FormattedLine, ScreenResponse};
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
 #[serde(tag = "event", rename_all = "snake_case")]
 pub enum Event {
     Line {
@@ -21,6 +22,21 @@ pub enum Event {
         seq: u64,
         alternate_active: bool,
     },
+    /// The scroll margins (DECSTBM) changed. Rows are 0-based and inclusive;
+    /// `0..=rows-1` means the whole screen scrolls.
+    ScrollRegion {
+        seq: u64,
+        top: usize,
+        bottom: usize,
+    },
+    /// `count` rows scrolled off the top of the primary screen into
+    /// scrollback, in order, before the `Line` events of the same output.
+    /// More rows than the screen holds means some were never shown in a
+    /// `Line` event; fetch those from scrollback.
+    Scroll {
+        seq: u64,
+        count: usize,
+    },
     Reset {
         seq: u64,
         reason: ResetReason,
@@ -30,6 +46,9 @@ pub enum Event {
         screen: ScreenResponse,
         scrollback_lines: usize,
     },
+    /// Sent in place of `Line` events when one chunk of output rewrites
+    /// most of the screen; see [`super::Parser::set_redraw_threshold`].
+    /// `changed_lines` are screen rows, like a `Line` event's `index`.
     Diff {
         seq: u64,
         changed_lines: Vec<usize>,
@@ -45,13 +64,145 @@ pub enum Event {
         seq: u64,
         generation: u64,
     },
+    /// The program asked the terminal something (DA, DSR, DECRQSS) and is
+    /// likely waiting for a reply on its input. `row`/`col` are the cursor
+    /// position right after the query, i.e. what a CPR reply should report.
+    DeviceQuery {
+        seq: u64,
+        kind: DeviceQueryKind,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        setting: Option<String>,
+        row: usize,
+        col: usize,
+    },
+    /// The program asked for a `rows` x `cols` terminal (XTWINOPS
+    /// `CSI 8 ; rows ; cols t`). Nothing is resized: the client that owns
+    /// the terminal decides whether to honor it. Sizes the program left out
+    /// or gave as 0 are filled in with the current size.
+    ResizeRequest {
+        seq: u64,
+        rows: usize,
+        cols: usize,
+    },
+    /// The program changed the default foreground or background color
+    /// (`OSC 10`/`OSC 11`, or reset one with `OSC 110`/`OSC 111`). Both
+    /// are sent; `None` means the terminal's own default.
+    DefaultColors {
+        seq: u64,
+        fg: Option<Color>,
+        bg: Option<Color>,
+    },
+    /// The program asked for a desktop notification (`OSC 9 ; body` or
+    /// `OSC 777 ; notify ; title ; body`). Nothing is shown; clients
+    /// decide how to surface it.
+    Notification {
+        seq: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        body: String,
+    },
+    /// Keepalive sent on an otherwise idle stream when the subscriber asked
+    /// for one. `seq` is the last parser seq the subscriber was sent (it does
+    /// not advance); `server_time_ms` is wall-clock Unix time, for estimating
+    /// clock skew.
+    Heartbeat {
+        seq: u64,
+        server_time_ms: u64,
+    },
+    /// The shell reported a new working directory (OSC 7). `path` is the
+    /// decoded absolute path. Changes are coalesced: at most one event per
+    /// [`super::CWD_DEBOUNCE`], carrying the latest directory.
+    Cwd {
+        seq: u64,
+        path: String,
+    },
+    /// The output stream ended: the program exited and the PTY closed, or
+    /// the session is shutting down. No terminal events follow. `reason` is
+    /// absent when the parser wasn't told why (e.g. it has no session).
+    End {
+        seq: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<ShutdownReason>,
+    },
 }
 
-#[derive(Debug, Clone, Serialize)]
+impl Event {
+    pub fn seq(&self) -> u64 {
+        match self {
+            Event::Line { seq, .. }
+            | Event::Cursor { seq, .. }
+            | Event::Mode { seq, .. }
+            | Event::ScrollRegion { seq, .. }
+            | Event::Scroll { seq, .. }
+            | Event::Reset { seq, .. }
+            | Event::Sync { seq, .. }
+            | Event::Diff { seq, .. }
+            | Event::Idle { seq, .. }
+            | Event::Running { seq, .. }
+            | Event::DeviceQuery { seq, .. }
+            | Event::ResizeRequest { seq, .. }
+            | Event::DefaultColors { seq, .. }
+            | Event::Notification { seq, .. }
+            | Event::Heartbeat { seq, .. }
+            | Event::Cwd { seq, .. }
+            | Event::End { seq, .. } => *seq,
+        }
+    }
+
+    /// The `Line` events a `Diff` stands for, sharing its `seq`; `None` for
+    /// any other event. For clients that follow lines but not diffs.
+    pub fn diff_as_lines(&self) -> Option<Vec<Event>> {
+        let Event::Diff { seq, changed_lines, screen } = self else {
+            return None;
+        };
+        let lines = changed_lines
+            .iter()
+            .filter_map(|&index| {
+                screen.lines.get(index).map(|line| Event::Line {
+                    seq: *seq,
+                    index,
+                    total_lines: screen.total_lines,
+                    line: line.clone(),
+                })
+            })
+            .collect();
+        Some(lines)
+    }
+
+    /// One `Line` event per row of `screen`, top to bottom, all with `seq`.
+    /// For followers that build their state from `Line` events alone.
+    pub fn screen_lines(seq: u64, screen: &ScreenResponse) -> Vec<Event> {
+        screen
+            .lines
+            .iter()
+            .enumerate()
+            .map(|(index, line)| Event::Line {
+                seq,
+                index,
+                total_lines: screen.total_lines,
+                line: line.clone(),
+            })
+            .collect()
+    }
+
+    /// A heartbeat stamped with the current server time.
+    pub fn heartbeat(seq: u64) -> Self {
+        let server_time_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        Event::Heartbeat { seq, server_time_ms }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ResetReason {
     ClearScreen,
     ClearScrollback,
+    /// The oldest scrollback was dropped to stay within the server's
+    /// scrollback memory budget; line indices have shifted.
+    ScrollbackEvicted,
     HardReset,
     AlternateScreenEnter,
     AlternateScreenExit,
@@ -61,6 +212,29 @@ pub enum ResetReason {
     ParserRestart,
 }
 
+/// Why a session's output ended; carried by [`Event::End`] and the
+/// WebSocket close frame.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ShutdownReason {
+    /// The program exited on its own. `code` is 1 if a signal killed it.
+    Exited { code: u32 },
+    /// The session was killed through the API, socket, or MCP.
+    Killed,
+    /// The server is shutting down.
+    ServerShutdown,
+}
+
+impl std::fmt::Display for ShutdownReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShutdownReason::Exited { code } => write!(f, "process exited with code {code}"),
+            ShutdownReason::Killed => f.write_str("session killed"),
+            ShutdownReason::ServerShutdown => f.write_str("server shutting down"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Subscribe {
     pub events: Vec<EventType>,
@@ -74,7 +248,7 @@ fn default_interval() -> u64 {
     100
 }
 
-#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum EventType {
     Lines,
@@ -85,6 +259,28 @@ pub enum EventType {
     Input,
     Overlay,
     Activity,
+    Queries,
+    ResizeRequests,
+    Cwd,
+    Notifications,
+}
+
+impl EventType {
+    /// Every event type, in declaration order.
+    pub const ALL: &'static [EventType] = &[
+        EventType::Lines,
+        EventType::Chars,
+        EventType::Cursor,
+        EventType::Mode,
+        EventType::Diffs,
+        EventType::Input,
+        EventType::Overlay,
+        EventType::Activity,
+        EventType::Queries,
+        EventType::ResizeRequests,
+        EventType::Cwd,
+        EventType::Notifications,
+    ];
 }
 
 #[cfg(test)]
@@ -92,6 +288,31 @@ mod tests {
     use super::*;
     use super::super::state::Cursor as CursorState;
 
+    #[test]
+    fn event_type_all_lists_every_variant() {
+        // Adding a variant breaks this match; add it to `ALL` too.
+        fn position(event_type: &EventType) -> usize {
+            match event_type {
+                EventType::Lines => 0,
+                EventType::Chars => 1,
+                EventType::Cursor => 2,
+                EventType::Mode => 3,
+                EventType::Diffs => 4,
+                EventType::Input => 5,
+                EventType::Overlay => 6,
+                EventType::Activity => 7,
+                EventType::Queries => 8,
+                EventType::ResizeRequests => 9,
+                EventType::Cwd => 10,
+                EventType::Notifications => 11,
+            }
+        }
+        assert_eq!(EventType::ALL.len(), 12);
+        for (i, event_type) in EventType::ALL.iter().enumerate() {
+            assert_eq!(position(event_type), i);
+        }
+    }
+
     #[test]
     fn idle_event_serializes_correctly() {
         let event = Event::Idle {
@@ -121,6 +342,16 @@ mod tests {
         assert_eq!(json["scrollback_lines"], 100);
     }
 
+    #[test]
+    fn heartbeat_event_serializes_correctly() {
+        let event = Event::heartbeat(12);
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "heartbeat");
+        assert_eq!(json["seq"], 12);
+        // Sometime after 2020-01-01.
+        assert!(json["server_time_ms"].as_u64().unwrap() > 1_577_836_800_000);
+    }
+
     #[test]
     fn running_event_serializes_correctly() {
         let event = Event::Running {
@@ -133,6 +364,64 @@ mod tests {
         assert_eq!(json["generation"], 3);
     }
 
+    #[test]
+    fn device_query_event_serializes_correctly() {
+        let event = Event::DeviceQuery {
+            seq: 5,
+            kind: DeviceQueryKind::CursorPosition,
+            setting: None,
+            row: 2,
+            col: 7,
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "device_query");
+        assert_eq!(json["kind"], "cursor_position");
+        assert!(json.get("setting").is_none());
+        assert_eq!(json["row"], 2);
+        assert_eq!(json["col"], 7);
+    }
+
+    #[test]
+    fn end_event_serializes_reason() {
+        let event = Event::End {
+            seq: 3,
+            reason: Some(ShutdownReason::Exited { code: 2 }),
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "end");
+        assert_eq!(json["reason"]["kind"], "exited");
+        assert_eq!(json["reason"]["code"], 2);
+
+        let event = Event::End { seq: 3, reason: None };
+        let json = serde_json::to_value(&event).unwrap();
+        assert!(json.get("reason").is_none());
+    }
+
+    #[test]
+    fn events_round_trip_through_json() {
+        use super::super::state::{Span, Style};
+
+        let json = serde_json::json!({
+            "event": "line",
+            "seq": 4,
+            "index": 1,
+            "total_lines": 24,
+            "line": [{"text": "ok", "fg": {"indexed": 2}, "bold": true}, {"text": " done"}],
+        });
+        let event: Event = serde_json::from_value(json.clone()).unwrap();
+        match &event {
+            Event::Line { line: FormattedLine::Styled(spans), .. } => {
+                assert_eq!(spans[0].style, Style { fg: Some(Color::Indexed(2)), bold: true, ..Style::default() });
+                assert!(matches!(&spans[1], Span { text, style } if text == " done" && style.is_default()));
+            }
+            other => panic!("expected styled line, got {other:?}"),
+        }
+        assert_eq!(serde_json::to_value(&event).unwrap(), json);
+
+        let event: Event = serde_json::from_value(serde_json::json!({"event": "end", "seq": 9})).unwrap();
+        assert!(matches!(event, Event::End { seq: 9, reason: None }));
+    }
+
     #[test]
     fn activity_event_type_deserializes() {
         let json = r#""activity""#;
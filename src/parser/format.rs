@@ -1,25 +1,56 @@
-use avt::{Line, Pen};
+use std::hash::{Hash, Hasher};
+
+use avt::{Cell, Line, Pen};
 
 use super::state::{Color, FormattedLine, Span, Style};
 
-/// Convert an avt Line to a FormattedLine based on format
+/// Convert the first `max_cells` cells of an avt Line to a FormattedLine
+///
+/// Cells past `max_cells` are never read, so the output stays bounded however
+/// wide the row is. A wide character that would straddle the cut is dropped.
 ///
 /// For plain format, trailing whitespace is always trimmed.
 /// For styled format, trailing whitespace is trimmed only if it has default styling
 /// (no colors or attributes), preserving intentional styled whitespace like colored backgrounds.
-pub fn format_line(line: &Line, styled: bool) -> FormattedLine {
+pub fn format_line(line: &Line, styled: bool, max_cells: usize) -> FormattedLine {
+    let cells = line.cells();
+    let mut end = max_cells.min(cells.len());
+    if end > 0 && end < cells.len() && cells[end - 1].width() > 1 {
+        end -= 1;
+    }
+    let cells = &cells[..end];
     if styled {
-        let mut spans = line_to_spans(line);
+        let mut spans = cells_to_spans(cells);
         trim_trailing_default_whitespace(&mut spans);
         FormattedLine::Styled(spans)
     } else {
-        FormattedLine::Plain(line.text().trim_end().to_string())
+        let text: String = cells
+            .iter()
+            .filter(|c| c.width() > 0)
+            .map(|c| c.char())
+            .collect();
+        FormattedLine::Plain(text.trim_end().to_string())
     }
 }
 
-/// Convert an avt Line to styled spans
-fn line_to_spans(line: &Line) -> Vec<Span> {
-    let cells = line.cells();
+/// Feed a line's cells (character, width, and style) into `hasher`.
+///
+/// Hashes the same cell data that `format_line` renders, so two lines hash
+/// equally when their styled output would be identical.
+pub fn hash_line<H: Hasher>(line: &Line, hasher: &mut H) {
+    line.len().hash(hasher);
+    for cell in line.cells() {
+        cell.char().hash(hasher);
+        cell.width().hash(hasher);
+        pen_to_style(cell.pen()).hash(hasher);
+    }
+}
+
+/// Convert a line's cells to styled spans
+///
+/// Style comes from each cell's own pen, not from SGR state carried across
+/// lines, so a line renders the same on screen and in scrollback.
+fn cells_to_spans(cells: &[Cell]) -> Vec<Span> {
     if cells.is_empty() {
         return vec![];
     }
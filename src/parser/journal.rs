@@ -0,0 +1,308 @@
+//! Bounded journal of recent parser events, keyed by `seq`.
+//!
+//! Lets a client that lost its connection resume from the last `seq` it saw
+//! instead of re-syncing the full screen. Events are recorded at the moment
+//! they are broadcast, under the same lock used to snapshot the journal and
+//! open a new subscription, so a resumed client sees neither gaps nor
+//! duplicates between the replayed events and the live stream.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use tokio::sync::broadcast;
+
+use super::events::Event;
+
+/// Default number of events retained for reconnect replay.
+pub const DEFAULT_JOURNAL_CAPACITY: usize = 1024;
+
+struct Inner {
+    /// Retained events with their serialized size, which is only measured
+    /// while `max_bytes` is set.
+    events: VecDeque<(Event, usize)>,
+    capacity: usize,
+    /// Cap on the serialized size of retained events; 0 for none.
+    max_bytes: usize,
+    bytes: usize,
+    /// `seq` of the most recently recorded event, if any.
+    last_seq: Option<u64>,
+    /// Resumes that asked for a `seq` the journal couldn't replay from.
+    misses: u64,
+}
+
+impl Inner {
+    /// Drop the oldest events until both limits hold.
+    fn trim(&mut self) {
+        while self.events.len() > self.capacity || (self.max_bytes > 0 && self.bytes > self.max_bytes) {
+            match self.events.pop_front() {
+                Some((_, size)) => self.bytes -= size,
+                None => break,
+            }
+        }
+    }
+}
+
+fn serialized_size(event: &Event) -> usize {
+    serde_json::to_vec(event).map_or(0, |json| json.len())
+}
+
+/// What the journal currently holds, for status reporting. A resume from
+/// `since` is replayable when `oldest_seq - 1 <= since <= newest_seq`.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct JournalStatus {
+    /// `seq` of the oldest retained event; `None` when empty.
+    pub oldest_seq: Option<u64>,
+    /// `seq` of the most recent event, retained or not.
+    pub newest_seq: Option<u64>,
+    pub events: usize,
+    pub capacity: usize,
+    /// Serialized size of the retained events; only counted while
+    /// `max_bytes` is set.
+    pub bytes: usize,
+    pub max_bytes: usize,
+    /// Resumes that fell back to a full sync because their `since` was no
+    /// longer (or never) in the journal.
+    pub misses: u64,
+}
+
+/// Broadcast sender that also records each event in a bounded journal.
+///
+/// Stands in for a bare `broadcast::Sender<Event>` inside the parser task.
+#[derive(Clone)]
+pub struct EventSender {
+    tx: broadcast::Sender<Event>,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl EventSender {
+    pub fn new(tx: broadcast::Sender<Event>, capacity: usize) -> Self {
+        Self {
+            tx,
+            inner: Arc::new(Mutex::new(Inner {
+                events: VecDeque::with_capacity(capacity.min(DEFAULT_JOURNAL_CAPACITY)),
+                capacity,
+                max_bytes: 0,
+                bytes: 0,
+                last_seq: None,
+                misses: 0,
+            })),
+        }
+    }
+
+    /// Record `event` in the journal and broadcast it to subscribers.
+    ///
+    /// Returns the number of live subscribers that received it.
+    pub fn send(&self, event: Event) -> usize {
+        let mut inner = self.inner.lock();
+        let seq = event.seq();
+        // A seq that doesn't advance means the parser restarted (seq resets
+        // to 0). Older entries belong to a previous numbering and can't be
+        // resumed from.
+        if inner.last_seq.is_some_and(|last| seq <= last) {
+            inner.events.clear();
+            inner.bytes = 0;
+        }
+        inner.last_seq = Some(seq);
+        if inner.capacity > 0 {
+            let size = if inner.max_bytes > 0 { serialized_size(&event) } else { 0 };
+            inner.bytes += size;
+            inner.events.push_back((event.clone(), size));
+            inner.trim();
+        }
+        self.tx.send(event).unwrap_or(0)
+    }
+
+    /// Change how many events are retained, dropping the oldest if there
+    /// are now too many. Zero disables replay.
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut inner = self.inner.lock();
+        inner.capacity = capacity;
+        inner.trim();
+    }
+
+    /// Also cap the retained events by serialized (JSON) size; 0 removes
+    /// the cap. Measuring costs a serialization per event, so it's only
+    /// done while a cap is set.
+    pub fn set_max_bytes(&self, max_bytes: usize) {
+        let mut inner = self.inner.lock();
+        if inner.max_bytes == 0 && max_bytes > 0 {
+            let inner = &mut *inner;
+            for (event, size) in inner.events.iter_mut() {
+                *size = serialized_size(event);
+            }
+            inner.bytes = inner.events.iter().map(|(_, size)| size).sum();
+        } else if max_bytes == 0 {
+            inner.events.iter_mut().for_each(|(_, size)| *size = 0);
+            inner.bytes = 0;
+        }
+        inner.max_bytes = max_bytes;
+        inner.trim();
+    }
+
+    /// Snapshot of the journal's range, limits, and miss count.
+    pub fn status(&self) -> JournalStatus {
+        let inner = self.inner.lock();
+        JournalStatus {
+            oldest_seq: inner.events.front().map(|(event, _)| event.seq()),
+            newest_seq: inner.last_seq,
+            events: inner.events.len(),
+            capacity: inner.capacity,
+            bytes: inner.bytes,
+            max_bytes: inner.max_bytes,
+            misses: inner.misses,
+        }
+    }
+
+    /// Create a live subscription without replay.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.tx.subscribe()
+    }
+
+    /// Atomically snapshot events after `since` and open a live subscription.
+    ///
+    /// Returns `None` for the replay if the journal no longer covers `since`
+    /// (too old, or from before a parser restart); the caller should fall
+    /// back to a full sync.
+    pub fn subscribe_since(&self, since: u64) -> (Option<Vec<Event>>, broadcast::Receiver<Event>) {
+        let mut inner = self.inner.lock();
+        let rx = self.tx.subscribe();
+        let replay = match inner.last_seq {
+            Some(last) if since == last => Some(Vec::new()),
+            Some(last) if since < last => {
+                // Need every event after `since`: the oldest retained event
+                // must be `since + 1` or earlier.
+                match inner.events.front() {
+                    Some((first, _)) if first.seq() <= since.saturating_add(1) => Some(
+                        inner
+                            .events
+                            .iter()
+                            .map(|(e, _)| e)
+                            .filter(|e| e.seq() > since)
+                            .cloned()
+                            .collect(),
+                    ),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        if replay.is_none() {
+            inner.misses += 1;
+        }
+        (replay, rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(seq: u64) -> Event {
+        Event::Cursor {
+            seq,
+            row: 0,
+            col: seq as usize,
+            visible: true,
+        }
+    }
+
+    fn seqs(events: &[Event]) -> Vec<u64> {
+        events.iter().map(Event::seq).collect()
+    }
+
+    #[test]
+    fn replays_events_after_since() {
+        let sender = EventSender::new(broadcast::channel(16).0, 8);
+        for seq in 1..=5 {
+            sender.send(cursor(seq));
+        }
+        let (replay, _rx) = sender.subscribe_since(2);
+        assert_eq!(seqs(&replay.unwrap()), vec![3, 4, 5]);
+
+        let (replay, _rx) = sender.subscribe_since(5);
+        assert!(replay.unwrap().is_empty());
+    }
+
+    #[test]
+    fn evicted_or_unknown_since_requires_sync() {
+        let sender = EventSender::new(broadcast::channel(16).0, 3);
+        for seq in 1..=6 {
+            sender.send(cursor(seq));
+        }
+        // Journal holds 4..=6; resuming from 3 is still possible, 2 is not.
+        assert_eq!(seqs(&sender.subscribe_since(3).0.unwrap()), vec![4, 5, 6]);
+        assert!(sender.subscribe_since(2).0.is_none());
+        // A seq from the future is not something this journal issued.
+        assert!(sender.subscribe_since(99).0.is_none());
+    }
+
+    #[test]
+    fn status_reports_range_and_misses() {
+        let sender = EventSender::new(broadcast::channel(16).0, 3);
+        let status = sender.status();
+        assert_eq!((status.oldest_seq, status.newest_seq), (None, None));
+
+        for seq in 1..=6 {
+            sender.send(cursor(seq));
+        }
+        let status = sender.status();
+        assert_eq!((status.oldest_seq, status.newest_seq), (Some(4), Some(6)));
+        assert_eq!((status.events, status.capacity), (3, 3));
+
+        assert!(sender.subscribe_since(1).0.is_none());
+        assert!(sender.subscribe_since(4).0.is_some());
+        assert_eq!(sender.status().misses, 1);
+    }
+
+    #[test]
+    fn limits_can_change_at_runtime() {
+        let sender = EventSender::new(broadcast::channel(16).0, 8);
+        for seq in 1..=6 {
+            sender.send(cursor(seq));
+        }
+        sender.set_capacity(2);
+        assert_eq!(sender.status().oldest_seq, Some(5));
+
+        sender.set_capacity(8);
+        // Room for exactly the last three.
+        sender.set_max_bytes((8..=10).map(|seq| serialized_size(&cursor(seq))).sum());
+        for seq in 7..=10 {
+            sender.send(cursor(seq));
+        }
+        let status = sender.status();
+        assert_eq!(status.oldest_seq, Some(8));
+        assert!(status.bytes <= status.max_bytes);
+
+        sender.set_max_bytes(0);
+        sender.send(cursor(11));
+        let status = sender.status();
+        assert_eq!((status.events, status.bytes), (4, 0));
+    }
+
+    #[test]
+    fn restart_clears_journal() {
+        let sender = EventSender::new(broadcast::channel(16).0, 8);
+        for seq in 1..=4 {
+            sender.send(cursor(seq));
+        }
+        sender.send(Event::Reset {
+            seq: 0,
+            reason: super::super::events::ResetReason::ParserRestart,
+        });
+        assert!(sender.subscribe_since(2).0.is_none());
+        assert!(sender.subscribe_since(0).0.unwrap().is_empty());
+    }
+
+    #[test]
+    fn no_gap_between_replay_and_live() {
+        let sender = EventSender::new(broadcast::channel(16).0, 8);
+        sender.send(cursor(1));
+        sender.send(cursor(2));
+        let (replay, mut rx) = sender.subscribe_since(1);
+        sender.send(cursor(3));
+        assert_eq!(seqs(&replay.unwrap()), vec![2]);
+        assert_eq!(rx.try_recv().unwrap().seq(), 3);
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,451 @@
+//! Terminal metadata that programs report through OSC sequences: the window
+//! title (`OSC 0`/`OSC 2`), the working directory (`OSC 7`, sent by shells
+//! with directory tracking as `file://host/path`), and the default
+//! foreground and background colors (`OSC 10`/`OSC 11`, reset by
+//! `OSC 110`/`OSC 111`).
+//!
+//! The title also follows xterm's title stack (`CSI 22 ; 0 t` pushes,
+//! `CSI 23 ; 0 t` pops), so a full-screen program that saves the title on
+//! start and restores it on exit leaves the shell's title behind, not its own.
+//!
+//! Resize requests (`CSI 8 ; rows ; cols t`) are only recorded; the parser
+//! reports them and leaves the size to the client. So are desktop
+//! notifications: iTerm2's `OSC 9 ; body` and urxvt's
+//! `OSC 777 ; notify ; title ; body`.
+
+use super::escape::{EscapeScanner, Sequence};
+use super::state::Color;
+
+/// Longest OSC body or CSI parameter string kept. Titles and paths beyond
+/// this are ignored.
+const MAX_SEQUENCE_LEN: usize = 4096;
+
+/// Deepest title stack kept, matching xterm. Pushing onto a full stack
+/// drops the oldest entry.
+const MAX_TITLE_STACK: usize = 10;
+
+/// Most notifications kept between [`MetadataTracker::take_notifications`]
+/// calls; later ones are dropped.
+const MAX_NOTIFICATIONS: usize = 16;
+
+/// A desktop notification a program asked the terminal to show.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    /// Only `OSC 777` carries a title.
+    pub title: Option<String>,
+    pub body: String,
+}
+
+/// Incremental scanner that remembers the latest title and working
+/// directory. Sequences may be split across chunks.
+pub struct MetadataTracker {
+    scanner: EscapeScanner,
+    title: Option<String>,
+    title_stack: Vec<Option<String>>,
+    cwd: Option<String>,
+    resize_request: Option<(Option<usize>, Option<usize>)>,
+    notifications: Vec<Notification>,
+    /// Default foreground and background, in that order.
+    default_colors: [Option<Color>; 2],
+    default_colors_changed: bool,
+}
+
+impl MetadataTracker {
+    pub fn new() -> Self {
+        Self {
+            scanner: EscapeScanner::new(MAX_SEQUENCE_LEN),
+            title: None,
+            title_stack: Vec::new(),
+            cwd: None,
+            resize_request: None,
+            notifications: Vec::new(),
+            default_colors: [None, None],
+            default_colors_changed: false,
+        }
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn cwd(&self) -> Option<&str> {
+        self.cwd.as_deref()
+    }
+
+    /// The latest resize request since the last call, as (rows, cols).
+    /// A size the program left out or gave as 0 is `None`, meaning "keep
+    /// the current one".
+    pub fn take_resize_request(&mut self) -> Option<(Option<usize>, Option<usize>)> {
+        self.resize_request.take()
+    }
+
+    /// Default foreground set by `OSC 10`; `None` means the terminal's own.
+    pub fn default_fg(&self) -> Option<&Color> {
+        self.default_colors[0].as_ref()
+    }
+
+    /// Default background set by `OSC 11`; `None` means the terminal's own.
+    pub fn default_bg(&self) -> Option<&Color> {
+        self.default_colors[1].as_ref()
+    }
+
+    /// Whether either default color changed since the last call.
+    pub fn take_default_colors_changed(&mut self) -> bool {
+        std::mem::take(&mut self.default_colors_changed)
+    }
+
+    /// Notifications received since the last call, oldest first.
+    pub fn take_notifications(&mut self) -> Vec<Notification> {
+        std::mem::take(&mut self.notifications)
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.step(byte);
+        }
+    }
+
+    fn step(&mut self, byte: u8) {
+        match self.scanner.step(byte) {
+            Some(Sequence::Osc(body)) => {
+                let body = String::from_utf8_lossy(body).into_owned();
+                self.finish(&body);
+            }
+            Some(Sequence::Csi { params, intermediates: [], final_byte: b't' }) => {
+                let params = params.to_vec();
+                self.finish_window_op(&params);
+            }
+            _ => {}
+        }
+    }
+
+    /// Handle the resize and title stack operations of `CSI Ps ; Ps t`.
+    /// Only the window title (`0` or `2`, the default being `0`) is
+    /// tracked; icon name operations (`1`) are ignored.
+    fn finish_window_op(&mut self, params: &[u8]) {
+        let params: Vec<&[u8]> = params.split(|&b| b == b';').collect();
+        if params[0] == b"8" && params.len() <= 3 {
+            let size = |param: Option<&&[u8]>| {
+                std::str::from_utf8(param?).ok()?.parse::<u16>().ok().filter(|&n| n > 0).map(usize::from)
+            };
+            self.resize_request = Some((size(params.get(1)), size(params.get(2))));
+            return;
+        }
+        let (op, which) = match params[..] {
+            [op] => (op, &b"0"[..]),
+            [op, which] => (op, which),
+            _ => return,
+        };
+        if !matches!(which, b"" | b"0" | b"2") {
+            return;
+        }
+        match op {
+            b"22" => {
+                if self.title_stack.len() == MAX_TITLE_STACK {
+                    self.title_stack.remove(0);
+                }
+                self.title_stack.push(self.title.clone());
+            }
+            b"23" => {
+                if let Some(title) = self.title_stack.pop() {
+                    self.title = title;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(&mut self, body: &str) {
+        let (code, value) = match body.split_once(';') {
+            Some(split) => split,
+            // The default color resets take no argument.
+            None if matches!(body, "110" | "111") => (body, ""),
+            None => return,
+        };
+        match code {
+            "0" | "2" => {
+                self.title = (!value.is_empty()).then(|| value.to_string());
+            }
+            "7" => {
+                if let Some(path) = file_url_path(value) {
+                    self.cwd = Some(path);
+                }
+            }
+            "10" | "11" => {
+                // Each argument sets the next color, so `OSC 10 ; fg ; bg`
+                // sets both. Queries (`?`) and unparsable specs are skipped.
+                let first = if code == "10" { 0 } else { 1 };
+                for (slot, spec) in (first..2).zip(value.split(';')) {
+                    if let Some(color) = parse_color_spec(spec) {
+                        self.set_default_color(slot, Some(color));
+                    }
+                }
+            }
+            "110" => self.set_default_color(0, None),
+            "111" => self.set_default_color(1, None),
+            "9" => {
+                // ConEmu reuses OSC 9 for numbered commands (`9;4;...` is
+                // progress); those aren't notifications.
+                let command = value.split_once(';').is_some_and(|(n, _)| n.bytes().all(|b| b.is_ascii_digit()));
+                if !value.is_empty() && !command {
+                    notify(&mut self.notifications, None, value);
+                }
+            }
+            "777" => {
+                let Some((title, body)) = value.strip_prefix("notify;").and_then(|rest| rest.split_once(';')) else {
+                    return;
+                };
+                if !body.is_empty() {
+                    notify(&mut self.notifications, (!title.is_empty()).then(|| title.to_string()), body);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn set_default_color(&mut self, slot: usize, color: Option<Color>) {
+        if self.default_colors[slot] != color {
+            self.default_colors[slot] = color;
+            self.default_colors_changed = true;
+        }
+    }
+}
+
+impl Default for MetadataTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An X11 color spec as sent with `OSC 10`/`OSC 11`: `rgb:r/g/b` with 1-4
+/// hex digits per component, scaled to 8 bits, or `#rgb` with 1-4 digits
+/// per component, keeping the most significant 8 bits. Color names aren't
+/// supported.
+fn parse_color_spec(spec: &str) -> Option<Color> {
+    let hex = |digits: &str| {
+        let valid = (1..=4).contains(&digits.len()) && digits.bytes().all(|b| b.is_ascii_hexdigit());
+        valid.then(|| u32::from_str_radix(digits, 16).ok()).flatten()
+    };
+    let components: Vec<u8> = if let Some(rgb) = spec.strip_prefix("rgb:") {
+        let parts: Vec<&str> = rgb.split('/').collect();
+        if parts.len() != 3 {
+            return None;
+        }
+        parts
+            .iter()
+            .map(|part| {
+                // `hex` caps components at 4 digits, keeping the shift in range.
+                hex(part).map(|v| {
+                    let max = (1u32 << (4 * part.len())) - 1;
+                    ((v * 255 + max / 2) / max) as u8
+                })
+            })
+            .collect::<Option<_>>()?
+    } else if let Some(digits) = spec.strip_prefix('#') {
+        let n = digits.len() / 3;
+        if digits.len() % 3 != 0 || !digits.is_ascii() {
+            return None;
+        }
+        (0..3)
+            .map(|i| {
+                let v = hex(&digits[i * n..(i + 1) * n])?;
+                Some(if n == 1 { (v << 4) as u8 } else { (v >> (4 * n - 8)) as u8 })
+            })
+            .collect::<Option<_>>()?
+    } else {
+        return None;
+    };
+    Some(Color::Rgb { r: components[0], g: components[1], b: components[2] })
+}
+
+/// Queue a notification unless [`MAX_NOTIFICATIONS`] are already waiting.
+fn notify(notifications: &mut Vec<Notification>, title: Option<String>, body: &str) {
+    if notifications.len() < MAX_NOTIFICATIONS {
+        notifications.push(Notification { title, body: body.to_string() });
+    }
+}
+
+/// Path of a `file://host/path` URL, percent-decoded.
+fn file_url_path(url: &str) -> Option<String> {
+    let rest = url.strip_prefix("file://")?;
+    let path = &rest[rest.find('/')?..];
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'%')
+            .then(|| path.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    Some(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_latest_title() {
+        let mut tracker = MetadataTracker::new();
+        tracker.feed(b"\x1b]0;first\x07text\x1b]2;second\x1b\\");
+        assert_eq!(tracker.title(), Some("second"));
+        tracker.feed(b"\x1b]2;\x07");
+        assert_eq!(tracker.title(), None);
+    }
+
+    #[test]
+    fn title_stack_restores_pushed_title() {
+        let mut tracker = MetadataTracker::new();
+        tracker.feed(b"\x1b]0;shell\x07\x1b[22;0t\x1b]2;vim file.rs\x07");
+        assert_eq!(tracker.title(), Some("vim file.rs"));
+        tracker.feed(b"\x1b[23;0t");
+        assert_eq!(tracker.title(), Some("shell"));
+        // Popping an empty stack leaves the title alone.
+        tracker.feed(b"\x1b[23;0t");
+        assert_eq!(tracker.title(), Some("shell"));
+        // Icon name operations don't touch the title stack.
+        tracker.feed(b"\x1b[22;1t\x1b]0;other\x07\x1b[23;1t");
+        assert_eq!(tracker.title(), Some("other"));
+    }
+
+    #[test]
+    fn title_stack_is_bounded() {
+        let mut tracker = MetadataTracker::new();
+        for i in 0..MAX_TITLE_STACK + 2 {
+            tracker.feed(format!("\x1b]0;t{i}\x07\x1b[22t").as_bytes());
+        }
+        for _ in 0..MAX_TITLE_STACK + 2 {
+            tracker.feed(b"\x1b[23t");
+        }
+        // The two oldest pushes fell off the bottom of the stack.
+        assert_eq!(tracker.title(), Some("t2"));
+    }
+
+    #[test]
+    fn records_latest_resize_request() {
+        let mut tracker = MetadataTracker::new();
+        assert_eq!(tracker.take_resize_request(), None);
+        tracker.feed(b"\x1b[8;24;80t\x1b[8;30;100t");
+        assert_eq!(tracker.take_resize_request(), Some((Some(30), Some(100))));
+        assert_eq!(tracker.take_resize_request(), None);
+
+        // Omitted or zero sizes keep the current one.
+        tracker.feed(b"\x1b[8;;120t");
+        assert_eq!(tracker.take_resize_request(), Some((None, Some(120))));
+        tracker.feed(b"\x1b[8;40;0t");
+        assert_eq!(tracker.take_resize_request(), Some((Some(40), None)));
+
+        // Other window ops aren't resize requests.
+        tracker.feed(b"\x1b[18t\x1b[8;1;2;3t");
+        assert_eq!(tracker.take_resize_request(), None);
+    }
+
+    #[test]
+    fn records_notifications() {
+        let mut tracker = MetadataTracker::new();
+        tracker.feed(b"\x1b]9;Build done\x07\x1b]777;notify;CI;Tests passed; 3 skipped\x1b\\");
+        assert_eq!(
+            tracker.take_notifications(),
+            [
+                Notification { title: None, body: "Build done".to_string() },
+                Notification { title: Some("CI".to_string()), body: "Tests passed; 3 skipped".to_string() },
+            ]
+        );
+        assert!(tracker.take_notifications().is_empty());
+
+        // Empty bodies, ConEmu progress, and other OSC 777 commands are
+        // ignored, as is a notify without a body.
+        tracker.feed(b"\x1b]9;\x07\x1b]9;4;1;50\x07\x1b]777;preexec\x07\x1b]777;notify;title only\x07");
+        assert!(tracker.take_notifications().is_empty());
+    }
+
+    #[test]
+    fn notifications_are_bounded() {
+        let mut tracker = MetadataTracker::new();
+        for i in 0..MAX_NOTIFICATIONS + 2 {
+            tracker.feed(format!("\x1b]9;n{i}\x07").as_bytes());
+        }
+        let notifications = tracker.take_notifications();
+        assert_eq!(notifications.len(), MAX_NOTIFICATIONS);
+        assert_eq!(notifications[0].body, "n0");
+    }
+
+    #[test]
+    fn tracks_default_colors() {
+        let mut tracker = MetadataTracker::new();
+        assert_eq!((tracker.default_fg(), tracker.default_bg()), (None, None));
+        assert!(!tracker.take_default_colors_changed());
+
+        tracker.feed(b"\x1b]10;rgb:ffff/8080/0000\x07\x1b]11;#102030\x1b\\");
+        assert_eq!(tracker.default_fg(), Some(&Color::Rgb { r: 255, g: 128, b: 0 }));
+        assert_eq!(tracker.default_bg(), Some(&Color::Rgb { r: 16, g: 32, b: 48 }));
+        assert!(tracker.take_default_colors_changed());
+        assert!(!tracker.take_default_colors_changed());
+
+        // Setting the same color again isn't a change; queries and bad
+        // specs are ignored.
+        tracker.feed(b"\x1b]11;rgb:10/20/30\x07\x1b]10;?\x07\x1b]11;white\x07\x1b]11;rgb:1/2\x07");
+        assert!(!tracker.take_default_colors_changed());
+
+        // One OSC 10 can set both; OSC 110/111 restore the defaults.
+        tracker.feed(b"\x1b]10;#fff;rgb:0/0/0\x07");
+        assert_eq!(tracker.default_fg(), Some(&Color::Rgb { r: 240, g: 240, b: 240 }));
+        assert_eq!(tracker.default_bg(), Some(&Color::Rgb { r: 0, g: 0, b: 0 }));
+        tracker.feed(b"\x1b]110\x07\x1b]111;\x07");
+        assert_eq!((tracker.default_fg(), tracker.default_bg()), (None, None));
+        assert!(tracker.take_default_colors_changed());
+    }
+
+    #[test]
+    fn rejects_oversized_color_components() {
+        let mut tracker = MetadataTracker::new();
+        tracker.feed(b"\x1b]11;rgb:123456789/0/0\x07\x1b]10;rgb:0/0/12345\x07\x1b]10;#123451234512345\x07");
+        assert_eq!((tracker.default_fg(), tracker.default_bg()), (None, None));
+        assert!(!tracker.take_default_colors_changed());
+    }
+
+    #[test]
+    fn tracks_cwd_from_file_url() {
+        let mut tracker = MetadataTracker::new();
+        tracker.feed(b"\x1b]7;file://host/home/me/my%20dir\x07");
+        assert_eq!(tracker.cwd(), Some("/home/me/my dir"));
+        // Not a file URL: keep the last known directory.
+        tracker.feed(b"\x1b]7;garbage\x07");
+        assert_eq!(tracker.cwd(), Some("/home/me/my dir"));
+    }
+
+    #[test]
+    fn sequences_split_across_chunks() {
+        let mut tracker = MetadataTracker::new();
+        tracker.feed(b"\x1b]0;spl");
+        assert_eq!(tracker.title(), None);
+        tracker.feed(b"it\x1b");
+        tracker.feed(b"\\");
+        assert_eq!(tracker.title(), Some("split"));
+    }
+
+    #[test]
+    fn ignores_other_osc_and_oversized_bodies() {
+        let mut tracker = MetadataTracker::new();
+        tracker.feed(b"\x1b]133;A\x07\x1b]8;;http://x\x07");
+        assert_eq!(tracker.title(), None);
+        assert_eq!(tracker.cwd(), None);
+
+        let mut long = b"\x1b]0;".to_vec();
+        long.extend(std::iter::repeat_n(b'x', MAX_SEQUENCE_LEN + 1));
+        long.push(0x07);
+        tracker.feed(&long);
+        assert_eq!(tracker.title(), None);
+    }
+}
@@ -1,15 +1,24 @@
 pub mod ansi;
+pub mod coalesce;
+pub mod commands;
+pub mod decode;
+pub mod device_query;
+pub mod escape;
 pub mod events;
 pub mod format;
+pub mod journal;
+pub mod metadata;
+pub mod scroll_region;
 pub mod state;
 
 mod task;
 
 use std::panic::AssertUnwindSafe;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use futures::FutureExt;
+use tracing::Instrument;
 use thiserror::Error;
 use tokio::sync::{broadcast, mpsc, oneshot};
 use tokio_stream::wrappers::BroadcastStream;
@@ -17,7 +26,9 @@ use tokio_stream::{Stream, StreamExt};
 
 use bytes::Bytes;
 
-use events::Event;
+use decode::Decoding;
+use events::{Event, ShutdownReason};
+use journal::EventSender;
 use state::{Query, QueryResponse};
 
 /// Wrapper for parser subscription events that includes lag notifications.
@@ -29,6 +40,51 @@ pub enum SubscriptionEvent {
     Lagged(u64),
 }
 
+/// A chunk of raw PTY bytes exactly as the parser consumed it.
+///
+/// Events with `seq_before < seq <= seq_after` were produced by this chunk,
+/// which correlates the raw input with the parsed event stream.
+#[derive(Debug, Clone)]
+pub struct RawChunk {
+    /// Bytes consumed before this chunk since the parser (re)started.
+    pub offset: u64,
+    pub bytes: Bytes,
+    pub seq_before: u64,
+    pub seq_after: u64,
+}
+
+/// Item of a [`Parser::subscribe_raw`] stream.
+#[derive(Debug, Clone)]
+pub enum RawTapEvent {
+    Chunk(RawChunk),
+    /// The subscriber fell behind and `skipped` chunks were dropped.
+    Lagged(u64),
+}
+
+/// Raw tap buffer size. Chunks are refcounted `Bytes`, so this is cheap.
+const RAW_TAP_CAPACITY: usize = 256;
+
+/// Input channel size for [`Parser::spawn_with_feed`], matching the PTY
+/// reader's channel.
+const FEED_CHANNEL_CAPACITY: usize = 256;
+
+/// Default for [`Parser::set_max_line_cells`]. Well above the widest
+/// terminal the API creates, so it only bites on pathological sizes.
+pub const DEFAULT_MAX_LINE_CELLS: usize = 4096;
+
+/// Default for [`Parser::set_redraw_threshold`]: off, so subscribers only
+/// see `Diff` events for sessions that ask for them.
+pub const DEFAULT_REDRAW_THRESHOLD_PERCENT: usize = 0;
+
+/// Window over which working directory changes are coalesced into one
+/// [`events::Event::Cwd`], so a script that `cd`s repeatedly doesn't flood
+/// subscribers.
+pub const CWD_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// How long [`Parser::query`] waits, counting time spent queued behind a
+/// full query channel, before giving up with `ParserError::QueryTimeout`.
+const QUERY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
 #[derive(Error, Debug)]
 pub enum ParserError {
     #[error("parser task died unexpectedly")]
@@ -42,12 +98,30 @@ pub enum ParserError {
 
     #[error("invalid query parameters: {0}")]
     InvalidQuery(String),
+
+    #[error("parser query cancelled")]
+    Cancelled,
+
+    #[error("parser input is fed by its own channel; use Parser::spawn_with_feed")]
+    FeedUnavailable,
 }
 
 #[derive(Clone)]
 pub struct Parser {
-    query_tx: mpsc::Sender<(Query, oneshot::Sender<QueryResponse>)>,
-    event_tx: broadcast::Sender<Event>,
+    query_tx: mpsc::Sender<(Query, tokio::time::Instant, oneshot::Sender<QueryResponse>)>,
+    event_tx: EventSender,
+    raw_tap: broadcast::Sender<RawChunk>,
+    reflow_on_resize: Arc<AtomicBool>,
+    plain_by_default: Arc<AtomicBool>,
+    max_line_cells: Arc<AtomicUsize>,
+    redraw_threshold: Arc<AtomicUsize>,
+    end_reason: Arc<parking_lot::Mutex<Option<ShutdownReason>>>,
+    cols: Arc<AtomicUsize>,
+    history_lines: Arc<AtomicUsize>,
+    alternate_active: Arc<AtomicBool>,
+    /// Set by [`Parser::spawn_with_feed`]; the raw channel's sender is held
+    /// elsewhere (e.g. by the PTY reader) otherwise.
+    feed_tx: Option<mpsc::Sender<Bytes>>,
 }
 
 impl Parser {
@@ -58,11 +132,60 @@ impl Parser {
     /// `blocking_send()` to apply backpressure when the parser can't keep up.
     /// See the design decision comment in `Session::spawn_with_options()` for
     /// the full rationale.
-    pub fn spawn(mut raw_rx: mpsc::Receiver<Bytes>, cols: usize, rows: usize, scrollback_limit: usize) -> Self {
+    pub fn spawn(raw_rx: mpsc::Receiver<Bytes>, cols: usize, rows: usize, scrollback_limit: usize) -> Self {
+        Self::spawn_with_journal(raw_rx, cols, rows, scrollback_limit, journal::DEFAULT_JOURNAL_CAPACITY)
+    }
+
+    /// Like [`Parser::spawn`], but the parser owns its input channel: write
+    /// output with [`Parser::feed`] or [`Parser::try_feed`] instead of
+    /// holding a sender. The task exits once every clone is dropped.
+    pub fn spawn_with_feed(cols: usize, rows: usize, scrollback_limit: usize) -> Self {
+        let (feed_tx, raw_rx) = mpsc::channel(FEED_CHANNEL_CAPACITY);
+        Self {
+            feed_tx: Some(feed_tx),
+            ..Self::spawn(raw_rx, cols, rows, scrollback_limit)
+        }
+    }
+
+    /// Like [`Parser::spawn`], but retaining up to `journal_capacity` recent
+    /// events for [`Parser::subscribe_since`]. Zero disables replay.
+    pub fn spawn_with_journal(
+        raw_rx: mpsc::Receiver<Bytes>,
+        cols: usize,
+        rows: usize,
+        scrollback_limit: usize,
+        journal_capacity: usize,
+    ) -> Self {
+        Self::spawn_with_options(raw_rx, cols, rows, scrollback_limit, journal_capacity, Decoding::default())
+    }
+
+    /// Like [`Parser::spawn`], but decoding PTY bytes with `decoding`.
+    pub fn spawn_with_decoding(
+        raw_rx: mpsc::Receiver<Bytes>,
+        cols: usize,
+        rows: usize,
+        scrollback_limit: usize,
+        decoding: Decoding,
+    ) -> Self {
+        Self::spawn_with_options(raw_rx, cols, rows, scrollback_limit, journal::DEFAULT_JOURNAL_CAPACITY, decoding)
+    }
+
+    /// Spawn with every option spelled out; see [`Parser::spawn_with_journal`]
+    /// and [`Parser::spawn_with_decoding`].
+    pub fn spawn_with_options(
+        mut raw_rx: mpsc::Receiver<Bytes>,
+        cols: usize,
+        rows: usize,
+        scrollback_limit: usize,
+        journal_capacity: usize,
+        decoding: Decoding,
+    ) -> Self {
         let (query_tx, query_rx) = mpsc::channel(32);
-        let (event_tx, _) = broadcast::channel(256);
+        let event_tx = EventSender::new(broadcast::channel(256).0, journal_capacity);
 
         let event_tx_clone = event_tx.clone();
+        let raw_tap = broadcast::channel(RAW_TAP_CAPACITY).0;
+        let task_raw_tap = raw_tap.clone();
 
         // Shared dimension tracking for panic recovery. When the parser
         // handles a Resize query it updates these atomics, so the restart
@@ -72,7 +195,19 @@ impl Parser {
         let current_rows = Arc::new(AtomicUsize::new(rows));
         let task_cols = current_cols.clone();
         let task_rows = current_rows.clone();
+        let max_line_cells = Arc::new(AtomicUsize::new(DEFAULT_MAX_LINE_CELLS));
+        let task_max_line_cells = max_line_cells.clone();
+        let redraw_threshold = Arc::new(AtomicUsize::new(DEFAULT_REDRAW_THRESHOLD_PERCENT));
+        let task_redraw_threshold = redraw_threshold.clone();
+        let end_reason = Arc::new(parking_lot::Mutex::new(None));
+        let task_end_reason = Arc::clone(&end_reason);
+        let history_lines = Arc::new(AtomicUsize::new(0));
+        let task_history_lines = Arc::clone(&history_lines);
+        let alternate_active = Arc::new(AtomicBool::new(false));
+        let task_alternate_active = Arc::clone(&alternate_active);
 
+        // Runs in the caller's span, so a session's parser logs are
+        // captured in that session's log.
         tokio::spawn(async move {
             let mut query_rx = query_rx;
             // On first iteration use the initial dimensions; on restart
@@ -95,8 +230,15 @@ impl Parser {
                     c,
                     r,
                     scrollback_limit,
+                    decoding,
+                    &task_raw_tap,
                     &task_cols,
                     &task_rows,
+                    &task_max_line_cells,
+                    &task_redraw_threshold,
+                    &task_end_reason,
+                    &task_history_lines,
+                    &task_alternate_active,
                 ))
                 .catch_unwind()
                 .await;
@@ -119,11 +261,21 @@ impl Parser {
                     }
                 }
             }
-        });
+        }.instrument(tracing::Span::current()));
 
         Self {
             query_tx,
             event_tx,
+            raw_tap,
+            reflow_on_resize: Arc::new(AtomicBool::new(true)),
+            plain_by_default: Arc::new(AtomicBool::new(false)),
+            max_line_cells,
+            redraw_threshold,
+            end_reason,
+            cols: current_cols,
+            history_lines,
+            alternate_active,
+            feed_tx: None,
         }
     }
 
@@ -132,38 +284,298 @@ impl Parser {
     /// Returns `ParserError::QueryTimeout` if the parser task doesn't respond
     /// within 5 seconds. This prevents callers from blocking indefinitely if
     /// the parser task is stalled.
+    ///
+    /// Dropping the returned future abandons the query: the parser skips
+    /// read-only queries whose caller has gone away or whose deadline has
+    /// passed, so a stall doesn't leave a backlog of stale work behind it.
     pub async fn query(&self, query: Query) -> Result<QueryResponse, ParserError> {
+        let deadline = tokio::time::Instant::now() + QUERY_TIMEOUT;
         let (tx, rx) = oneshot::channel();
-        self.query_tx
-            .send((query, tx))
+        tokio::time::timeout_at(deadline, self.query_tx.send((query, deadline, tx)))
             .await
+            .map_err(|_| ParserError::QueryTimeout)?
             .map_err(|_| ParserError::TaskDied)?;
-        tokio::time::timeout(std::time::Duration::from_secs(5), rx)
+        let response = tokio::time::timeout_at(deadline, rx)
             .await
             .map_err(|_| ParserError::QueryTimeout)?
-            .map_err(|_| ParserError::TaskDied)
+            .map_err(|_| ParserError::TaskDied)?;
+        match response {
+            QueryResponse::Invalid(detail) => Err(ParserError::InvalidQuery(detail)),
+            response => Ok(response),
+        }
+    }
+
+    /// Like [`Parser::query`], but returns `ParserError::Cancelled` as soon
+    /// as `cancel` fires instead of waiting out the timeout.
+    pub async fn query_cancellable(
+        &self,
+        query: Query,
+        cancel: &tokio_util::sync::CancellationToken,
+    ) -> Result<QueryResponse, ParserError> {
+        tokio::select! {
+            biased;
+            _ = cancel.cancelled() => Err(ParserError::Cancelled),
+            result = self.query(query) => result,
+        }
+    }
+
+    /// Queue terminal output, waiting while the input channel is full.
+    /// Only for parsers from [`Parser::spawn_with_feed`]; others return
+    /// `ParserError::FeedUnavailable`.
+    pub async fn feed(&self, bytes: Bytes) -> Result<(), ParserError> {
+        let feed_tx = self.feed_tx.as_ref().ok_or(ParserError::FeedUnavailable)?;
+        feed_tx.send(bytes).await.map_err(|_| ParserError::TaskDied)
+    }
+
+    /// Like [`Parser::feed`], but returns `ParserError::ChannelFull` instead
+    /// of waiting when the parser is behind.
+    pub fn try_feed(&self, bytes: Bytes) -> Result<(), ParserError> {
+        let feed_tx = self.feed_tx.as_ref().ok_or(ParserError::FeedUnavailable)?;
+        feed_tx.try_send(bytes).map_err(|e| match e {
+            mpsc::error::TrySendError::Full(_) => ParserError::ChannelFull,
+            mpsc::error::TrySendError::Closed(_) => ParserError::TaskDied,
+        })
+    }
+
+    /// Wait until every chunk sent to the parser before this call has been
+    /// parsed. Use it between writing output and querying state instead of
+    /// sleeping.
+    pub async fn barrier(&self) -> Result<(), ParserError> {
+        self.query(Query::Barrier).await?;
+        Ok(())
     }
 
     /// Notify parser of terminal resize
     pub async fn resize(&self, cols: usize, rows: usize) -> Result<(), ParserError> {
-        self.query(Query::Resize { cols, rows }).await?;
+        let reflow = self.reflow_on_resize.load(Ordering::Relaxed);
+        self.query(Query::Resize { cols, rows, reflow }).await?;
+        Ok(())
+    }
+
+    /// Like [`Parser::resize`], but also returns the resized screen. No
+    /// output is parsed between the resize and the snapshot.
+    pub async fn resize_and_screen(
+        &self,
+        cols: usize,
+        rows: usize,
+        format: state::Format,
+    ) -> Result<state::ScreenResponse, ParserError> {
+        let reflow = self.reflow_on_resize.load(Ordering::Relaxed);
+        match self.query(Query::ResizeAndScreen { cols, rows, reflow, format }).await? {
+            QueryResponse::Screen(screen) => Ok(screen),
+            _ => unreachable!("ResizeAndScreen always answers with a screen"),
+        }
+    }
+
+    /// Remember the current screen so a later [`Parser::diff_since`] can
+    /// report which rows changed.
+    pub async fn snapshot(&self) -> Result<u64, ParserError> {
+        match self.query(Query::Snapshot).await? {
+            QueryResponse::Snapshot(s) => Ok(s.snapshot_id),
+            _ => unreachable!("Snapshot always answers with a snapshot id"),
+        }
+    }
+
+    /// Rows of the visible screen that changed since `snapshot_id`, with
+    /// their current content.
+    pub async fn diff_since(
+        &self,
+        snapshot_id: u64,
+        format: state::Format,
+    ) -> Result<state::ScreenDiffResponse, ParserError> {
+        match self.query(Query::DiffSince { snapshot_id, format }).await? {
+            QueryResponse::ScreenDiff(diff) => Ok(diff),
+            _ => unreachable!("DiffSince always answers with a diff"),
+        }
+    }
+
+    /// Erase the screen and/or scrollback. Followers get a `Reset` and a
+    /// `Sync` with the cleared screen.
+    pub async fn clear(&self, scope: state::ClearScope) -> Result<(), ParserError> {
+        self.query(Query::Clear { scope }).await?;
         Ok(())
     }
 
+    /// Drop all but the `keep` most recent lines of scrollback. Followers
+    /// get a `Reset` and a `Sync` when anything was dropped.
+    pub async fn trim_scrollback(&self, keep: usize) -> Result<(), ParserError> {
+        self.query(Query::TrimScrollback { keep }).await?;
+        Ok(())
+    }
+
+    /// Lines of primary-screen scrollback held, as of the last chunk parsed.
+    pub fn scrollback_lines(&self) -> usize {
+        self.history_lines.load(Ordering::Relaxed)
+    }
+
+    /// Whether the alternate screen is up, as of the last chunk parsed.
+    /// Cheaper than a screen query when that's all a caller needs.
+    pub fn alternate_active(&self) -> bool {
+        self.alternate_active.load(Ordering::Relaxed)
+    }
+
+    /// Rough memory held by one line of scrollback at the current width.
+    pub fn scrollback_line_bytes(&self) -> usize {
+        self.cols.load(Ordering::Relaxed) * std::mem::size_of::<avt::Cell>() + std::mem::size_of::<avt::Line>()
+    }
+
+    /// Rough memory held by scrollback: [`Parser::scrollback_lines`] times
+    /// [`Parser::scrollback_line_bytes`].
+    pub fn scrollback_bytes(&self) -> usize {
+        self.scrollback_lines() * self.scrollback_line_bytes()
+    }
+
+    /// Parse previously captured output (e.g. a session's raw log) as if the
+    /// program had just written it. With `quiet`, followers get one `Sync`
+    /// once it's all parsed instead of incremental events.
+    ///
+    /// Restored bytes are not program output: they don't appear on the raw
+    /// tap and don't advance raw offsets.
+    pub async fn restore(&self, bytes: Bytes, quiet: bool) -> Result<(), ParserError> {
+        self.query(Query::Restore { bytes, quiet }).await?;
+        Ok(())
+    }
+
+    /// Choose whether [`Parser::resize`] reflows soft-wrapped lines to the
+    /// new width (the default) or keeps every row as is, truncating or
+    /// padding it. Shared by all clones of this parser.
+    pub fn set_reflow_on_resize(&self, reflow: bool) {
+        self.reflow_on_resize.store(reflow, Ordering::Relaxed);
+    }
+
+    /// Set the format event streams use when a subscriber doesn't name one
+    /// (styled unless changed). Shared by all clones of this parser.
+    pub fn set_default_format(&self, format: state::Format) {
+        self.plain_by_default.store(format == state::Format::Plain, Ordering::Relaxed);
+    }
+
+    /// The format event streams use when a subscriber doesn't name one.
+    pub fn default_format(&self) -> state::Format {
+        if self.plain_by_default.load(Ordering::Relaxed) {
+            state::Format::Plain
+        } else {
+            state::Format::Styled
+        }
+    }
+
+    /// Cap the cells serialized per row in screen, scrollback, and line
+    /// output (default [`DEFAULT_MAX_LINE_CELLS`], minimum 1). Text still
+    /// wraps at the terminal width; this only bounds rows wider than the
+    /// cap. Shared by all clones of this parser.
+    pub fn set_max_line_cells(&self, cells: usize) {
+        self.max_line_cells.store(cells.max(1), Ordering::Relaxed);
+    }
+
+    /// Current per-row serialization cap; see [`Parser::set_max_line_cells`].
+    pub fn max_line_cells(&self) -> usize {
+        self.max_line_cells.load(Ordering::Relaxed)
+    }
+
+    /// Send one `Diff` event with the whole screen, instead of a `Line`
+    /// event per row, when a chunk of output rewrites at least `percent` of
+    /// the screen's rows without scrolling any into scrollback (default
+    /// [`DEFAULT_REDRAW_THRESHOLD_PERCENT`]). Zero turns this off. Shared by
+    /// all clones of this parser.
+    pub fn set_redraw_threshold(&self, percent: usize) {
+        self.redraw_threshold.store(percent.min(100), Ordering::Relaxed);
+    }
+
+    /// Current redraw threshold; see [`Parser::set_redraw_threshold`].
+    pub fn redraw_threshold(&self) -> usize {
+        self.redraw_threshold.load(Ordering::Relaxed)
+    }
+
+    /// Record why the output is ending, for the final [`Event::End`]. The
+    /// first reason wins; returns whether this one was recorded.
+    pub fn set_end_reason(&self, reason: ShutdownReason) -> bool {
+        let mut slot = self.end_reason.lock();
+        if slot.is_some() {
+            return false;
+        }
+        *slot = Some(reason);
+        true
+    }
+
+    /// The reason recorded by [`Parser::set_end_reason`], if any.
+    pub fn end_reason(&self) -> Option<ShutdownReason> {
+        self.end_reason.lock().clone()
+    }
+
+    /// Change how many recent events are kept for
+    /// [`Parser::subscribe_since`]; zero disables replay.
+    pub fn set_journal_capacity(&self, events: usize) {
+        self.event_tx.set_capacity(events);
+    }
+
+    /// Also cap the replay journal by the events' serialized size in bytes;
+    /// 0 removes the cap.
+    pub fn set_journal_max_bytes(&self, bytes: usize) {
+        self.event_tx.set_max_bytes(bytes);
+    }
+
+    /// Which `seq` range [`Parser::subscribe_since`] can currently resume
+    /// from, with the journal's limits and how many resumes missed it.
+    pub fn journal_status(&self) -> journal::JournalStatus {
+        self.event_tx.status()
+    }
+
+    /// JSON Schema (draft 2020-12) of [`events::Event`] as serialized, for
+    /// generating client types in other languages. Fields the server omits
+    /// when empty are optional.
+    pub fn event_schema() -> serde_json::Value {
+        let schema = schemars::generate::SchemaSettings::draft2020_12()
+            .for_serialize()
+            .into_generator()
+            .into_root_schema_for::<events::Event>();
+        serde_json::to_value(schema).expect("event schema serializes")
+    }
+
     /// Subscribe to events (returns async Stream).
     ///
     /// The stream yields `SubscriptionEvent::Event` for normal events and
     /// `SubscriptionEvent::Lagged(n)` when the subscriber falls behind,
     /// allowing consumers to detect data loss and re-query state.
     pub fn subscribe(&self) -> impl Stream<Item = SubscriptionEvent> {
-        BroadcastStream::new(self.event_tx.subscribe()).filter_map(|result| match result {
-            Ok(event) => Some(SubscriptionEvent::Event(event)),
+        subscription_stream(self.event_tx.subscribe())
+    }
+
+    /// Resume a subscription after `since`, the `seq` of the last event a
+    /// client received.
+    ///
+    /// Returns the missed events followed by a live stream with no gap or
+    /// overlap between them. The replay is `None` when the journal no longer
+    /// reaches back to `since`; the caller should then send a full sync.
+    pub fn subscribe_since(
+        &self,
+        since: u64,
+    ) -> (Option<Vec<Event>>, impl Stream<Item = SubscriptionEvent>) {
+        let (replay, rx) = self.event_tx.subscribe_since(since);
+        (replay, subscription_stream(rx))
+    }
+
+    /// Subscribe to the raw bytes the parser consumes, in order, for
+    /// debugging and replaying the exact input behind a parser bug.
+    ///
+    /// Opt-in: the parser only publishes chunks while a raw subscriber
+    /// exists, so normal event subscribers pay nothing for it.
+    pub fn subscribe_raw(&self) -> impl Stream<Item = RawTapEvent> {
+        BroadcastStream::new(self.raw_tap.subscribe()).filter_map(|result| match result {
+            Ok(chunk) => Some(RawTapEvent::Chunk(chunk)),
             Err(tokio_stream::wrappers::errors::BroadcastStreamRecvError::Lagged(n)) => {
-                Some(SubscriptionEvent::Lagged(n))
+                Some(RawTapEvent::Lagged(n))
             }
         })
     }
 }
 
+fn subscription_stream(rx: broadcast::Receiver<Event>) -> impl Stream<Item = SubscriptionEvent> {
+    BroadcastStream::new(rx).filter_map(|result| match result {
+        Ok(event) => Some(SubscriptionEvent::Event(event)),
+        Err(tokio_stream::wrappers::errors::BroadcastStreamRecvError::Lagged(n)) => {
+            Some(SubscriptionEvent::Lagged(n))
+        }
+    })
+}
+
 #[cfg(test)]
 mod tests;
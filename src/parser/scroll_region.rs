@@ -0,0 +1,157 @@
+//! Scroll margins set by DECSTBM (`CSI top ; bottom r`).
+//!
+//! avt applies the margins but doesn't expose them, so this scanner follows
+//! the same rules: an invalid region is ignored, and DECSTR (`CSI ! p`),
+//! RIS (`ESC c`) and a change in row count restore the full screen.
+
+use serde::Serialize;
+
+use super::escape::{EscapeScanner, Sequence};
+
+/// Longest CSI parameter string kept. Longer sequences are ignored.
+const MAX_PARAMS_LEN: usize = 32;
+
+/// Rows scrolled by line feeds and reverse index, 0-based and inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ScrollRegion {
+    pub top: usize,
+    pub bottom: usize,
+}
+
+/// Incremental scanner that remembers the active scroll region. Sequences
+/// may be split across chunks.
+pub struct ScrollRegionTracker {
+    scanner: EscapeScanner,
+    rows: usize,
+    region: ScrollRegion,
+}
+
+impl ScrollRegionTracker {
+    pub fn new(rows: usize) -> Self {
+        Self {
+            scanner: EscapeScanner::new(MAX_PARAMS_LEN),
+            rows,
+            region: full_screen(rows),
+        }
+    }
+
+    pub fn region(&self) -> ScrollRegion {
+        self.region
+    }
+
+    pub fn is_full_screen(&self) -> bool {
+        self.region == full_screen(self.rows)
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.step(byte);
+        }
+    }
+
+    /// Follows [`avt::Vt::resize`], which only resets the margins when the
+    /// row count changes.
+    pub fn resize(&mut self, rows: usize) {
+        if rows != self.rows {
+            self.rows = rows;
+            self.reset();
+        }
+    }
+
+    /// Restore the full screen, e.g. after the terminal was rebuilt.
+    pub fn reset(&mut self) {
+        self.region = full_screen(self.rows);
+    }
+
+    fn step(&mut self, byte: u8) {
+        match self.scanner.step(byte) {
+            Some(Sequence::Esc { intermediates: [], final_byte: b'c' }) => self.reset(),
+            // A private marker (`?`, `>`, ...) means it isn't DECSTBM.
+            Some(Sequence::Csi { params, intermediates: [], final_byte: b'r' })
+                if params.iter().all(|b| matches!(b, b'0'..=b'9' | b';' | b':')) =>
+            {
+                let params = String::from_utf8_lossy(params).into_owned();
+                self.set_margins(&params);
+            }
+            Some(Sequence::Csi { intermediates: b"!", final_byte: b'p', .. }) => self.reset(),
+            _ => {}
+        }
+    }
+
+    fn set_margins(&mut self, params: &str) {
+        let mut values = params.split(';').map(|p| p.parse::<usize>().unwrap_or(0));
+        // Missing or zero parameters select the screen edges.
+        let top = values.next().filter(|&v| v > 0).unwrap_or(1) - 1;
+        let bottom = values.next().filter(|&v| v > 0).unwrap_or(self.rows) - 1;
+        if top < bottom && bottom < self.rows {
+            self.region = ScrollRegion { top, bottom };
+        }
+    }
+}
+
+fn full_screen(rows: usize) -> ScrollRegion {
+    ScrollRegion {
+        top: 0,
+        bottom: rows.saturating_sub(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(top: usize, bottom: usize) -> ScrollRegion {
+        ScrollRegion { top, bottom }
+    }
+
+    #[test]
+    fn tracks_margins_and_reset() {
+        let mut tracker = ScrollRegionTracker::new(24);
+        assert_eq!(tracker.region(), region(0, 23));
+        tracker.feed(b"\x1b[2;20r");
+        assert_eq!(tracker.region(), region(1, 19));
+        // No parameters: full screen.
+        tracker.feed(b"\x1b[r");
+        assert_eq!(tracker.region(), region(0, 23));
+        tracker.feed(b"\x1b[5r");
+        assert_eq!(tracker.region(), region(4, 23));
+    }
+
+    #[test]
+    fn ignores_invalid_regions_and_other_sequences() {
+        let mut tracker = ScrollRegionTracker::new(24);
+        tracker.feed(b"\x1b[3;10r");
+        for seq in [&b"\x1b[10;3r"[..], b"\x1b[1;25r", b"\x1b[?1;2r", b"\x1b[1;2$r", b"\x1b[5;6m"] {
+            tracker.feed(seq);
+            assert_eq!(tracker.region(), region(2, 9));
+        }
+    }
+
+    #[test]
+    fn soft_and_hard_reset_restore_full_screen() {
+        let mut tracker = ScrollRegionTracker::new(24);
+        tracker.feed(b"\x1b[3;10r\x1b[!p");
+        assert_eq!(tracker.region(), region(0, 23));
+        tracker.feed(b"\x1b[3;10r\x1bc");
+        assert_eq!(tracker.region(), region(0, 23));
+    }
+
+    #[test]
+    fn sequence_split_across_chunks() {
+        let mut tracker = ScrollRegionTracker::new(24);
+        tracker.feed(b"text\x1b[4;");
+        assert_eq!(tracker.region(), region(0, 23));
+        tracker.feed(b"12r");
+        assert_eq!(tracker.region(), region(3, 11));
+    }
+
+    #[test]
+    fn resize_resets_only_when_rows_change() {
+        let mut tracker = ScrollRegionTracker::new(24);
+        tracker.feed(b"\x1b[3;10r");
+        tracker.resize(24);
+        assert_eq!(tracker.region(), region(2, 9));
+        tracker.resize(40);
+        assert_eq!(tracker.region(), region(0, 39));
+    }
+}
@@ -8,12 +8,91 @@ pub enum Format {
     Styled,
 }
 
+impl Format {
+    /// Every line format.
+    pub const ALL: &'static [Format] = &[Format::Plain, Format::Styled];
+}
+
+/// Cap on the text returned by [`Query::FullText`]. Beyond it the oldest
+/// lines are left out; page through `Scrollback` to read them.
+pub const MAX_FULL_TEXT_BYTES: usize = 8 * 1024 * 1024;
+
+/// What [`Query::Clear`] erases.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClearScope {
+    /// The visible screen; the cursor moves to the top left.
+    Screen,
+    /// Lines above the screen. The screen is left as is.
+    #[default]
+    Scrollback,
+    /// Both.
+    All,
+}
+
 #[derive(Debug, Clone)]
 pub enum Query {
     Screen { format: Format },
-    Scrollback { format: Format, offset: usize, limit: usize },
+    /// `offset` counts from the oldest line, or back from the newest when
+    /// `from_end` is set.
+    Scrollback { format: Format, offset: usize, limit: usize, from_end: bool },
+    /// One line by absolute index, numbered like `Scrollback` offsets
+    /// (scrollback then screen). Out of range is `InvalidQuery`.
+    Line { index: usize, format: Format },
+    /// Every line (scrollback then screen, numbered like `Scrollback`)
+    /// joined with `\n`; `Styled` lines are rendered as ANSI escapes.
+    /// Only the newest lines that fit in [`MAX_FULL_TEXT_BYTES`] are kept.
+    FullText { format: Format },
     Cursor,
-    Resize { cols: usize, rows: usize },
+    /// The screen row the cursor is on, with the cursor position.
+    CursorLine { format: Format },
+    /// `reflow` re-wraps soft-wrapped lines to the new width; otherwise
+    /// each row keeps its content, cut off or padded at the new width.
+    Resize { cols: usize, rows: usize, reflow: bool },
+    /// `Resize` followed by `Screen` in the same round trip, so no output
+    /// is parsed between the two.
+    ResizeAndScreen { cols: usize, rows: usize, reflow: bool, format: Format },
+    /// Cheap fingerprint of the visible screen for change detection.
+    ScreenHash,
+    /// `Screen` together with its `ScreenHash`, so both describe the same
+    /// state.
+    ScreenWithHash { format: Format },
+    /// Remember a hash of each visible row; the returned `snapshot_id` can
+    /// later be passed to `DiffSince`. Only the most recent snapshots are
+    /// kept.
+    Snapshot,
+    /// Visible rows whose content differs from snapshot `snapshot_id`.
+    /// Unknown or evicted ids are `InvalidQuery`.
+    DiffSince { snapshot_id: u64, format: Format },
+    /// Terminal input modes set by the running program (e.g. DECCKM).
+    InputModes,
+    /// Scroll margins set by the running program (DECSTBM).
+    ScrollRegion,
+    /// Default foreground and background colors set by the running program
+    /// (OSC 10/OSC 11).
+    DefaultColors,
+    /// Commands delimited by OSC 133 shell-integration marks.
+    Commands,
+    /// Output of the most recent command with known boundaries.
+    LastCommandOutput,
+    /// Answered (with `Ok`) once every raw chunk queued before it has been
+    /// parsed, so a following query sees all previously sent output.
+    Barrier,
+    /// Everything the parser knows in one response; scrollback is capped at
+    /// the `scrollback_limit` most recent lines.
+    Dump { format: Format, scrollback_limit: usize },
+    /// Erase the screen and/or scrollback, then announce it with `Reset`
+    /// and `Sync` events. Clearing scrollback is rejected while the
+    /// alternate screen is active.
+    Clear { scope: ClearScope },
+    /// Parse `bytes` as if the program had written them, ahead of any
+    /// output not yet read. With `quiet`, no incremental events are sent
+    /// while they're parsed; a single `Sync` follows instead.
+    Restore { bytes: bytes::Bytes, quiet: bool },
+    /// Drop the oldest scrollback, keeping the `keep` most recent lines,
+    /// then announce it with `Reset` and `Sync` events. Rejected while the
+    /// alternate screen is active.
+    TrimScrollback { keep: usize },
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -21,11 +100,28 @@ pub enum Query {
 pub enum QueryResponse {
     Screen(ScreenResponse),
     Scrollback(ScrollbackResponse),
+    Line(LineResponse),
+    FullText(FullTextResponse),
     Cursor(CursorResponse),
+    CursorLine(CursorLineResponse),
+    ScreenHash(ScreenHashResponse),
+    ScreenWithHash(ScreenWithHashResponse),
+    Snapshot(SnapshotResponse),
+    ScreenDiff(ScreenDiffResponse),
+    InputModes(InputModesResponse),
+    ScrollRegion(ScrollRegionResponse),
+    DefaultColors(DefaultColorsResponse),
+    Commands(CommandsResponse),
+    LastCommandOutput(Option<LastCommandOutput>),
+    Dump(DumpResponse),
     Ok,
+    /// The query was rejected; [`super::Parser::query`] turns this into
+    /// `ParserError::InvalidQuery`.
+    #[serde(skip)]
+    Invalid(String),
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
 pub struct ScreenResponse {
     pub epoch: u64,
     pub first_line_index: usize,
@@ -37,7 +133,7 @@ pub struct ScreenResponse {
     pub alternate_active: bool,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScrollbackResponse {
     pub epoch: u64,
     pub lines: Vec<FormattedLine>,
@@ -45,34 +141,200 @@ pub struct ScrollbackResponse {
     pub offset: usize,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct LineResponse {
+    pub epoch: u64,
+    pub index: usize,
+    pub total_lines: usize,
+    pub line: FormattedLine,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FullTextResponse {
+    pub epoch: u64,
+    pub text: String,
+    pub total_lines: usize,
+    /// Index of the first screen line, as in [`ScreenResponse`].
+    pub first_line_index: usize,
+    /// Index of the first line in `text`; non-zero when `truncated`.
+    pub offset: usize,
+    pub truncated: bool,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct CursorResponse {
     pub epoch: u64,
     pub cursor: Cursor,
 }
 
+/// The line under the cursor, e.g. a shell prompt and what's been typed.
+///
+/// `line` is trimmed like any other formatted line, so `cursor.col` can lie
+/// past its end when the cursor sits after trailing blanks or on an empty
+/// row. `index` is the line's absolute index, as for `Query::Line`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CursorLineResponse {
+    pub epoch: u64,
+    pub index: usize,
+    pub cursor: Cursor,
+    pub line: FormattedLine,
+}
+
+/// Fingerprint of the visible screen (lines, styles, cursor, size, mode).
+///
+/// The hash is opaque: it only guarantees that two equal hashes taken from
+/// the same server process describe the same screen contents.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScreenHashResponse {
+    pub epoch: u64,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScreenWithHashResponse {
+    #[serde(flatten)]
+    pub screen: ScreenResponse,
+    /// As in [`ScreenHashResponse`].
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotResponse {
+    pub epoch: u64,
+    pub snapshot_id: u64,
+}
+
+/// Rows changed since a snapshot. Rows past the snapshot's height (after
+/// the screen grew) always count as changed; rows the screen lost are not
+/// listed, compare `rows` instead.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScreenDiffResponse {
+    pub epoch: u64,
+    pub snapshot_id: u64,
+    pub rows: usize,
+    pub changed: Vec<ChangedLine>,
+}
+
 #[derive(Debug, Clone, Serialize)]
+pub struct ChangedLine {
+    pub row: usize,
+    pub line: FormattedLine,
+}
+
+/// Input modes that change how keys must be encoded for the program.
+#[derive(Debug, Clone, Serialize)]
+pub struct InputModesResponse {
+    pub epoch: u64,
+    /// Cursor-key application mode (DECCKM): arrows and Home/End are sent
+    /// as `ESC O x` rather than `ESC [ x`.
+    pub cursor_key_app_mode: bool,
+}
+
+/// Active scroll region; rows are 0-based and inclusive.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScrollRegionResponse {
+    pub epoch: u64,
+    pub top: usize,
+    pub bottom: usize,
+}
+
+/// Default colors set by the program; `None` means the terminal's own
+/// default.
+#[derive(Debug, Clone, Serialize)]
+pub struct DefaultColorsResponse {
+    pub epoch: u64,
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+}
+
+/// Commands seen by the parser, oldest first. Empty when the shell doesn't
+/// emit OSC 133 marks.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandsResponse {
+    pub epoch: u64,
+    pub commands: Vec<CommandInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandInfo {
+    /// The command line as echoed between the `B` and `C` marks.
+    pub command_text: Option<String>,
+    /// Exit code from the `D` mark; `None` while running or if unreported.
+    pub exit_code: Option<i32>,
+    /// Scrollback line where the command's output starts.
+    pub start_line: usize,
+    /// Scrollback line where the command finished; `None` while running.
+    pub end_line: Option<usize>,
+}
+
+/// Text a command printed, between its `C` and `D` marks.
+#[derive(Debug, Clone, Serialize)]
+pub struct LastCommandOutput {
+    pub epoch: u64,
+    pub command_text: Option<String>,
+    /// Exit code from the `D` mark; `None` while running or if unreported.
+    pub exit_code: Option<i32>,
+    /// No `D` mark yet; `output` is everything printed so far.
+    pub running: bool,
+    /// Output lines joined with `\n`, trailing blanks trimmed.
+    pub output: String,
+    /// The start of the output was trimmed from scrollback; `output` begins
+    /// at the oldest line still retained.
+    pub truncated: bool,
+}
+
+/// Complete terminal state: screen, scrollback, modes and metadata.
+#[derive(Debug, Clone, Serialize)]
+pub struct DumpResponse {
+    pub epoch: u64,
+    pub screen: ScreenResponse,
+    /// Lines above the screen, oldest first. `offset` is the index of the
+    /// first returned line; earlier lines were left out to respect the limit.
+    pub scrollback: ScrollbackResponse,
+    pub cursor_key_app_mode: bool,
+    pub scroll_region: super::scroll_region::ScrollRegion,
+    /// Window title from the last `OSC 0`/`OSC 2`.
+    pub title: Option<String>,
+    /// Working directory from the last `OSC 7`.
+    pub cwd: Option<String>,
+    /// Default colors from `OSC 10`/`OSC 11`.
+    pub default_fg: Option<Color>,
+    pub default_bg: Option<Color>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
 pub struct Cursor {
     pub row: usize,
     pub col: usize,
     pub visible: bool,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
 #[serde(untagged)]
 pub enum FormattedLine {
     Plain(String),
     Styled(Vec<Span>),
 }
 
-#[derive(Debug, Clone, Serialize)]
+impl FormattedLine {
+    /// The line's characters without styling.
+    pub fn text(&self) -> String {
+        match self {
+            FormattedLine::Plain(text) => text.clone(),
+            FormattedLine::Styled(spans) => spans.iter().map(|s| s.text.as_str()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
 pub struct Span {
     pub text: String,
     #[serde(flatten)]
     pub style: Style,
 }
 
-#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Hash, schemars::JsonSchema)]
+#[serde(default)]
 pub struct Style {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fg: Option<Color>,
@@ -109,7 +371,7 @@ impl Style {
     }
 }
 
-#[derive(Debug, Clone, Serialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, schemars::JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum Color {
     Indexed(u8),
@@ -1,64 +1,244 @@
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use bytes::Bytes;
 use tokio::sync::{broadcast, mpsc, oneshot};
 
-use super::events::{Event, ResetReason};
-use super::format::format_line;
+use super::commands::{CommandTracker, Position, PromptMark, PromptMarkDetector};
+use super::decode::{Decoder, Decoding};
+use super::device_query::{DeviceQuery, DeviceQueryDetector};
+use super::escape::{EscapeScanner, Sequence};
+use super::events::{Event, ResetReason, ShutdownReason};
+use super::RawChunk;
+use super::journal::EventSender;
+use super::metadata::MetadataTracker;
+use super::scroll_region::ScrollRegionTracker;
+use super::ansi::line_to_ansi;
+use super::format::{format_line, hash_line};
 use super::state::{
-    Cursor, CursorResponse, Format, Query, QueryResponse, ScreenResponse, ScrollbackResponse,
+    ChangedLine, ClearScope, CommandInfo, CommandsResponse, Cursor, CursorLineResponse, CursorResponse, DefaultColorsResponse, DumpResponse,
+    Format, FormattedLine, FullTextResponse, InputModesResponse, LastCommandOutput, LineResponse, Query, QueryResponse,
+    ScreenDiffResponse, ScreenHashResponse, ScreenResponse, ScreenWithHashResponse, ScrollRegionResponse, ScrollbackResponse,
+    SnapshotResponse, MAX_FULL_TEXT_BYTES,
 };
 
+/// Consecutive chunks avt may panic on before the parser gives up on its
+/// state and lets [`super::Parser::spawn`] restart it from scratch.
+const MAX_CONSECUTIVE_FEED_PANICS: u32 = 3;
+
+/// Screen snapshots kept for `Query::DiffSince`; older ones are evicted.
+const MAX_SCREEN_SNAPSHOTS: usize = 16;
+
+/// Makes [`feed_vt`] panic as avt would on malformed input; tests only.
+#[cfg(test)]
+pub(super) const FEED_PANIC_TRIGGER: &str = "\x1b]wsh-test-feed-panic\x07";
+
+/// Feed `text` to avt, returning the changed view lines and the number of
+/// lines trimmed from scrollback, or `None` if avt panicked. The panic is
+/// contained so one bad chunk doesn't cost the whole terminal state.
+fn feed_vt(vt: &mut avt::Vt, text: &str) -> Option<(Vec<usize>, u64)> {
+    std::panic::catch_unwind(AssertUnwindSafe(|| {
+        #[cfg(test)]
+        if text.contains(FEED_PANIC_TRIGGER) {
+            panic!("test-triggered avt panic");
+        }
+        let changes = vt.feed_str(text);
+        (changes.lines, changes.scrollback.count() as u64)
+    }))
+    .ok()
+}
+
+/// Number of lines `vt` holds, scrollback included, without walking them:
+/// avt keeps them in a `VecDeque`, whose iterator knows its length. This
+/// runs on every chunk, so it must not be O(scrollback).
+fn line_count(vt: &avt::Vt) -> usize {
+    match vt.lines().size_hint() {
+        (lower, Some(upper)) if lower == upper => lower,
+        _ => vt.lines().count(),
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn run(
     raw_rx: &mut mpsc::Receiver<Bytes>,
-    query_rx: &mut mpsc::Receiver<(Query, oneshot::Sender<QueryResponse>)>,
-    event_tx: broadcast::Sender<Event>,
+    query_rx: &mut mpsc::Receiver<(Query, tokio::time::Instant, oneshot::Sender<QueryResponse>)>,
+    event_tx: EventSender,
     cols: usize,
     rows: usize,
     scrollback_limit: usize,
+    decoding: Decoding,
+    raw_tap: &broadcast::Sender<RawChunk>,
     shared_cols: &Arc<AtomicUsize>,
     shared_rows: &Arc<AtomicUsize>,
+    max_line_cells: &Arc<AtomicUsize>,
+    redraw_threshold: &Arc<AtomicUsize>,
+    end_reason: &Arc<parking_lot::Mutex<Option<ShutdownReason>>>,
+    history_lines: &Arc<AtomicUsize>,
+    shared_alternate: &Arc<AtomicBool>,
 ) {
+    // A zero-sized terminal makes avt panic or misbehave, which would cost
+    // the whole parser state in a restart. Callers validate sizes; this is
+    // the backstop.
+    let cols = cols.max(1);
+    let rows = rows.max(1);
     let mut vt = avt::Vt::builder()
         .size(cols, rows)
         .scrollback_limit(scrollback_limit)
         .build();
+    // avt starts with every row marked changed. Followers start from a
+    // sync, so don't let the first output look like a full redraw.
+    let _ = vt.feed_str("");
 
     let mut seq: u64 = 0;
     let epoch: u64 = 0;
     let mut last_cursor = vt.cursor();
     let mut alternate_active = false;
+    shared_alternate.store(false, Ordering::Relaxed);
+    let mut decoder = Decoder::new(decoding);
     let mut alt_detect = AlternateScreenDetector::new();
+    let mut query_detect = DeviceQueryDetector::new();
+    let mut prompt_detect = PromptMarkDetector::new();
+    let mut commands = CommandTracker::new();
+    let mut metadata = MetadataTracker::new();
+    let mut scroll_region = ScrollRegionTracker::new(rows);
+    // Lines trimmed from the top of scrollback so far. Added to buffer
+    // indices to get absolute line numbers for command tracking.
+    let mut dropped_lines: u64 = 0;
+    let mut raw_offset: u64 = 0;
+    // Barriers wait until the chunk count reaches the value it will have once
+    // every chunk queued at the time of the barrier is processed.
+    let mut chunks_processed: u64 = 0;
+    let mut barriers: VecDeque<(u64, oneshot::Sender<QueryResponse>)> = VecDeque::new();
+    let mut feed_panics: u32 = 0;
+    // Restored content waiting to be parsed, with whether to keep it quiet.
+    let mut restores: VecDeque<(Bytes, bool, oneshot::Sender<QueryResponse>)> = VecDeque::new();
+    let mut snapshots = ScreenSnapshots::new();
+    // Stands in for `event_tx` while quiet restore content is parsed.
+    let muted = EventSender::new(broadcast::channel(1).0, 0);
+    // Last working directory announced, and when a newer one is due.
+    let mut announced_cwd: Option<String> = None;
+    let mut cwd_due: Option<tokio::time::Instant> = None;
 
     loop {
         tokio::select! {
-            result = raw_rx.recv() => {
+            result = async {
+                match restores.pop_front() {
+                    Some((bytes, quiet, response_tx)) => Some((bytes, Some((quiet, response_tx)))),
+                    None => raw_rx.recv().await.map(|bytes| (bytes, None)),
+                }
+            } => {
                 match result {
-                    Some(bytes) => {
-                        let text = String::from_utf8_lossy(&bytes);
+                    Some((bytes, restore)) => {
+                        let seq_before = seq;
+                        let events = match restore {
+                            Some((true, _)) => &muted,
+                            _ => &event_tx,
+                        };
+                        let text = decoder.decode(&bytes);
+                        // Rows ever pushed into scrollback, counting those
+                        // since trimmed. The alternate screen has no history.
+                        let history_before = (!alternate_active).then(|| line_count(&vt) as u64 + dropped_lines);
 
                         // Detect alternate screen transitions before feeding to avt
                         let new_alternate = alt_detect.feed(&text, alternate_active);
 
-                        let changes = vt.feed_str(&text);
-
-                        // Extract changed line indices before dropping the Changes struct
-                        // (Changes contains a reference to vt via its scrollback iterator)
-                        let changed_lines: Vec<usize> = changes.lines.clone();
-                        drop(changes);
+                        // Feed up to the end of each device query or prompt
+                        // mark so it's handled against the cursor position at
+                        // the moment it was written (a CPR reply must report
+                        // that position; command boundaries start there).
+                        //
+                        // A panic inside avt skips the rest of that segment.
+                        let mut marks: Vec<(usize, Mark)> = query_detect
+                            .feed(text.as_bytes())
+                            .into_iter()
+                            .map(|(end, query)| (end, Mark::Query(query)))
+                            .collect();
+                        metadata.feed(text.as_bytes());
+                        let resize_request = metadata.take_resize_request();
+                        let notifications = metadata.take_notifications();
+                        let default_colors_changed = metadata.take_default_colors_changed();
+                        if cwd_due.is_none() && metadata.cwd() != announced_cwd.as_deref() {
+                            cwd_due = Some(tokio::time::Instant::now() + super::CWD_DEBOUNCE);
+                        }
+                        let region_before = scroll_region.region();
+                        scroll_region.feed(text.as_bytes());
+                        let prompt_marks = prompt_detect.feed(text.as_bytes());
+                        if !prompt_marks.is_empty() {
+                            marks.extend(prompt_marks.into_iter().map(|(end, mark)| (end, Mark::Prompt(mark))));
+                            marks.sort_by_key(|(end, _)| *end);
+                        }
+                        let mut device_queries = Vec::new();
+                        let mut changed_lines: Vec<usize> = Vec::new();
+                        let mut panicked = false;
+                        let mut fed = 0;
+                        for (end, mark) in marks {
+                            if end > fed {
+                                match feed_vt(&mut vt, &text[fed..end]) {
+                                    Some((lines, dropped)) => {
+                                        changed_lines.extend(lines);
+                                        dropped_lines += dropped;
+                                    }
+                                    None => panicked = true,
+                                }
+                                fed = end;
+                            }
+                            match mark {
+                                Mark::Query(query) => {
+                                    let cursor = vt.cursor();
+                                    device_queries.push((query, cursor.row, cursor.col));
+                                }
+                                // The alternate screen isn't part of the shell's
+                                // history, so marks there are ignored.
+                                Mark::Prompt(_) if new_alternate => {}
+                                Mark::Prompt(mark) => {
+                                    track_prompt_mark(&vt, &mut commands, dropped_lines, mark);
+                                }
+                            }
+                        }
+                        if fed < text.len() {
+                            match feed_vt(&mut vt, &text[fed..]) {
+                                Some((lines, dropped)) => {
+                                    changed_lines.extend(lines);
+                                    dropped_lines += dropped;
+                                }
+                                None => panicked = true,
+                            }
+                        }
+                        if panicked {
+                            feed_panics += 1;
+                            tracing::warn!(
+                                len = bytes.len(),
+                                consecutive = feed_panics,
+                                "terminal emulator panicked on output chunk; skipping it"
+                            );
+                            // Repeated panics suggest the VT state itself is
+                            // broken: escalate to a full restart.
+                            if feed_panics >= MAX_CONSECUTIVE_FEED_PANICS {
+                                panic!("terminal emulator panicked on {} consecutive chunks", feed_panics);
+                            }
+                        } else {
+                            feed_panics = 0;
+                        }
+                        if fed > 0 {
+                            changed_lines.sort_unstable();
+                            changed_lines.dedup();
+                        }
 
                         // Emit mode/reset events if alternate screen state changed
                         if new_alternate != alternate_active {
                             alternate_active = new_alternate;
+                            shared_alternate.store(alternate_active, Ordering::Relaxed);
                             seq = seq.wrapping_add(1);
-                            let _ = event_tx.send(Event::Mode {
+                            let _ = events.send(Event::Mode {
                                 seq,
                                 alternate_active,
                             });
                             seq = seq.wrapping_add(1);
-                            let _ = event_tx.send(Event::Reset {
+                            let _ = events.send(Event::Reset {
                                 seq,
                                 reason: if alternate_active {
                                     ResetReason::AlternateScreenEnter
@@ -68,22 +248,64 @@ pub async fn run(
                             });
                         }
 
+                        let region = scroll_region.region();
+                        if region != region_before {
+                            seq = seq.wrapping_add(1);
+                            let _ = events.send(Event::ScrollRegion {
+                                seq,
+                                top: region.top,
+                                bottom: region.bottom,
+                            });
+                        }
+
+                        // Rows that scrolled off the top go first, so followers
+                        // move them into their transcript before the line
+                        // events below overwrite the view.
+                        let mut scrolled = 0;
+                        if let Some(before) = history_before.filter(|_| !alternate_active) {
+                            let history_after = line_count(&vt) as u64 + dropped_lines;
+                            scrolled = history_after.saturating_sub(before);
+                            if scrolled > 0 {
+                                seq = seq.wrapping_add(1);
+                                let _ = events.send(Event::Scroll {
+                                    seq,
+                                    count: scrolled as usize,
+                                });
+                            }
+                        }
+
+                        // A full-screen app repainting sends one `Diff` rather
+                        // than a `Line` event per row. Output that scrolls is
+                        // a stream of lines, not a repaint, so it's left alone.
+                        let threshold = redraw_threshold.load(Ordering::Relaxed);
+                        let redraw = threshold > 0
+                            && scrolled == 0
+                            && changed_lines.len() * 100 >= threshold * vt.size().1;
+
                         // Emit line events for changed lines.
                         // changes.lines contains view-relative indices (screen row 0..rows-1)
                         // Use vt.view() to get the correct visible line content.
                         // Only collect when there are actual changes to avoid
                         // O(n) iteration on every PTY chunk.
-                        if !changed_lines.is_empty() {
-                            let total_lines = vt.lines().count();
+                        if redraw {
+                            let screen = screen_response(&vt, epoch, alternate_active, Format::Styled, max_line_cells.load(Ordering::Relaxed));
+                            seq = seq.wrapping_add(1);
+                            let _ = events.send(Event::Diff {
+                                seq,
+                                changed_lines,
+                                screen,
+                            });
+                        } else if !changed_lines.is_empty() {
+                            let (_, total_lines) = line_bounds(&vt, alternate_active);
                             let view_lines: Vec<_> = vt.view().collect();
                             for line_idx in changed_lines {
                                 if let Some(line) = view_lines.get(line_idx) {
                                     seq = seq.wrapping_add(1);
-                                    let _ = event_tx.send(Event::Line {
+                                    let _ = events.send(Event::Line {
                                         seq,
                                         index: line_idx,
                                         total_lines,
-                                        line: format_line(*line, true),
+                                        line: format_line(line, true, max_line_cells.load(Ordering::Relaxed)),
                                     });
                                 }
                             }
@@ -96,7 +318,7 @@ pub async fn run(
                             || cursor.visible != last_cursor.visible
                         {
                             seq = seq.wrapping_add(1);
-                            let _ = event_tx.send(Event::Cursor {
+                            let _ = events.send(Event::Cursor {
                                 seq,
                                 row: cursor.row,
                                 col: cursor.col,
@@ -104,73 +326,471 @@ pub async fn run(
                             });
                             last_cursor = cursor;
                         }
+
+                        for (query, row, col) in device_queries {
+                            seq = seq.wrapping_add(1);
+                            let _ = events.send(Event::DeviceQuery {
+                                seq,
+                                kind: query.kind,
+                                setting: query.setting,
+                                row,
+                                col,
+                            });
+                        }
+
+                        if let Some((rows, cols)) = resize_request {
+                            let (current_cols, current_rows) = vt.size();
+                            seq = seq.wrapping_add(1);
+                            let _ = events.send(Event::ResizeRequest {
+                                seq,
+                                rows: rows.unwrap_or(current_rows),
+                                cols: cols.unwrap_or(current_cols),
+                            });
+                        }
+
+                        if default_colors_changed {
+                            seq = seq.wrapping_add(1);
+                            let _ = events.send(Event::DefaultColors {
+                                seq,
+                                fg: metadata.default_fg().cloned(),
+                                bg: metadata.default_bg().cloned(),
+                            });
+                        }
+
+                        for notification in notifications {
+                            seq = seq.wrapping_add(1);
+                            let _ = events.send(Event::Notification {
+                                seq,
+                                title: notification.title,
+                                body: notification.body,
+                            });
+                        }
+
+                        // Lines touched by the skipped part aren't known;
+                        // hand followers the whole screen instead.
+                        if panicked {
+                            let screen = screen_response(&vt, epoch, alternate_active, Format::Styled, max_line_cells.load(Ordering::Relaxed));
+                            seq = seq.wrapping_add(1);
+                            let _ = events.send(Event::Sync {
+                                seq,
+                                scrollback_lines: screen.total_lines,
+                                screen,
+                            });
+                        }
+
+                        // Restored content isn't program output: it has no
+                        // raw offset and doesn't count toward barriers.
+                        if let Some((quiet, response_tx)) = restore {
+                            if quiet {
+                                // Nothing was published, so reuse the seqs.
+                                let screen = screen_response(&vt, epoch, alternate_active, Format::Styled, max_line_cells.load(Ordering::Relaxed));
+                                seq = seq_before.wrapping_add(1);
+                                let _ = event_tx.send(Event::Sync {
+                                    seq,
+                                    scrollback_lines: screen.total_lines,
+                                    screen,
+                                });
+                            }
+                            let _ = response_tx.send(QueryResponse::Ok);
+                            continue;
+                        }
+
+                        let len = bytes.len() as u64;
+                        if raw_tap.receiver_count() > 0 {
+                            let _ = raw_tap.send(RawChunk {
+                                offset: raw_offset,
+                                bytes,
+                                seq_before,
+                                seq_after: seq,
+                            });
+                        }
+                        raw_offset += len;
+
+                        chunks_processed += 1;
+                        publish_history_lines(&vt, alternate_active, history_lines);
+                        while barriers.front().is_some_and(|(target, _)| *target <= chunks_processed) {
+                            let (_, response_tx) = barriers.pop_front().unwrap();
+                            let _ = response_tx.send(QueryResponse::Ok);
+                        }
+                    }
+                    None => {
+                        // Nothing more will arrive; everything queued is parsed.
+                        for (_, response_tx) in barriers.drain(..) {
+                            let _ = response_tx.send(QueryResponse::Ok);
+                        }
+                        if cwd_due.is_some() {
+                            announce_cwd(&metadata, &mut announced_cwd, &mut seq, &event_tx);
+                        }
+                        // Say so explicitly rather than letting subscribers'
+                        // streams just go quiet.
+                        seq = seq.wrapping_add(1);
+                        let reason = end_reason.lock().clone();
+                        let _ = event_tx.send(Event::End { seq, reason });
+                        break;
                     }
-                    None => break,
                 }
             }
 
-            Some((query, response_tx)) = query_rx.recv() => {
-                let response = handle_query(&mut vt, query, epoch, alternate_active, &mut seq, &event_tx, shared_cols, shared_rows);
+            _ = tokio::time::sleep_until(cwd_due.unwrap_or_else(tokio::time::Instant::now)), if cwd_due.is_some() => {
+                cwd_due = None;
+                announce_cwd(&metadata, &mut announced_cwd, &mut seq, &event_tx);
+            }
+
+            Some((query, deadline, response_tx)) = query_rx.recv() => {
+                // The caller gave up (timed out, cancelled, or dropped the
+                // future), or is about to. Read-only queries can be
+                // skipped; a resize, clear, or restore still has to be
+                // applied.
+                let abandoned = response_tx.is_closed() || tokio::time::Instant::now() >= deadline;
+                if abandoned && !matches!(query, Query::Resize { .. } | Query::ResizeAndScreen { .. } | Query::Clear { .. } | Query::Restore { .. } | Query::TrimScrollback { .. }) {
+                    continue;
+                }
+                if let Query::Restore { bytes, quiet } = query {
+                    restores.push_back((bytes, quiet, response_tx));
+                    continue;
+                }
+                if matches!(query, Query::Barrier) {
+                    if raw_rx.is_empty() {
+                        let _ = response_tx.send(QueryResponse::Ok);
+                    } else {
+                        barriers.push_back((chunks_processed + raw_rx.len() as u64, response_tx));
+                    }
+                    continue;
+                }
+                let response = handle_query(&mut vt, query, epoch, alternate_active, &commands, &metadata, &mut scroll_region, &mut snapshots, &mut dropped_lines, scrollback_limit, &mut seq, &event_tx, shared_cols, shared_rows, max_line_cells.load(Ordering::Relaxed));
+                // Queries that move the cursor announce it in a `Sync`, so
+                // later cursor events compare against the new position.
+                last_cursor = vt.cursor();
+                publish_history_lines(&vt, alternate_active, history_lines);
                 let _ = response_tx.send(response);
             }
         }
     }
 }
 
+/// Record how many lines of primary scrollback `vt` holds. The count is left
+/// alone while the alternate screen is up, since the history is still held.
+fn publish_history_lines(vt: &avt::Vt, alternate_active: bool, history_lines: &AtomicUsize) {
+    if !alternate_active {
+        let (_, rows) = vt.size();
+        history_lines.store(line_count(vt).saturating_sub(rows), Ordering::Relaxed);
+    }
+}
+
+/// Send a `Cwd` event if the tracked directory differs from the last one
+/// announced. A `cd` that ends up back where it started sends nothing.
+fn announce_cwd(metadata: &MetadataTracker, announced: &mut Option<String>, seq: &mut u64, event_tx: &EventSender) {
+    let Some(path) = metadata.cwd() else {
+        return;
+    };
+    if announced.as_deref() == Some(path) {
+        return;
+    }
+    *announced = Some(path.to_string());
+    *seq = seq.wrapping_add(1);
+    let _ = event_tx.send(Event::Cwd {
+        seq: *seq,
+        path: path.to_string(),
+    });
+}
+
+/// Build a `cols` x `rows` terminal holding `vt`'s lines one per row, cut at
+/// `cols` when narrower, instead of re-wrapping them like [`avt::Vt::resize`].
+///
+/// Blank rows below the cursor are dropped rather than pushed into
+/// scrollback. Everything else avt knows about carries over from a full
+/// [`avt::Vt::dump`]: modes (origin, auto-wrap, insert, new line, cursor
+/// keys), the pen, tab stops, charsets, saved cursors, cursor visibility,
+/// and a half-parsed escape sequence, so a sequence split across the resize
+/// still completes. As with [`avt::Vt::resize`], the margins are kept when
+/// the row count is unchanged (`top_margin` is their top) and reset
+/// otherwise.
+fn resize_without_reflow(vt: &avt::Vt, cols: usize, rows: usize, scrollback_limit: usize, top_margin: usize) -> avt::Vt {
+    let (old_cols, old_rows) = vt.size();
+    let cursor = vt.cursor();
+    let lines: Vec<&avt::Line> = vt.lines().collect();
+    let cursor_line = lines.len() - old_rows + cursor.row;
+    let last_content = lines
+        .iter()
+        .rposition(|line| !line.text().trim_end().is_empty())
+        .unwrap_or(0);
+    let keep = cursor_line.max(last_content) + 1;
+
+    let mut replay = String::new();
+    for (i, line) in lines[..keep].iter().enumerate() {
+        if i > 0 {
+            replay.push_str("\r\n");
+        }
+        replay.push_str(&line_to_ansi(&format_line(line, true, cols)));
+    }
+
+    // Split the dump into terminal state and the parser's pending sequence
+    // by replaying it into a copy and cancelling (CAN) the sequence there.
+    let dump = vt.dump();
+    let mut copy = avt::Vt::builder().size(old_cols, old_rows).build();
+    let _ = copy.feed_str(&dump);
+    let _ = copy.feed_str("\x18");
+    let terminal = copy.dump();
+    let pending = dump.strip_prefix(terminal.as_str()).unwrap_or_default();
+    // A cursor waiting to wrap past the right edge is dumped by re-printing
+    // the last column's character, which would land on whatever row now
+    // sits there. Park it on the last column instead.
+    if cursor.col >= old_cols {
+        let _ = copy.feed_str(&format!("\x1b[{old_cols}G"));
+    }
+    let state = copy.dump();
+    // The screen's cells come first and use only `ESC [ 0 ... m` pens and
+    // `ESC [ n b` repeats; the state after them starts with the first C1 CSI
+    // or with the `ESC [ m` that always follows the primary screen.
+    let cells_end = [state.find('\u{9b}'), state.find("\x1b[m")]
+        .into_iter()
+        .flatten()
+        .min()
+        .unwrap_or(state.len());
+    replay.push_str(&state[cells_end..]);
+    let origin_mode = state.matches("\u{9b}?6h").count() > state.matches("\u{9b}?6l").count();
+
+    // The dumped cursor is in the old geometry; move it to the kept row,
+    // parking it on the top row if it scrolled off with the content above.
+    let top = if rows == old_rows {
+        top_margin
+    } else {
+        replay.push_str("\x1b[r");
+        0
+    };
+    let row = cursor_line
+        .saturating_sub(keep.saturating_sub(rows))
+        .min(rows.saturating_sub(1));
+    let row = if origin_mode { row.saturating_sub(top) } else { row };
+    let col = cursor.col.min(cols.saturating_sub(1));
+    replay.push_str(&format!("\x1b[{};{}H", row + 1, col + 1));
+    replay.push_str(pending);
+
+    let mut resized = avt::Vt::builder()
+        .size(cols, rows)
+        .scrollback_limit(scrollback_limit)
+        .build();
+    let _ = resized.feed_str(&replay);
+    resized
+}
+
+/// A sequence found in PTY output that must be handled at its exact position.
+enum Mark {
+    Query(DeviceQuery),
+    Prompt(PromptMark),
+}
+
+/// Absolute position of the cursor in the primary buffer.
+fn cursor_position(vt: &avt::Vt, dropped_lines: u64) -> Position {
+    let (_, rows) = vt.size();
+    let cursor = vt.cursor();
+    let first_line_index = line_count(vt).saturating_sub(rows);
+    Position {
+        line: dropped_lines + (first_line_index + cursor.row) as u64,
+        col: cursor.col,
+    }
+}
+
+fn track_prompt_mark(vt: &avt::Vt, commands: &mut CommandTracker, dropped_lines: u64, mark: PromptMark) {
+    let pos = cursor_position(vt, dropped_lines);
+    match mark {
+        PromptMark::PromptStart => commands.prompt_start(pos),
+        PromptMark::CommandStart => commands.command_start(pos),
+        PromptMark::CommandExecuted => {
+            let command_text = commands
+                .input_position()
+                .and_then(|input| text_between(vt, dropped_lines, input, pos))
+                .filter(|text| !text.is_empty());
+            commands.command_executed(pos, command_text);
+        }
+        PromptMark::CommandFinished { exit_code } => commands.command_finished(pos, exit_code),
+    }
+}
+
+/// Text between two absolute positions, one line per buffer line, with
+/// trailing blanks trimmed. `None` if `start` was trimmed from scrollback.
+fn text_between(vt: &avt::Vt, dropped_lines: u64, start: Position, end: Position) -> Option<String> {
+    let first = usize::try_from(start.line.checked_sub(dropped_lines)?).ok()?;
+    let last = usize::try_from(end.line.saturating_sub(dropped_lines)).ok()?;
+    if last < first {
+        return Some(String::new());
+    }
+    let mut lines: Vec<String> = vt
+        .lines()
+        .enumerate()
+        .skip(first)
+        .take(last - first + 1)
+        .map(|(i, line)| {
+            let cells = line.cells();
+            let from = if i == first { start.col.min(cells.len()) } else { 0 };
+            let to = if i == last { end.col.min(cells.len()) } else { cells.len() };
+            let text: String = cells[from..to.max(from)]
+                .iter()
+                .filter(|cell| cell.width() > 0 && cell.char() != '\0')
+                .map(|cell| cell.char())
+                .collect();
+            text.trim_end().to_string()
+        })
+        .collect();
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    Some(lines.join("\n"))
+}
+
+/// Per-row hashes of recent screens, for diffing without keeping the
+/// lines themselves.
+struct ScreenSnapshots {
+    next_id: u64,
+    entries: VecDeque<(u64, Vec<u64>)>,
+}
+
+impl ScreenSnapshots {
+    fn new() -> Self {
+        Self { next_id: 1, entries: VecDeque::new() }
+    }
+
+    fn row_hashes(vt: &avt::Vt) -> Vec<u64> {
+        vt.view()
+            .map(|line| {
+                let mut hasher = DefaultHasher::new();
+                hash_line(line, &mut hasher);
+                hasher.finish()
+            })
+            .collect()
+    }
+
+    fn take(&mut self, vt: &avt::Vt) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        if self.entries.len() >= MAX_SCREEN_SNAPSHOTS {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((id, Self::row_hashes(vt)));
+        id
+    }
+
+    /// Rows of the current screen that differ from snapshot `id`, or `None`
+    /// if it isn't held.
+    fn changed_rows(&self, id: u64, vt: &avt::Vt) -> Option<Vec<usize>> {
+        let (_, old) = self.entries.iter().find(|(sid, _)| *sid == id)?;
+        let current = Self::row_hashes(vt);
+        Some(
+            current
+                .iter()
+                .enumerate()
+                .filter(|(row, hash)| old.get(*row) != Some(hash))
+                .map(|(row, _)| row)
+                .collect(),
+        )
+    }
+}
+
+/// `(first_line_index, total_lines)` for the active buffer.
+///
+/// The primary buffer numbers scrollback first, so the visible screen starts
+/// at `total_lines - rows`. That holds before the screen fills too: avt
+/// keeps all `rows` view lines, blank or not, at the end of the buffer, so
+/// it never holds less than a screenful. The alternate screen has no
+/// history: its lines are numbered from 0 and `total_lines` is the screen
+/// height. Switching buffers emits a `Reset` event, which tells clients to
+/// discard indices from the other numbering.
+fn line_bounds(vt: &avt::Vt, alternate_active: bool) -> (usize, usize) {
+    let (_, rows) = vt.size();
+    if alternate_active {
+        return (0, rows);
+    }
+    let total_lines = line_count(vt);
+    (total_lines.saturating_sub(rows), total_lines)
+}
+
+fn screen_response(
+    vt: &avt::Vt,
+    epoch: u64,
+    alternate_active: bool,
+    format: Format,
+    max_line_cells: usize,
+) -> ScreenResponse {
+    let styled = matches!(format, Format::Styled);
+    let (cols, rows) = vt.size();
+    let cursor = vt.cursor();
+
+    let (first_line_index, total_lines) = line_bounds(vt, alternate_active);
+    let lines: Vec<_> = vt.view().map(|l| format_line(l, styled, max_line_cells)).collect();
+
+    ScreenResponse {
+        epoch,
+        first_line_index,
+        total_lines,
+        lines,
+        cursor: Cursor {
+            row: cursor.row,
+            col: cursor.col,
+            visible: cursor.visible,
+        },
+        cols,
+        rows,
+        alternate_active,
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 fn handle_query(
     vt: &mut avt::Vt,
     query: Query,
     epoch: u64,
     alternate_active: bool,
+    commands: &CommandTracker,
+    metadata: &MetadataTracker,
+    scroll_region: &mut ScrollRegionTracker,
+    snapshots: &mut ScreenSnapshots,
+    dropped_lines: &mut u64,
+    scrollback_limit: usize,
     seq: &mut u64,
-    event_tx: &broadcast::Sender<Event>,
+    event_tx: &EventSender,
     shared_cols: &Arc<AtomicUsize>,
     shared_rows: &Arc<AtomicUsize>,
+    max_line_cells: usize,
 ) -> QueryResponse {
     match query {
         Query::Screen { format } => {
-            let styled = matches!(format, Format::Styled);
-            let (cols, rows) = vt.size();
-            let cursor = vt.cursor();
-
-            let total_lines = vt.lines().count();
-            let first_line_index = total_lines.saturating_sub(rows);
-            let lines: Vec<_> = vt.view().map(|l| format_line(l, styled)).collect();
-
-            QueryResponse::Screen(ScreenResponse {
-                epoch,
-                first_line_index,
-                total_lines,
-                lines,
-                cursor: Cursor {
-                    row: cursor.row,
-                    col: cursor.col,
-                    visible: cursor.visible,
-                },
-                cols,
-                rows,
-                alternate_active,
-            })
+            QueryResponse::Screen(screen_response(vt, epoch, alternate_active, format, max_line_cells))
         }
 
         Query::Scrollback {
             format,
             offset,
             limit,
+            from_end,
         } => {
             let styled = matches!(format, Format::Styled);
 
             // Collect lines once to avoid iterating twice (once for count,
             // once for skip/take). With 10k+ scrollback lines, the double
             // iteration adds measurable latency for agents reading scrollback.
-            let all_lines: Vec<_> = vt.lines().collect();
+            //
+            // The alternate screen has no history: only its visible lines are
+            // returned, numbered from 0 (see `line_bounds`).
+            let all_lines: Vec<_> = if alternate_active {
+                vt.view().collect()
+            } else {
+                vt.lines().collect()
+            };
             let total_lines = all_lines.len();
+            // Counted from the end, the window is the `limit` lines before
+            // the last `offset` ones. The response reports where it starts.
+            let (offset, limit) = if from_end {
+                let end = total_lines.saturating_sub(offset);
+                let start = end.saturating_sub(limit);
+                (start, end - start)
+            } else {
+                (offset, limit)
+            };
             let lines: Vec<_> = all_lines
                 .into_iter()
                 .skip(offset)
                 .take(limit)
-                .map(|l| format_line(&l, styled))
+                .map(|l| format_line(l, styled, max_line_cells))
                 .collect();
 
             QueryResponse::Scrollback(ScrollbackResponse {
@@ -181,6 +801,58 @@ fn handle_query(
             })
         }
 
+        Query::Line { index, format } => {
+            let lines: Vec<_> = if alternate_active {
+                vt.view().collect()
+            } else {
+                vt.lines().collect()
+            };
+            let total_lines = lines.len();
+            match lines.get(index) {
+                Some(line) => QueryResponse::Line(LineResponse {
+                    epoch,
+                    index,
+                    total_lines,
+                    line: format_line(line, matches!(format, Format::Styled), max_line_cells),
+                }),
+                None => QueryResponse::Invalid(format!(
+                    "line {index} out of range (total_lines: {total_lines})"
+                )),
+            }
+        }
+
+        Query::FullText { format } => {
+            let styled = matches!(format, Format::Styled);
+            let all_lines: Vec<_> = if alternate_active {
+                vt.view().collect()
+            } else {
+                vt.lines().collect()
+            };
+            let (first_line_index, total_lines) = line_bounds(vt, alternate_active);
+            // Newest first, so hitting the cap drops the oldest lines.
+            let mut texts = Vec::new();
+            let mut bytes = 0;
+            for line in all_lines.iter().rev() {
+                let formatted = format_line(line, styled, max_line_cells);
+                let text = if styled { line_to_ansi(&formatted) } else { formatted.text() };
+                if !texts.is_empty() && bytes + text.len() + 1 > MAX_FULL_TEXT_BYTES {
+                    break;
+                }
+                bytes += text.len() + 1;
+                texts.push(text);
+            }
+            texts.reverse();
+            let offset = total_lines - texts.len();
+            QueryResponse::FullText(FullTextResponse {
+                epoch,
+                text: texts.join("\n"),
+                total_lines,
+                first_line_index,
+                offset,
+                truncated: offset > 0,
+            })
+        }
+
         Query::Cursor => {
             let cursor = vt.cursor();
             QueryResponse::Cursor(CursorResponse {
@@ -193,8 +865,267 @@ fn handle_query(
             })
         }
 
-        Query::Resize { cols, rows } => {
-            let _changes = vt.resize(cols, rows);
+        Query::CursorLine { format } => {
+            let cursor = vt.cursor();
+            let (first_line_index, _) = line_bounds(vt, alternate_active);
+            let line = vt
+                .view()
+                .nth(cursor.row)
+                .map(|line| format_line(line, matches!(format, Format::Styled), max_line_cells))
+                .unwrap_or_else(|| match format {
+                    Format::Plain => FormattedLine::Plain(String::new()),
+                    Format::Styled => FormattedLine::Styled(Vec::new()),
+                });
+            QueryResponse::CursorLine(CursorLineResponse {
+                epoch,
+                index: first_line_index + cursor.row,
+                cursor: Cursor {
+                    row: cursor.row,
+                    col: cursor.col,
+                    visible: cursor.visible,
+                },
+                line,
+            })
+        }
+
+        Query::ScreenHash => QueryResponse::ScreenHash(ScreenHashResponse {
+            epoch,
+            hash: screen_hash(vt, alternate_active),
+        }),
+
+        Query::ScreenWithHash { format } => QueryResponse::ScreenWithHash(ScreenWithHashResponse {
+            screen: screen_response(vt, epoch, alternate_active, format, max_line_cells),
+            hash: screen_hash(vt, alternate_active),
+        }),
+
+        Query::Snapshot => QueryResponse::Snapshot(SnapshotResponse {
+            epoch,
+            snapshot_id: snapshots.take(vt),
+        }),
+
+        Query::DiffSince { snapshot_id, format } => {
+            let Some(rows) = snapshots.changed_rows(snapshot_id, vt) else {
+                return QueryResponse::Invalid(format!("unknown snapshot {snapshot_id}"));
+            };
+            let styled = matches!(format, Format::Styled);
+            let view: Vec<_> = vt.view().collect();
+            let changed = rows
+                .into_iter()
+                .map(|row| ChangedLine {
+                    row,
+                    line: format_line(view[row], styled, max_line_cells),
+                })
+                .collect();
+            QueryResponse::ScreenDiff(ScreenDiffResponse {
+                epoch,
+                snapshot_id,
+                rows: view.len(),
+                changed,
+            })
+        }
+
+        // Deferred in `run` until queued output is parsed.
+        Query::Barrier => QueryResponse::Ok,
+
+        // Parsed in `run` like program output.
+        Query::Restore { .. } => QueryResponse::Ok,
+
+        Query::Dump { format, scrollback_limit } => {
+            let screen = screen_response(vt, epoch, alternate_active, format, max_line_cells);
+            // The alternate screen has no scrollback (see `line_bounds`).
+            let above = screen.first_line_index;
+            let offset = above.saturating_sub(scrollback_limit);
+            let lines = vt
+                .lines()
+                .take(above)
+                .skip(offset)
+                .map(|l| format_line(l, matches!(format, Format::Styled), max_line_cells))
+                .collect();
+            QueryResponse::Dump(DumpResponse {
+                epoch,
+                scrollback: ScrollbackResponse {
+                    epoch,
+                    lines,
+                    total_lines: above,
+                    offset,
+                },
+                screen,
+                cursor_key_app_mode: vt.cursor_key_app_mode(),
+                scroll_region: scroll_region.region(),
+                title: metadata.title().map(str::to_string),
+                cwd: metadata.cwd().map(str::to_string),
+                default_fg: metadata.default_fg().cloned(),
+                default_bg: metadata.default_bg().cloned(),
+            })
+        }
+
+        Query::InputModes => QueryResponse::InputModes(InputModesResponse {
+            epoch,
+            cursor_key_app_mode: vt.cursor_key_app_mode(),
+        }),
+
+        Query::ScrollRegion => {
+            let region = scroll_region.region();
+            QueryResponse::ScrollRegion(ScrollRegionResponse {
+                epoch,
+                top: region.top,
+                bottom: region.bottom,
+            })
+        }
+
+        Query::DefaultColors => QueryResponse::DefaultColors(DefaultColorsResponse {
+            epoch,
+            fg: metadata.default_fg().cloned(),
+            bg: metadata.default_bg().cloned(),
+        }),
+
+        Query::Commands => QueryResponse::Commands(CommandsResponse {
+            epoch,
+            // Commands whose output start has been trimmed from scrollback
+            // can no longer be addressed by line, so they're dropped.
+            commands: commands
+                .commands()
+                .filter_map(|command| {
+                    let start = command.output?.line.checked_sub(*dropped_lines)?;
+                    Some(CommandInfo {
+                        command_text: command.command_text.clone(),
+                        exit_code: command.exit_code,
+                        start_line: start as usize,
+                        end_line: command.end.map(|end| end.line.saturating_sub(*dropped_lines) as usize),
+                    })
+                })
+                .collect(),
+        }),
+
+        Query::LastCommandOutput => {
+            QueryResponse::LastCommandOutput(commands.commands().last().and_then(|command| {
+                let output = command.output?;
+                // A running command's output extends to the cursor.
+                let end = command.end.unwrap_or_else(|| cursor_position(vt, *dropped_lines));
+                let truncated = output.line < *dropped_lines;
+                let start = if truncated {
+                    Position { line: *dropped_lines, col: 0 }
+                } else {
+                    output
+                };
+                Some(LastCommandOutput {
+                    epoch,
+                    command_text: command.command_text.clone(),
+                    exit_code: command.exit_code,
+                    running: command.end.is_none(),
+                    output: text_between(vt, *dropped_lines, start, end)?,
+                    truncated,
+                })
+            }))
+        }
+
+        Query::Clear { scope } => {
+            let clear_history = matches!(scope, ClearScope::Scrollback | ClearScope::All);
+            // avt keeps the primary buffer's history out of reach while the
+            // alternate screen is up, so it couldn't be counted as dropped.
+            if clear_history && alternate_active {
+                return QueryResponse::Invalid(
+                    "scrollback can't be cleared while the alternate screen is active".to_string(),
+                );
+            }
+            if matches!(scope, ClearScope::Screen | ClearScope::All) {
+                let _ = feed_vt(vt, "\x1b[H\x1b[2J");
+            }
+            if clear_history {
+                // avt ignores `CSI 3 J`. A fresh terminal replaying the
+                // visible state (screen, modes, margins, cursor) has no
+                // history; the lines it loses count as trimmed, so command
+                // positions stay valid.
+                let (cols, rows) = vt.size();
+                *dropped_lines += line_count(vt).saturating_sub(rows) as u64;
+                let state = vt.dump();
+                *vt = avt::Vt::builder()
+                    .size(cols, rows)
+                    .scrollback_limit(scrollback_limit)
+                    .build();
+                let _ = vt.feed_str(&state);
+            }
+            *seq = seq.wrapping_add(1);
+            let _ = event_tx.send(Event::Reset {
+                seq: *seq,
+                reason: if clear_history {
+                    ResetReason::ClearScrollback
+                } else {
+                    ResetReason::ClearScreen
+                },
+            });
+            let screen = screen_response(vt, epoch, alternate_active, Format::Styled, max_line_cells);
+            *seq = seq.wrapping_add(1);
+            let _ = event_tx.send(Event::Sync {
+                seq: *seq,
+                scrollback_lines: screen.total_lines,
+                screen,
+            });
+            QueryResponse::Ok
+        }
+
+        Query::TrimScrollback { keep } => {
+            if alternate_active {
+                return QueryResponse::Invalid(
+                    "scrollback can't be trimmed while the alternate screen is active".to_string(),
+                );
+            }
+            let (cols, rows) = vt.size();
+            let lines: Vec<&avt::Line> = vt.lines().collect();
+            let history = lines.len().saturating_sub(rows);
+            if history <= keep {
+                return QueryResponse::Ok;
+            }
+            // Like clearing, but the kept history is written first and
+            // scrolled off the top before the visible state is replayed.
+            let removed = history - keep;
+            let mut replay = String::new();
+            for line in &lines[removed..history] {
+                replay.push_str(&line_to_ansi(&format_line(line, true, cols)));
+                replay.push_str("\x1b[0m\r\n");
+            }
+            replay.push_str(&"\n".repeat(rows.saturating_sub(1)));
+            replay.push_str("\x1b[H");
+            replay.push_str(&vt.dump());
+            *vt = avt::Vt::builder()
+                .size(cols, rows)
+                .scrollback_limit(scrollback_limit)
+                .build();
+            let _ = vt.feed_str(&replay);
+            *dropped_lines += removed as u64;
+            *seq = seq.wrapping_add(1);
+            let _ = event_tx.send(Event::Reset {
+                seq: *seq,
+                reason: ResetReason::ScrollbackEvicted,
+            });
+            let screen = screen_response(vt, epoch, alternate_active, Format::Styled, max_line_cells);
+            *seq = seq.wrapping_add(1);
+            let _ = event_tx.send(Event::Sync {
+                seq: *seq,
+                scrollback_lines: screen.total_lines,
+                screen,
+            });
+            QueryResponse::Ok
+        }
+
+        query @ (Query::Resize { .. } | Query::ResizeAndScreen { .. }) => {
+            let (cols, rows, reflow, screen_format) = match query {
+                Query::Resize { cols, rows, reflow } => (cols, rows, reflow, None),
+                Query::ResizeAndScreen { cols, rows, reflow, format } => (cols, rows, reflow, Some(format)),
+                _ => unreachable!(),
+            };
+            // Same backstop as at startup: never resize to zero.
+            let (cols, rows) = (cols.max(1), rows.max(1));
+            // Full-screen programs repaint the alternate screen on SIGWINCH,
+            // so only the primary screen is rebuilt row by row.
+            let had_margins = !scroll_region.is_full_screen();
+            if reflow || alternate_active {
+                let _changes = vt.resize(cols, rows);
+                scroll_region.resize(rows);
+            } else {
+                *vt = resize_without_reflow(vt, cols, rows, scrollback_limit, scroll_region.region().top);
+                scroll_region.resize(rows);
+            }
             // Update shared dimensions so the restart loop uses current
             // values instead of stale spawn-time dimensions.
             shared_cols.store(cols, Ordering::Release);
@@ -204,209 +1135,84 @@ fn handle_query(
                 seq: *seq,
                 reason: ResetReason::Resize,
             });
-            QueryResponse::Ok
+            // Followers' line indices and geometry are stale now; hand them
+            // the new screen so they can rebuild without a round trip.
+            let screen = screen_response(vt, epoch, alternate_active, Format::Styled, max_line_cells);
+            *seq = seq.wrapping_add(1);
+            let _ = event_tx.send(Event::Sync {
+                seq: *seq,
+                scrollback_lines: screen.total_lines,
+                screen,
+            });
+            // A full-screen region follows from the resize itself; only
+            // margins the program set and lost need announcing.
+            if had_margins && scroll_region.is_full_screen() {
+                let region = scroll_region.region();
+                *seq = seq.wrapping_add(1);
+                let _ = event_tx.send(Event::ScrollRegion {
+                    seq: *seq,
+                    top: region.top,
+                    bottom: region.bottom,
+                });
+            }
+            match screen_format {
+                Some(format) => QueryResponse::Screen(screen_response(vt, epoch, alternate_active, format, max_line_cells)),
+                None => QueryResponse::Ok,
+            }
         }
     }
 }
 
+/// Fingerprint for `Query::ScreenHash`. Covers everything a
+/// `ScreenResponse` reports, so an unchanged hash means an unchanged screen
+/// payload (modulo epoch).
+fn screen_hash(vt: &avt::Vt, alternate_active: bool) -> String {
+    let mut hasher = DefaultHasher::new();
+    vt.size().hash(&mut hasher);
+    line_count(vt).hash(&mut hasher);
+    alternate_active.hash(&mut hasher);
+    let cursor = vt.cursor();
+    (cursor.row, cursor.col, cursor.visible).hash(&mut hasher);
+    for line in vt.view() {
+        hash_line(line, &mut hasher);
+    }
+    format!("{:016x}", hasher.finish())
+}
+
 /// Stateful detector for alternate screen mode transitions.
 ///
 /// Tracks DEC private mode set/reset sequences (modes 47, 1047, 1049) across
 /// chunk boundaries. Terminal output arrives in arbitrary-sized chunks that may
-/// split an escape sequence (e.g. `\x1b` in one chunk, `[?1049h` in the next).
-/// This detector buffers partial sequences to handle such splits correctly.
+/// split an escape sequence (e.g. `\x1b` in one chunk, `[?1049h` in the next);
+/// the scanner carries the partial sequence over to the next chunk.
 struct AlternateScreenDetector {
-    /// Partial CSI sequence carried over from previous chunk.
-    /// Contains bytes from ESC or CSI introducer through any partial params.
-    partial: Vec<u8>,
-}
-
-/// Internal states while scanning a byte within the detector.
-#[derive(Clone, Copy)]
-enum ScanState {
-    /// Not inside any escape sequence
-    Ground,
-    /// Seen ESC (0x1b), waiting for '['
-    Esc,
-    /// Inside CSI, waiting for '?' or skipping non-DEC CSI
-    CsiEntry,
-    /// Seen CSI ?, collecting parameter bytes
-    DecParams,
+    scanner: EscapeScanner,
 }
 
 impl AlternateScreenDetector {
     fn new() -> Self {
         Self {
-            partial: Vec::new(),
+            scanner: EscapeScanner::new(64),
         }
     }
 
     /// Feed a chunk of text and return the new alternate_active state.
     fn feed(&mut self, text: &str, current: bool) -> bool {
         let mut state = current;
-        let mut scan = if self.partial.is_empty() {
-            ScanState::Ground
-        } else {
-            // Determine where we left off from the partial buffer
-            self.classify_partial()
-        };
-
         for &byte in text.as_bytes() {
-            // Guard against unbounded growth from malformed sequences
-            if self.partial.len() > 64 {
-                self.partial.clear();
-                scan = ScanState::Ground;
-            }
-
-            match scan {
-                ScanState::Ground => {
-                    if byte == 0x1b {
-                        self.partial.clear();
-                        self.partial.push(byte);
-                        scan = ScanState::Esc;
-                    } else if byte == 0xC2 {
-                        // Potential start of C1 CSI (U+009B = 0xC2 0x9B in UTF-8)
-                        self.partial.clear();
-                        self.partial.push(byte);
-                        // Stay in ground — we'll check next byte
-                    } else if !self.partial.is_empty() && self.partial[0] == 0xC2 && byte == 0x9B {
-                        // Completed C1 CSI
-                        self.partial.clear();
-                        self.partial.push(0xC2);
-                        self.partial.push(0x9B);
-                        scan = ScanState::CsiEntry;
-                    } else {
-                        self.partial.clear();
-                    }
-                }
-
-                ScanState::Esc => {
-                    if byte == b'[' {
-                        self.partial.push(byte);
-                        scan = ScanState::CsiEntry;
-                    } else {
-                        // Not a CSI, abandon
-                        self.partial.clear();
-                        scan = ScanState::Ground;
-                    }
-                }
-
-                ScanState::CsiEntry => {
-                    if byte == b'?' {
-                        self.partial.push(byte);
-                        scan = ScanState::DecParams;
-                    } else {
-                        // Not a DEC private mode sequence, abandon
-                        self.partial.clear();
-                        scan = ScanState::Ground;
-                        // Re-check this byte as potential sequence start
-                        if byte == 0x1b {
-                            self.partial.push(byte);
-                            scan = ScanState::Esc;
-                        }
-                    }
-                }
-
-                ScanState::DecParams => {
-                    if (0x30..=0x3f).contains(&byte) {
-                        // Parameter byte (digits, semicolons, etc.)
-                        self.partial.push(byte);
-                    } else if byte == b'h' || byte == b'l' {
-                        // Final byte — process the complete sequence
-                        let entering = byte == b'h';
-                        if let Some(new_state) = self.process_params(entering) {
-                            state = new_state;
-                        }
-                        self.partial.clear();
-                        scan = ScanState::Ground;
-                    } else {
-                        // Unexpected byte, not a valid DEC mode sequence
-                        self.partial.clear();
-                        scan = ScanState::Ground;
-                        if byte == 0x1b {
-                            self.partial.push(byte);
-                            scan = ScanState::Esc;
-                        }
-                    }
+            if let Some(Sequence::Csi { params, intermediates: [], final_byte: final_byte @ (b'h' | b'l') }) =
+                self.scanner.step(byte)
+            {
+                let Some(modes) = params.strip_prefix(b"?") else {
+                    continue;
+                };
+                if modes.split(|&b| b == b';').any(|mode| matches!(mode, b"47" | b"1047" | b"1049")) {
+                    state = final_byte == b'h';
                 }
             }
         }
-
-        // partial remains populated for next call if we're mid-sequence
-        if matches!(scan, ScanState::Ground) {
-            self.partial.clear();
-        }
-
         state
     }
-
-    /// Classify what scan state the existing partial buffer represents.
-    fn classify_partial(&self) -> ScanState {
-        let p = &self.partial;
-        if p.is_empty() {
-            return ScanState::Ground;
-        }
-
-        // Check for C1 CSI partial (0xC2 alone — waiting for 0x9B)
-        if p.len() == 1 && p[0] == 0xC2 {
-            return ScanState::Ground; // handled specially in Ground
-        }
-
-        // ESC alone
-        if p.len() == 1 && p[0] == 0x1b {
-            return ScanState::Esc;
-        }
-
-        // ESC [ or C1 CSI (0xC2 0x9B)
-        let csi = (p.len() >= 2 && p[0] == 0x1b && p[1] == b'[')
-            || (p.len() >= 2 && p[0] == 0xC2 && p[1] == 0x9B);
-
-        if !csi {
-            return ScanState::Ground;
-        }
-
-        // Both ESC [ and C1 CSI (0xC2 0x9B) have 2-byte prefixes
-        let after_csi = 2;
-        if p.len() <= after_csi {
-            return ScanState::CsiEntry;
-        }
-
-        if p[after_csi] == b'?' {
-            ScanState::DecParams
-        } else {
-            ScanState::Ground
-        }
-    }
-
-    /// Extract params from partial buffer and check for alternate screen modes.
-    /// Returns Some(bool) if an alternate screen mode was found.
-    fn process_params(&self, entering: bool) -> Option<bool> {
-        // Params start after the 3-byte prefix: ESC [ ? or 0xC2 0x9B ?
-        let params_start = 3;
-
-        if params_start > self.partial.len() {
-            return None;
-        }
-
-        let params = &self.partial[params_start..];
-        let params_str = std::str::from_utf8(params).ok()?;
-
-        let mut found = false;
-        for param in params_str.split(';') {
-            match param {
-                "47" | "1047" | "1049" => {
-                    found = true;
-                }
-                _ => {}
-            }
-        }
-
-        if found {
-            Some(entering)
-        } else {
-            None
-        }
-    }
 }
 
 #[cfg(test)]
@@ -608,4 +1414,14 @@ mod tests {
         }
         assert!(state);
     }
+
+    #[test]
+    fn line_count_is_exact_without_walking_lines() {
+        let mut vt = avt::Vt::builder().size(10, 3).scrollback_limit(100).build();
+        vt.feed_str(&"line\r\n".repeat(20));
+        let (lower, upper) = vt.lines().size_hint();
+        assert_eq!(Some(lower), upper, "avt's line iterator should know its length");
+        assert_eq!(super::line_count(&vt), vt.lines().count());
+        assert_eq!(super::line_count(&vt), 21);
+    }
 }
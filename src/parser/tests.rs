@@ -1,6 +1,6 @@
 // src/parser/tests.rs
 use super::*;
-use state::Format;
+use state::{Color, Format};
 use tokio::sync::mpsc;
 use tokio_stream::StreamExt;
 
@@ -113,13 +113,14 @@ async fn test_parser_scrollback() {
         tx.send(bytes::Bytes::from(format!("Line {}\r\n", i))).await.unwrap();
     }
 
-    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    parser.barrier().await.unwrap();
 
     let response = parser
         .query(Query::Scrollback {
             format: Format::Plain,
             offset: 0,
             limit: 100,
+            from_end: false,
         })
         .await
         .unwrap();
@@ -133,6 +134,68 @@ async fn test_parser_scrollback() {
     }
 }
 
+#[tokio::test]
+async fn test_line_query_by_absolute_index() {
+    let (tx, parser) = spawn_test_parser(80, 5, 100).await;
+    for i in 0..10 {
+        tx.send(bytes::Bytes::from(format!("Line {}\r\n", i))).await.unwrap();
+    }
+    parser.barrier().await.unwrap();
+
+    // Line 2 has scrolled off the 5-row screen.
+    match parser.query(Query::Line { index: 2, format: Format::Plain }).await.unwrap() {
+        QueryResponse::Line(line) => {
+            assert_eq!(line.index, 2);
+            assert_eq!(line.total_lines, 11);
+            assert!(matches!(line.line, state::FormattedLine::Plain(ref t) if t == "Line 2"));
+        }
+        other => panic!("expected Line response, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_line_query_out_of_range_is_invalid() {
+    let (tx, parser) = spawn_test_parser(80, 5, 100).await;
+    tx.send(bytes::Bytes::from("only\r\n")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    let err = parser
+        .query(Query::Line { index: 5, format: Format::Plain })
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ParserError::InvalidQuery(ref detail) if detail.contains("out of range")));
+}
+
+#[tokio::test]
+async fn test_cursor_line_query() {
+    let (tx, parser) = spawn_test_parser(80, 5, 100).await;
+    for i in 0..7 {
+        tx.send(bytes::Bytes::from(format!("Line {}\r\n", i))).await.unwrap();
+    }
+    tx.send(bytes::Bytes::from("$ ls  ")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    match parser.query(Query::CursorLine { format: Format::Plain }).await.unwrap() {
+        QueryResponse::CursorLine(resp) => {
+            assert_eq!(resp.index, 7);
+            assert_eq!((resp.cursor.row, resp.cursor.col), (4, 6));
+            // Trailing blanks are trimmed; the cursor stays past them.
+            assert!(matches!(resp.line, state::FormattedLine::Plain(ref t) if t == "$ ls"));
+        }
+        other => panic!("expected CursorLine response, got {other:?}"),
+    }
+
+    tx.send(bytes::Bytes::from("\r\n")).await.unwrap();
+    parser.barrier().await.unwrap();
+    match parser.query(Query::CursorLine { format: Format::Styled }).await.unwrap() {
+        QueryResponse::CursorLine(resp) => {
+            assert_eq!((resp.cursor.row, resp.cursor.col), (4, 0));
+            assert!(matches!(resp.line, state::FormattedLine::Styled(ref spans) if spans.is_empty()));
+        }
+        other => panic!("expected CursorLine response, got {other:?}"),
+    }
+}
+
 #[tokio::test]
 async fn test_scrollback_includes_all_lines() {
     let (tx, parser) = spawn_test_parser(80, 5, 100).await;
@@ -163,6 +226,7 @@ async fn test_scrollback_includes_all_lines() {
             format: Format::Plain,
             offset: 0,
             limit: 100,
+            from_end: false,
         })
         .await
         .unwrap();
@@ -234,7 +298,7 @@ async fn test_scrollback_when_in_alternate_screen() {
     for i in 0..10 {
         tx.send(bytes::Bytes::from(format!("Line {}\r\n", i))).await.unwrap();
     }
-    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    parser.barrier().await.unwrap();
 
     // Verify we have scrollback before switching to alternate screen
     let response = parser
@@ -242,6 +306,7 @@ async fn test_scrollback_when_in_alternate_screen() {
             format: Format::Plain,
             offset: 0,
             limit: 100,
+            from_end: false,
         })
         .await
         .unwrap();
@@ -254,7 +319,7 @@ async fn test_scrollback_when_in_alternate_screen() {
 
     // Enter alternate screen mode (DECSET 1049 or smcup)
     tx.send(bytes::Bytes::from("\x1b[?1049h")).await.unwrap();
-    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    parser.barrier().await.unwrap();
 
     // Query scrollback while in alternate screen
     let response = parser
@@ -262,6 +327,7 @@ async fn test_scrollback_when_in_alternate_screen() {
             format: Format::Plain,
             offset: 0,
             limit: 100,
+            from_end: false,
         })
         .await
         .unwrap();
@@ -278,7 +344,7 @@ async fn test_scrollback_when_in_alternate_screen() {
 
     // Exit alternate screen mode (DECRST 1049 or rmcup)
     tx.send(bytes::Bytes::from("\x1b[?1049l")).await.unwrap();
-    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    parser.barrier().await.unwrap();
 
     // Query scrollback after exiting alternate screen
     let response = parser
@@ -286,6 +352,7 @@ async fn test_scrollback_when_in_alternate_screen() {
             format: Format::Plain,
             offset: 0,
             limit: 100,
+            from_end: false,
         })
         .await
         .unwrap();
@@ -320,7 +387,7 @@ async fn test_alternate_active_in_screen_response() {
 
     // Enter alternate screen mode
     tx.send(bytes::Bytes::from("\x1b[?1049h")).await.unwrap();
-    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    parser.barrier().await.unwrap();
 
     let response = parser
         .query(Query::Screen {
@@ -335,10 +402,11 @@ async fn test_alternate_active_in_screen_response() {
         }
         _ => panic!("expected Screen response"),
     }
+    assert!(parser.alternate_active());
 
     // Exit alternate screen mode
     tx.send(bytes::Bytes::from("\x1b[?1049l")).await.unwrap();
-    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    parser.barrier().await.unwrap();
 
     let response = parser
         .query(Query::Screen {
@@ -353,6 +421,7 @@ async fn test_alternate_active_in_screen_response() {
         }
         _ => panic!("expected Screen response"),
     }
+    assert!(!parser.alternate_active());
 }
 
 #[tokio::test]
@@ -393,6 +462,86 @@ async fn test_alternate_screen_emits_mode_event() {
     assert!(!mode_event, "Mode event should indicate alternate_active = false");
 }
 
+async fn screen_bounds(parser: &Parser) -> (usize, usize, bool) {
+    match parser.query(Query::Screen { format: Format::Plain }).await.unwrap() {
+        QueryResponse::Screen(screen) => (screen.first_line_index, screen.total_lines, screen.alternate_active),
+        _ => panic!("expected Screen response"),
+    }
+}
+
+#[tokio::test]
+async fn test_alternate_screen_line_numbering_transitions() {
+    let (tx, parser) = spawn_test_parser(80, 5, 100).await;
+
+    for i in 0..10 {
+        tx.send(bytes::Bytes::from(format!("Line {}\r\n", i))).await.unwrap();
+    }
+    parser.barrier().await.unwrap();
+    let (primary_first, primary_total, _) = screen_bounds(&parser).await;
+    assert!(primary_first > 0, "primary buffer should have scrollback");
+    assert_eq!(primary_total, primary_first + 5);
+
+    let mut events = parser.subscribe();
+
+    // Primary -> alternate: numbering restarts at 0 with screen-height lines.
+    tx.send(bytes::Bytes::from("\x1b[?1049h\x1b[Halt")).await.unwrap();
+    parser.barrier().await.unwrap();
+    assert_eq!(screen_bounds(&parser).await, (0, 5, true));
+
+    let mut saw_reset = false;
+    let mut alt_line_total = None;
+    tokio::time::timeout(tokio::time::Duration::from_millis(500), async {
+        while alt_line_total.is_none() {
+            match events.next().await {
+                Some(SubscriptionEvent::Event(Event::Reset { reason, .. })) => {
+                    assert!(matches!(reason, events::ResetReason::AlternateScreenEnter));
+                    saw_reset = true;
+                }
+                Some(SubscriptionEvent::Event(Event::Line { index, total_lines, .. })) => {
+                    assert!(saw_reset, "Reset must precede lines in the new numbering");
+                    assert!(index < 5);
+                    alt_line_total = Some(total_lines);
+                }
+                // Switching screens repaints every row.
+                Some(SubscriptionEvent::Event(Event::Diff { screen, .. })) => {
+                    assert!(saw_reset, "Reset must precede lines in the new numbering");
+                    alt_line_total = Some(screen.total_lines);
+                }
+                _ => {}
+            }
+        }
+    })
+    .await
+    .expect("should receive Reset and Line events");
+    assert_eq!(alt_line_total, Some(5));
+
+    match parser
+        .query(Query::Scrollback { format: Format::Plain, offset: 0, limit: 100, from_end: false })
+        .await
+        .unwrap()
+    {
+        QueryResponse::Scrollback(sb) => {
+            assert_eq!(sb.total_lines, 5);
+            assert!(matches!(&sb.lines[0], state::FormattedLine::Plain(t) if t == "alt"));
+        }
+        _ => panic!("expected Scrollback response"),
+    }
+
+    // Alternate -> primary: the original numbering comes back intact.
+    tx.send(bytes::Bytes::from("\x1b[?1049l")).await.unwrap();
+    tokio::time::timeout(tokio::time::Duration::from_millis(500), async {
+        loop {
+            if let Some(SubscriptionEvent::Event(Event::Reset { reason, .. })) = events.next().await {
+                assert!(matches!(reason, events::ResetReason::AlternateScreenExit));
+                break;
+            }
+        }
+    })
+    .await
+    .expect("should receive Reset on exit");
+    assert_eq!(screen_bounds(&parser).await, (primary_first, primary_total, false));
+}
+
 #[tokio::test]
 async fn test_screen_response_includes_line_indices() {
     let (tx, parser) = spawn_test_parser(80, 5, 100).await;
@@ -401,7 +550,7 @@ async fn test_screen_response_includes_line_indices() {
     for i in 0..10 {
         tx.send(bytes::Bytes::from(format!("Line {}\r\n", i))).await.unwrap();
     }
-    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    parser.barrier().await.unwrap();
 
     let response = parser
         .query(Query::Screen { format: Format::Plain })
@@ -444,3 +593,1420 @@ async fn test_parser_channel_does_not_lose_data() {
         _ => panic!("expected Screen response"),
     }
 }
+
+async fn screen_hash(parser: &Parser) -> String {
+    match parser.query(Query::ScreenHash).await.unwrap() {
+        QueryResponse::ScreenHash(h) => h.hash,
+        _ => panic!("expected ScreenHash response"),
+    }
+}
+
+#[tokio::test]
+async fn test_screen_hash_stable_when_unchanged() {
+    let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
+    tx.send(bytes::Bytes::from("hello")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    let a = screen_hash(&parser).await;
+    let b = screen_hash(&parser).await;
+    assert_eq!(a, b);
+    assert_eq!(a.len(), 16);
+}
+
+#[tokio::test]
+async fn test_screen_hash_changes_with_content_style_and_cursor() {
+    let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
+    let initial = screen_hash(&parser).await;
+
+    tx.send(bytes::Bytes::from("x")).await.unwrap();
+    parser.barrier().await.unwrap();
+    let text = screen_hash(&parser).await;
+    assert_ne!(initial, text, "text change should change the hash");
+
+    // Restyle the same character in place: text is identical, style is not.
+    tx.send(bytes::Bytes::from("\r\x1b[1mx\x1b[0m")).await.unwrap();
+    parser.barrier().await.unwrap();
+    let styled = screen_hash(&parser).await;
+    assert_ne!(text, styled, "style change should change the hash");
+
+    // Cursor movement alone.
+    tx.send(bytes::Bytes::from("\x1b[5;5H")).await.unwrap();
+    parser.barrier().await.unwrap();
+    let moved = screen_hash(&parser).await;
+    assert_ne!(styled, moved, "cursor move should change the hash");
+}
+
+#[tokio::test]
+async fn test_screen_with_hash_matches_separate_queries() {
+    let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
+    tx.send(bytes::Bytes::from("hello\r\n\x1b[1mworld")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    let combined = match parser.query(Query::ScreenWithHash { format: Format::Styled }).await.unwrap() {
+        QueryResponse::ScreenWithHash(r) => r,
+        _ => panic!("expected ScreenWithHash response"),
+    };
+    assert_eq!(combined.hash, screen_hash(&parser).await);
+    let screen = match parser.query(Query::Screen { format: Format::Styled }).await.unwrap() {
+        QueryResponse::Screen(screen) => screen,
+        _ => panic!("expected Screen response"),
+    };
+    assert_eq!(
+        serde_json::to_value(&combined.screen).unwrap(),
+        serde_json::to_value(&screen).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_diff_since_reports_only_changed_rows() {
+    let (tx, parser) = spawn_test_parser(80, 5, 1000).await;
+    tx.send(bytes::Bytes::from("one\r\ntwo\r\nthree")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    let snapshot = parser.snapshot().await.unwrap();
+    let unchanged = parser.diff_since(snapshot, Format::Plain).await.unwrap();
+    assert_eq!(unchanged.snapshot_id, snapshot);
+    assert_eq!(unchanged.rows, 5);
+    assert!(unchanged.changed.is_empty());
+
+    // Rewrite row 1 and add text to row 3; rows 0 and 2 are untouched.
+    tx.send(bytes::Bytes::from("\x1b[2;1HTWO\x1b[4;1Hfour")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    let diff = parser.diff_since(snapshot, Format::Plain).await.unwrap();
+    let rows: Vec<_> = diff.changed.iter().map(|c| c.row).collect();
+    assert_eq!(rows, vec![1, 3]);
+    assert!(matches!(&diff.changed[0].line, state::FormattedLine::Plain(t) if t == "TWO"));
+    assert!(matches!(&diff.changed[1].line, state::FormattedLine::Plain(t) if t == "four"));
+
+    // A style-only change counts too.
+    let restyle = parser.snapshot().await.unwrap();
+    tx.send(bytes::Bytes::from("\x1b[1;1H\x1b[1mone\x1b[0m")).await.unwrap();
+    parser.barrier().await.unwrap();
+    let diff = parser.diff_since(restyle, Format::Styled).await.unwrap();
+    assert_eq!(diff.changed.len(), 1);
+    assert_eq!(diff.changed[0].row, 0);
+}
+
+#[tokio::test]
+async fn test_diff_since_rejects_unknown_and_evicted_snapshots() {
+    let (_tx, parser) = spawn_test_parser(80, 5, 1000).await;
+    assert!(matches!(
+        parser.diff_since(12345, Format::Plain).await,
+        Err(ParserError::InvalidQuery(_))
+    ));
+
+    let first = parser.snapshot().await.unwrap();
+    for _ in 0..16 {
+        parser.snapshot().await.unwrap();
+    }
+    assert!(matches!(
+        parser.diff_since(first, Format::Plain).await,
+        Err(ParserError::InvalidQuery(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_full_text_styled_renders_ansi() {
+    let (tx, parser) = spawn_test_parser(80, 3, 1000).await;
+    tx.send(bytes::Bytes::from("plain\r\n\x1b[31mred\x1b[0m\r\nlast")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    let text = match parser.query(Query::FullText { format: Format::Styled }).await.unwrap() {
+        QueryResponse::FullText(t) => t,
+        _ => panic!("expected FullText response"),
+    };
+    let lines: Vec<_> = text.text.split('\n').collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], "plain");
+    assert!(lines[1].contains("\x1b[") && lines[1].contains("red"), "{:?}", lines[1]);
+    assert_eq!(lines[2], "last");
+    assert!(!text.truncated);
+
+    // The alternate screen has no history: only its rows are returned.
+    tx.send(bytes::Bytes::from("\x1b[?1049h\x1b[Halt")).await.unwrap();
+    parser.barrier().await.unwrap();
+    let text = match parser.query(Query::FullText { format: Format::Plain }).await.unwrap() {
+        QueryResponse::FullText(t) => t,
+        _ => panic!("expected FullText response"),
+    };
+    assert_eq!(text.total_lines, 3);
+    assert_eq!(text.first_line_index, 0);
+    assert_eq!(text.text, "alt\n\n");
+}
+
+#[tokio::test]
+async fn test_styled_scrollback_keeps_color_across_lines() {
+    let (tx, parser) = spawn_test_parser(80, 3, 1000).await;
+
+    // One SGR sets red for a block spanning several lines; only the first
+    // line contains the escape sequence itself.
+    tx.send(bytes::Bytes::from("\x1b[31mone\r\ntwo\x1b[0m\r\n"))
+        .await
+        .unwrap();
+    parser.barrier().await.unwrap();
+
+    let on_screen = match parser
+        .query(Query::Screen { format: Format::Styled })
+        .await
+        .unwrap()
+    {
+        QueryResponse::Screen(screen) => serde_json::to_value(&screen.lines[..2]).unwrap(),
+        _ => panic!("expected Screen response"),
+    };
+
+    // Push the colored block off the top of the 3-row screen.
+    tx.send(bytes::Bytes::from("a\r\nb\r\nc\r\n")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    let scrolled = match parser
+        .query(Query::Scrollback {
+            format: Format::Styled,
+            offset: 0,
+            limit: 2,
+            from_end: false,
+        })
+        .await
+        .unwrap()
+    {
+        QueryResponse::Scrollback(sb) => serde_json::to_value(&sb.lines).unwrap(),
+        _ => panic!("expected Scrollback response"),
+    };
+
+    assert_eq!(scrolled, on_screen, "scrollback should render as it did on screen");
+    assert_eq!(
+        scrolled,
+        serde_json::json!([
+            [{"text": "one", "fg": {"indexed": 1}}],
+            [{"text": "two", "fg": {"indexed": 1}}],
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_input_modes_tracks_cursor_key_mode() {
+    let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
+
+    let app_mode = |resp: QueryResponse| match resp {
+        QueryResponse::InputModes(m) => m.cursor_key_app_mode,
+        _ => panic!("expected InputModes response"),
+    };
+
+    assert!(!app_mode(parser.query(Query::InputModes).await.unwrap()));
+
+    tx.send(bytes::Bytes::from("\x1b[?1h")).await.unwrap();
+    parser.barrier().await.unwrap();
+    assert!(app_mode(parser.query(Query::InputModes).await.unwrap()));
+
+    tx.send(bytes::Bytes::from("\x1b[?1l")).await.unwrap();
+    parser.barrier().await.unwrap();
+    assert!(!app_mode(parser.query(Query::InputModes).await.unwrap()));
+}
+
+#[tokio::test]
+async fn test_scroll_region_query_and_event() {
+    let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
+    let mut events = parser.subscribe();
+
+    let region = |resp: QueryResponse| match resp {
+        QueryResponse::ScrollRegion(r) => (r.top, r.bottom),
+        _ => panic!("expected ScrollRegion response"),
+    };
+    assert_eq!(region(parser.query(Query::ScrollRegion).await.unwrap()), (0, 23));
+
+    tx.send(bytes::Bytes::from("\x1b[3;18r")).await.unwrap();
+    let event = tokio::time::timeout(tokio::time::Duration::from_millis(200), async {
+        loop {
+            if let Some(SubscriptionEvent::Event(Event::ScrollRegion { top, bottom, .. })) = events.next().await {
+                return (top, bottom);
+            }
+        }
+    })
+    .await
+    .expect("should receive ScrollRegion event");
+    assert_eq!(event, (2, 17));
+    assert_eq!(region(parser.query(Query::ScrollRegion).await.unwrap()), (2, 17));
+
+    // A resize that changes the row count restores the full screen.
+    parser.resize(80, 30).await.unwrap();
+    assert_eq!(region(parser.query(Query::ScrollRegion).await.unwrap()), (0, 29));
+}
+
+#[tokio::test]
+async fn test_scroll_events_count_rows_pushed_into_history() {
+    // Scrollback smaller than the output, so trimmed rows still count.
+    let (tx, parser) = spawn_test_parser(80, 4, 3).await;
+    let mut events = parser.subscribe();
+
+    for i in 0..10 {
+        tx.send(Bytes::from(format!("line {i}\r\n"))).await.unwrap();
+    }
+    // The alternate screen has no history.
+    tx.send(Bytes::from("\x1b[?1049h\r\n\r\n\r\n\r\n\r\n")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    let mut scrolled = 0;
+    while let Ok(Some(SubscriptionEvent::Event(event))) =
+        tokio::time::timeout(tokio::time::Duration::from_millis(100), events.next()).await
+    {
+        if let Event::Scroll { count, .. } = event {
+            scrolled += count;
+        }
+    }
+    // 10 lines plus the cursor's blank row on a 4-row screen.
+    assert_eq!(scrolled, 7);
+}
+
+#[tokio::test]
+async fn test_full_screen_redraw_sends_one_diff() {
+    let (tx, parser) = spawn_test_parser(20, 4, 100).await;
+    // Off by default.
+    assert_eq!(parser.redraw_threshold(), 0);
+    parser.set_redraw_threshold(75);
+    tx.send(Bytes::from("\x1b[?1049h")).await.unwrap();
+    parser.barrier().await.unwrap();
+    let mut events = parser.subscribe();
+
+    let repaint = Bytes::from("\x1b[2J\x1b[Hrow 0\r\nrow 1\r\nrow 2\r\nrow 3");
+    tx.send(repaint.clone()).await.unwrap();
+    // A single row isn't a redraw.
+    tx.send(Bytes::from("\x1b[1;1Hedit")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    let mut received = Vec::new();
+    while let Ok(Some(SubscriptionEvent::Event(event))) =
+        tokio::time::timeout(tokio::time::Duration::from_millis(100), events.next()).await
+    {
+        received.push(event);
+    }
+    let diff = received.iter().find(|e| matches!(e, Event::Diff { .. })).expect("expected a Diff");
+    let Event::Diff { changed_lines, screen, .. } = diff else { unreachable!() };
+    assert_eq!(changed_lines, &[0, 1, 2, 3]);
+    assert_eq!(screen.lines[3].text(), "row 3");
+    let lines = diff.diff_as_lines().unwrap();
+    assert!(matches!(&lines[2], Event::Line { index: 2, line, .. } if line.text() == "row 2"));
+    let line_indices: Vec<usize> = received
+        .iter()
+        .filter_map(|e| match e {
+            Event::Line { index, .. } => Some(*index),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(line_indices, [0]);
+
+    // Zero turns batching off.
+    parser.set_redraw_threshold(0);
+    tx.send(repaint).await.unwrap();
+    parser.barrier().await.unwrap();
+    let mut lines = 0;
+    while let Ok(Some(SubscriptionEvent::Event(event))) =
+        tokio::time::timeout(tokio::time::Duration::from_millis(100), events.next()).await
+    {
+        assert!(!matches!(event, Event::Diff { .. }));
+        lines += matches!(event, Event::Line { .. }) as usize;
+    }
+    assert_eq!(lines, 4);
+}
+
+#[tokio::test]
+async fn test_clear_scrollback_keeps_screen_and_resyncs_followers() {
+    let (tx, parser) = spawn_test_parser(20, 4, 100).await;
+    for i in 0..10 {
+        tx.send(Bytes::from(format!("line {i}\r\n"))).await.unwrap();
+    }
+    tx.send(Bytes::from("\x1b[2;3r$ ")).await.unwrap();
+    parser.barrier().await.unwrap();
+    let before = plain_screen_text(&parser).await;
+    let cursor_before = match parser.query(Query::Cursor).await.unwrap() {
+        QueryResponse::Cursor(c) => (c.cursor.row, c.cursor.col),
+        _ => panic!("expected Cursor response"),
+    };
+    let mut events = parser.subscribe();
+
+    parser.clear(state::ClearScope::Scrollback).await.unwrap();
+
+    assert_eq!(plain_screen_text(&parser).await, before);
+    match parser.query(Query::Screen { format: Format::Plain }).await.unwrap() {
+        QueryResponse::Screen(screen) => {
+            assert_eq!(screen.total_lines, 4);
+            assert_eq!(screen.first_line_index, 0);
+            assert_eq!((screen.cursor.row, screen.cursor.col), cursor_before);
+        }
+        _ => panic!("expected Screen response"),
+    }
+    // Margins survive the rebuild.
+    match parser.query(Query::ScrollRegion).await.unwrap() {
+        QueryResponse::ScrollRegion(region) => assert_eq!((region.top, region.bottom), (1, 2)),
+        _ => panic!("expected ScrollRegion response"),
+    }
+
+    let mut got_reset = false;
+    let mut sync_lines = None;
+    while let Ok(Some(SubscriptionEvent::Event(event))) =
+        tokio::time::timeout(tokio::time::Duration::from_millis(100), events.next()).await
+    {
+        match event {
+            Event::Reset { reason, .. } => {
+                assert!(matches!(reason, events::ResetReason::ClearScrollback));
+                got_reset = true;
+            }
+            Event::Sync { scrollback_lines, .. } => sync_lines = Some(scrollback_lines),
+            _ => {}
+        }
+    }
+    assert!(got_reset, "expected a ClearScrollback reset");
+    assert_eq!(sync_lines, Some(4));
+
+    // New output scrolls into a fresh history.
+    tx.send(Bytes::from("\x1b[r\r\nnext\r\n")).await.unwrap();
+    parser.barrier().await.unwrap();
+    let text = plain_screen_text(&parser).await;
+    assert!(text.contains("next"), "{text:?}");
+}
+
+#[tokio::test]
+async fn test_trim_scrollback_keeps_newest_history() {
+    let (tx, parser) = spawn_test_parser(20, 4, 100).await;
+    for i in 0..10 {
+        tx.send(Bytes::from(format!("line {i}\r\n"))).await.unwrap();
+    }
+    parser.barrier().await.unwrap();
+    let before = plain_screen_text(&parser).await;
+    assert_eq!(parser.scrollback_lines(), 7);
+    let mut events = parser.subscribe();
+
+    parser.trim_scrollback(3).await.unwrap();
+
+    assert_eq!(plain_screen_text(&parser).await, before);
+    assert_eq!(parser.scrollback_lines(), 3);
+    match parser
+        .query(Query::Scrollback { format: Format::Plain, offset: 0, limit: 3, from_end: false })
+        .await
+        .unwrap()
+    {
+        QueryResponse::Scrollback(scrollback) => {
+            let texts: Vec<String> = scrollback.lines.iter().map(|line| line.text()).collect();
+            assert_eq!(texts, ["line 4", "line 5", "line 6"]);
+            assert_eq!(scrollback.total_lines, 7);
+        }
+        _ => panic!("expected Scrollback response"),
+    }
+    let mut got_reset = false;
+    while let Ok(Some(SubscriptionEvent::Event(event))) =
+        tokio::time::timeout(tokio::time::Duration::from_millis(100), events.next()).await
+    {
+        if let Event::Reset { reason, .. } = event {
+            assert!(matches!(reason, events::ResetReason::ScrollbackEvicted));
+            got_reset = true;
+        }
+    }
+    assert!(got_reset, "expected a ScrollbackEvicted reset");
+
+    // Trimming to more than is held changes nothing.
+    parser.trim_scrollback(50).await.unwrap();
+    assert_eq!(parser.scrollback_lines(), 3);
+}
+
+#[tokio::test]
+async fn test_scrollback_on_partly_filled_screen() {
+    let (tx, parser) = spawn_test_parser(20, 6, 100).await;
+    tx.send(Bytes::from("one\r\ntwo")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    // The blank rows below the content are still the screen, not history.
+    assert_eq!(parser.scrollback_lines(), 0);
+    match parser.query(Query::Screen { format: Format::Plain }).await.unwrap() {
+        QueryResponse::Screen(screen) => {
+            assert_eq!(screen.first_line_index, 0);
+            assert_eq!(screen.total_lines, 6);
+        }
+        _ => panic!("expected Screen response"),
+    }
+    match parser
+        .query(Query::Scrollback { format: Format::Plain, offset: 0, limit: 100, from_end: false })
+        .await
+        .unwrap()
+    {
+        QueryResponse::Scrollback(scrollback) => {
+            let texts: Vec<String> = scrollback.lines.iter().map(|line| line.text()).collect();
+            assert_eq!(texts, ["one", "two", "", "", "", ""]);
+            assert_eq!(scrollback.total_lines, 6);
+        }
+        _ => panic!("expected Scrollback response"),
+    }
+
+    // Growing the screen past the content pulls no lines into history.
+    parser.resize(20, 10).await.unwrap();
+    parser.barrier().await.unwrap();
+    assert_eq!(parser.scrollback_lines(), 0);
+    match parser.query(Query::Screen { format: Format::Plain }).await.unwrap() {
+        QueryResponse::Screen(screen) => {
+            assert_eq!(screen.first_line_index, 0);
+            assert_eq!(screen.total_lines, 10);
+        }
+        _ => panic!("expected Screen response"),
+    }
+}
+
+#[tokio::test]
+async fn test_scrollback_count_after_growing_taller_than_history() {
+    let (tx, parser) = spawn_test_parser(20, 4, 100).await;
+    for i in 0..6 {
+        tx.send(Bytes::from(format!("line {i}\r\n"))).await.unwrap();
+    }
+    parser.barrier().await.unwrap();
+    assert_eq!(parser.scrollback_lines(), 3);
+
+    // A taller screen takes its rows back out of history.
+    parser.resize(20, 12).await.unwrap();
+    parser.barrier().await.unwrap();
+    match parser.query(Query::Screen { format: Format::Plain }).await.unwrap() {
+        QueryResponse::Screen(screen) => {
+            assert_eq!(screen.total_lines - screen.first_line_index, 12);
+            assert_eq!(screen.first_line_index, parser.scrollback_lines());
+        }
+        _ => panic!("expected Screen response"),
+    }
+}
+
+#[tokio::test]
+async fn test_clear_screen_and_all() {
+    let (tx, parser) = spawn_test_parser(20, 4, 100).await;
+    for i in 0..10 {
+        tx.send(Bytes::from(format!("line {i}\r\n"))).await.unwrap();
+    }
+    parser.barrier().await.unwrap();
+
+    parser.clear(state::ClearScope::Screen).await.unwrap();
+    match parser.query(Query::Screen { format: Format::Plain }).await.unwrap() {
+        QueryResponse::Screen(screen) => {
+            assert!(screen.lines.iter().all(|line| line.text().is_empty()));
+            assert_eq!((screen.cursor.row, screen.cursor.col), (0, 0));
+            // History is untouched.
+            assert!(screen.total_lines > 4);
+        }
+        _ => panic!("expected Screen response"),
+    }
+
+    tx.send(Bytes::from("kept")).await.unwrap();
+    parser.barrier().await.unwrap();
+    parser.clear(state::ClearScope::All).await.unwrap();
+    match parser.query(Query::Screen { format: Format::Plain }).await.unwrap() {
+        QueryResponse::Screen(screen) => {
+            assert!(screen.lines.iter().all(|line| line.text().is_empty()));
+            assert_eq!(screen.total_lines, 4);
+        }
+        _ => panic!("expected Screen response"),
+    }
+}
+
+#[tokio::test]
+async fn test_clear_scrollback_rejected_on_alternate_screen() {
+    let (tx, parser) = spawn_test_parser(20, 4, 100).await;
+    tx.send(Bytes::from("\x1b[?1049hfull screen")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    for scope in [state::ClearScope::Scrollback, state::ClearScope::All] {
+        assert!(matches!(parser.clear(scope).await, Err(ParserError::InvalidQuery(_))));
+    }
+    parser.clear(state::ClearScope::Screen).await.unwrap();
+    assert_eq!(plain_screen_text(&parser).await.trim(), "");
+}
+
+#[tokio::test]
+async fn test_quiet_restore_emits_single_sync() {
+    let (tx, parser) = spawn_test_parser(20, 4, 100).await;
+    tx.send(Bytes::from("$ ")).await.unwrap();
+    parser.barrier().await.unwrap();
+    let mut events = parser.subscribe();
+
+    let saved: String = (0..10).map(|i| format!("old {i}\r\n")).collect();
+    parser.restore(Bytes::from(saved), true).await.unwrap();
+    tx.send(Bytes::from("x")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    let mut received = Vec::new();
+    while let Ok(Some(SubscriptionEvent::Event(event))) =
+        tokio::time::timeout(tokio::time::Duration::from_millis(100), events.next()).await
+    {
+        received.push(event);
+    }
+    match received.first() {
+        Some(Event::Sync { scrollback_lines, screen, .. }) => {
+            assert!(*scrollback_lines > 4);
+            assert!(screen.lines.iter().any(|line| line.text().contains("old 9")));
+        }
+        other => panic!("expected a Sync first, got {other:?}"),
+    }
+    assert!(!received[1..].iter().any(|e| matches!(e, Event::Sync { .. } | Event::Scroll { .. })));
+    // Seqs stay contiguous across the muted stretch.
+    let seqs: Vec<u64> = received.iter().map(|e| e.seq()).collect();
+    assert!(seqs.windows(2).all(|w| w[1] == w[0] + 1), "{seqs:?}");
+
+    // Without `quiet`, followers see it parsed like ordinary output.
+    let mut events = parser.subscribe();
+    parser.restore(Bytes::from("\r\nloud"), false).await.unwrap();
+    let mut lines = 0;
+    while let Ok(Some(SubscriptionEvent::Event(event))) =
+        tokio::time::timeout(tokio::time::Duration::from_millis(100), events.next()).await
+    {
+        assert!(!matches!(event, Event::Sync { .. }));
+        lines += matches!(event, Event::Line { .. }) as usize;
+    }
+    assert!(lines > 0);
+}
+
+#[tokio::test]
+async fn test_cwd_changes_are_coalesced() {
+    let (tx, parser) = spawn_test_parser(80, 24, 100).await;
+    let mut events = parser.subscribe();
+
+    for dir in ["/tmp", "/usr", "/home/me/my%20dir"] {
+        tx.send(Bytes::from(format!("\x1b]7;file://host{dir}\x07$ "))).await.unwrap();
+    }
+    let mut cwds = Vec::new();
+    while let Ok(Some(SubscriptionEvent::Event(event))) =
+        tokio::time::timeout(CWD_DEBOUNCE * 2, events.next()).await
+    {
+        if let Event::Cwd { path, .. } = event {
+            cwds.push(path);
+        }
+    }
+    assert_eq!(cwds, ["/home/me/my dir"]);
+
+    // Reporting the same directory again isn't a change.
+    tx.send(Bytes::from("\x1b]7;file://host/home/me/my%20dir\x07")).await.unwrap();
+    while let Ok(Some(SubscriptionEvent::Event(event))) =
+        tokio::time::timeout(CWD_DEBOUNCE * 2, events.next()).await
+    {
+        assert!(!matches!(event, Event::Cwd { .. }), "unexpected {event:?}");
+    }
+}
+
+#[tokio::test]
+async fn test_resize_during_alternate_screen_switch() {
+    for reflow in [false, true] {
+        let (tx, parser) = spawn_test_parser(80, 24, 100).await;
+        parser.set_reflow_on_resize(reflow);
+        tx.send(Bytes::from("primary text\r\n")).await.unwrap();
+        // The switch is split across chunks with a resize in between.
+        tx.send(Bytes::from("\x1b[?104")).await.unwrap();
+        parser.barrier().await.unwrap();
+        let mut events = parser.subscribe();
+        parser.resize(40, 10).await.unwrap();
+        tx.send(Bytes::from("9hfull screen")).await.unwrap();
+        parser.barrier().await.unwrap();
+
+        match parser.query(Query::Screen { format: Format::Plain }).await.unwrap() {
+            QueryResponse::Screen(screen) => {
+                assert!(screen.alternate_active, "reflow={reflow}");
+                assert_eq!((screen.cols, screen.rows), (40, 10));
+                // Entering keeps the cursor row; the tail of the sequence
+                // must not show up as text.
+                let texts: Vec<_> = screen.lines.iter().map(|line| line.text()).collect();
+                assert!(texts.iter().any(|t| t == "full screen"), "reflow={reflow}: {texts:?}");
+                assert!(!texts.iter().any(|t| t.contains("9h") || t.contains("primary")), "reflow={reflow}: {texts:?}");
+            }
+            _ => panic!("expected Screen response"),
+        }
+
+        // Resize, then the transition: always in that order.
+        let mut reasons = Vec::new();
+        while let Ok(Some(SubscriptionEvent::Event(event))) =
+            tokio::time::timeout(tokio::time::Duration::from_millis(100), events.next()).await
+        {
+            if let Event::Reset { reason, .. } = event {
+                reasons.push(format!("{reason:?}"));
+            }
+        }
+        assert_eq!(reasons, ["Resize", "AlternateScreenEnter"]);
+
+        // The primary screen has the new size too.
+        tx.send(Bytes::from("\x1b[?1049l")).await.unwrap();
+        parser.barrier().await.unwrap();
+        match parser.query(Query::Screen { format: Format::Plain }).await.unwrap() {
+            QueryResponse::Screen(screen) => {
+                assert!(!screen.alternate_active);
+                assert_eq!((screen.cols, screen.rows), (40, 10));
+                assert!(screen.lines.iter().any(|line| line.text() == "primary text"), "reflow={reflow}");
+            }
+            _ => panic!("expected Screen response"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_end_event_when_raw_sender_dropped() {
+    let (tx, parser) = spawn_test_parser(80, 24, 100).await;
+    let mut events = parser.subscribe();
+
+    tx.send(Bytes::from("bye\r\n")).await.unwrap();
+    drop(tx);
+
+    let mut last = None;
+    loop {
+        match tokio::time::timeout(tokio::time::Duration::from_secs(2), events.next()).await {
+            Ok(Some(SubscriptionEvent::Event(Event::End { seq, reason }))) => {
+                assert_eq!(reason, None);
+                assert!(last.is_none_or(|prev| seq > prev), "End must come last");
+                break;
+            }
+            Ok(Some(SubscriptionEvent::Event(event))) => last = Some(event.seq()),
+            Ok(Some(_)) => {}
+            Ok(None) | Err(_) => panic!("no End event after the raw channel closed"),
+        }
+    }
+    // The parser task has exited, so queries fail.
+    assert!(parser.query(Query::Cursor).await.is_err());
+}
+
+async fn plain_screen_text(parser: &Parser) -> String {
+    match parser.query(Query::Screen { format: Format::Plain }).await.unwrap() {
+        QueryResponse::Screen(screen) => screen
+            .lines
+            .iter()
+            .map(|line| serde_json::to_value(line).unwrap().as_str().unwrap_or_default().to_string())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => panic!("expected Screen response"),
+    }
+}
+
+#[tokio::test]
+async fn test_feed_panic_skips_chunk_and_keeps_state() {
+    let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
+    let mut events = parser.subscribe();
+
+    tx.send(bytes::Bytes::from("before\r\n")).await.unwrap();
+    tx.send(bytes::Bytes::from(task::FEED_PANIC_TRIGGER)).await.unwrap();
+    tx.send(bytes::Bytes::from("after")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    let text = plain_screen_text(&parser).await;
+    assert!(text.contains("before"), "screen lost: {text:?}");
+    assert!(text.contains("after"), "parser stopped: {text:?}");
+
+    // Followers get a sync instead of a restart.
+    let mut saw_sync = false;
+    while let Ok(Some(SubscriptionEvent::Event(event))) =
+        tokio::time::timeout(tokio::time::Duration::from_millis(50), events.next()).await
+    {
+        match event {
+            Event::Sync { .. } => saw_sync = true,
+            Event::Reset { reason: events::ResetReason::ParserRestart, .. } => {
+                panic!("a single bad chunk must not restart the parser")
+            }
+            _ => {}
+        }
+    }
+    assert!(saw_sync);
+}
+
+#[tokio::test]
+async fn test_repeated_feed_panics_restart_parser() {
+    let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
+    let mut events = parser.subscribe();
+
+    tx.send(bytes::Bytes::from("before\r\n")).await.unwrap();
+    for _ in 0..3 {
+        tx.send(bytes::Bytes::from(task::FEED_PANIC_TRIGGER)).await.unwrap();
+    }
+    tokio::time::timeout(tokio::time::Duration::from_secs(1), async {
+        loop {
+            if let Some(SubscriptionEvent::Event(Event::Reset {
+                reason: events::ResetReason::ParserRestart,
+                ..
+            })) = events.next().await
+            {
+                return;
+            }
+        }
+    })
+    .await
+    .expect("should restart after repeated panics");
+
+    // Fresh state, but the parser keeps working.
+    tx.send(bytes::Bytes::from("after")).await.unwrap();
+    parser.barrier().await.unwrap();
+    let text = plain_screen_text(&parser).await;
+    assert!(!text.contains("before"));
+    assert!(text.contains("after"));
+}
+
+#[tokio::test]
+async fn test_query_cancellable_aborts_stalled_query() {
+    // A parser whose task never answers: hold the query receiver ourselves.
+    let (query_tx, mut query_rx) = mpsc::channel(32);
+    let parser = Parser {
+        query_tx,
+        event_tx: journal::EventSender::new(tokio::sync::broadcast::channel(16).0, 0),
+        raw_tap: tokio::sync::broadcast::channel(16).0,
+        reflow_on_resize: Default::default(),
+        plain_by_default: Default::default(),
+        max_line_cells: Default::default(),
+        redraw_threshold: Default::default(),
+        end_reason: Default::default(),
+        cols: Default::default(),
+        history_lines: Default::default(),
+        alternate_active: Default::default(),
+        feed_tx: None,
+    };
+
+    let cancel = tokio_util::sync::CancellationToken::new();
+    let canceller = cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        canceller.cancel();
+    });
+
+    let start = tokio::time::Instant::now();
+    let result = parser.query_cancellable(Query::Cursor, &cancel).await;
+    assert!(matches!(result, Err(ParserError::Cancelled)));
+    assert!(start.elapsed() < tokio::time::Duration::from_secs(1), "should not wait for the timeout");
+
+    // The abandoned request is visibly dead, so the parser can skip it.
+    let (_query, _deadline, response_tx) = query_rx.try_recv().unwrap();
+    assert!(response_tx.is_closed());
+}
+
+#[tokio::test]
+async fn test_expired_queries_are_skipped_but_resize_applies() {
+    let (_tx, parser) = spawn_test_parser(80, 24, 1000).await;
+    let expired = tokio::time::Instant::now();
+
+    // The caller is still listening, but its deadline has passed.
+    let (cursor_tx, cursor_rx) = oneshot::channel();
+    parser.query_tx.send((Query::Cursor, expired, cursor_tx)).await.unwrap();
+    let (resize_tx, resize_rx) = oneshot::channel();
+    parser
+        .query_tx
+        .send((Query::Resize { cols: 40, rows: 10, reflow: true }, expired, resize_tx))
+        .await
+        .unwrap();
+
+    assert!(cursor_rx.await.is_err(), "stale read-only query should be dropped unanswered");
+    assert!(matches!(resize_rx.await, Ok(QueryResponse::Ok)));
+    match parser.query(Query::Screen { format: Format::Plain }).await.unwrap() {
+        QueryResponse::Screen(screen) => assert_eq!((screen.cols, screen.rows), (40, 10)),
+        _ => panic!("expected Screen response"),
+    }
+}
+
+#[tokio::test]
+async fn test_rapidly_cancelled_queries_do_not_leak_slots() {
+    let (_tx, parser) = spawn_test_parser(80, 24, 1000).await;
+
+    // Far more abandoned queries than the query channel holds.
+    for _ in 0..500 {
+        let cancel = tokio_util::sync::CancellationToken::new();
+        cancel.cancel();
+        let result = parser
+            .query_cancellable(Query::Screen { format: Format::Styled }, &cancel)
+            .await;
+        assert!(matches!(result, Err(ParserError::Cancelled)));
+
+        // Dropped mid-flight, after the request is already queued.
+        let _ = tokio::time::timeout(
+            tokio::time::Duration::from_micros(1),
+            parser.query(Query::Screen { format: Format::Styled }),
+        )
+        .await;
+    }
+
+    let response = tokio::time::timeout(
+        tokio::time::Duration::from_secs(1),
+        parser.query(Query::Cursor),
+    )
+    .await
+    .expect("query channel should not be clogged by abandoned queries")
+    .unwrap();
+    assert!(matches!(response, QueryResponse::Cursor(_)));
+}
+
+#[tokio::test]
+async fn test_default_color_changes_emit_events() {
+    let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
+    let mut events = parser.subscribe();
+
+    match parser.query(Query::DefaultColors).await.unwrap() {
+        QueryResponse::DefaultColors(colors) => assert_eq!((colors.fg, colors.bg), (None, None)),
+        _ => panic!("expected DefaultColors response"),
+    }
+
+    // Setting the same color twice is one change; the reset is another.
+    tx.send(bytes::Bytes::from("\x1b]11;rgb:00/00/80\x07\x1b]11;rgb:00/00/80\x07")).await.unwrap();
+    tx.send(bytes::Bytes::from("\x1b]111\x07")).await.unwrap();
+
+    let mut changes = Vec::new();
+    tokio::time::timeout(tokio::time::Duration::from_millis(500), async {
+        while changes.len() < 2 {
+            if let Some(SubscriptionEvent::Event(Event::DefaultColors { fg, bg, .. })) = events.next().await {
+                changes.push((fg, bg));
+            }
+        }
+    })
+    .await
+    .expect("should receive DefaultColors events");
+    assert_eq!(changes, [(None, Some(Color::Rgb { r: 0, g: 0, b: 128 })), (None, None)]);
+
+    tx.send(bytes::Bytes::from("\x1b]10;#abcdef\x07")).await.unwrap();
+    parser.barrier().await.unwrap();
+    match parser.query(Query::DefaultColors).await.unwrap() {
+        QueryResponse::DefaultColors(colors) => {
+            assert_eq!(colors.fg, Some(Color::Rgb { r: 0xab, g: 0xcd, b: 0xef }));
+            assert_eq!(colors.bg, None);
+        }
+        _ => panic!("expected DefaultColors response"),
+    }
+}
+
+#[tokio::test]
+async fn test_osc_notifications_emit_notification_events() {
+    let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
+    let mut events = parser.subscribe();
+
+    tx.send(bytes::Bytes::from("\x1b]9;malformed\x1b]9;Build done\x07")).await.unwrap();
+    tx.send(bytes::Bytes::from("\x1b]777;notify;CI;Tests passed\x1b\\")).await.unwrap();
+
+    let mut notifications = Vec::new();
+    tokio::time::timeout(tokio::time::Duration::from_millis(500), async {
+        while notifications.len() < 2 {
+            if let Some(SubscriptionEvent::Event(Event::Notification { title, body, .. })) = events.next().await {
+                notifications.push((title, body));
+            }
+        }
+    })
+    .await
+    .expect("should receive Notification events");
+
+    // The unterminated first sequence is abandoned at the next ESC.
+    assert_eq!(
+        notifications,
+        [(None, "Build done".to_string()), (Some("CI".to_string()), "Tests passed".to_string())]
+    );
+}
+
+#[tokio::test]
+async fn test_xtwinops_resize_emits_resize_request_without_resizing() {
+    let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
+    let mut events = parser.subscribe();
+
+    tx.send(bytes::Bytes::from("\x1b[8;30;100t\x1b[8;;132t")).await.unwrap();
+    tx.send(bytes::Bytes::from("\x1b[8;30;100t")).await.unwrap();
+
+    let mut requests = Vec::new();
+    tokio::time::timeout(tokio::time::Duration::from_millis(500), async {
+        while requests.len() < 2 {
+            if let Some(SubscriptionEvent::Event(Event::ResizeRequest { rows, cols, .. })) = events.next().await {
+                requests.push((rows, cols));
+            }
+        }
+    })
+    .await
+    .expect("should receive ResizeRequest events");
+
+    // One per chunk, the latest request; an omitted size is the current one.
+    assert_eq!(requests, [(24, 132), (30, 100)]);
+
+    match parser.query(Query::Screen { format: Format::Plain }).await.unwrap() {
+        QueryResponse::Screen(screen) => assert_eq!((screen.rows, screen.cols), (24, 80)),
+        _ => panic!("expected Screen response"),
+    }
+}
+
+#[tokio::test]
+async fn test_device_query_event_reports_cursor_at_query() {
+    let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
+    let mut events = parser.subscribe();
+
+    // Text after the query moves the cursor, but the event reports where
+    // it was when the query was written.
+    tx.send(bytes::Bytes::from("ab\x1b[6ncdef\x1bP$qm\x1b\\"))
+        .await
+        .unwrap();
+
+    let mut queries = Vec::new();
+    tokio::time::timeout(tokio::time::Duration::from_millis(500), async {
+        while queries.len() < 2 {
+            if let Some(SubscriptionEvent::Event(Event::DeviceQuery { kind, setting, row, col, .. })) =
+                events.next().await
+            {
+                queries.push((kind, setting, row, col));
+            }
+        }
+    })
+    .await
+    .expect("should receive DeviceQuery events");
+
+    use device_query::DeviceQueryKind;
+    assert_eq!(queries[0], (DeviceQueryKind::CursorPosition, None, 0, 2));
+    assert_eq!(queries[1], (DeviceQueryKind::StatusString, Some("m".to_string()), 0, 6));
+
+    // The query bytes themselves don't end up on screen.
+    match parser.query(Query::Screen { format: Format::Plain }).await.unwrap() {
+        QueryResponse::Screen(screen) => {
+            assert!(matches!(&screen.lines[0], state::FormattedLine::Plain(text) if text == "abcdef"));
+        }
+        _ => panic!("expected Screen response"),
+    }
+}
+
+fn commands(resp: QueryResponse) -> Vec<super::state::CommandInfo> {
+    match resp {
+        QueryResponse::Commands(c) => c.commands,
+        _ => panic!("expected Commands response"),
+    }
+}
+
+#[tokio::test]
+async fn test_commands_tracks_osc133_marks() {
+    let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
+
+    // Typical bash/zsh integration: D before the first prompt, then one
+    // finished command and a second one still running.
+    tx.send(bytes::Bytes::from(
+        "\x1b]133;D\x07\x1b]133;A\x07$ \x1b]133;B\x07echo hi\r\n\x1b]133;C\x07hi\r\n\x1b]133;D;0\x07\
+         \x1b]133;A\x07$ \x1b]133;B\x07sleep 10\r\n\x1b]133;C\x07",
+    ))
+    .await
+    .unwrap();
+    parser.barrier().await.unwrap();
+
+    let cmds = commands(parser.query(Query::Commands).await.unwrap());
+    assert_eq!(cmds.len(), 2);
+    assert_eq!(cmds[0].command_text.as_deref(), Some("echo hi"));
+    assert_eq!(cmds[0].exit_code, Some(0));
+    assert_eq!(cmds[0].start_line, 1);
+    assert_eq!(cmds[0].end_line, Some(2));
+    assert_eq!(cmds[1].command_text.as_deref(), Some("sleep 10"));
+    assert_eq!(cmds[1].exit_code, None);
+    assert_eq!(cmds[1].start_line, 3);
+    assert_eq!(cmds[1].end_line, None);
+}
+
+#[tokio::test]
+async fn test_commands_empty_without_shell_integration() {
+    let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
+
+    tx.send(bytes::Bytes::from("$ ls\r\nfile\r\n$ ")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    assert!(commands(parser.query(Query::Commands).await.unwrap()).is_empty());
+}
+
+#[tokio::test]
+async fn test_commands_lines_follow_scrollback_trimming() {
+    let (tx, parser) = spawn_test_parser(80, 5, 10).await;
+
+    tx.send(bytes::Bytes::from("\x1b]133;A\x07$ \x1b]133;B\x07old\r\n\x1b]133;C\x07\x1b]133;D;1\x07"))
+        .await
+        .unwrap();
+    let mut filler = String::new();
+    for i in 0..30 {
+        filler.push_str(&format!("line {i}\r\n"));
+    }
+    tx.send(bytes::Bytes::from(filler)).await.unwrap();
+    tx.send(bytes::Bytes::from("\x1b]133;A\x07$ \x1b]133;B\x07new\r\n\x1b]133;C\x07out\r\n\x1b]133;D;0\x07"))
+        .await
+        .unwrap();
+    parser.barrier().await.unwrap();
+
+    // The first command scrolled out of the buffer; the second is addressed
+    // relative to what's still there.
+    let cmds = commands(parser.query(Query::Commands).await.unwrap());
+    assert_eq!(cmds.len(), 1);
+    assert_eq!(cmds[0].command_text.as_deref(), Some("new"));
+    let resp = parser
+        .query(Query::Scrollback { format: Format::Plain, offset: cmds[0].start_line, limit: 1, from_end: false })
+        .await
+        .unwrap();
+    match resp {
+        QueryResponse::Scrollback(s) => {
+            assert!(matches!(&s.lines[0], state::FormattedLine::Plain(text) if text == "out"));
+        }
+        _ => panic!("expected Scrollback response"),
+    }
+}
+
+#[tokio::test]
+async fn test_latin1_decoding_maps_bytes_to_code_points() {
+    let (tx, rx) = mpsc::channel(TEST_PARSER_CHANNEL_CAPACITY);
+    let parser = Parser::spawn_with_decoding(rx, 80, 24, 1000, decode::Decoding::Latin1);
+
+    tx.send(bytes::Bytes::from_static(b"caf\xe9 \xb0C")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    match parser.query(Query::Screen { format: Format::Plain }).await.unwrap() {
+        QueryResponse::Screen(s) => {
+            assert!(matches!(&s.lines[0], state::FormattedLine::Plain(text) if text == "café °C"));
+        }
+        _ => panic!("expected Screen response"),
+    }
+}
+
+#[tokio::test]
+async fn test_subscribe_raw_correlates_chunks_with_events() {
+    let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
+    let mut raw = Box::pin(parser.subscribe_raw());
+    let mut events = Box::pin(parser.subscribe());
+
+    tx.send(bytes::Bytes::from_static(b"hello")).await.unwrap();
+    tx.send(bytes::Bytes::from_static(b"\r\nworld")).await.unwrap();
+
+    let mut chunks = Vec::new();
+    while chunks.len() < 2 {
+        match tokio::time::timeout(tokio::time::Duration::from_secs(1), raw.next()).await {
+            Ok(Some(RawTapEvent::Chunk(chunk))) => chunks.push(chunk),
+            other => panic!("expected raw chunk, got {:?}", other),
+        }
+    }
+    let (first, second) = (&chunks[0], &chunks[1]);
+    assert_eq!(first.bytes.as_ref(), b"hello");
+    assert_eq!(first.offset, 0);
+    assert_eq!(second.bytes.as_ref(), b"\r\nworld");
+    assert_eq!(second.offset, 5);
+    assert_eq!(second.seq_before, first.seq_after);
+    assert!(first.seq_after > first.seq_before, "first chunk should produce events");
+
+    // The events arrive in the seq ranges their chunks report.
+    for _ in first.seq_before..second.seq_after {
+        let event = match events.next().await {
+            Some(SubscriptionEvent::Event(event)) => event,
+            other => panic!("expected event, got {:?}", other),
+        };
+        let seq = serde_json::to_value(&event).unwrap()["seq"].as_u64().unwrap();
+        assert!(seq > first.seq_before && seq <= second.seq_after);
+    }
+}
+
+#[tokio::test]
+async fn test_barrier_waits_for_queued_chunks() {
+    let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
+
+    // Nothing queued: answered right away.
+    parser.barrier().await.unwrap();
+
+    for i in 0..200 {
+        tx.send(bytes::Bytes::from(format!("\r\x1b[Kchunk {i}"))).await.unwrap();
+    }
+    parser.barrier().await.unwrap();
+
+    match parser.query(Query::Screen { format: Format::Plain }).await.unwrap() {
+        QueryResponse::Screen(s) => {
+            assert!(matches!(&s.lines[0], state::FormattedLine::Plain(text) if text == "chunk 199"));
+        }
+        _ => panic!("expected Screen response"),
+    }
+}
+
+async fn screen_text(parser: &Parser) -> Vec<String> {
+    match parser.query(Query::Screen { format: Format::Plain }).await.unwrap() {
+        QueryResponse::Screen(screen) => screen
+            .lines
+            .into_iter()
+            .map(|line| match line {
+                state::FormattedLine::Plain(text) => text,
+                other => panic!("expected plain line, got {:?}", other),
+            })
+            .collect(),
+        other => panic!("expected Screen response, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_zero_size_resize_is_clamped_without_restart() {
+    for reflow in [true, false] {
+        let (tx, parser) = spawn_test_parser(10, 4, 100).await;
+        parser.set_reflow_on_resize(reflow);
+        tx.send(Bytes::from("kept")).await.unwrap();
+        parser.barrier().await.unwrap();
+        let mut events = parser.subscribe();
+
+        parser.resize(0, 0).await.unwrap();
+        match parser.query(Query::Screen { format: Format::Plain }).await.unwrap() {
+            QueryResponse::Screen(screen) => assert_eq!((screen.cols, screen.rows), (1, 1), "reflow={reflow}"),
+            _ => panic!("expected Screen response"),
+        }
+        tx.send(Bytes::from("more")).await.unwrap();
+        parser.barrier().await.unwrap();
+        parser.resize(10, 4).await.unwrap();
+        let text = screen_text(&parser).await.concat();
+        assert!(text.contains("more"), "reflow={reflow}: {text:?}");
+
+        while let Ok(Some(SubscriptionEvent::Event(event))) =
+            tokio::time::timeout(tokio::time::Duration::from_millis(100), events.next()).await
+        {
+            assert!(
+                !matches!(event, Event::Reset { reason: events::ResetReason::ParserRestart, .. }),
+                "reflow={reflow}: parser restarted"
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_resize_reflows_wrapped_lines_by_default() {
+    let (tx, parser) = spawn_test_parser(10, 4, 100).await;
+    tx.send(Bytes::from("0123456789abcdef")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    // Rows re-wrapped above the cursor's row move into scrollback.
+    parser.resize(5, 4).await.unwrap();
+    assert_eq!(screen_text(&parser).await, vec!["abcde", "f", "", ""]);
+
+    parser.resize(20, 4).await.unwrap();
+    assert_eq!(screen_text(&parser).await, vec!["0123456789abcdef", "", "", ""]);
+}
+
+#[tokio::test]
+async fn test_resize_without_reflow_truncates_and_pads_rows() {
+    let (tx, parser) = spawn_test_parser(10, 4, 100).await;
+    parser.set_reflow_on_resize(false);
+    tx.send(Bytes::from("0123456789abcdef")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    parser.resize(5, 4).await.unwrap();
+    assert_eq!(screen_text(&parser).await, vec!["01234", "abcde", "", ""]);
+
+    // Cut-off text is gone; rows stay rows when widening again.
+    parser.resize(20, 4).await.unwrap();
+    assert_eq!(screen_text(&parser).await, vec!["01234", "abcde", "", ""]);
+
+    // The cursor stays on its row and output continues from there.
+    tx.send(Bytes::from("\r\nnext")).await.unwrap();
+    parser.barrier().await.unwrap();
+    assert_eq!(screen_text(&parser).await, vec!["01234", "abcde", "next", ""]);
+}
+
+#[tokio::test]
+async fn test_resize_without_reflow_with_cursor_above_content() {
+    let (tx, parser) = spawn_test_parser(80, 24, 100).await;
+    parser.set_reflow_on_resize(false);
+    let rows: Vec<String> = (0..24).map(|i| format!("row {i}")).collect();
+    tx.send(Bytes::from(format!("{}\x1b[H", rows.join("\r\n")))).await.unwrap();
+    parser.barrier().await.unwrap();
+    let mut events = parser.subscribe();
+
+    parser.resize(80, 10).await.unwrap();
+    assert_eq!(screen_text(&parser).await, rows[14..].to_vec());
+
+    // The cursor is parked on the top row.
+    tx.send(Bytes::from("X")).await.unwrap();
+    parser.barrier().await.unwrap();
+    assert_eq!(screen_text(&parser).await[0], "Xow 14");
+    while let Ok(Some(SubscriptionEvent::Event(event))) =
+        tokio::time::timeout(tokio::time::Duration::from_millis(100), events.next()).await
+    {
+        assert!(
+            !matches!(event, Event::Reset { reason: events::ResetReason::ParserRestart, .. }),
+            "parser restarted"
+        );
+    }
+}
+
+/// Style of the span holding `text` on screen, or `None` if no span does.
+async fn span_style(parser: &Parser, text: &str) -> Option<state::Style> {
+    match parser.query(Query::Screen { format: Format::Styled }).await.unwrap() {
+        QueryResponse::Screen(screen) => screen.lines.into_iter().find_map(|line| match line {
+            state::FormattedLine::Styled(spans) => spans.into_iter().find(|span| span.text == text).map(|span| span.style),
+            other => panic!("expected styled line, got {:?}", other),
+        }),
+        other => panic!("expected Screen response, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_resize_without_reflow_completes_split_sgr() {
+    let (tx, parser) = spawn_test_parser(80, 24, 100).await;
+    parser.set_reflow_on_resize(false);
+    tx.send(Bytes::from("plain \x1b[3")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    parser.resize(40, 10).await.unwrap();
+    tx.send(Bytes::from("1mred")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    assert_eq!(screen_text(&parser).await[0], "plain red");
+    let style = span_style(&parser, "red").await.expect("red span");
+    assert_eq!(style.fg, Some(Color::Indexed(1)));
+}
+
+#[tokio::test]
+async fn test_resize_without_reflow_keeps_terminal_state() {
+    let (tx, parser) = spawn_test_parser(20, 10, 100).await;
+    parser.set_reflow_on_resize(false);
+    // Bold green pen, no auto-wrap, one tab stop at column 4, margins on
+    // rows 2-5, insert mode.
+    tx.send(Bytes::from("\x1b[1;32m\x1b[?7l\x1b[3g\x1b[5G\x1bH\x1b[2;5r\x1b[4h\x1b[1;1Habc\x1b[1;1H"))
+        .await
+        .unwrap();
+    parser.barrier().await.unwrap();
+
+    parser.resize(12, 10).await.unwrap();
+    // Insert mode pushes "abc" right; the tab stops at column 4, not 8.
+    tx.send(Bytes::from("x\ty")).await.unwrap();
+    // A line feed on the bottom margin scrolls rows 2-5 only.
+    tx.send(Bytes::from("\x1b[2;1Htop\x1b[6;1Hout\x1b[5;1H\n")).await.unwrap();
+    // No auto-wrap: the long line overwrites the last column.
+    tx.send(Bytes::from("\x1b[9;1H0123456789abcdefgh")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    let text = screen_text(&parser).await;
+    assert_eq!(text[0], "xabcy");
+    assert_eq!(text[8], "0123456789ah");
+    assert_eq!((text[1].as_str(), text[5].as_str()), ("", "out"));
+    let style = span_style(&parser, "xabcy").await.expect("first row span");
+    assert_eq!((style.fg, style.bold), (Some(Color::Indexed(2)), true));
+}
+
+#[tokio::test]
+async fn test_spawn_with_feed_accepts_output_directly() {
+    let parser = Parser::spawn_with_feed(80, 24, 1000);
+    parser.feed(Bytes::from("hello ")).await.unwrap();
+    parser.try_feed(Bytes::from("world")).unwrap();
+    parser.barrier().await.unwrap();
+    assert_eq!(screen_text(&parser).await[0], "hello world");
+
+    // A parser fed by someone else's channel has nothing to feed.
+    let (_tx, channel_parser) = spawn_test_parser(80, 24, 1000).await;
+    assert!(matches!(channel_parser.feed(Bytes::from("x")).await, Err(ParserError::FeedUnavailable)));
+}
+
+#[tokio::test]
+async fn test_resize_and_screen_returns_resized_snapshot() {
+    let (tx, parser) = spawn_test_parser(10, 4, 100).await;
+    tx.send(Bytes::from("0123456789abcdef")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    let screen = parser.resize_and_screen(20, 3, Format::Plain).await.unwrap();
+    assert_eq!((screen.cols, screen.rows), (20, 3));
+    assert!(matches!(&screen.lines[0], state::FormattedLine::Plain(text) if text == "0123456789abcdef"));
+    assert_eq!(screen.lines.len(), 3);
+}
+
+#[tokio::test]
+async fn test_megabyte_without_newline_wraps_and_stays_bounded() {
+    let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
+    let chunk = Bytes::from(vec![b'x'; 64 * 1024]);
+    let start = std::time::Instant::now();
+    for _ in 0..16 {
+        tx.send(chunk.clone()).await.unwrap();
+    }
+    parser.barrier().await.unwrap();
+
+    // Every row wraps at the terminal width and scrollback stays capped.
+    let screen = screen_text(&parser).await;
+    assert!(screen.iter().all(|line| line.len() <= 80));
+    assert_eq!(screen[0], "x".repeat(80));
+    match parser
+        .query(Query::Scrollback { format: Format::Plain, offset: 0, limit: usize::MAX, from_end: false })
+        .await
+        .unwrap()
+    {
+        QueryResponse::Scrollback(scrollback) => {
+            assert!(scrollback.total_lines <= 1000 + 24);
+            assert!(scrollback.lines.iter().all(|line| matches!(
+                line,
+                state::FormattedLine::Plain(text) if text.len() <= 80
+            )));
+        }
+        other => panic!("expected Scrollback response, got {:?}", other),
+    }
+    assert!(start.elapsed() < std::time::Duration::from_secs(10), "took {:?}", start.elapsed());
+}
+
+#[tokio::test]
+async fn test_max_line_cells_caps_serialized_rows() {
+    let (tx, parser) = spawn_test_parser(10, 4, 100).await;
+    assert_eq!(parser.max_line_cells(), DEFAULT_MAX_LINE_CELLS);
+    parser.set_max_line_cells(4);
+    tx.send(Bytes::from("0123456789ab")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    // Rows still wrap at 10 columns; only their output is cut at 4 cells.
+    assert_eq!(screen_text(&parser).await, vec!["0123", "ab", "", ""]);
+}
+
+#[tokio::test]
+async fn test_scrollback_offset_from_start_and_from_end() {
+    let (tx, parser) = spawn_test_parser(80, 3, 100).await;
+    let text: Vec<String> = (0..10).map(|i| format!("l{}", i)).collect();
+    tx.send(Bytes::from(text.join("\r\n"))).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    let page = |offset, limit, from_end| {
+        let parser = parser.clone();
+        async move {
+            match parser
+                .query(Query::Scrollback { format: Format::Plain, offset, limit, from_end })
+                .await
+                .unwrap()
+            {
+                QueryResponse::Scrollback(sb) => {
+                    assert_eq!(sb.total_lines, 10);
+                    let lines: Vec<String> = sb
+                        .lines
+                        .into_iter()
+                        .map(|line| match line {
+                            state::FormattedLine::Plain(text) => text,
+                            other => panic!("expected plain line, got {:?}", other),
+                        })
+                        .collect();
+                    (sb.offset, lines)
+                }
+                other => panic!("expected Scrollback response, got {:?}", other),
+            }
+        }
+    };
+
+    assert_eq!(page(2, 3, false).await, (2, vec!["l2".into(), "l3".into(), "l4".into()]));
+
+    // The most recent lines, with the starting index reported back.
+    assert_eq!(page(0, 3, true).await, (7, vec!["l7".into(), "l8".into(), "l9".into()]));
+    assert_eq!(page(2, 3, true).await, (5, vec!["l5".into(), "l6".into(), "l7".into()]));
+    // Windows running past the oldest line are cut short.
+    assert_eq!(page(8, 5, true).await, (0, vec!["l0".into(), "l1".into()]));
+    assert_eq!(page(20, 5, true).await, (0, vec![]));
+}
+
+#[tokio::test]
+async fn test_resize_emits_reset_then_sync() {
+    let (tx, parser) = spawn_test_parser(80, 24, 100).await;
+    tx.send(Bytes::from("hello")).await.unwrap();
+    parser.barrier().await.unwrap();
+
+    let mut events = Box::pin(parser.subscribe());
+    parser.resize(40, 10).await.unwrap();
+
+    let mut received = Vec::new();
+    while received.len() < 2 {
+        match tokio::time::timeout(tokio::time::Duration::from_secs(1), events.next()).await {
+            Ok(Some(SubscriptionEvent::Event(event))) => received.push(event),
+            other => panic!("expected an event, got {:?}", other),
+        }
+    }
+
+    let reset_seq = match &received[0] {
+        Event::Reset { seq, reason: events::ResetReason::Resize } => *seq,
+        other => panic!("expected resize reset, got {:?}", other),
+    };
+    match &received[1] {
+        Event::Sync { seq, screen, .. } => {
+            assert_eq!(*seq, reset_seq + 1);
+            assert_eq!((screen.cols, screen.rows), (40, 10));
+            assert_eq!(screen.lines.len(), 10);
+        }
+        other => panic!("expected sync, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_event_schema_covers_every_variant() {
+    let schema = Parser::event_schema().to_string();
+    for tag in [
+        "line", "cursor", "mode", "scroll_region", "scroll", "reset", "sync", "diff", "idle", "running",
+        "device_query", "resize_request", "default_colors", "notification", "heartbeat", "cwd", "end",
+    ] {
+        assert!(schema.contains(&format!("\"const\":\"{tag}\"")), "missing {tag} in {schema}");
+    }
+}
@@ -382,7 +382,12 @@ pub struct ManageTagsResponseMsg {
 
 /// Client → Server: request to shut down the server.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ShutdownServerMsg {}
+pub struct ShutdownServerMsg {
+    /// The server is being restarted and will be back shortly; clients
+    /// are told to reconnect sooner than after a plain stop.
+    #[serde(default)]
+    pub restart: bool,
+}
 
 /// Server → Client: acknowledgment before shutdown.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,28 +19,95 @@ pub enum PtyError {
     #[error("failed to resize pty: {0}")]
     Resize(#[source] anyhow::Error),
 
+    #[error("failed to get pty size: {0}")]
+    GetSize(#[source] anyhow::Error),
+
     #[error("failed to wait for child: {0}")]
     Wait(#[from] std::io::Error),
 }
 
+impl PtyError {
+    /// Whether opening the PTY failed because the process or system ran out
+    /// of file descriptors (EMFILE/ENFILE) or PTYs (ENOSPC). Retrying won't
+    /// help until limits are raised or other sessions exit.
+    pub fn is_resource_exhausted(&self) -> bool {
+        match self {
+            PtyError::OpenPty(e) => {
+                os_error_code(e).is_some_and(|code| matches!(code, libc::EMFILE | libc::ENFILE | libc::ENOSPC))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The OS error code behind `err`. portable_pty reports openpty failures
+/// as text (`failed to openpty: Os { code: 24, .. }`), so fall back to
+/// parsing that when there's no `io::Error` in the chain.
+fn os_error_code(err: &anyhow::Error) -> Option<i32> {
+    if let Some(code) = err
+        .chain()
+        .find_map(|e| e.downcast_ref::<std::io::Error>())
+        .and_then(|e| e.raw_os_error())
+    {
+        return Some(code);
+    }
+    let text = err.to_string();
+    let rest = &text[text.find("code: ")? + "code: ".len()..];
+    let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    rest[..end].parse().ok()
+}
+
 /// Configuration for what command to spawn in the PTY.
 #[derive(Debug, Clone)]
 pub enum SpawnCommand {
     /// Spawn the user's shell ($SHELL or /bin/sh fallback).
-    /// The bool indicates whether to force interactive mode (-i flag).
-    /// An optional shell path overrides $SHELL.
-    Shell { interactive: bool, shell: Option<String> },
+    /// `interactive` asks for interactive mode (-i flag); it is left off
+    /// for shells known to reject it, unless `force_interactive` says
+    /// otherwise. An optional shell path overrides $SHELL. `login` starts
+    /// it as a login shell (-l flag) so profile files are sourced.
+    /// `scrub_env` is as for `Command`.
+    Shell {
+        interactive: bool,
+        shell: Option<String>,
+        login: bool,
+        force_interactive: Option<bool>,
+        scrub_env: Option<Vec<String>>,
+    },
     /// Spawn a command via `sh -c 'command'`.
-    /// The bool indicates whether to force interactive mode (-i flag).
-    Command { command: String, interactive: bool },
+    /// The bool indicates whether to force interactive mode (-i flag);
+    /// `force_interactive`, when set, takes precedence. `scrub_env` names
+    /// inherited variables to remove before spawning, [`DEFAULT_SCRUB_ENV`]
+    /// when `None`.
+    Command { command: String, interactive: bool, force_interactive: Option<bool>, scrub_env: Option<Vec<String>> },
 }
 
 impl Default for SpawnCommand {
     fn default() -> Self {
-        Self::Shell { interactive: false, shell: None }
+        Self::Shell { interactive: false, shell: None, login: false, force_interactive: None, scrub_env: None }
     }
 }
 
+/// Variables removed from the inherited environment by default. They tell
+/// programs they're running inside tmux, screen, or a particular terminal
+/// app, which stops being true inside a wsh session: tmux refuses to nest,
+/// and tools pick features for a terminal that isn't there.
+pub const DEFAULT_SCRUB_ENV: &[&str] = &["TMUX", "TMUX_PANE", "STY", "TERM_PROGRAM", "TERM_PROGRAM_VERSION"];
+
+/// Login shells known to reject `-i`: restricted shells that only accept
+/// their own arguments, and placeholders that refuse any login. They're
+/// started without it unless forced.
+const REJECTS_INTERACTIVE_FLAG: &[&str] = &["git-shell", "rssh", "scponly", "nologin", "false"];
+
+/// Whether the shell at `path` takes `-i`, i.e. isn't one of
+/// [`REJECTS_INTERACTIVE_FLAG`].
+fn accepts_interactive_flag(path: &str) -> bool {
+    let name = std::path::Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(path);
+    !REJECTS_INTERACTIVE_FLAG.contains(&name.trim_start_matches('-'))
+}
+
 pub struct Pty {
     // Only the master side is retained. The slave side is dropped after
     // spawn (see `spawn_with_cmd`) so that the kernel PTY master read
@@ -94,29 +161,57 @@ impl Pty {
         let term = std::env::var("TERM").unwrap_or_else(|_| "xterm-256color".to_string());
 
         let mut cmd = match spawn_cmd {
-            SpawnCommand::Shell { interactive, shell } => {
+            SpawnCommand::Shell { interactive, shell, login, force_interactive, .. } => {
                 let shell_path = match shell {
                     Some(s) => s.clone(),
                     None => std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string()),
                 };
+                let interactive = match force_interactive {
+                    Some(force) => *force,
+                    None if *interactive && !accepts_interactive_flag(&shell_path) => {
+                        tracing::info!(
+                            shell = %shell_path,
+                            "not passing -i to a shell that rejects it; set force_interactive to override"
+                        );
+                        false
+                    }
+                    None => *interactive,
+                };
                 let mut cmd = CommandBuilder::new(&shell_path);
-                if *interactive {
+                // A `-`-prefixed argv0 would be the traditional way, but
+                // CommandBuilder uses argv[0] as the program to exec too.
+                // `-l` is understood by sh, bash, zsh, ksh and fish.
+                if *login {
+                    cmd.arg("-l");
+                }
+                if interactive {
                     cmd.arg("-i");
                 }
+                tracing::debug!(shell = %shell_path, login = *login, interactive, "spawning shell");
                 cmd
             }
-            SpawnCommand::Command { command, interactive } => {
+            SpawnCommand::Command { command, interactive, force_interactive, .. } => {
+                let interactive = force_interactive.unwrap_or(*interactive);
                 let mut cmd = CommandBuilder::new("/bin/sh");
-                if *interactive {
+                if interactive {
                     cmd.arg("-ic");
                 } else {
                     cmd.arg("-c");
                 }
                 cmd.arg(command);
+                tracing::debug!(interactive, "spawning command via /bin/sh");
                 cmd
             }
         };
 
+        let scrub_env = match spawn_cmd {
+            SpawnCommand::Shell { scrub_env, .. } | SpawnCommand::Command { scrub_env, .. } => scrub_env,
+        };
+        match scrub_env {
+            Some(names) => names.iter().for_each(|name| cmd.env_remove(name)),
+            None => DEFAULT_SCRUB_ENV.iter().for_each(|name| cmd.env_remove(name)),
+        }
+
         cmd.env("TERM", term);
         cmd
     }
@@ -138,6 +233,12 @@ impl Pty {
         }).map_err(PtyError::Resize)
     }
 
+    /// The PTY's current size as (rows, cols), as the kernel reports it.
+    pub fn size(&self) -> Result<(u16, u16), PtyError> {
+        let size = self.master.get_size().map_err(PtyError::GetSize)?;
+        Ok((size.rows, size.cols))
+    }
+
     pub fn take_child(&mut self) -> Option<Box<dyn portable_pty::Child + Send + Sync>> {
         self.child.take()
     }
@@ -158,6 +259,28 @@ mod tests {
     use std::thread;
     use std::time::Duration;
 
+    #[test]
+    fn fd_exhaustion_on_open_is_resource_exhausted() {
+        // As portable_pty reports it: the io::Error formatted into the text.
+        for code in [libc::EMFILE, libc::ENFILE, libc::ENOSPC] {
+            let err = PtyError::OpenPty(anyhow::anyhow!(
+                "failed to openpty: {:?}",
+                std::io::Error::from_raw_os_error(code)
+            ));
+            assert!(err.is_resource_exhausted(), "code {code}");
+        }
+        let wrapped = PtyError::OpenPty(std::io::Error::from_raw_os_error(libc::EMFILE).into());
+        assert!(wrapped.is_resource_exhausted());
+
+        let other = PtyError::OpenPty(anyhow::anyhow!(
+            "failed to openpty: {:?}",
+            std::io::Error::from_raw_os_error(libc::EACCES)
+        ));
+        assert!(!other.is_resource_exhausted());
+        let spawn = PtyError::SpawnCommand(std::io::Error::from_raw_os_error(libc::EMFILE).into());
+        assert!(!spawn.is_resource_exhausted());
+    }
+
     /// Helper to read from PTY with a timeout to avoid blocking forever.
     /// Returns the bytes read, or an empty vec if timeout occurred.
     fn read_with_timeout(
@@ -201,16 +324,108 @@ mod tests {
         let pty = Pty::spawn(24, 80, SpawnCommand::Command {
             command: "echo hello".to_string(),
             interactive: false,
+            force_interactive: None,
+            scrub_env: None,
         });
         assert!(pty.is_ok(), "Failed to spawn PTY with command: {:?}", pty.err());
     }
 
     #[test]
     fn test_spawn_interactive_shell() {
-        let pty = Pty::spawn(24, 80, SpawnCommand::Shell { interactive: true, shell: None });
+        let pty = Pty::spawn(24, 80, SpawnCommand::Shell { interactive: true, shell: None, login: false, force_interactive: None, scrub_env: None });
         assert!(pty.is_ok(), "Failed to spawn interactive shell: {:?}", pty.err());
     }
 
+    #[test]
+    fn test_login_shell_passes_login_flag() {
+        let cmd = Pty::build_command(&SpawnCommand::Shell {
+            interactive: true,
+            shell: Some("/bin/sh".to_string()),
+            login: true,
+            force_interactive: None,
+            scrub_env: None,
+        });
+        assert_eq!(cmd.get_argv(), &["/bin/sh", "-l", "-i"]);
+
+        let cmd = Pty::build_command(&SpawnCommand::default());
+        assert!(!cmd.get_argv().iter().any(|a| a == "-l"));
+    }
+
+    #[test]
+    fn test_interactive_flag_skipped_for_shells_that_reject_it() {
+        let shell = |path: &str, force_interactive| {
+            Pty::build_command(&SpawnCommand::Shell {
+                interactive: true,
+                shell: Some(path.to_string()),
+                login: false,
+                force_interactive,
+                scrub_env: None,
+            })
+        };
+        assert_eq!(shell("/usr/bin/fish", None).get_argv(), &["/usr/bin/fish", "-i"]);
+        assert_eq!(shell("/usr/bin/nu", None).get_argv(), &["/usr/bin/nu", "-i"]);
+        assert_eq!(shell("/usr/bin/git-shell", None).get_argv(), &["/usr/bin/git-shell"]);
+        assert_eq!(shell("/usr/bin/git-shell", Some(true)).get_argv(), &["/usr/bin/git-shell", "-i"]);
+        assert_eq!(shell("/bin/bash", Some(false)).get_argv(), &["/bin/bash"]);
+
+        let cmd = Pty::build_command(&SpawnCommand::Command {
+            command: "true".to_string(),
+            interactive: true,
+            force_interactive: Some(false),
+            scrub_env: None,
+        });
+        assert_eq!(cmd.get_argv(), &["/bin/sh", "-c", "true"]);
+    }
+
+    #[test]
+    fn test_scrub_env_removes_inherited_variables() {
+        let build = |scrub_env| {
+            Pty::build_command(&SpawnCommand::Command {
+                command: "true".to_string(),
+                interactive: false,
+                force_interactive: None,
+                scrub_env,
+            })
+        };
+        let cmd = build(None);
+        for name in DEFAULT_SCRUB_ENV {
+            assert!(cmd.get_env(name).is_none(), "{name} should be scrubbed");
+        }
+        assert!(cmd.get_env("PATH").is_some());
+
+        let cmd = build(Some(vec!["PATH".to_string(), "TERM".to_string()]));
+        assert!(cmd.get_env("PATH").is_none());
+        // TERM is set after scrubbing, so it's always present.
+        assert!(cmd.get_env("TERM").is_some());
+    }
+
+    #[test]
+    fn test_login_shell_sources_profile() {
+        let home = tempfile::tempdir().unwrap();
+        let marker = home.path().join("profile_sourced");
+        std::fs::write(
+            home.path().join(".profile"),
+            format!("echo yes > '{}'\n", marker.display()),
+        )
+        .unwrap();
+
+        let mut cmd = Pty::build_command(&SpawnCommand::Shell {
+            interactive: true,
+            shell: Some("/bin/sh".to_string()),
+            login: true,
+            force_interactive: None,
+            scrub_env: None,
+        });
+        cmd.env("HOME", home.path());
+        let _pty = Pty::spawn_with_cmd(24, 80, cmd).expect("Failed to spawn login shell");
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while !marker.exists() && std::time::Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert!(marker.exists(), "login shell did not source ~/.profile");
+    }
+
     #[test]
     fn test_take_reader_returns_handle() {
         let pty = Pty::spawn(24, 80, SpawnCommand::default()).expect("Failed to spawn PTY");
@@ -258,6 +473,8 @@ mod tests {
         let pty = Pty::spawn(24, 80, SpawnCommand::Command {
             command: format!("echo {}", marker),
             interactive: false,
+            force_interactive: None,
+            scrub_env: None,
         }).expect("Failed to spawn PTY with command");
 
         let reader = pty.take_reader().expect("Failed to get reader");
@@ -12,8 +12,9 @@ use std::path::{Path, PathBuf};
 use std::time::Duration;
 use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio::net::UnixListener;
-use tracing;
+use tracing::{self, Instrument};
 
+use crate::broker::MirrorError;
 use crate::panel::layout::compute_layout;
 use crate::protocol::*;
 use crate::pty::SpawnCommand;
@@ -59,7 +60,7 @@ pub async fn serve(
     socket_path: &Path,
     cancel: tokio_util::sync::CancellationToken,
     token: Option<String>,
-    shutdown_request: tokio_util::sync::CancellationToken,
+    shutdown_request: crate::shutdown::ShutdownRequest,
 ) -> io::Result<()> {
     // Remove stale socket file if it exists. When used with
     // acquire_instance_lock(), the caller has already proven exclusive
@@ -165,7 +166,7 @@ async fn handle_client<S: AsyncRead + AsyncWrite + Unpin>(
     mut stream: S,
     sessions: SessionRegistry,
     token: Option<String>,
-    shutdown_request: tokio_util::sync::CancellationToken,
+    shutdown_request: crate::shutdown::ShutdownRequest,
 ) -> io::Result<()> {
     // Read initial control frame (with timeout to reject idle connections)
     let frame = tokio::time::timeout(
@@ -214,7 +215,10 @@ async fn handle_client<S: AsyncRead + AsyncWrite + Unpin>(
             handle_manage_tags(&mut stream, sessions, msg).await
         }
         FrameType::ShutdownServer => {
-            handle_shutdown_server(&mut stream, shutdown_request).await
+            let msg: ShutdownServerMsg = frame.parse_json().map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, e)
+            })?;
+            handle_shutdown_server(&mut stream, shutdown_request, msg).await
         }
         other => {
             let err = ErrorMsg {
@@ -245,6 +249,8 @@ async fn handle_create_session<S: AsyncRead + AsyncWrite + Unpin>(
         Some(cmd) => SpawnCommand::Command {
             command: cmd.clone(),
             interactive: true,
+            force_interactive: None,
+            scrub_env: None,
         },
         None => SpawnCommand::default(),
     };
@@ -259,10 +265,14 @@ async fn handle_create_session<S: AsyncRead + AsyncWrite + Unpin>(
     let cols = msg.cols.max(1);
 
     let name_for_spawn = msg.name.clone().unwrap_or_default();
-    let cwd = msg.cwd;
-    let env = msg.env;
+    let options = crate::session::SpawnOptions {
+        cwd: msg.cwd,
+        env: msg.env,
+        read_buffer_bytes: sessions.read_buffer_bytes(),
+        ..Default::default()
+    };
     let (session, child_exit_rx) = tokio::task::spawn_blocking(move || {
-        Session::spawn_with_options(name_for_spawn, command, rows, cols, cwd, env)
+        Session::spawn_with_options(name_for_spawn, command, rows, cols, options)
     })
     .await
     .map_err(io::Error::other)?
@@ -305,7 +315,7 @@ async fn handle_create_session<S: AsyncRead + AsyncWrite + Unpin>(
     send_initial_visual_state(stream, &session).await?;
 
     // Enter streaming loop
-    run_streaming(stream, &session).await
+    run_streaming(stream, &session).instrument(session.log_span()).await
 }
 
 /// Handle an AttachSession request: look up session and enter streaming.
@@ -334,10 +344,7 @@ async fn handle_attach_session<S: AsyncRead + AsyncWrite + Unpin>(
     // Resize session to match client terminal (clamp to min 1 to avoid zero-size PTY)
     let rows = msg.rows.max(1);
     let cols = msg.cols.max(1);
-    if let Err(e) = session.pty.lock().resize(rows, cols) {
-        tracing::warn!(?e, "failed to resize PTY on attach");
-    }
-    if let Err(e) = session.parser.resize(cols as usize, rows as usize).await {
+    if let Err(e) = session.resize(rows, cols).await {
         tracing::warn!(?e, "failed to resize parser on attach");
     }
 
@@ -358,6 +365,7 @@ async fn handle_attach_session<S: AsyncRead + AsyncWrite + Unpin>(
                     format: Format::Styled,
                     offset: 0,
                     limit,
+                    from_end: false,
                 }),
             ).await {
                 Ok(Ok(QueryResponse::Scrollback(sb))) => {
@@ -420,7 +428,7 @@ async fn handle_attach_session<S: AsyncRead + AsyncWrite + Unpin>(
     send_initial_visual_state(stream, &session).await?;
 
     // Enter streaming loop
-    run_streaming(stream, &session).await
+    run_streaming(stream, &session).instrument(session.log_span()).await
 }
 
 /// Handle a ListSessions request: return all session names and disconnect.
@@ -599,14 +607,15 @@ async fn handle_manage_tags<S: AsyncRead + AsyncWrite + Unpin>(
 /// Handle a ShutdownServer request: ack then cancel the server.
 async fn handle_shutdown_server<S: AsyncRead + AsyncWrite + Unpin>(
     stream: &mut S,
-    shutdown_request: tokio_util::sync::CancellationToken,
+    shutdown_request: crate::shutdown::ShutdownRequest,
+    msg: ShutdownServerMsg,
 ) -> io::Result<()> {
     let resp = ShutdownServerResponseMsg {};
     let resp_frame = Frame::control(FrameType::ShutdownServerResponse, &resp)
         .map_err(io::Error::other)?;
     resp_frame.write_to(stream).await?;
-    tracing::info!("shutdown requested via socket, signaling server");
-    shutdown_request.cancel();
+    tracing::info!(restart = msg.restart, "shutdown requested via socket, signaling server");
+    shutdown_request.request(msg.restart);
     Ok(())
 }
 
@@ -624,7 +633,7 @@ async fn send_initial_visual_state<S: AsyncWrite + Unpin>(
     let mode = *session.screen_mode.read();
 
     // Send current overlay state
-    let overlays = session.overlays.list_by_mode(mode);
+    let overlays = session.overlays.list_local_by_mode(mode);
     if !overlays.is_empty() {
         let msg = OverlaySyncMsg { overlays };
         let frame = Frame::control(FrameType::OverlaySync, &msg)
@@ -665,8 +674,9 @@ async fn write_frame_with_timeout<W: AsyncWriteExt + Unpin>(
 
 /// Main streaming loop: proxy I/O between the client and the session.
 ///
-/// - Client → Server: StdinInput frames are forwarded to session.input_tx
-/// - Server → Client: Session broker output is forwarded as PtyOutput frames
+/// - Client → Server: StdinInput frames are published to input subscribers,
+///   then forwarded to session.input_tx unless the session is in capture mode
+/// - Server → Client: Session output (lossless mirror) is forwarded as PtyOutput frames
 /// - Client → Server: Resize frames resize the PTY and parser
 /// - Client → Server: Detach frame ends the loop cleanly
 async fn run_streaming<S: AsyncRead + AsyncWrite + Unpin>(
@@ -688,12 +698,13 @@ async fn run_streaming<S: AsyncRead + AsyncWrite + Unpin>(
     // making Frame::read_from cancellation-safe.
     let mut reader = tokio::io::BufReader::new(reader);
 
-    // Subscribe to session output
-    let mut output_rx = session.output_rx.subscribe();
+    // Subscribe to session output. Socket clients mirror the session onto a
+    // local terminal, so they take the lossless path: a dropped chunk would
+    // leave the user's screen corrupted. A client that falls a whole queue
+    // behind is cut off and resynced from the screen below.
+    let mut output_rx = session.output_mirror.subscribe();
 
     let input_tx = session.input_tx.clone();
-    let pty = session.pty.clone();
-    let parser = session.parser.clone();
     let activity = session.activity.clone();
     let terminal_size = session.terminal_size.clone();
     let input_mode = session.input_mode.clone();
@@ -735,7 +746,7 @@ async fn run_streaming<S: AsyncRead + AsyncWrite + Unpin>(
                     Ok(VisualUpdate::OverlaysChanged) => {
                         let mode = *session.screen_mode.read();
                         let msg = OverlaySyncMsg {
-                            overlays: session.overlays.list_by_mode(mode),
+                            overlays: session.overlays.list_local_by_mode(mode),
                         };
                         if let Ok(frame) = Frame::control(FrameType::OverlaySync, &msg) {
                             if !write_frame_with_timeout(&frame, &mut writer).await {
@@ -775,9 +786,9 @@ async fn run_streaming<S: AsyncRead + AsyncWrite + Unpin>(
                             break;
                         }
                     }
-                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
-                    Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
-                        tracing::warn!(skipped = n, "socket client lagged on output, sending screen sync");
+                    Err(MirrorError::Closed) => break,
+                    Err(MirrorError::Overflowed) => {
+                        tracing::warn!("socket client fell behind on output, sending screen sync");
                         // ── DESIGN DECISION: lag recovery strategy ──────────────
                         //
                         // This has been deliberated across multiple review cycles.
@@ -810,12 +821,20 @@ async fn run_streaming<S: AsyncRead + AsyncWrite + Unpin>(
                         //
                         // We chose (3). Do not change this to (1) or (2) without
                         // revisiting the above tradeoffs.
+                        //
+                        // Waiting on the client instead (so it never falls
+                        // behind) was also tried, and rejected: one stalled
+                        // client froze the PTY reader and with it the child
+                        // and every other client.
                         // ────────────────────────────────────────────────────────
                         use crate::parser::ansi::line_to_ansi;
                         use crate::parser::state::{Format, Query, QueryResponse};
+                        // Resubscribe before querying so output produced
+                        // while the sync is built follows it, not precedes it.
+                        output_rx = session.output_mirror.subscribe();
                         if let Ok(Ok(QueryResponse::Screen(screen))) = tokio::time::timeout(
                             Duration::from_secs(5),
-                            parser.query(Query::Screen { format: Format::Styled }),
+                            session.parser.query(Query::Screen { format: Format::Styled }),
                         ).await {
                             let mut buf = String::new();
                             buf.push_str("\x1b[H\x1b[2J");
@@ -861,6 +880,10 @@ async fn run_streaming<S: AsyncRead + AsyncWrite + Unpin>(
                                 last_pong = tokio::time::Instant::now();
                                 ping_sent = false;
                             }
+                            // Local keystrokes follow the session's input
+                            // mode, the same switch API clients flip with
+                            // /input/capture: subscribers always see them,
+                            // the PTY only in passthrough mode.
                             FrameType::StdinInput => {
                                 let data = &f.payload;
                                 let mode = input_mode.get();
@@ -871,13 +894,20 @@ async fn run_streaming<S: AsyncRead + AsyncWrite + Unpin>(
                                 // Ctrl+\ toggles input capture; never forwarded to PTY
                                 if crate::input::is_ctrl_backslash(data) {
                                     let new_mode = input_mode.toggle();
-                                    input_broadcaster.broadcast_mode(new_mode);
                                     tracing::debug!("Ctrl+\\ pressed, toggled to {new_mode:?} mode");
                                     continue;
                                 }
 
-                                // In capture mode, don't forward to PTY
+                                // In capture mode, don't forward to PTY. With
+                                // echo on, draw it on the session's screen
+                                // instead, for capture UIs.
                                 if mode == crate::input::Mode::Capture {
+                                    if input_mode.echo_captured() {
+                                        let echo = crate::input::InputMode::echo(data);
+                                        if let Err(e) = session.parser.restore(echo, false).await {
+                                            tracing::warn!(?e, "failed to echo captured input");
+                                        }
+                                    }
                                     continue;
                                 }
 
@@ -897,14 +927,7 @@ async fn run_streaming<S: AsyncRead + AsyncWrite + Unpin>(
                                 if let Ok(msg) = f.parse_json::<ResizeMsg>() {
                                     let rows = msg.rows.max(1);
                                     let cols = msg.cols.max(1);
-                                    terminal_size.set(rows, cols);
-                                    if let Err(e) = pty.lock().resize(rows, cols) {
-                                        tracing::warn!(?e, "failed to resize PTY");
-                                    }
-                                    if let Err(e) = parser.resize(
-                                        cols as usize,
-                                        rows as usize,
-                                    ).await {
+                                    if let Err(e) = session.resize(rows, cols).await {
                                         tracing::warn!(?e, "failed to resize parser");
                                     }
                                 }
@@ -946,7 +969,7 @@ mod tests {
         let path = socket_path.clone();
 
         let cancel = tokio_util::sync::CancellationToken::new();
-        let shutdown_request = tokio_util::sync::CancellationToken::new();
+        let shutdown_request = crate::shutdown::ShutdownRequest::new();
         tokio::spawn(async move {
             serve(sessions, &socket_path, cancel, token, shutdown_request).await.unwrap();
         });
@@ -1107,6 +1130,88 @@ mod tests {
         std::fs::remove_file(&path).ok();
     }
 
+    #[tokio::test]
+    async fn test_stdin_in_capture_mode_reaches_subscribers_only() {
+        let sessions = SessionRegistry::new();
+        let (path, _dir) = start_test_server(sessions.clone()).await;
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+
+        let msg = CreateSessionMsg {
+            name: Some("capture-test".to_string()),
+            command: None,
+            cwd: None,
+            env: None,
+            rows: 24,
+            cols: 80,
+            tags: vec![],
+        };
+        Frame::control(FrameType::CreateSession, &msg).unwrap().write_to(&mut stream).await.unwrap();
+        let _resp = Frame::read_from(&mut stream).await.unwrap();
+
+        let session = sessions.get("capture-test").unwrap();
+        session.input_mode.capture();
+        let mut input_rx = session.input_broadcaster.subscribe();
+
+        Frame::data(FrameType::StdinInput, Bytes::from("secret"))
+            .write_to(&mut stream)
+            .await
+            .unwrap();
+        let event = tokio::time::timeout(std::time::Duration::from_secs(2), input_rx.recv())
+            .await
+            .expect("captured input should reach subscribers")
+            .unwrap();
+        assert!(matches!(
+            event,
+            crate::input::InputEvent::Input { mode: crate::input::Mode::Capture, ref raw, .. } if raw == b"secret"
+        ));
+        assert_eq!(session.io_counters.bytes_written(), 0, "captured input must not reach the PTY");
+
+        // With echo on, captured input shows on the screen, still without
+        // reaching the PTY.
+        session.input_mode.set_echo_captured(true);
+        Frame::data(FrameType::StdinInput, Bytes::from("typed-ahead"))
+            .write_to(&mut stream)
+            .await
+            .unwrap();
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+        loop {
+            let screen = session
+                .parser
+                .query(crate::parser::state::Query::Screen { format: crate::parser::state::Format::Plain })
+                .await
+                .unwrap();
+            let crate::parser::state::QueryResponse::Screen(screen) = screen else {
+                panic!("expected a screen");
+            };
+            if screen.lines.iter().any(|line| line.text().contains("typed-ahead")) {
+                break;
+            }
+            assert!(tokio::time::Instant::now() < deadline, "echoed input never appeared");
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(session.io_counters.bytes_written(), 0, "echoed input must not reach the PTY");
+        session.input_mode.set_echo_captured(false);
+
+        // Ctrl+\ releases capture without reaching the PTY either; what
+        // follows goes to the PTY again.
+        Frame::data(FrameType::StdinInput, Bytes::from_static(&[0x1c]))
+            .write_to(&mut stream)
+            .await
+            .unwrap();
+        Frame::data(FrameType::StdinInput, Bytes::from("ok"))
+            .write_to(&mut stream)
+            .await
+            .unwrap();
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+        while session.io_counters.bytes_written() < 2 && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(session.input_mode.get(), crate::input::Mode::Passthrough);
+        assert_eq!(session.io_counters.bytes_written(), 2);
+
+        std::fs::remove_file(&path).ok();
+    }
+
     #[tokio::test]
     async fn test_detach_ends_streaming() {
         let sessions = SessionRegistry::new();
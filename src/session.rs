@@ -1,7 +1,7 @@
 use bytes::Bytes;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use parking_lot::RwLock;
 use tokio::sync::{broadcast, mpsc};
 use tokio::sync::broadcast as tokio_broadcast;
@@ -10,7 +10,11 @@ use crate::activity::ActivityTracker;
 use crate::input::{FocusTracker, InputBroadcaster, InputMode};
 use crate::overlay::{OverlayStore, ScreenMode};
 use crate::panel::PanelStore;
-use crate::parser::Parser;
+use crate::parser::decode::{Decoder, Decoding};
+use crate::parser::escape::EscapeScanner;
+use crate::parser::events::ShutdownReason;
+use crate::parser::state::{Format, ScreenResponse};
+use crate::parser::{Parser, ParserError};
 use crate::protocol::VisualUpdate;
 use crate::pty::{Pty, PtyError, SpawnCommand};
 use crate::shutdown::ShutdownCoordinator;
@@ -24,9 +28,15 @@ pub fn validate_session_name(name: &str) -> Result<(), String> {
     if name.len() > 64 {
         return Err(format!("session name too long ({} chars, max 64)", name.len()));
     }
-    if !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '.') {
-        return Err(format!("session name contains invalid characters: {}",
-            &name[..name.len().min(64)]));
+    // Names are URL path segments: ASCII only (no percent-encoding needed),
+    // and never `.` or `..`, which clients and proxies normalize away.
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.') {
+        return Err(format!(
+            "session name {name:?} contains invalid characters (allowed: ASCII letters, digits, '-', '_', '.')"
+        ));
+    }
+    if name.chars().all(|c| c == '.') {
+        return Err(format!("session name {name:?} is a reserved path segment"));
     }
     Ok(())
 }
@@ -45,6 +55,147 @@ pub fn validate_tag(tag: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// Bytes moved through a session's PTY, for metering.
+///
+/// Counters are cumulative for the session's lifetime: they start at zero
+/// when the session is spawned and are never reset, including across
+/// renames and client reconnects.
+#[derive(Clone, Default)]
+pub struct IoCounters {
+    read: Arc<AtomicU64>,
+    written: Arc<AtomicU64>,
+}
+
+impl IoCounters {
+    /// Bytes read from the PTY (program output).
+    pub fn bytes_read(&self) -> u64 {
+        self.read.load(Ordering::Relaxed)
+    }
+
+    /// Bytes written to the PTY (input).
+    pub fn bytes_written(&self) -> u64 {
+        self.written.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn add_read(&self, n: usize) {
+        self.read.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_written(&self, n: usize) {
+        self.written.fetch_add(n as u64, Ordering::Relaxed);
+    }
+}
+
+/// Set once the PTY reader has handed the program's first output to the
+/// parser, so clients can wait for a new session to draw something instead
+/// of sleeping. Backs `/ready`.
+#[derive(Clone)]
+pub struct OutputReady(Arc<tokio::sync::watch::Sender<bool>>);
+
+impl Default for OutputReady {
+    fn default() -> Self {
+        Self(Arc::new(tokio::sync::watch::channel(false).0))
+    }
+}
+
+impl OutputReady {
+    pub fn is_ready(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    pub(crate) fn mark(&self) {
+        self.0.send_if_modified(|ready| !std::mem::replace(ready, true));
+    }
+
+    /// Resolve once the first output has been read.
+    pub async fn wait(&self) {
+        let _ = self.0.subscribe().wait_for(|ready| *ready).await;
+    }
+}
+
+/// Back door into the parser's output channel, for bytes that should be
+/// treated as program output without the program writing them. Backs
+/// `/feed`. The channel is held weakly so the parser still finishes when
+/// the PTY closes.
+#[derive(Clone, Default)]
+pub struct OutputFeed(Option<mpsc::WeakSender<Bytes>>);
+
+impl OutputFeed {
+    pub fn new(parser_tx: &mpsc::Sender<Bytes>) -> Self {
+        Self(Some(parser_tx.downgrade()))
+    }
+
+    /// The parser's output channel, unless the session's output has ended.
+    fn sender(&self) -> Option<mpsc::Sender<Bytes>> {
+        self.0.as_ref()?.upgrade()
+    }
+}
+
+/// Optional regex that recognizes the shell prompt, used by `wait-prompt`
+/// to decide that a command has finished when the shell does not emit
+/// OSC 133 marks.
+#[derive(Debug, Clone, Default)]
+pub struct PromptPattern(Arc<RwLock<Option<regex::Regex>>>);
+
+impl PromptPattern {
+    pub fn get(&self) -> Option<regex::Regex> {
+        self.0.read().clone()
+    }
+
+    /// Compile and store `pattern`; an empty string clears it.
+    pub fn set(&self, pattern: &str) -> Result<(), regex::Error> {
+        let compiled = if pattern.is_empty() {
+            None
+        } else {
+            Some(regex::Regex::new(pattern)?)
+        };
+        *self.0.write() = compiled;
+        Ok(())
+    }
+
+    /// Source of the current pattern, if one is set.
+    pub fn as_str(&self) -> Option<String> {
+        self.0.read().as_ref().map(|re| re.as_str().to_string())
+    }
+}
+
+/// Server-side flow control for the PTY reader, like XOFF/XON on a real
+/// terminal. While paused the reader stops draining the PTY, so once the
+/// kernel's PTY buffer fills, the program blocks on its next write.
+/// Nothing is dropped: on resume the buffered output is read in order.
+#[derive(Clone, Default)]
+pub struct ReadPause(Arc<(parking_lot::Mutex<bool>, parking_lot::Condvar)>);
+
+/// How often a paused reader checks whether its session was killed.
+const READ_PAUSE_POLL: std::time::Duration = std::time::Duration::from_millis(100);
+
+impl ReadPause {
+    pub fn is_paused(&self) -> bool {
+        *self.0.0.lock()
+    }
+
+    /// Hold output from now on. A read already in progress still
+    /// completes, but its data is held with the rest until `resume`.
+    pub fn pause(&self) {
+        *self.0.0.lock() = true;
+    }
+
+    pub fn resume(&self) {
+        *self.0.0.lock() = false;
+        self.0.1.notify_all();
+    }
+
+    /// Block while paused. Returns early once `cancelled` fires so a
+    /// killed session's reader can drain the PTY and exit.
+    fn wait(&self, cancelled: &tokio_util::sync::CancellationToken) {
+        let (paused, resumed) = &*self.0;
+        let mut paused = paused.lock();
+        while *paused && !cancelled.is_cancelled() {
+            resumed.wait_for(&mut paused, READ_PAUSE_POLL);
+        }
+    }
+}
+
 /// A single terminal session with all associated state.
 ///
 /// Each `Session` owns the PTY, parser, I/O channels, and auxiliary stores
@@ -64,6 +215,10 @@ pub struct Session {
     pub tags: Arc<RwLock<HashSet<String>>>,
     pub input_tx: mpsc::Sender<Bytes>,
     pub output_rx: broadcast::Sender<Bytes>,
+    /// Lossless copy of the PTY output for consumers that must not drop
+    /// bytes (socket clients mirroring the session to a local terminal).
+    /// `output_rx` is the lossy path for everything else; see [`crate::broker`].
+    pub output_mirror: crate::broker::OutputMirror,
     pub shutdown: ShutdownCoordinator,
     pub parser: Parser,
     pub overlays: OverlayStore,
@@ -94,6 +249,20 @@ pub struct Session {
     /// Checked by `send_sighup()` and `kill_child()` to avoid signaling a
     /// potentially-recycled PID.
     pub child_exited: Arc<AtomicBool>,
+    /// Bytes read from and written to the PTY.
+    pub io_counters: IoCounters,
+    /// Prompt regex for `wait-prompt`; unset by default.
+    pub prompt_pattern: PromptPattern,
+    /// Recent raw PTY output, for `/raw-log` export.
+    pub raw_log: crate::broker::RawLog,
+    /// Recent log lines from work done for this session, for `/logs`.
+    pub logs: crate::session_log::SessionLog,
+    /// Pauses the PTY reader; see `/pause`.
+    pub read_pause: ReadPause,
+    /// Whether the program has written anything yet; see `/ready`.
+    pub output_ready: OutputReady,
+    /// Injects virtual output; see [`Session::feed_output`].
+    pub output_feed: OutputFeed,
 }
 
 impl std::fmt::Debug for Session {
@@ -112,6 +281,65 @@ impl std::fmt::Debug for Session {
 /// socket connections to a single session.
 const MAX_CLIENTS_PER_SESSION: usize = 64;
 
+/// How often a server with a scrollback budget checks it.
+pub const SCROLLBACK_BUDGET_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How often a server with [`JournalKeepalive`] bounds resizes journals.
+pub const JOURNAL_KEEPALIVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Quiet time after which a session's journal shrinks to its idle size.
+pub const DEFAULT_JOURNAL_IDLE_AFTER: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How long the PTY reader waits, after the PTY closes, for the child's exit
+/// code before ending the parser without one.
+const CHILD_EXIT_GRACE: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Default size of the buffer each session's PTY reader reads into.
+pub const DEFAULT_READ_BUFFER_BYTES: usize = 4096;
+
+/// Bounds for [`SpawnOptions::read_buffer_bytes`].
+pub const MIN_READ_BUFFER_BYTES: usize = 1024;
+pub const MAX_READ_BUFFER_BYTES: usize = 1024 * 1024;
+
+/// Optional settings for [`Session::spawn_with_options`].
+#[derive(Debug, Clone)]
+pub struct SpawnOptions {
+    /// Working directory for the child, instead of the server's.
+    pub cwd: Option<String>,
+    /// Variables added to the child's environment.
+    pub env: Option<HashMap<String, String>>,
+    /// How PTY output is decoded.
+    pub decoding: Decoding,
+    /// Size of the buffer the PTY reader reads into, clamped to
+    /// [`MIN_READ_BUFFER_BYTES`]..=[`MAX_READ_BUFFER_BYTES`].
+    ///
+    /// A read returns whatever output is ready, up to the buffer size, so a
+    /// bigger buffer doesn't delay small writes. It only matters when the
+    /// program outputs faster than the reader keeps up: bigger buffers then
+    /// mean fewer syscalls and fewer, larger chunks for clients and the
+    /// parser, at the cost of coarser updates (each chunk is parsed and
+    /// published as one).
+    pub read_buffer_bytes: usize,
+    /// Regex recognizing the shell prompt; see [`PromptPattern`].
+    pub prompt_pattern: PromptPattern,
+    /// Retention of the raw output log, set before the first read so it
+    /// holds the session's output from the start.
+    pub raw_log_bytes: usize,
+}
+
+impl Default for SpawnOptions {
+    fn default() -> Self {
+        Self {
+            cwd: None,
+            env: None,
+            decoding: Decoding::default(),
+            read_buffer_bytes: DEFAULT_READ_BUFFER_BYTES,
+            prompt_pattern: PromptPattern::default(),
+            raw_log_bytes: crate::broker::DEFAULT_RAW_LOG_LIMIT,
+        }
+    }
+}
+
 /// RAII guard that decrements the session client count on drop.
 pub struct ClientGuard {
     counter: Arc<AtomicUsize>,
@@ -124,6 +352,48 @@ impl Drop for ClientGuard {
 }
 
 impl Session {
+    /// Where API input goes: the running command of the focused panel, or
+    /// the session's own PTY when no command panel has focus.
+    pub fn input_target(&self) -> mpsc::Sender<Bytes> {
+        self.focus
+            .focused()
+            .and_then(|id| self.panels.command(&id))
+            .map(|command| command.input_sender())
+            .unwrap_or_else(|| self.input_tx.clone())
+    }
+
+    /// Queue input for the session's PTY without waiting, for callers that
+    /// can't `.await` (sync callbacks, FFI boundaries).
+    ///
+    /// Never blocks: when the input channel is full the bytes are dropped
+    /// and handed back in [`TrySendError::Full`](mpsc::error::TrySendError::Full),
+    /// for the caller to retry or discard. [`TrySendError::Closed`](mpsc::error::TrySendError::Closed)
+    /// means the session's writer has stopped. Like other input, the bytes
+    /// reach the PTY as one contiguous write.
+    pub fn try_input(&self, bytes: Bytes) -> Result<(), mpsc::error::TrySendError<Bytes>> {
+        self.input_tx.try_send(bytes)
+    }
+
+    /// Treat `data` as if the program had written it: it goes to the
+    /// parser, streaming and mirror subscribers, and the raw log, in the
+    /// same order as PTY output, but never reaches the program. Waits while
+    /// the parser is behind. Returns `false` once the session's output has
+    /// ended.
+    pub async fn feed_output(&self, data: Bytes) -> bool {
+        let Some(parser_tx) = self.output_feed.sender() else {
+            return false;
+        };
+        self.raw_log.append(&data);
+        let _ = self.output_rx.send(data.clone());
+        self.output_mirror.send(data.clone());
+        if parser_tx.send(data).await.is_err() {
+            return false;
+        }
+        self.output_ready.mark();
+        self.activity.touch();
+        true
+    }
+
     /// Register a new streaming client, returning an RAII guard that decrements
     /// the count when dropped.
     ///
@@ -140,6 +410,7 @@ impl Session {
                 .compare_exchange(current, current + 1, Ordering::AcqRel, Ordering::Acquire)
                 .is_ok()
             {
+                self.log_span().in_scope(|| tracing::debug!(clients = current + 1, "client connected"));
                 return Some(ClientGuard {
                     counter: Arc::clone(&self.client_count),
                 });
@@ -159,6 +430,43 @@ impl Session {
         let _ = self.detach_signal.send(());
     }
 
+    /// Resize the session: records the new size in `terminal_size`, then
+    /// resizes the PTY (so the program gets SIGWINCH) and the parser's
+    /// terminal. Use this rather than resizing the pieces separately so the
+    /// three never disagree.
+    ///
+    /// A failed PTY resize is logged and doesn't stop the parser resize.
+    pub async fn resize(&self, rows: u16, cols: u16) -> Result<(), ParserError> {
+        self.resize_pty(rows, cols);
+        self.parser.resize(cols as usize, rows as usize).await
+    }
+
+    /// [`Session::resize`], returning the screen in `format` as captured
+    /// right after the parser resized, before any redraw by the program.
+    pub async fn resize_and_screen(&self, rows: u16, cols: u16, format: Format) -> Result<ScreenResponse, ParserError> {
+        self.resize_pty(rows, cols);
+        self.parser.resize_and_screen(cols as usize, rows as usize, format).await
+    }
+
+    fn resize_pty(&self, rows: u16, cols: u16) {
+        let _span = self.log_span().entered();
+        tracing::debug!(rows, cols, "resizing");
+        self.terminal_size.set(rows, cols);
+        if let Err(e) = self.pty.lock().resize(rows, cols) {
+            tracing::warn!(session = %self.name, ?e, "failed to resize PTY");
+        }
+    }
+
+    /// Span that routes events logged inside it to [`Session::logs`].
+    pub fn log_span(&self) -> tracing::Span {
+        self.logs.span(&self.name)
+    }
+
+    /// Why the session's output ended or is ending, once known.
+    pub fn shutdown_reason(&self) -> Option<ShutdownReason> {
+        self.parser.end_reason()
+    }
+
     /// Explicitly shut down this session's background tasks.
     ///
     /// Called when a spawned session cannot be registered in the registry
@@ -176,6 +484,7 @@ impl Session {
     /// Unlike relying on `Arc<Pty>` drop (SIGHUP), this ensures the
     /// child is terminated immediately regardless of outstanding references.
     pub fn force_kill(&self) {
+        self.parser.set_end_reason(ShutdownReason::Killed);
         self.cancelled.cancel();
         self.detach();
         self.kill_child();
@@ -234,6 +543,105 @@ impl Session {
         }
     }
 
+    /// Wait until `pattern` appears in the session's output.
+    ///
+    /// Only output produced after this call is considered, so start waiting
+    /// before sending the input that triggers it. Matching runs against the
+    /// accumulated output with ANSI escape sequences removed, so a pattern
+    /// split across PTY reads (or interleaved with color codes) still matches.
+    /// With `regex` set, `pattern` is a regular expression; otherwise it is a
+    /// literal substring.
+    pub async fn wait_for_output(
+        &self,
+        pattern: &str,
+        regex: bool,
+        timeout: std::time::Duration,
+    ) -> Result<OutputMatch, ExpectError> {
+        let matcher = if regex {
+            Matcher::Regex(
+                regex::Regex::new(pattern).map_err(|e| ExpectError::InvalidPattern(e.to_string()))?,
+            )
+        } else if pattern.is_empty() {
+            return Err(ExpectError::InvalidPattern("pattern must not be empty".into()));
+        } else {
+            Matcher::Literal(pattern.to_string())
+        };
+
+        let mut rx = self.output_rx.subscribe();
+        let mut stripper = AnsiStripper::new();
+        let mut text = String::new();
+
+        let wait = async {
+            loop {
+                match rx.recv().await {
+                    Ok(chunk) => {
+                        stripper.push(&chunk, &mut text);
+                        if text.len() > EXPECT_BUFFER_LIMIT {
+                            let mut cut = text.len() - EXPECT_BUFFER_LIMIT;
+                            while !text.is_char_boundary(cut) {
+                                cut += 1;
+                            }
+                            text.drain(..cut);
+                        }
+                        if let Some((start, end)) = matcher.find(&text) {
+                            return Ok(OutputMatch {
+                                matched: text[start..end].to_string(),
+                                output: text[..end].to_string(),
+                            });
+                        }
+                    }
+                    // Missed some output; keep going with what we have.
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return Err(ExpectError::Closed),
+                }
+            }
+        };
+
+        tokio::time::timeout(timeout, wait)
+            .await
+            .unwrap_or(Err(ExpectError::Timeout))
+    }
+
+    /// Answer cursor position reports (`ESC [ 6 n`) on the program's behalf.
+    ///
+    /// Meant for headless sessions with no real terminal attached: a local
+    /// client's terminal answers CPR itself, and a second reply would reach
+    /// the program as stray input. The task stops when the session is
+    /// cancelled or its parser goes away.
+    pub fn spawn_cpr_responder(&self) -> tokio::task::JoinHandle<()> {
+        use crate::parser::device_query::{cursor_position_report, DeviceQueryKind};
+        use crate::parser::events::Event;
+        use crate::parser::SubscriptionEvent;
+        use tokio_stream::StreamExt;
+
+        let mut events = Box::pin(self.parser.subscribe());
+        let input_tx = self.input_tx.clone();
+        let cancelled = self.cancelled.clone();
+        tokio::spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    _ = cancelled.cancelled() => break,
+                    event = events.next() => event,
+                };
+                match event {
+                    Some(SubscriptionEvent::Event(Event::DeviceQuery {
+                        kind: DeviceQueryKind::CursorPosition,
+                        row,
+                        col,
+                        ..
+                    })) => {
+                        let reply = Bytes::from(cursor_position_report(row, col));
+                        if input_tx.send(reply).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(_) => {}
+                    None => break,
+                }
+            }
+        })
+    }
+
     /// Spawn a new session with a PTY and all associated I/O tasks.
     ///
     /// The PTY reader only publishes to the broker (no stdout -- server mode).
@@ -248,18 +656,19 @@ impl Session {
         rows: u16,
         cols: u16,
     ) -> Result<(Self, tokio::sync::oneshot::Receiver<()>), PtyError> {
-        Self::spawn_with_options(name, command, rows, cols, None, None)
+        Self::spawn_with_options(name, command, rows, cols, SpawnOptions::default())
     }
 
-    /// Spawn a new session with optional cwd and environment overrides.
+    /// Spawn a new session with the cwd, environment, and other settings
+    /// in `options`.
     pub fn spawn_with_options(
         name: String,
         command: SpawnCommand,
         rows: u16,
         cols: u16,
-        cwd: Option<String>,
-        env: Option<std::collections::HashMap<String, String>>,
+        options: SpawnOptions,
     ) -> Result<(Self, tokio::sync::oneshot::Receiver<()>), PtyError> {
+        let SpawnOptions { cwd, env, decoding, read_buffer_bytes, prompt_pattern, raw_log_bytes } = options;
         let command_display = match &command {
             SpawnCommand::Shell { shell, .. } => {
                 shell.clone().unwrap_or_else(|| {
@@ -284,38 +693,12 @@ impl Session {
         let pid = pty_child.as_ref().and_then(|c| c.process_id());
         let pty = Arc::new(parking_lot::Mutex::new(pty));
 
-        // Monitor child exit via a oneshot channel.
-        //
-        // NOTE: The JoinHandles from the three spawn_blocking tasks below
-        // (child exit monitor, PTY reader, PTY writer) are intentionally not
-        // stored. Session derives Clone, and JoinHandle is not Clone, so
-        // tracking them would require Arc<Mutex<Option<JoinHandle>>> per task.
-        // This complexity is unnecessary because:
-        //   1. All three tasks self-terminate when the PTY fd closes or the
-        //      child exits (triggered by Session drop / drain's SIGKILL).
-        //   2. The tokio runtime does not abort blocking tasks on shutdown —
-        //      they run to completion on the blocking thread pool.
-        //   3. drain() already ensures children are killed within 3 seconds.
-        let (child_exit_tx, child_exit_rx) = tokio::sync::oneshot::channel::<()>();
-        if let Some(mut child) = pty_child {
-            tokio::task::spawn_blocking(move || {
-                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-                    match child.wait() {
-                        Ok(status) => tracing::debug!(?status, "session child exited"),
-                        Err(e) => tracing::error!(?e, "error waiting for session child"),
-                    }
-                }));
-                if let Err(e) = result {
-                    tracing::error!("child exit monitor task panicked: {:?}", e);
-                }
-                let _ = child_exit_tx.send(());
-            });
-        } else {
-            // No child to wait on; signal immediately.
-            let _ = child_exit_tx.send(());
-        }
-
         let broker = crate::broker::Broker::new();
+        // Entered while the parser task is spawned so it inherits the span;
+        // the PTY threads below enter their own clones.
+        let logs = crate::session_log::SessionLog::default();
+        let log_span = logs.span(&name);
+        let spawn_guard = log_span.clone().entered();
 
         // ── Design decision: bounded parser channel with PTY backpressure ──
         //
@@ -371,7 +754,52 @@ impl Session {
         // ────────────────────────────────────────────────────────────────────
         const PARSER_CHANNEL_CAPACITY: usize = 256;
         let (parser_tx, parser_rx) = mpsc::channel::<Bytes>(PARSER_CHANNEL_CAPACITY);
-        let parser = Parser::spawn(parser_rx, cols as usize, rows as usize, 10_000);
+        let parser = Parser::spawn_with_decoding(parser_rx, cols as usize, rows as usize, 10_000, decoding);
+        let output_feed = OutputFeed::new(&parser_tx);
+        drop(spawn_guard);
+
+        // Monitor child exit via a oneshot channel.
+        //
+        // NOTE: The JoinHandles from the three spawn_blocking tasks below
+        // (child exit monitor, PTY reader, PTY writer) are intentionally not
+        // stored. Session derives Clone, and JoinHandle is not Clone, so
+        // tracking them would require Arc<Mutex<Option<JoinHandle>>> per task.
+        // This complexity is unnecessary because:
+        //   1. All three tasks self-terminate when the PTY fd closes or the
+        //      child exits (triggered by Session drop / drain's SIGKILL).
+        //   2. The tokio runtime does not abort blocking tasks on shutdown —
+        //      they run to completion on the blocking thread pool.
+        //   3. drain() already ensures children are killed within 3 seconds.
+        let (child_exit_tx, child_exit_rx) = tokio::sync::oneshot::channel::<()>();
+        // Lets the PTY reader hold the parser open until the exit code is
+        // recorded, so the final `End` event can carry it.
+        let mut exit_seen_rx = None;
+        if let Some(mut child) = pty_child {
+            let (exit_seen_tx, rx) = std::sync::mpsc::channel::<()>();
+            exit_seen_rx = Some(rx);
+            let exit_parser = parser.clone();
+            let span = log_span.clone();
+            tokio::task::spawn_blocking(move || {
+                let _span = span.entered();
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    match child.wait() {
+                        Ok(status) => {
+                            tracing::debug!(?status, "session child exited");
+                            exit_parser.set_end_reason(ShutdownReason::Exited { code: status.exit_code() });
+                        }
+                        Err(e) => tracing::error!(?e, "error waiting for session child"),
+                    }
+                }));
+                if let Err(e) = result {
+                    tracing::error!("child exit monitor task panicked: {:?}", e);
+                }
+                let _ = child_exit_tx.send(());
+                let _ = exit_seen_tx.send(());
+            });
+        } else {
+            // No child to wait on; signal immediately.
+            let _ = child_exit_tx.send(());
+        }
 
         let (input_tx, input_rx) = mpsc::channel::<Bytes>(64);
         let shutdown = ShutdownCoordinator::new();
@@ -386,33 +814,62 @@ impl Session {
         // Spawn PTY reader (server mode -- no stdout, only broker + parser)
         //
         // Order matters: broadcast first (non-blocking, lossy for streaming
-        // clients), then blocking_send to parser (applies backpressure).
-        // This ensures streaming clients and the local terminal are never
-        // blocked by parser throughput.
+        // clients), then the mirror for local terminals (non-blocking,
+        // lossless until a subscriber overflows and resyncs), then
+        // blocking_send to parser (applies backpressure). No client can
+        // block the reader; only the parser applies backpressure.
         let broker_clone = broker.clone();
+        let mirror = broker.mirror();
         let activity_clone = activity.clone();
+        let io_counters = IoCounters::default();
+        let read_counter = io_counters.clone();
+        let raw_log = crate::broker::RawLog::new(raw_log_bytes);
+        let raw_log_clone = raw_log.clone();
+        let read_buffer_bytes = read_buffer_bytes.clamp(MIN_READ_BUFFER_BYTES, MAX_READ_BUFFER_BYTES);
+        let read_pause = ReadPause::default();
+        let reader_pause = read_pause.clone();
+        let cancelled = tokio_util::sync::CancellationToken::new();
+        let reader_cancelled = cancelled.clone();
+        let output_ready = OutputReady::default();
+        let reader_ready = output_ready.clone();
+        let span = log_span.clone();
         tokio::task::spawn_blocking(move || {
+            let _span = span.entered();
             let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
                 use std::io::Read;
                 let mut reader = pty_reader;
-                let mut buf = [0u8; 4096];
+                let mut buf = vec![0u8; read_buffer_bytes];
                 loop {
                     match reader.read(&mut buf) {
                         Ok(0) => break,
                         Ok(n) => {
+                            // Checked after the read, not before: a pause
+                            // that arrives while blocked in `read` must
+                            // still hold the chunk it returns.
+                            reader_pause.wait(&reader_cancelled);
+                            read_counter.add_read(n);
+                            raw_log_clone.append(&buf[..n]);
                             let data = Bytes::copy_from_slice(&buf[..n]);
                             // 1. Broadcast to streaming clients (non-blocking, lossy)
                             broker_clone.publish(data.clone());
-                            // 2. Send to parser (blocks if channel full → PTY backpressure)
+                            // 2. Mirror to local terminals (non-blocking, full subscribers are cut off)
+                            mirror.send(data.clone());
+                            // 3. Send to parser (blocks if channel full → PTY backpressure)
                             if parser_tx.blocking_send(data).is_err() {
                                 // Parser channel closed — session is shutting down
                                 break;
                             }
+                            reader_ready.mark();
                             activity_clone.touch();
                         }
                         Err(_) => break,
                     }
                 }
+                // The PTY closes as the child exits; give the exit monitor a
+                // moment to record the exit code before the parser ends.
+                if let Some(rx) = exit_seen_rx {
+                    let _ = rx.recv_timeout(CHILD_EXIT_GRACE);
+                }
             }));
             if let Err(e) = result {
                 tracing::error!("PTY reader task panicked: {:?}", e);
@@ -449,7 +906,16 @@ impl Session {
         //    latency to normal input handling for negligible shutdown
         //    benefit. The current design is the right tradeoff.
         // ────────────────────────────────────────────────────────────────
+        //
+        // Every input source (HTTP, WebSocket, MCP, attached terminals,
+        // device-query replies) sends one submission as one `Bytes`, and
+        // this single writer finishes each with `write_all` before taking
+        // the next, so submissions reach the PTY whole and never interleave.
+        // Keep it that way: splitting a submission across sends would let
+        // a concurrent sender cut an escape sequence in two.
+        let write_counter = io_counters.clone();
         tokio::task::spawn_blocking(move || {
+            let _span = log_span.entered();
             let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
                 use std::io::Write;
                 let mut writer = pty_writer;
@@ -458,6 +924,7 @@ impl Session {
                     if writer.write_all(&data).is_err() {
                         break;
                     }
+                    write_counter.add_written(data.len());
                     let _ = writer.flush();
                 }
             }));
@@ -474,6 +941,7 @@ impl Session {
             tags: Arc::new(RwLock::new(HashSet::new())),
             input_tx,
             output_rx: broker.sender(),
+            output_mirror: broker.mirror(),
             shutdown,
             parser,
             overlays,
@@ -487,8 +955,15 @@ impl Session {
             detach_signal: broadcast::channel::<()>(1).0,
             visual_update_tx: broadcast::channel::<VisualUpdate>(16).0,
             screen_mode: Arc::new(RwLock::new(ScreenMode::Normal)),
-            cancelled: tokio_util::sync::CancellationToken::new(),
+            cancelled,
             child_exited: Arc::new(AtomicBool::new(false)),
+            io_counters,
+            prompt_pattern,
+            raw_log,
+            logs,
+            read_pause,
+            output_ready,
+            output_feed,
         };
 
         // Watch for alternate screen mode changes from the parser and
@@ -569,11 +1044,108 @@ pub enum RegistryError {
     InvalidName(String),
 }
 
+/// Upper bound on output retained while waiting for a pattern. Older text
+/// is discarded, so matches must fall within the most recent 64 KiB of
+/// output with escape sequences removed.
+const EXPECT_BUFFER_LIMIT: usize = 64 * 1024;
+
+/// Result of a successful [`Session::wait_for_output`].
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct OutputMatch {
+    /// The text that matched the pattern.
+    pub matched: String,
+    /// Output seen since waiting began, up to and including the match.
+    pub output: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExpectError {
+    #[error("invalid pattern: {0}")]
+    InvalidPattern(String),
+    #[error("timed out waiting for pattern")]
+    Timeout,
+    #[error("session output closed")]
+    Closed,
+}
+
+enum Matcher {
+    Literal(String),
+    Regex(regex::Regex),
+}
+
+impl Matcher {
+    fn find(&self, text: &str) -> Option<(usize, usize)> {
+        match self {
+            Matcher::Literal(p) => text.find(p.as_str()).map(|i| (i, i + p.len())),
+            Matcher::Regex(re) => re.find(text).map(|m| (m.start(), m.end())),
+        }
+    }
+}
+
+/// Removes ANSI escape sequences from output as it arrives.
+///
+/// Sequences and UTF-8 characters split across chunks carry over to the
+/// next one, so each byte is only looked at once.
+struct AnsiStripper {
+    decoder: Decoder,
+    scanner: EscapeScanner,
+}
+
+impl AnsiStripper {
+    fn new() -> Self {
+        Self {
+            decoder: Decoder::new(Decoding::Utf8),
+            // Only sequence boundaries matter; nothing is collected.
+            scanner: EscapeScanner::new(0),
+        }
+    }
+
+    /// Append the text of `bytes` to `out`.
+    fn push(&mut self, bytes: &[u8], out: &mut String) {
+        for c in self.decoder.decode(bytes).chars() {
+            // ESC and C1 controls start sequences (or are dropped).
+            if self.scanner.is_ground() && c != '\x1b' && !('\u{80}'..='\u{9f}').contains(&c) {
+                out.push(c);
+                continue;
+            }
+            let mut utf8 = [0; 4];
+            for &byte in c.encode_utf8(&mut utf8).as_bytes() {
+                self.scanner.step(byte);
+            }
+        }
+    }
+}
+
 struct RegistryInner {
     sessions: HashMap<String, Session>,
     next_id: u64,
     max_sessions: Option<usize>,
     tags_index: HashMap<String, HashSet<String>>,
+    scrollback_budget: Option<usize>,
+    scrollback_evicted_lines: u64,
+    journal_keepalive: Option<JournalKeepalive>,
+    read_buffer_bytes: usize,
+}
+
+/// Replay journal sizes that follow session activity: a session producing
+/// output keeps `active_events` so a client dropped mid-burst can still
+/// resume, and one quiet for `idle_after` is trimmed to `idle_events`.
+#[derive(Debug, Clone, Copy)]
+pub struct JournalKeepalive {
+    pub idle_events: usize,
+    pub active_events: usize,
+    pub idle_after: std::time::Duration,
+}
+
+/// Estimated scrollback memory across all sessions; see
+/// [`SessionRegistry::scrollback_usage`].
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ScrollbackUsage {
+    pub bytes: usize,
+    pub budget_bytes: Option<usize>,
+    pub lines: usize,
+    /// Lines dropped to stay within the budget since the server started.
+    pub evicted_lines: u64,
 }
 
 /// Manages multiple sessions by name.
@@ -612,6 +1184,10 @@ impl SessionRegistry {
                 next_id: 0,
                 max_sessions,
                 tags_index: HashMap::new(),
+                scrollback_budget: None,
+                scrollback_evicted_lines: 0,
+                journal_keepalive: None,
+                read_buffer_bytes: DEFAULT_READ_BUFFER_BYTES,
             })),
             events_tx,
         }
@@ -888,6 +1464,7 @@ impl SessionRegistry {
             let drained: Vec<(String, Session)> = inner.sessions.drain().collect();
             inner.tags_index.clear();
             for (name, ref session) in &drained {
+                session.parser.set_end_reason(ShutdownReason::ServerShutdown);
                 session.cancelled.cancel();
                 session.detach();
                 session.send_sighup();
@@ -909,6 +1486,139 @@ impl SessionRegistry {
         }))
     }
 
+    /// Set the [`SpawnOptions::read_buffer_bytes`] for sessions spawned
+    /// from now on, clamped to its bounds.
+    pub fn set_read_buffer_bytes(&self, bytes: usize) {
+        self.inner.write().read_buffer_bytes = bytes.clamp(MIN_READ_BUFFER_BYTES, MAX_READ_BUFFER_BYTES);
+    }
+
+    /// The PTY read buffer size new sessions use.
+    pub fn read_buffer_bytes(&self) -> usize {
+        self.inner.read().read_buffer_bytes
+    }
+
+    /// Cap the estimated scrollback memory of all sessions together, or
+    /// remove the cap with `None`. Enforced by
+    /// [`SessionRegistry::enforce_scrollback_budget`].
+    pub fn set_scrollback_budget(&self, bytes: Option<usize>) {
+        self.inner.write().scrollback_budget = bytes;
+    }
+
+    /// Current scrollback memory estimate, budget, and evictions so far.
+    pub fn scrollback_usage(&self) -> ScrollbackUsage {
+        let inner = self.inner.read();
+        let (lines, bytes) = inner.sessions.values().fold((0, 0), |(lines, bytes), session| {
+            (lines + session.parser.scrollback_lines(), bytes + session.parser.scrollback_bytes())
+        });
+        ScrollbackUsage {
+            bytes,
+            budget_bytes: inner.scrollback_budget,
+            lines,
+            evicted_lines: inner.scrollback_evicted_lines,
+        }
+    }
+
+    /// Drop scrollback until the estimate fits the budget, returning the
+    /// number of lines dropped.
+    ///
+    /// Sessions are taken least recently active first, and each loses its
+    /// oldest lines before the next session is touched, so a busy session
+    /// keeps its history at the expense of ones nobody is using. Sessions
+    /// showing the alternate screen are skipped.
+    pub async fn enforce_scrollback_budget(&self) -> u64 {
+        let (budget, mut sessions) = {
+            let inner = self.inner.read();
+            let Some(budget) = inner.scrollback_budget else {
+                return 0;
+            };
+            (budget, inner.sessions.values().cloned().collect::<Vec<_>>())
+        };
+        let mut usage: usize = sessions.iter().map(|s| s.parser.scrollback_bytes()).sum();
+        if usage <= budget {
+            return 0;
+        }
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.activity.quiet_for()));
+
+        let mut evicted = 0;
+        for session in &sessions {
+            if usage <= budget {
+                break;
+            }
+            let held = session.parser.scrollback_lines();
+            let line_bytes = session.parser.scrollback_line_bytes().max(1);
+            let drop = (usage - budget).div_ceil(line_bytes).min(held);
+            if drop == 0 || session.parser.trim_scrollback(held - drop).await.is_err() {
+                continue;
+            }
+            usage = usage.saturating_sub(drop * line_bytes);
+            evicted += drop as u64;
+            tracing::debug!(session = %session.name, lines = drop, "evicted scrollback over budget");
+        }
+        self.inner.write().scrollback_evicted_lines += evicted;
+        evicted
+    }
+
+    /// Run [`SessionRegistry::enforce_scrollback_budget`] every `interval`
+    /// for as long as the server lives.
+    pub fn spawn_scrollback_enforcer(&self, interval: std::time::Duration) -> tokio::task::JoinHandle<()> {
+        let registry = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                registry.enforce_scrollback_budget().await;
+            }
+        })
+    }
+
+    /// Size every session's journal by its activity, or stop with `None`
+    /// (journals keep whatever capacity they last had). Applied by
+    /// [`SessionRegistry::apply_journal_keepalive`]; it overrides a
+    /// session's own `journal_events`.
+    pub fn set_journal_keepalive(&self, keepalive: Option<JournalKeepalive>) {
+        self.inner.write().journal_keepalive = keepalive;
+    }
+
+    /// Give each session its active or idle journal capacity, returning
+    /// how many are currently idle.
+    pub fn apply_journal_keepalive(&self) -> usize {
+        let (keepalive, sessions) = {
+            let inner = self.inner.read();
+            let Some(keepalive) = inner.journal_keepalive else {
+                return 0;
+            };
+            (keepalive, inner.sessions.values().cloned().collect::<Vec<_>>())
+        };
+        let mut idle = 0;
+        for session in &sessions {
+            let capacity = if session.activity.quiet_for() >= keepalive.idle_after {
+                idle += 1;
+                keepalive.idle_events
+            } else {
+                keepalive.active_events
+            };
+            if session.parser.journal_status().capacity != capacity {
+                session.parser.set_journal_capacity(capacity);
+            }
+        }
+        idle
+    }
+
+    /// Run [`SessionRegistry::apply_journal_keepalive`] every `interval`
+    /// for as long as the server lives.
+    pub fn spawn_journal_keepalive(&self, interval: std::time::Duration) -> tokio::task::JoinHandle<()> {
+        let registry = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                registry.apply_journal_keepalive();
+            }
+        })
+    }
+
     /// Subscribe to session lifecycle events.
     pub fn subscribe_events(&self) -> tokio_broadcast::Receiver<SessionEvent> {
         self.events_tx.subscribe()
@@ -1125,6 +1835,7 @@ mod tests {
             child_exited: Arc::new(AtomicBool::new(false)),
             input_tx,
             output_rx: broker.sender(),
+            output_mirror: broker.mirror(),
             shutdown: ShutdownCoordinator::new(),
             parser,
             overlays: OverlayStore::new(),
@@ -1139,6 +1850,13 @@ mod tests {
             visual_update_tx: broadcast::channel::<VisualUpdate>(16).0,
             screen_mode: Arc::new(RwLock::new(ScreenMode::Normal)),
             cancelled: tokio_util::sync::CancellationToken::new(),
+            io_counters: crate::session::IoCounters::default(),
+            prompt_pattern: crate::session::PromptPattern::default(),
+            raw_log: crate::broker::RawLog::default(),
+            logs: crate::session_log::SessionLog::default(),
+            read_pause: crate::session::ReadPause::default(),
+            output_ready: crate::session::OutputReady::default(),
+            output_feed: crate::session::OutputFeed::default(),
         };
         (session, input_rx)
     }
@@ -1149,6 +1867,23 @@ mod tests {
         assert_eq!(session.name, "my-session");
     }
 
+    #[tokio::test]
+    async fn try_input_drops_bytes_when_channel_is_full() {
+        let (session, rx) = create_test_session("try-input");
+        for _ in 0..64 {
+            session.try_input(Bytes::from_static(b"x")).unwrap();
+        }
+        match session.try_input(Bytes::from_static(b"full")) {
+            Err(mpsc::error::TrySendError::Full(bytes)) => assert_eq!(bytes, "full"),
+            other => panic!("expected Full, got {other:?}"),
+        }
+        drop(rx);
+        assert!(matches!(
+            session.try_input(Bytes::from_static(b"closed")),
+            Err(mpsc::error::TrySendError::Closed(_))
+        ));
+    }
+
     #[tokio::test]
     async fn test_session_is_cloneable() {
         let (session, _rx) = create_test_session("clone-me");
@@ -1397,6 +2132,307 @@ mod tests {
             .expect("oneshot should not be dropped");
     }
 
+    #[tokio::test]
+    async fn scrollback_budget_evicts_least_recently_active_first() {
+        let registry = SessionRegistry::new();
+        for name in ["idle", "busy"] {
+            let (mut session, _) = create_test_session(name);
+            session.parser = Parser::spawn_with_feed(80, 24, 1000);
+            let output: String = (0..100).map(|i| format!("{name} {i}\r\n")).collect();
+            session.parser.feed(Bytes::from(output)).await.unwrap();
+            session.parser.barrier().await.unwrap();
+            session.activity.touch();
+            registry.insert(Some(name.to_string()), session).unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let idle = registry.get("idle").unwrap();
+        let held = idle.parser.scrollback_lines();
+        assert!(held > 50);
+
+        // No budget, nothing happens.
+        assert_eq!(registry.enforce_scrollback_budget().await, 0);
+
+        let usage = registry.scrollback_usage();
+        assert_eq!(usage.lines, 2 * held);
+        registry.set_scrollback_budget(Some(usage.bytes - 50 * idle.parser.scrollback_line_bytes()));
+        assert_eq!(registry.enforce_scrollback_budget().await, 50);
+
+        assert_eq!(idle.parser.scrollback_lines(), held - 50);
+        assert_eq!(registry.get("busy").unwrap().parser.scrollback_lines(), held);
+        let usage = registry.scrollback_usage();
+        assert!(usage.bytes <= usage.budget_bytes.unwrap());
+        assert_eq!(usage.evicted_lines, 50);
+    }
+
+    #[tokio::test]
+    async fn journal_keepalive_grows_during_burst_and_shrinks_when_idle() {
+        let registry = SessionRegistry::new();
+        let (mut session, _) = create_test_session("burst");
+        session.parser = Parser::spawn_with_feed(80, 24, 1000);
+        registry.insert(Some("burst".to_string()), session).unwrap();
+        let session = registry.get("burst").unwrap();
+
+        // No keepalive configured, nothing changes.
+        assert_eq!(registry.apply_journal_keepalive(), 0);
+        let default_capacity = session.parser.journal_status().capacity;
+
+        registry.set_journal_keepalive(Some(JournalKeepalive {
+            idle_events: 10,
+            active_events: 4096,
+            idle_after: std::time::Duration::from_millis(50),
+        }));
+
+        // Burst: output with activity, enough events to overflow the idle size.
+        let output: String = (0..200).map(|i| format!("line {i}\r\n")).collect();
+        session.activity.touch();
+        assert_eq!(registry.apply_journal_keepalive(), 0);
+        session.parser.feed(Bytes::from(output)).await.unwrap();
+        session.parser.barrier().await.unwrap();
+        let active = session.parser.journal_status();
+        assert_eq!(active.capacity, 4096);
+        assert_ne!(active.capacity, default_capacity);
+        assert!(active.events > 10, "burst kept {} events", active.events);
+
+        // Quiet long enough to count as idle: the journal is trimmed.
+        tokio::time::sleep(std::time::Duration::from_millis(80)).await;
+        assert_eq!(registry.apply_journal_keepalive(), 1);
+        let idle = session.parser.journal_status();
+        assert_eq!(idle.capacity, 10);
+        assert_eq!(idle.events, 10);
+        assert_eq!(idle.newest_seq, active.newest_seq);
+
+        // New activity brings the larger journal back.
+        session.activity.touch();
+        assert_eq!(registry.apply_journal_keepalive(), 0);
+        assert_eq!(session.parser.journal_status().capacity, 4096);
+    }
+
+    #[tokio::test]
+    async fn resize_keeps_pty_parser_and_terminal_size_in_sync() {
+        let (session, _child_exit_rx) = Session::spawn(
+            "resize".to_string(),
+            crate::pty::SpawnCommand::Command {
+                command: "sleep 5".to_string(),
+                interactive: false,
+                force_interactive: None,
+                scrub_env: None,
+            },
+            24,
+            80,
+        )
+        .expect("Session::spawn should succeed");
+
+        session.resize(30, 100).await.unwrap();
+        assert_eq!(session.pty.lock().size().unwrap(), (30, 100));
+        assert_eq!(session.terminal_size.get(), (30, 100));
+        let crate::parser::state::QueryResponse::Screen(screen) = session
+            .parser
+            .query(crate::parser::state::Query::Screen { format: Format::Plain })
+            .await
+            .unwrap()
+        else {
+            panic!("expected a screen");
+        };
+        assert_eq!((screen.rows, screen.cols), (30, 100));
+
+        let screen = session.resize_and_screen(12, 40, Format::Plain).await.unwrap();
+        assert_eq!((screen.rows, screen.cols), (12, 40));
+        assert_eq!(session.pty.lock().size().unwrap(), (12, 40));
+        assert_eq!(session.terminal_size.get(), (12, 40));
+        session.force_kill();
+    }
+
+    #[tokio::test]
+    async fn concurrent_input_reaches_pty_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("input");
+        let (session, _child_exit_rx) = Session::spawn(
+            "atomic".to_string(),
+            crate::pty::SpawnCommand::Command {
+                command: format!("stty raw -echo; cat > {}", out.display()),
+                interactive: false,
+                force_interactive: None,
+                scrub_env: None,
+            },
+            24,
+            80,
+        )
+        .expect("Session::spawn should succeed");
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+
+        // Each sender writes blocks made of one letter, larger than a
+        // single PTY write is likely to take at once.
+        const BLOCK: usize = 3000;
+        const BLOCKS: usize = 4;
+        let senders: Vec<_> = (b'a'..=b'h')
+            .map(|letter| {
+                let input_tx = session.input_tx.clone();
+                tokio::spawn(async move {
+                    for _ in 0..BLOCKS {
+                        input_tx
+                            .send(Bytes::from(vec![letter; BLOCK]))
+                            .await
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for sender in senders {
+            sender.await.unwrap();
+        }
+
+        let expected = 8 * BLOCKS * BLOCK;
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(10);
+        let written = loop {
+            let written = std::fs::read(&out).unwrap_or_default();
+            if written.len() >= expected || tokio::time::Instant::now() > deadline {
+                break written;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        };
+        session.force_kill();
+        assert_eq!(written.len(), expected);
+        for block in written.chunks(BLOCK) {
+            assert!(
+                block.iter().all(|&b| b == block[0]),
+                "a block was interleaved"
+            );
+        }
+    }
+
+    /// The reason carried by the parser's final `End` event.
+    async fn end_reason(session: &Session) -> Option<ShutdownReason> {
+        use crate::parser::{events::Event, SubscriptionEvent};
+        use tokio_stream::StreamExt;
+        let mut events = session.parser.subscribe();
+        loop {
+            match tokio::time::timeout(std::time::Duration::from_secs(5), events.next()).await {
+                Ok(Some(SubscriptionEvent::Event(Event::End { reason, .. }))) => return reason,
+                Ok(Some(_)) => {}
+                Ok(None) | Err(_) => panic!("no End event"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn end_event_carries_exit_code() {
+        let (session, _child_exit_rx) = Session::spawn(
+            "exits".to_string(),
+            crate::pty::SpawnCommand::Command {
+                command: "sleep 0.2; exit 3".to_string(),
+                interactive: false,
+                force_interactive: None,
+                scrub_env: None,
+            },
+            24,
+            80,
+        )
+        .expect("Session::spawn should succeed");
+
+        assert_eq!(end_reason(&session).await, Some(ShutdownReason::Exited { code: 3 }));
+        assert_eq!(session.shutdown_reason(), Some(ShutdownReason::Exited { code: 3 }));
+    }
+
+    #[tokio::test]
+    async fn end_event_reports_kill() {
+        let (session, _child_exit_rx) = Session::spawn(
+            "killed".to_string(),
+            crate::pty::SpawnCommand::Command {
+                command: "sleep 30".to_string(),
+                interactive: false,
+                force_interactive: None,
+                scrub_env: None,
+            },
+            24,
+            80,
+        )
+        .expect("Session::spawn should succeed");
+
+        let reason = tokio::spawn({
+            let session = session.clone();
+            async move { end_reason(&session).await }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        session.force_kill();
+        assert_eq!(reason.await.unwrap(), Some(ShutdownReason::Killed));
+    }
+
+    #[tokio::test]
+    async fn paused_reader_holds_output_until_resumed() {
+        let (session, _child_exit_rx) = Session::spawn(
+            "paused".to_string(),
+            crate::pty::SpawnCommand::Command {
+                command: "sleep 0.3; echo held-back".to_string(),
+                interactive: false,
+                force_interactive: None,
+                scrub_env: None,
+            },
+            24,
+            80,
+        )
+        .expect("Session::spawn should succeed");
+        let mut output_rx = session.output_rx.subscribe();
+        // Let the reader block in `read` first, so the pause lands mid-read.
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        session.read_pause.pause();
+
+        let held = tokio::time::timeout(std::time::Duration::from_millis(800), output_rx.recv()).await;
+        assert!(held.is_err(), "no output should be read while paused");
+
+        session.read_pause.resume();
+        let mut collected = Vec::new();
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+        while let Ok(Ok(data)) = tokio::time::timeout_at(deadline, output_rx.recv()).await {
+            collected.extend_from_slice(&data);
+            if String::from_utf8_lossy(&collected).contains("held-back") {
+                break;
+            }
+        }
+        assert!(String::from_utf8_lossy(&collected).contains("held-back"));
+    }
+
+    #[tokio::test]
+    async fn output_ready_is_set_by_first_output() {
+        let (session, _child_exit_rx) = Session::spawn(
+            "ready".to_string(),
+            crate::pty::SpawnCommand::Command {
+                command: "sleep 0.2; echo up; sleep 5".to_string(),
+                interactive: false,
+                force_interactive: None,
+                scrub_env: None,
+            },
+            24,
+            80,
+        )
+        .expect("Session::spawn should succeed");
+        assert!(!session.output_ready.is_ready());
+        tokio::time::timeout(std::time::Duration::from_secs(5), session.output_ready.wait())
+            .await
+            .expect("first output should mark the session ready");
+        assert!(session.output_ready.is_ready());
+        session.force_kill();
+    }
+
+    #[test]
+    fn read_pause_wait_returns_on_resume_or_cancel() {
+        let pause = ReadPause::default();
+        let cancelled = tokio_util::sync::CancellationToken::new();
+        pause.pause();
+        let waiter = std::thread::spawn({
+            let (pause, cancelled) = (pause.clone(), cancelled.clone());
+            move || pause.wait(&cancelled)
+        });
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!waiter.is_finished());
+        pause.resume();
+        waiter.join().unwrap();
+
+        pause.pause();
+        cancelled.cancel();
+        pause.wait(&cancelled);
+        assert!(pause.is_paused());
+    }
+
     #[tokio::test]
     async fn session_spawn_with_options_applies_env() {
         let mut env = std::collections::HashMap::new();
@@ -1407,8 +2443,7 @@ mod tests {
             crate::pty::SpawnCommand::default(),
             24,
             80,
-            None,
-            Some(env),
+            SpawnOptions { env: Some(env), ..Default::default() },
         )
         .expect("Session::spawn_with_options should succeed");
 
@@ -1456,6 +2491,16 @@ mod tests {
         assert!(result.unwrap().is_ok(), "detach signal should not be an error");
     }
 
+    #[test]
+    fn read_buffer_bytes_is_clamped() {
+        let registry = SessionRegistry::new();
+        assert_eq!(registry.read_buffer_bytes(), DEFAULT_READ_BUFFER_BYTES);
+        registry.set_read_buffer_bytes(1);
+        assert_eq!(registry.read_buffer_bytes(), MIN_READ_BUFFER_BYTES);
+        registry.set_read_buffer_bytes(usize::MAX);
+        assert_eq!(registry.read_buffer_bytes(), MAX_READ_BUFFER_BYTES);
+    }
+
     #[test]
     fn validate_tag_accepts_valid() {
         assert!(validate_tag("build").is_ok());
@@ -1659,6 +2704,15 @@ mod tests {
         assert!(validate_session_name("null\0byte").is_err());
         assert!(validate_session_name("semi;colon").is_err());
         assert!(validate_session_name("slash/path").is_err());
+        assert!(validate_session_name("tab\tname").is_err());
+        assert!(validate_session_name("caf\u{e9}").is_err(), "non-ASCII needs URL encoding");
+    }
+
+    #[test]
+    fn validate_session_name_rejects_dot_segments() {
+        assert!(validate_session_name(".").is_err());
+        assert!(validate_session_name("..").is_err());
+        assert!(validate_session_name(".hidden").is_ok());
     }
 
     #[test]
@@ -1671,4 +2725,113 @@ mod tests {
         assert!(registry.name_available(&Some("valid-name".to_string())).is_ok());
         assert!(registry.name_available(&None).is_ok()); // auto-generated names bypass validation
     }
+
+    fn strip(chunks: &[&[u8]]) -> String {
+        let mut stripper = AnsiStripper::new();
+        let mut out = String::new();
+        for chunk in chunks {
+            stripper.push(chunk, &mut out);
+        }
+        out
+    }
+
+    #[test]
+    fn stripper_removes_csi_osc_and_charset() {
+        assert_eq!(strip(&[b"\x1b[1;32muser@host\x1b[0m:~$ "]), "user@host:~$ ");
+        assert_eq!(strip(&[b"\x1b]0;title\x07ok"]), "ok");
+        assert_eq!(strip(&[b"\x1b]0;title\x1b\\ok"]), "ok");
+        assert_eq!(strip(&[b"\x1b(Bplain"]), "plain");
+        assert_eq!(strip(&[b"partial\x1b[3"]), "partial");
+    }
+
+    #[test]
+    fn stripper_carries_split_sequences_and_characters() {
+        assert_eq!(strip(&[b"partial\x1b[3", b"1mred"]), "partialred");
+        assert_eq!(strip(&[b"a\x1b]0;ti", b"tle\x1b", b"\\b"]), "ab");
+        assert_eq!(strip(&[b"caf\xc3", b"\xa9 \xc2", b"\x9b1m!"]), "caf\u{e9} !");
+        assert_eq!(strip(&[b"\x1bP$qm\x1b\\done"]), "done");
+    }
+
+    #[tokio::test]
+    async fn wait_for_output_matches_pattern_split_across_chunks() {
+        let (session, _rx) = create_test_session("expect");
+        let waiter = {
+            let session = session.clone();
+            tokio::spawn(async move {
+                session
+                    .wait_for_output("DONE$", false, std::time::Duration::from_secs(5))
+                    .await
+            })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        // Pattern split across chunks and interleaved with a color code.
+        let _ = session.output_rx.send(Bytes::from("build ok\r\nDO"));
+        let _ = session.output_rx.send(Bytes::from("\x1b[32mNE"));
+        let _ = session.output_rx.send(Bytes::from("$\x1b[0m trailing"));
+
+        let m = waiter.await.unwrap().unwrap();
+        assert_eq!(m.matched, "DONE$");
+        assert_eq!(m.output, "build ok\r\nDONE$");
+    }
+
+    #[tokio::test]
+    async fn wait_for_output_regex_and_timeout() {
+        let (session, _rx) = create_test_session("expect-re");
+        let waiter = {
+            let session = session.clone();
+            tokio::spawn(async move {
+                session
+                    .wait_for_output(r"exit=\d+", true, std::time::Duration::from_secs(5))
+                    .await
+            })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        let _ = session.output_rx.send(Bytes::from("exit=42\n"));
+        assert_eq!(waiter.await.unwrap().unwrap().matched, "exit=42");
+
+        let err = session
+            .wait_for_output("never", false, std::time::Duration::from_millis(50))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ExpectError::Timeout));
+
+        let err = session
+            .wait_for_output("(", true, std::time::Duration::from_millis(50))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ExpectError::InvalidPattern(_)));
+    }
+
+    #[tokio::test]
+    async fn cpr_responder_replies_with_cursor_position() {
+        let (mut session, mut input_rx) = create_test_session("cpr");
+        let (parser_tx, parser_rx) = mpsc::channel(16);
+        session.parser = Parser::spawn(parser_rx, 80, 24, 1000);
+        let responder = session.spawn_cpr_responder();
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        // Cursor sits at row 1, col 3 when the query arrives; output after
+        // the query must not affect the reported position.
+        parser_tx.send(Bytes::from("a\r\nabc\x1b[6nmore")).await.unwrap();
+        let reply = tokio::time::timeout(std::time::Duration::from_secs(2), input_rx.recv())
+            .await
+            .expect("timed out waiting for CPR reply")
+            .unwrap();
+        assert_eq!(&reply[..], b"\x1b[2;4R");
+
+        // Other queries are left for clients to answer.
+        parser_tx.send(Bytes::from("\x1b[c")).await.unwrap();
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(100), input_rx.recv())
+                .await
+                .is_err()
+        );
+
+        session.cancelled.cancel();
+        tokio::time::timeout(std::time::Duration::from_secs(1), responder)
+            .await
+            .expect("responder should stop on cancel")
+            .unwrap();
+    }
 }
@@ -0,0 +1,285 @@
+//! Per-session capture of log events.
+//!
+//! `RUST_LOG` applies to the whole process, so chasing one misbehaving
+//! session means wading through every other session's logs. Each session
+//! instead keeps a small ring buffer of its own recent log lines, served by
+//! `GET /sessions/:name/logs`.
+//!
+//! Events are attributed to a session by the `session_id` field of an
+//! enclosing span (see [`SessionLog::span`]); [`SessionLogLayer`] is the
+//! `tracing` layer that does the routing. Capture is best-effort: events
+//! logged outside a session span are not captured, and only the newest
+//! [`LOG_CAPACITY`] lines are kept.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, Weak};
+
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+use tracing::field::{Field, Visit};
+use tracing::{span, Event, Level, Subscriber};
+use tracing_subscriber::filter::{Filtered, Targets};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Lines kept per session; older lines are dropped first.
+pub const LOG_CAPACITY: usize = 500;
+
+/// Longest message kept for one line; longer messages are cut at a char
+/// boundary.
+pub const MAX_LINE_BYTES: usize = 4096;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Live session logs by id, for the layer to look up. Entries are weak so a
+/// session's buffer is freed with the session; dead entries are pruned when
+/// new logs are registered.
+fn logs_by_id() -> &'static RwLock<HashMap<u64, Weak<Inner>>> {
+    static LOGS: OnceLock<RwLock<HashMap<u64, Weak<Inner>>>> = OnceLock::new();
+    LOGS.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// One captured log line.
+#[derive(Clone, Debug, Serialize)]
+pub struct LogLine {
+    /// Milliseconds since the Unix epoch.
+    pub time_ms: u64,
+    pub level: &'static str,
+    pub target: String,
+    /// The event message followed by its other fields as `key=value`.
+    pub message: String,
+}
+
+struct Inner {
+    id: u64,
+    enabled: AtomicBool,
+    lines: Mutex<VecDeque<LogLine>>,
+    /// Lines pushed out of the buffer since the session started.
+    dropped: AtomicU64,
+}
+
+/// A session's log buffer. Clones share the buffer.
+#[derive(Clone)]
+pub struct SessionLog(Arc<Inner>);
+
+impl Default for SessionLog {
+    fn default() -> Self {
+        let inner = Arc::new(Inner {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            enabled: AtomicBool::new(true),
+            lines: Mutex::new(VecDeque::new()),
+            dropped: AtomicU64::new(0),
+        });
+        let mut logs = logs_by_id().write();
+        logs.retain(|_, log| log.strong_count() > 0);
+        logs.insert(inner.id, Arc::downgrade(&inner));
+        drop(logs);
+        Self(inner)
+    }
+}
+
+impl SessionLog {
+    /// Stable id carried by the session's span; unlike the name, it
+    /// survives renames.
+    pub fn id(&self) -> u64 {
+        self.0.id
+    }
+
+    /// A span whose events are captured into this log. Enter it (or
+    /// `instrument` a future with it) around work done for the session.
+    pub fn span(&self, name: &str) -> tracing::Span {
+        tracing::debug_span!("session", session_id = self.0.id, session = %name)
+    }
+
+    /// Whether events are being captured.
+    pub fn enabled(&self) -> bool {
+        self.0.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Turn capture on or off. Lines already captured are kept.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.0.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Lines dropped to stay within [`LOG_CAPACITY`].
+    pub fn dropped(&self) -> u64 {
+        self.0.dropped.load(Ordering::Relaxed)
+    }
+
+    /// The newest `limit` lines (all of them if `None`), oldest first.
+    pub fn lines(&self, limit: Option<usize>) -> Vec<LogLine> {
+        let lines = self.0.lines.lock();
+        let skip = limit.map_or(0, |n| lines.len().saturating_sub(n));
+        lines.iter().skip(skip).cloned().collect()
+    }
+
+    fn push(&self, line: LogLine) {
+        let mut lines = self.0.lines.lock();
+        if lines.len() >= LOG_CAPACITY {
+            lines.pop_front();
+            self.0.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        lines.push_back(line);
+    }
+
+    fn lookup(id: u64) -> Option<SessionLog> {
+        let log = logs_by_id().read().get(&id)?.upgrade();
+        log.map(SessionLog)
+    }
+}
+
+/// Session id recorded on a span when it is created.
+struct SessionId(u64);
+
+#[derive(Default)]
+struct SessionIdVisitor(Option<u64>);
+
+impl Visit for SessionIdVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "session_id" {
+            self.0 = Some(value);
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+/// Formats an event as its message followed by `key=value` pairs.
+#[derive(Default)]
+struct LineVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for LineVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// `tracing` layer that copies events inside a session span into that
+/// session's [`SessionLog`].
+pub struct SessionLogLayer;
+
+/// [`SessionLogLayer`] limited to wsh's own events up to `DEBUG`, so a
+/// session's log has detail even when `RUST_LOG` is at `info`. Add it to
+/// the subscriber next to the output layer, each with its own filter.
+pub fn layer<S>() -> Filtered<SessionLogLayer, Targets, S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    SessionLogLayer.with_filter(Targets::new().with_target("wsh", Level::DEBUG))
+}
+
+impl<S> Layer<S> for SessionLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let mut visitor = SessionIdVisitor::default();
+        attrs.record(&mut visitor);
+        if let (Some(session_id), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().insert(SessionId(session_id));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(scope) = ctx.event_scope(event) else {
+            return;
+        };
+        let Some(session_id) = scope
+            .into_iter()
+            .find_map(|span| span.extensions().get::<SessionId>().map(|id| id.0))
+        else {
+            return;
+        };
+        let Some(log) = SessionLog::lookup(session_id).filter(|log| log.enabled()) else {
+            return;
+        };
+
+        let mut visitor = LineVisitor::default();
+        event.record(&mut visitor);
+        let mut message = visitor.message;
+        message.push_str(&visitor.fields);
+        if message.len() > MAX_LINE_BYTES {
+            let mut end = MAX_LINE_BYTES;
+            while !message.is_char_boundary(end) {
+                end -= 1;
+            }
+            message.truncate(end);
+        }
+        let time_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        log.push(LogLine {
+            time_ms,
+            level: event.metadata().level().as_str(),
+            target: event.metadata().target().to_string(),
+            message,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::prelude::*;
+
+    fn with_layer(f: impl FnOnce()) {
+        let subscriber = tracing_subscriber::registry().with(layer());
+        tracing::subscriber::with_default(subscriber, f);
+    }
+
+    #[test]
+    fn captures_events_inside_the_session_span() {
+        let log = SessionLog::default();
+        let other = SessionLog::default();
+        with_layer(|| {
+            tracing::info!("outside any session");
+            let _span = log.span("dev").entered();
+            tracing::debug!(rows = 24, "resized");
+            let _inner = tracing::debug_span!("query").entered();
+            tracing::warn!("from a nested span");
+        });
+
+        let lines = log.lines(None);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].level, "DEBUG");
+        assert_eq!(lines[0].message, "resized rows=24");
+        assert_eq!(lines[1].message, "from a nested span");
+        assert!(other.lines(None).is_empty());
+    }
+
+    #[test]
+    fn keeps_newest_lines_and_honors_the_toggle() {
+        let log = SessionLog::default();
+        with_layer(|| {
+            let _span = log.span("dev").entered();
+            for i in 0..LOG_CAPACITY + 3 {
+                tracing::info!(i, "line");
+            }
+            log.set_enabled(false);
+            tracing::info!("not captured");
+        });
+
+        assert_eq!(log.dropped(), 3);
+        let lines = log.lines(Some(2));
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].message, format!("line i={}", LOG_CAPACITY + 2));
+        assert_eq!(log.lines(None).len(), LOG_CAPACITY);
+    }
+}
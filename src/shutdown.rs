@@ -3,10 +3,16 @@
 //! Tracks active WebSocket connections and provides a mechanism to:
 //! 1. Signal all connections to close
 //! 2. Wait until all connections have actually closed
+//!
+//! A shutdown can carry a suggested reconnect delay, which WebSocket close
+//! frames pass on so clients don't all reconnect at once.
 
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
+use parking_lot::Mutex;
 use tokio::sync::{watch, Notify};
+use tokio_util::sync::CancellationToken;
 
 /// Coordinates graceful shutdown of client connections.
 #[derive(Clone)]
@@ -21,6 +27,8 @@ struct Inner {
     active: AtomicUsize,
     /// Notified when all connections close
     all_closed: Notify,
+    /// Reconnect delay suggested to clients, set before shutdown is signaled
+    retry_after: Mutex<Option<Duration>>,
 }
 
 impl ShutdownCoordinator {
@@ -31,6 +39,7 @@ impl ShutdownCoordinator {
                 shutdown_tx,
                 active: AtomicUsize::new(0),
                 all_closed: Notify::new(),
+                retry_after: Mutex::new(None),
             }),
         }
     }
@@ -51,6 +60,18 @@ impl ShutdownCoordinator {
         let _ = self.inner.shutdown_tx.send(true);
     }
 
+    /// Signal all connections to shut down, suggesting that clients wait
+    /// `retry_after` before reconnecting.
+    pub fn shutdown_with_retry_after(&self, retry_after: Duration) {
+        *self.inner.retry_after.lock() = Some(retry_after);
+        self.shutdown();
+    }
+
+    /// The reconnect delay suggested with the shutdown, if any.
+    pub fn retry_after(&self) -> Option<Duration> {
+        *self.inner.retry_after.lock()
+    }
+
     /// Wait until all connections have closed.
     /// Returns immediately if there are no active connections.
     pub async fn wait_for_all_closed(&self) {
@@ -79,6 +100,39 @@ impl Default for ShutdownCoordinator {
     }
 }
 
+/// A request to stop the server, made over the Unix socket by `wsh stop`.
+/// Clones share the request.
+#[derive(Clone, Default)]
+pub struct ShutdownRequest {
+    token: CancellationToken,
+    restart: Arc<AtomicBool>,
+}
+
+impl ShutdownRequest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the server to stop. `restart` means it is coming straight back,
+    /// so clients may reconnect sooner.
+    pub fn request(&self, restart: bool) {
+        if restart {
+            self.restart.store(true, Ordering::SeqCst);
+        }
+        self.token.cancel();
+    }
+
+    /// Completes once a stop has been requested.
+    pub async fn requested(&self) {
+        self.token.cancelled().await;
+    }
+
+    /// Whether the stop was requested as a restart.
+    pub fn is_restart(&self) -> bool {
+        self.restart.load(Ordering::SeqCst)
+    }
+}
+
 /// RAII guard that decrements connection count when dropped.
 pub struct ConnectionGuard {
     inner: Arc<Inner>,
@@ -192,4 +246,28 @@ mod tests {
         shutdown_rx.changed().await.unwrap();
         assert!(*shutdown_rx.borrow());
     }
+
+    #[tokio::test]
+    async fn test_shutdown_carries_retry_after() {
+        let coord = ShutdownCoordinator::new();
+        let (_guard, shutdown_rx) = coord.register();
+        assert_eq!(coord.retry_after(), None);
+
+        coord.shutdown_with_retry_after(Duration::from_secs(30));
+        assert!(*shutdown_rx.borrow());
+        assert_eq!(coord.retry_after(), Some(Duration::from_secs(30)));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_request_records_restart() {
+        let request = ShutdownRequest::new();
+        let clone = request.clone();
+        clone.request(true);
+
+        tokio::time::timeout(Duration::from_millis(100), request.requested())
+            .await
+            .expect("should complete");
+        assert!(request.is_restart());
+        assert!(!ShutdownRequest::new().is_restart());
+    }
 }
@@ -1,5 +1,5 @@
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode, size};
-use std::io::Write;
+use std::io::{IsTerminal, Write};
 use std::sync::Arc;
 use parking_lot::RwLock;
 use thiserror::Error;
@@ -11,6 +11,12 @@ pub enum TerminalError {
 
     #[error("failed to set up screen: {0}")]
     Screen(#[source] std::io::Error),
+
+    #[error("not attached to a terminal")]
+    NoTty,
+
+    #[error("failed to read terminal size: {0}")]
+    Size(#[source] std::io::Error),
 }
 
 /// RAII guard for terminal raw mode.
@@ -20,19 +26,64 @@ pub enum TerminalError {
 ///
 /// Raw mode is needed to capture all keystrokes (including Ctrl+C, etc.) and
 /// forward them to the PTY instead of having the local terminal handle them.
+///
+/// Tests without a tty use [`RawModeGuard::simulated`] instead (with the
+/// `test-support` feature), which tracks the state without touching the
+/// terminal.
 pub struct RawModeGuard {
-    _private: (),
+    #[cfg(any(test, feature = "test-support"))]
+    simulated: bool,
+}
+
+#[cfg(any(test, feature = "test-support"))]
+thread_local! {
+    /// Raw mode state of a simulated guard on this thread.
+    static SIMULATED_RAW_MODE: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
 }
 
 impl RawModeGuard {
     pub fn new() -> Result<Self, TerminalError> {
         enable_raw_mode().map_err(TerminalError::EnableRawMode)?;
-        Ok(Self { _private: () })
+        Ok(Self {
+            #[cfg(any(test, feature = "test-support"))]
+            simulated: false,
+        })
+    }
+
+    /// Whether the local terminal is in raw mode (with `test-support`, also
+    /// whether a simulated guard on this thread has it enabled).
+    pub fn is_enabled() -> bool {
+        #[cfg(any(test, feature = "test-support"))]
+        if SIMULATED_RAW_MODE.with(std::cell::Cell::get) {
+            return true;
+        }
+        crossterm::terminal::is_raw_mode_enabled().unwrap_or(false)
+    }
+
+    /// A guard that enables raw mode for [`RawModeGuard::is_enabled`] on
+    /// this thread without a tty. Dropping it restores cooked mode, like
+    /// the real guard.
+    #[cfg(any(test, feature = "test-support"))]
+    pub fn simulated() -> Self {
+        SIMULATED_RAW_MODE.with(|state| state.set(true));
+        Self { simulated: true }
+    }
+
+    /// Switch a simulated guard's raw mode on or off.
+    #[cfg(any(test, feature = "test-support"))]
+    pub fn set_enabled(&self, enabled: bool) {
+        assert!(self.simulated, "only simulated raw mode can be toggled");
+        SIMULATED_RAW_MODE.with(|state| state.set(enabled));
     }
 }
 
 impl Drop for RawModeGuard {
     fn drop(&mut self) {
+        #[cfg(any(test, feature = "test-support"))]
+        if self.simulated {
+            SIMULATED_RAW_MODE.with(|state| state.set(false));
+            return;
+        }
         // Ignore SIGQUIT before restoring cooked mode. In raw mode the kernel
         // doesn't generate SIGQUIT for Ctrl+\, but during the brief window
         // while crossterm restores cooked mode the signal disposition is
@@ -47,6 +98,24 @@ impl Drop for RawModeGuard {
     }
 }
 
+/// Install a panic hook that leaves raw mode before the previous hook prints
+/// the panic message.
+///
+/// [`RawModeGuard`] only restores the terminal when the panicking thread
+/// unwinds through it. A panic on another thread or task, or one that ends
+/// in `process::exit`, would otherwise leave the shell in raw mode with the
+/// message smeared across the screen. Outside raw mode (e.g. `wsh server`)
+/// the hook only defers to the previous one.
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if RawModeGuard::is_enabled() {
+            let _ = disable_raw_mode();
+        }
+        previous(info);
+    }));
+}
+
 /// The screen mode to use when wsh starts.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ScreenMode {
@@ -111,9 +180,47 @@ impl Drop for ScreenGuard {
 ///
 /// Returns (rows, cols) to match PtySize convention.
 /// Note: crossterm::terminal::size() returns (cols, rows), so we swap them.
-pub fn terminal_size() -> anyhow::Result<(u16, u16)> {
-    let (cols, rows) = size()?;
-    Ok((rows, cols))
+///
+/// Fails with [`TerminalError::NoTty`] when neither stdin nor stdout is a
+/// terminal (pipes, CI, headless launches), so callers can tell that apart
+/// from a terminal that couldn't be queried.
+pub fn terminal_size() -> Result<(u16, u16), TerminalError> {
+    match size() {
+        Ok((cols, rows)) => Ok((rows, cols)),
+        Err(_) if !std::io::stdin().is_terminal() && !std::io::stdout().is_terminal() => {
+            Err(TerminalError::NoTty)
+        }
+        Err(e) => Err(TerminalError::Size(e)),
+    }
+}
+
+/// Initial (rows, cols) for a client session.
+///
+/// Explicit dimensions win over auto-detection. Any dimension left unset is
+/// taken from the local terminal, falling back to 24x80 (logged at info
+/// level, with the reason) when the size can't be detected.
+pub fn initial_size(rows: Option<u16>, cols: Option<u16>) -> (u16, u16) {
+    resolve_size(rows, cols, terminal_size)
+}
+
+fn resolve_size(
+    rows: Option<u16>,
+    cols: Option<u16>,
+    detect: impl FnOnce() -> Result<(u16, u16), TerminalError>,
+) -> (u16, u16) {
+    match (rows, cols) {
+        (Some(rows), Some(cols)) => (rows, cols),
+        _ => {
+            let (detected_rows, detected_cols) = detect().unwrap_or_else(|e| {
+                tracing::info!(
+                    reason = %e,
+                    "terminal size unavailable, using 24x80; pass --rows/--cols to set it"
+                );
+                (24, 80)
+            });
+            (rows.unwrap_or(detected_rows), cols.unwrap_or(detected_cols))
+        }
+    }
 }
 
 /// Thread-safe shared terminal dimensions.
@@ -143,3 +250,108 @@ impl TerminalSize {
         *self.inner.write() = (rows, cols);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simulated_raw_mode_is_restored_on_drop() {
+        assert!(!RawModeGuard::is_enabled());
+        let guard = RawModeGuard::simulated();
+        assert!(RawModeGuard::is_enabled());
+        guard.set_enabled(false);
+        assert!(!RawModeGuard::is_enabled());
+        guard.set_enabled(true);
+        assert!(RawModeGuard::is_enabled());
+        drop(guard);
+        assert!(!RawModeGuard::is_enabled());
+    }
+
+    #[test]
+    fn explicit_size_skips_detection() {
+        let size = resolve_size(Some(50), Some(200), || panic!("should not detect"));
+        assert_eq!(size, (50, 200));
+    }
+
+    #[test]
+    fn partial_override_fills_from_detection() {
+        assert_eq!(resolve_size(Some(50), None, || Ok((30, 100))), (50, 100));
+        assert_eq!(resolve_size(None, Some(200), || Ok((30, 100))), (30, 200));
+    }
+
+    #[test]
+    fn no_tty_falls_back_to_24x80() {
+        assert_eq!(resolve_size(None, None, || Err(TerminalError::NoTty)), (24, 80));
+        assert_eq!(resolve_size(None, Some(132), || Err(TerminalError::NoTty)), (24, 132));
+    }
+
+    #[test]
+    fn size_errors_are_distinct_from_no_tty() {
+        // Needs control over the process's terminal, so the query runs in
+        // a child process in a new session (no controlling terminal) with
+        // no `TERM`, which rules out crossterm's `tput` fallback.
+        const CHILD_ENV: &str = "WSH_TERMINAL_SIZE_CHILD";
+        if std::env::var_os(CHILD_ENV).is_some() {
+            match terminal_size() {
+                Ok(size) => println!("size: {size:?}"),
+                Err(e) => println!("error: {e}"),
+            }
+            return;
+        }
+        let query = |stdin: std::process::Stdio| {
+            use std::os::unix::process::CommandExt;
+            let mut cmd = std::process::Command::new(std::env::current_exe().unwrap());
+            cmd.args(["--exact", "terminal::tests::size_errors_are_distinct_from_no_tty", "--nocapture"])
+                .env(CHILD_ENV, "1")
+                .env_remove("TERM")
+                .stdin(stdin);
+            // SAFETY: setsid is async-signal-safe.
+            unsafe {
+                cmd.pre_exec(|| {
+                    libc::setsid();
+                    Ok(())
+                });
+            }
+            let output = cmd.output().unwrap();
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        };
+
+        let stdout = query(std::process::Stdio::null());
+        assert!(stdout.contains("error: not attached to a terminal"), "stdout: {stdout}");
+
+        // A terminal on stdin that isn't the controlling terminal: attached,
+        // but the size can't be read.
+        let (mut master, mut slave) = (0, 0);
+        // SAFETY: openpty writes the two descriptors; the rest may be null.
+        let rc = unsafe {
+            libc::openpty(&mut master, &mut slave, std::ptr::null_mut(), std::ptr::null(), std::ptr::null())
+        };
+        assert_eq!(rc, 0, "openpty failed");
+        // SAFETY: both descriptors were just opened and are owned here.
+        let (_master, slave) = unsafe {
+            use std::os::unix::io::FromRawFd;
+            (std::os::unix::io::OwnedFd::from_raw_fd(master), std::os::unix::io::OwnedFd::from_raw_fd(slave))
+        };
+        let stdout = query(std::process::Stdio::from(slave));
+        assert!(stdout.contains("error: failed to read terminal size"), "stdout: {stdout}");
+    }
+
+    #[test]
+    fn panic_hook_keeps_panic_message() {
+        // The hook is process-wide, so the panic runs in a child process.
+        const CHILD_ENV: &str = "WSH_PANIC_HOOK_CHILD";
+        if std::env::var_os(CHILD_ENV).is_some() {
+            install_panic_hook();
+            panic!("panic hook test message");
+        }
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "terminal::tests::panic_hook_keeps_panic_message", "--nocapture"])
+            .env(CHILD_ENV, "1")
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("panic hook test message"), "stderr: {stderr}");
+    }
+}
@@ -42,6 +42,7 @@ fn create_test_app() -> (axum::Router, mpsc::Receiver<Bytes>, broadcast::Sender<
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
         input_tx,
         output_rx: broker.sender(),
+        output_mirror: broker.mirror(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -56,6 +57,13 @@ fn create_test_app() -> (axum::Router, mpsc::Receiver<Bytes>, broadcast::Sender<
         visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
         screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
         cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
@@ -68,6 +76,56 @@ fn create_test_app() -> (axum::Router, mpsc::Receiver<Bytes>, broadcast::Sender<
     (router(state, RouterConfig::default()), input_rx, broker.sender())
 }
 
+/// Like `create_test_app`, but also returns the parser's input sender so tests
+/// can drive terminal state directly (the parser exits once it is dropped).
+fn create_test_app_with_parser() -> (axum::Router, mpsc::Receiver<Bytes>, mpsc::Sender<Bytes>) {
+    let (input_tx, input_rx) = mpsc::channel(64);
+    let broker = Broker::new();
+    let (parser_tx, parser_rx) = mpsc::channel(256);
+    let parser = Parser::spawn(parser_rx, 80, 24, 1000);
+    let session = Session {
+        name: "test".to_string(),
+        pid: None,
+        command: "test".to_string(),
+        client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+        tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
+        child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        input_tx,
+        output_rx: broker.sender(),
+        output_mirror: broker.mirror(),
+        shutdown: ShutdownCoordinator::new(),
+        parser,
+        overlays: OverlayStore::new(),
+        input_mode: InputMode::new(),
+        input_broadcaster: InputBroadcaster::new(),
+        panels: wsh::panel::PanelStore::new(),
+        pty: std::sync::Arc::new(parking_lot::Mutex::new(wsh::pty::Pty::spawn(24, 80, wsh::pty::SpawnCommand::default()).expect("failed to spawn PTY for test"))),
+        terminal_size: wsh::terminal::TerminalSize::new(24, 80),
+        activity: wsh::activity::ActivityTracker::new(),
+        focus: FocusTracker::new(),
+        detach_signal: tokio::sync::broadcast::channel::<()>(1).0,
+        visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
+        screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
+        cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
+    };
+    let registry = SessionRegistry::new();
+    registry.insert(Some("test".into()), session).unwrap();
+    let state = AppState {
+        sessions: registry,
+        shutdown: ShutdownCoordinator::new(),
+        server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)),
+            server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+    };
+    (router(state, RouterConfig::default()), input_rx, parser_tx)
+}
+
 /// Starts the server on a random available port and returns the address.
 async fn start_test_server(app: axum::Router) -> SocketAddr {
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
@@ -145,6 +203,7 @@ async fn test_api_input_multiple_requests() {
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
         input_tx,
         output_rx: broker.sender(),
+        output_mirror: broker.mirror(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -159,6 +218,13 @@ async fn test_api_input_multiple_requests() {
         visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
         screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
         cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
@@ -224,6 +290,21 @@ async fn test_websocket_upgrade_response() {
     );
 }
 
+#[tokio::test]
+async fn test_websocket_unknown_session_rejected_before_upgrade() {
+    let (app, _input_rx, _output_tx) = create_test_app();
+    let addr = start_test_server(app).await;
+
+    let url = format!("ws://{}/sessions/nope/ws/raw", addr);
+    match connect_async(&url).await {
+        Err(tokio_tungstenite::tungstenite::Error::Http(response)) => {
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+        Err(e) => panic!("expected HTTP 404, got {e:?}"),
+        Ok(_) => panic!("upgrade should be refused for an unknown session"),
+    }
+}
+
 #[tokio::test]
 async fn test_websocket_receives_pty_output() {
     let (input_tx, _input_rx) = mpsc::channel(64);
@@ -240,6 +321,7 @@ async fn test_websocket_receives_pty_output() {
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
         input_tx,
         output_rx: output_tx.clone(),
+        output_mirror: broker.mirror(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -254,6 +336,13 @@ async fn test_websocket_receives_pty_output() {
         visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
         screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
         cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
@@ -307,6 +396,7 @@ async fn test_websocket_sends_input_to_pty() {
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
         input_tx,
         output_rx: broker.sender(),
+        output_mirror: broker.mirror(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -321,6 +411,13 @@ async fn test_websocket_sends_input_to_pty() {
         visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
         screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
         cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
@@ -354,6 +451,91 @@ async fn test_websocket_sends_input_to_pty() {
     assert_eq!(received.as_ref(), test_input);
 }
 
+#[tokio::test]
+async fn test_websocket_view_is_output_only() {
+    let (input_tx, mut input_rx) = mpsc::channel(64);
+    let broker = Broker::new();
+    let output_tx = broker.sender();
+    let (_parser_tx, parser_rx) = mpsc::channel(256);
+    let parser = Parser::spawn(parser_rx, 80, 24, 1000);
+    let session = Session {
+        name: "test".to_string(),
+        pid: None,
+        command: "test".to_string(),
+        client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+        tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
+        child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        input_tx,
+        output_rx: output_tx.clone(),
+        output_mirror: broker.mirror(),
+        shutdown: ShutdownCoordinator::new(),
+        parser,
+        overlays: OverlayStore::new(),
+        input_mode: InputMode::new(),
+        input_broadcaster: InputBroadcaster::new(),
+        panels: wsh::panel::PanelStore::new(),
+        pty: std::sync::Arc::new(parking_lot::Mutex::new(wsh::pty::Pty::spawn(24, 80, wsh::pty::SpawnCommand::default()).expect("failed to spawn PTY for test"))),
+        terminal_size: wsh::terminal::TerminalSize::new(24, 80),
+        activity: wsh::activity::ActivityTracker::new(),
+        focus: FocusTracker::new(),
+        detach_signal: tokio::sync::broadcast::channel::<()>(1).0,
+        visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
+        screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
+        cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
+    };
+    let registry = SessionRegistry::new();
+    registry.insert(Some("test".into()), session).unwrap();
+    let state = AppState { sessions: registry, shutdown: ShutdownCoordinator::new(), server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)), server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)) };
+    let app = router(state, RouterConfig::default());
+
+    let addr = start_test_server(app).await;
+    let ws_url = format!("ws://{}/sessions/test/ws/view", addr);
+
+    let (mut ws_stream, _response) = connect_async(&ws_url)
+        .await
+        .expect("Failed to connect WebSocket");
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // Input frames are accepted but never forwarded.
+    ws_stream
+        .send(Message::Binary(b"binary input".to_vec().into()))
+        .await
+        .expect("Failed to send WebSocket message");
+    ws_stream
+        .send(Message::Text("text input".into()))
+        .await
+        .expect("Failed to send WebSocket message");
+    assert!(
+        tokio::time::timeout(Duration::from_millis(200), input_rx.recv())
+            .await
+            .is_err(),
+        "view socket must not forward input"
+    );
+
+    // Output still flows.
+    let test_output = Bytes::from("view output");
+    output_tx
+        .send(test_output.clone())
+        .expect("Failed to send to broadcast channel");
+    let received = tokio::time::timeout(Duration::from_secs(2), ws_stream.next())
+        .await
+        .expect("timed out waiting for WebSocket message")
+        .expect("WebSocket stream ended")
+        .expect("WebSocket error");
+    match received {
+        Message::Binary(data) => assert_eq!(data.to_vec(), test_output.to_vec()),
+        other => panic!("Expected binary message, got: {:?}", other),
+    }
+}
+
 #[tokio::test]
 async fn test_websocket_text_input_to_pty() {
     // Test that text messages are also handled
@@ -370,6 +552,7 @@ async fn test_websocket_text_input_to_pty() {
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
         input_tx,
         output_rx: broker.sender(),
+        output_mirror: broker.mirror(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -384,6 +567,13 @@ async fn test_websocket_text_input_to_pty() {
         visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
         screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
         cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
@@ -432,6 +622,7 @@ async fn test_websocket_bidirectional_communication() {
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
         input_tx,
         output_rx: output_tx.clone(),
+        output_mirror: broker.mirror(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -446,6 +637,13 @@ async fn test_websocket_bidirectional_communication() {
         visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
         screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
         cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
@@ -513,6 +711,7 @@ async fn test_websocket_multiple_outputs() {
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
         input_tx,
         output_rx: output_tx.clone(),
+        output_mirror: broker.mirror(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -527,6 +726,13 @@ async fn test_websocket_multiple_outputs() {
         visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
         screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
         cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
@@ -678,6 +884,7 @@ async fn test_websocket_line_event_includes_total_lines() {
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
         input_tx,
         output_rx: broker.sender(),
+        output_mirror: broker.mirror(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -692,6 +899,13 @@ async fn test_websocket_line_event_includes_total_lines() {
         visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
         screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
         cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
@@ -770,6 +984,7 @@ async fn test_scrollback_endpoint() {
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
         input_tx,
         output_rx: broker.sender(),
+        output_mirror: broker.mirror(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -784,6 +999,13 @@ async fn test_scrollback_endpoint() {
         visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
         screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
         cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
@@ -841,6 +1063,7 @@ async fn test_scrollback_initial_state() {
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
         input_tx,
         output_rx: broker.sender(),
+        output_mirror: broker.mirror(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -855,6 +1078,13 @@ async fn test_scrollback_initial_state() {
         visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
         screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
         cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
@@ -884,3 +1114,540 @@ async fn test_scrollback_initial_state() {
     let total_lines = json["total_lines"].as_u64().unwrap_or(0);
     assert_eq!(total_lines, 24, "Expected initial screen lines (24 rows), got {}", total_lines);
 }
+
+#[tokio::test]
+async fn test_screen_etag_and_not_modified() {
+    let (app, _input_rx, parser_tx) = create_test_app_with_parser();
+
+    parser_tx.send(Bytes::from("hello")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let get_screen = |query: &'static str, etag: Option<String>| {
+        let mut req = Request::builder().uri(format!("/sessions/test/screen?{}", query));
+        if let Some(etag) = etag {
+            req = req.header("if-none-match", etag);
+        }
+        app.clone().oneshot(req.body(Body::empty()).unwrap())
+    };
+
+    let response = get_screen("format=plain", None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response
+        .headers()
+        .get("etag")
+        .expect("screen response should carry an ETag")
+        .to_str()
+        .unwrap()
+        .to_string();
+    assert!(etag.ends_with("-plain\""), "unexpected etag: {}", etag);
+
+    // Same ETag, unchanged screen -> 304 with empty body.
+    let response = get_screen("format=plain", Some(etag.clone())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert!(body.is_empty());
+
+    // The ETag is format-specific: a styled request does not match.
+    let response = get_screen("format=styled", Some(etag.clone())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // New output invalidates the ETag.
+    parser_tx.send(Bytes::from(" world")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let response = get_screen("format=plain", Some(etag)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_clear_endpoint_scopes() {
+    let (app, _input_rx, parser_tx) = create_test_app_with_parser();
+
+    for i in 0..40 {
+        parser_tx.send(Bytes::from(format!("line {}\r\n", i))).await.unwrap();
+    }
+    parser_tx.send(Bytes::from("$ ")).await.unwrap();
+
+    let post = |uri: &'static str| {
+        app.clone()
+            .oneshot(Request::builder().method("POST").uri(uri).body(Body::empty()).unwrap())
+    };
+    let screen = || async {
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri("/sessions/test/screen?format=plain").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+    };
+
+    // Default scope: scrollback only. Output queued before the request is
+    // cleared too.
+    assert_eq!(post("/sessions/test/clear").await.unwrap().status(), StatusCode::NO_CONTENT);
+    let json = screen().await;
+    assert_eq!(json["total_lines"], 24);
+    assert_eq!(json["lines"][23], "$");
+
+    assert_eq!(post("/sessions/test/clear?scope=all").await.unwrap().status(), StatusCode::NO_CONTENT);
+    let json = screen().await;
+    assert!(json["lines"].as_array().unwrap().iter().all(|l| l == ""));
+
+    assert_eq!(post("/sessions/test/clear?scope=everything").await.unwrap().status(), StatusCode::BAD_REQUEST);
+    assert_eq!(post("/sessions/missing/clear").await.unwrap().status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_restore_endpoint_feeds_parser_only() {
+    let (app, mut input_rx, _parser_tx) = create_test_app_with_parser();
+
+    let saved: String = (0..30).map(|i| format!("saved {}\r\n", i)).collect();
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/sessions/test/restore?quiet=true")
+                .header("content-type", "application/octet-stream")
+                .body(Body::from(saved))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = app
+        .clone()
+        .oneshot(Request::builder().uri("/sessions/test/screen?format=plain").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(json["total_lines"].as_u64().unwrap() > 24);
+    assert_eq!(json["lines"][22], "saved 29");
+    // Nothing reaches the program.
+    assert!(input_rx.try_recv().is_err());
+
+    let response = app
+        .oneshot(Request::builder().method("POST").uri("/sessions/missing/restore").body(Body::from("x")).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_feed_endpoint_injects_output() {
+    let feed = |app: &axum::Router, uri: &'static str, body: &'static str| {
+        app.clone()
+            .oneshot(Request::builder().method("POST").uri(uri).body(Body::from(body)).unwrap())
+    };
+
+    // Off by default.
+    let (app, _input_rx, _parser_tx) = create_test_app_with_parser();
+    let response = feed(&app, "/sessions/test/feed", "x").await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"]["code"], "feed_disabled");
+
+    let sessions = SessionRegistry::new();
+    let state = AppState {
+        sessions: sessions.clone(),
+        shutdown: ShutdownCoordinator::new(),
+        server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false).with_allow_feed(true)),
+        server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+    };
+    let app = router(state, RouterConfig::default());
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/sessions")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"name": "fed", "command": "sleep 30", "raw_log_bytes": 4096}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let session = sessions.get("fed").unwrap();
+    let mut raw = session.output_rx.subscribe();
+
+    let response = feed(&app, "/sessions/fed/feed", "\x1b[1mvirtual\x1b[0m output\r\n").await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    session.parser.barrier().await.unwrap();
+
+    let response = app
+        .clone()
+        .oneshot(Request::builder().uri("/sessions/fed/screen?format=plain").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["lines"][0], "virtual output");
+    // Raw subscribers and the raw log see it like program output.
+    tokio::time::timeout(Duration::from_secs(2), async {
+        while &raw.recv().await.unwrap()[..] != b"\x1b[1mvirtual\x1b[0m output\r\n" {}
+    })
+    .await
+    .expect("raw subscribers should get the fed bytes");
+    assert!(session.raw_log.snapshot().bytes.ends_with(b"output\r\n"));
+
+    let response = feed(&app, "/sessions/missing/feed", "x").await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    session.force_kill();
+}
+
+#[tokio::test]
+async fn test_keys_follow_cursor_key_mode() {
+    let (app, mut input_rx, parser_tx) = create_test_app_with_parser();
+
+    let send_keys = |keys: serde_json::Value| {
+        app.clone().oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/sessions/test/input/keys")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::json!({ "keys": keys }).to_string()))
+                .unwrap(),
+        )
+    };
+
+    // Normal cursor-key mode: CSI sequences.
+    let response = send_keys(serde_json::json!(["Home", "PageUp", "Backspace"]))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["cursor_key_app_mode"], false);
+    assert_eq!(json["keys"][0]["bytes"], serde_json::json!([0x1b, b'[', b'H']));
+    let received = tokio::time::timeout(Duration::from_secs(1), input_rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(received.as_ref(), b"\x1b[H\x1b[5~\x7f");
+
+    // The program enables application cursor keys (DECCKM).
+    parser_tx.send(Bytes::from("\x1b[?1h")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let response = send_keys(serde_json::json!(["Home", "End"])).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let received = tokio::time::timeout(Duration::from_secs(1), input_rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(received.as_ref(), b"\x1bOH\x1bOF");
+
+    // Unknown keys are rejected and nothing is sent.
+    let response = send_keys(serde_json::json!(["Home", "Hyper"])).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(input_rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_expect_endpoint_resolves_on_pattern() {
+    let (app, _input_rx, output_tx) = create_test_app();
+
+    let request = tokio::spawn(app.clone().oneshot(
+        Request::builder()
+            .uri("/sessions/test/expect?pattern=ready%3E&timeout_ms=5000")
+            .body(Body::empty())
+            .unwrap(),
+    ));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    output_tx.send(Bytes::from("booting...\r\nrea")).unwrap();
+    output_tx.send(Bytes::from("dy> ")).unwrap();
+
+    let response = request.await.unwrap().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["matched"], "ready>");
+    assert_eq!(json["output"], "booting...\r\nready>");
+
+    // No matching output -> 408.
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/sessions/test/expect?pattern=nope&timeout_ms=50")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+}
+
+#[tokio::test]
+async fn test_last_output_follows_osc133_marks() {
+    let (app, _input_rx, parser_tx) = create_test_app_with_parser();
+
+    let get_last_output = || async {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/sessions/test/last-output")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        (status, json)
+    };
+
+    // Without shell integration there are no boundaries to report.
+    parser_tx.send(Bytes::from("$ ls\r\nfile\r\n$ ")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let (status, json) = get_last_output().await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(json["error"]["code"], "no_command_history");
+
+    // A command that has started but not finished.
+    parser_tx
+        .send(Bytes::from(
+            "\x1b]133;A\x07$ \x1b]133;B\x07make\r\n\x1b]133;C\x07building\r\n",
+        ))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let (status, json) = get_last_output().await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["command_text"], "make");
+    assert_eq!(json["running"], true);
+    assert_eq!(json["exit_code"], serde_json::Value::Null);
+    assert_eq!(json["output"], "building");
+
+    // It finishes and the next prompt appears.
+    parser_tx
+        .send(Bytes::from(
+            "error: no rule\r\n\x1b]133;D;2\x07\x1b]133;A\x07$ \x1b]133;B\x07",
+        ))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let (status, json) = get_last_output().await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["running"], false);
+    assert_eq!(json["exit_code"], 2);
+    assert_eq!(json["output"], "building\nerror: no rule");
+    assert_eq!(json["truncated"], false);
+}
+
+#[tokio::test]
+async fn test_cursor_endpoint() {
+    let (app, _input_rx, parser_tx) = create_test_app_with_parser();
+
+    parser_tx.send(Bytes::from("hello\r\nab\x1b[?25l")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let response = app
+        .clone()
+        .oneshot(Request::builder().uri("/sessions/test/cursor").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json, serde_json::json!({"row": 1, "col": 2, "visible": false}));
+
+    let response = app
+        .oneshot(Request::builder().uri("/sessions/missing/cursor").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_dump_combines_screen_scrollback_and_metadata() {
+    let (app, _input_rx, parser_tx) = create_test_app_with_parser();
+
+    let mut output = String::from("\x1b]2;build logs\x07\x1b]7;file://host/tmp/my%20project\x07");
+    for i in 0..30 {
+        output.push_str(&format!("line{}\r\n", i));
+    }
+    parser_tx.send(Bytes::from(output)).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/sessions/test/dump?format=plain&scrollback_limit=3")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(json["screen"]["lines"].as_array().unwrap().len(), 24);
+    assert_eq!(json["screen"]["lines"][0], "line7");
+    assert_eq!(json["screen"]["cursor"]["row"], 23);
+    // 7 lines sit above the screen; only the 3 most recent are returned.
+    assert_eq!(json["scrollback"]["total_lines"], 7);
+    assert_eq!(json["scrollback"]["offset"], 4);
+    assert_eq!(
+        json["scrollback"]["lines"],
+        serde_json::json!(["line4", "line5", "line6"])
+    );
+    assert_eq!(json["title"], "build logs");
+    assert_eq!(json["cwd"], "/tmp/my project");
+    assert_eq!(json["cursor_key_app_mode"], false);
+    assert_eq!(json["input_mode"], "passthrough");
+}
+
+#[tokio::test]
+async fn test_full_text_joins_scrollback_and_screen() {
+    let (app, _input_rx, parser_tx) = create_test_app_with_parser();
+
+    let output: String = (0..30).map(|i| format!("line{}\r\n", i)).collect();
+    parser_tx.send(Bytes::from(output)).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/sessions/test/text?format=plain")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    let expected: Vec<String> = (0..30).map(|i| format!("line{}", i)).chain([String::new()]).collect();
+    assert_eq!(json["text"], expected.join("\n"));
+    assert_eq!(json["total_lines"], 31);
+    assert_eq!(json["first_line_index"], 7);
+    assert_eq!(json["offset"], 0);
+    assert_eq!(json["truncated"], false);
+}
+
+/// `from_end` pages scrollback back from the newest line.
+#[tokio::test]
+async fn test_scrollback_from_end() {
+    let (app, _input_rx, parser_tx) = create_test_app_with_parser();
+    for i in 0..30 {
+        parser_tx.send(Bytes::from(format!("Line {}\r\n", i))).await.unwrap();
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // Skip the (blank) line the cursor sits on, then take two.
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/sessions/test/scrollback?format=plain&from_end=true&offset=1&limit=2")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(json["total_lines"], 31);
+    assert_eq!(json["offset"], 28);
+    assert_eq!(json["lines"], serde_json::json!(["Line 28", "Line 29"]));
+}
+
+/// A single line can be read by its absolute index.
+#[tokio::test]
+async fn test_scrollback_line_by_index() {
+    let (app, _input_rx, parser_tx) = create_test_app_with_parser();
+    for i in 0..30 {
+        parser_tx.send(Bytes::from(format!("Line {}\r\n", i))).await.unwrap();
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/sessions/test/scrollback/3?format=plain")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["index"], 3);
+    assert_eq!(json["total_lines"], 31);
+    assert_eq!(json["line"], "Line 3");
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/sessions/test/scrollback/31")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"]["code"], "invalid_request");
+}
+
+/// `?format=` sets the format of subscriptions that don't name one, and an
+/// unknown format is refused before the upgrade.
+#[tokio::test]
+async fn test_ws_json_format_query_sets_subscription_default() {
+    let (app, _input_rx, parser_tx) = create_test_app_with_parser();
+    let addr = start_test_server(app).await;
+    parser_tx.send(Bytes::from("hello\r\n")).await.unwrap();
+
+    match connect_async(format!("ws://{}/sessions/test/ws/json?format=html", addr)).await {
+        Err(tokio_tungstenite::tungstenite::Error::Http(response)) => {
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+        Err(e) => panic!("expected HTTP 400, got {e:?}"),
+        Ok(_) => panic!("upgrade should be refused for an unknown format"),
+    }
+
+    let (mut ws_stream, _) = connect_async(format!("ws://{}/sessions/test/ws/json?format=plain", addr))
+        .await
+        .expect("Failed to connect WebSocket");
+    let _ = ws_stream.next().await; // connected
+    let subscribe_msg = serde_json::json!({"method": "subscribe", "params": {"events": ["lines"]}});
+    ws_stream.send(Message::Text(subscribe_msg.to_string().into())).await.unwrap();
+    let _ = ws_stream.next().await; // subscribe response
+
+    let Some(Ok(Message::Text(text))) = ws_stream.next().await else {
+        panic!("expected a sync event");
+    };
+    let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(json["event"], "sync");
+    assert_eq!(json["screen"]["lines"][0], "hello", "sync: {json}");
+}
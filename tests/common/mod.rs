@@ -43,6 +43,7 @@ pub fn create_test_session_with_size(name: &str, rows: u16, cols: u16) -> TestSe
         child_exited: Arc::new(std::sync::atomic::AtomicBool::new(false)),
         input_tx,
         output_rx: broker.sender(),
+        output_mirror: broker.mirror(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -60,6 +61,13 @@ pub fn create_test_session_with_size(name: &str, rows: u16, cols: u16) -> TestSe
         visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
         screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
         cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
     };
     TestSession {
         session,
@@ -76,6 +76,7 @@ async fn test_concurrent_input_from_multiple_sources() {
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
         input_tx: input_tx.clone(),
         output_rx: broker.sender(),
+        output_mirror: broker.mirror(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -90,6 +91,13 @@ async fn test_concurrent_input_from_multiple_sources() {
         visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
         screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
         cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
@@ -242,6 +250,7 @@ async fn test_rapid_http_requests() {
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
         input_tx: input_tx.clone(),
         output_rx: broker.sender(),
+        output_mirror: broker.mirror(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -256,6 +265,13 @@ async fn test_rapid_http_requests() {
         visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
         screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
         cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
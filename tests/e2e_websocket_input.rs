@@ -78,6 +78,7 @@ async fn test_websocket_input_reaches_pty_and_output_returns() {
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
         input_tx: input_tx.clone(),
         output_rx: broker.sender(),
+        output_mirror: broker.mirror(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -92,6 +93,13 @@ async fn test_websocket_input_reaches_pty_and_output_returns() {
         visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
         screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
         cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
@@ -224,6 +232,7 @@ async fn test_websocket_text_input_reaches_pty() {
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
         input_tx: input_tx.clone(),
         output_rx: broker.sender(),
+        output_mirror: broker.mirror(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -238,6 +247,13 @@ async fn test_websocket_text_input_reaches_pty() {
         visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
         screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
         cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
@@ -0,0 +1,109 @@
+//! Integration tests for the typed HTTP/WebSocket client
+//! (`wsh::client::http`), run against an in-process server.
+#![cfg(feature = "http-client")]
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures::StreamExt;
+use tokio::net::TcpListener;
+use wsh::api::{router, AppState, RouterConfig};
+use wsh::client::http::{Client, Error};
+use wsh::parser::events::{Event, EventType};
+use wsh::parser::state::{FormattedLine, Format};
+use wsh::session::SessionRegistry;
+use wsh::shutdown::ShutdownCoordinator;
+
+async fn start_server() -> SocketAddr {
+    let state = AppState {
+        sessions: SessionRegistry::new(),
+        shutdown: ShutdownCoordinator::new(),
+        server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)),
+        server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+    };
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, router(state, RouterConfig::default())).await.unwrap();
+    });
+    addr
+}
+
+async fn create_session(addr: SocketAddr, name: &str, command: &str) {
+    let resp = reqwest::Client::new()
+        .post(format!("http://{}/sessions", addr))
+        .json(&serde_json::json!({ "name": name, "command": command }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+}
+
+#[tokio::test]
+async fn test_client_reads_screen_and_scrollback() {
+    let addr = start_server().await;
+    create_session(addr, "reader", "printf 'one\\ntwo\\n'; sleep 30").await;
+    let client = Client::connect(&format!("http://{}", addr)).await.unwrap();
+
+    let screen = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let screen = client.screen("reader", Format::Plain).await.unwrap();
+            if screen.lines.first().map(FormattedLine::text).as_deref() == Some("one") {
+                return screen;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("output should reach the screen");
+    assert_eq!(screen.lines[1].text(), "two");
+
+    let scrollback = client.scrollback("reader", Format::Styled, 0, 1).await.unwrap();
+    assert_eq!(scrollback.offset, 0);
+    assert!(scrollback.lines.len() <= 1);
+
+    client.send_keys("reader", &["Enter"]).await.unwrap();
+    match client.send_keys("reader", &["NoSuchKey"]).await {
+        Err(Error::Api { status: 400, code, .. }) => assert_eq!(code, "invalid_request"),
+        other => panic!("expected invalid_request, got {:?}", other),
+    }
+    match client.screen("missing", Format::Plain).await {
+        Err(e @ Error::Api { .. }) => assert_eq!(e.status(), Some(reqwest::StatusCode::NOT_FOUND)),
+        other => panic!("expected session_not_found, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_client_subscribes_to_events() {
+    let addr = start_server().await;
+    create_session(addr, "events", "sleep 0.3; echo later; sleep 30").await;
+    let client = Client::connect(&format!("http://{}", addr)).await.unwrap();
+
+    let mut events = client
+        .subscribe_events("events", &[EventType::Lines], Format::Plain)
+        .await
+        .unwrap();
+    match events.next().await {
+        Some(Ok(Event::Sync { screen, .. })) => assert_eq!(screen.rows, 24),
+        other => panic!("expected initial sync, got {:?}", other),
+    }
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(event) = events.next().await {
+            if let Event::Line { line, .. } = event.unwrap() {
+                if line.text() == "later" {
+                    return;
+                }
+            }
+        }
+        panic!("event stream ended early");
+    })
+    .await
+    .expect("should see the line event");
+}
+
+#[tokio::test]
+async fn test_client_rejects_bad_urls() {
+    assert!(matches!(Client::connect("not a url").await, Err(Error::InvalidUrl(_))));
+    assert!(matches!(Client::connect("ftp://127.0.0.1/").await, Err(Error::InvalidUrl(_))));
+}
@@ -5,6 +5,7 @@
 //! - WebSocket await_idle method
 //! - Subscription idle_timeout_ms parameter
 //! - Activity tracking from input sources
+//! - HTTP GET /wait-prompt (quiescence plus prompt regex)
 
 use bytes::Bytes;
 use std::net::SocketAddr;
@@ -38,6 +39,7 @@ fn create_test_state() -> (api::AppState, mpsc::Receiver<Bytes>, ActivityTracker
         child_exited: Arc::new(std::sync::atomic::AtomicBool::new(false)),
         input_tx,
         output_rx: broker.sender(),
+        output_mirror: broker.mirror(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -55,6 +57,13 @@ fn create_test_state() -> (api::AppState, mpsc::Receiver<Bytes>, ActivityTracker
         visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
         screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
         cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
@@ -177,6 +186,65 @@ async fn test_http_idle_returns_immediately_when_already_idle() {
     );
 }
 
+// ---------------------------------------------------------------------------
+// HTTP /wait-prompt tests
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_wait_prompt_requires_a_pattern() {
+    let (state, _rx, _activity, _parser_tx) = create_test_state();
+    let app = api::router(state, api::RouterConfig::default());
+    let addr = start_server(app).await;
+
+    let (status, json) = http_get(addr, "/sessions/test/wait-prompt?timeout_ms=200").await;
+    assert_eq!(status, 400);
+    assert_eq!(json["error"]["code"], "invalid_request");
+}
+
+#[tokio::test]
+async fn test_wait_prompt_waits_past_quiet_output_for_the_prompt() {
+    let (state, _rx, activity, parser_tx) = create_test_state();
+    state.sessions.get("test").unwrap().prompt_pattern.set(r"\$ $").unwrap();
+    let app = api::router(state, api::RouterConfig::default());
+    let addr = start_server(app).await;
+
+    // A slow command: output, then silence without a prompt.
+    parser_tx.send(Bytes::from("$ make\r\nbuilding...\r\n")).await.unwrap();
+    activity.touch();
+
+    let request = tokio::spawn(async move {
+        http_get(addr, "/sessions/test/wait-prompt?timeout_ms=5000&idle_ms=100&format=plain").await
+    });
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    assert!(!request.is_finished(), "quiet screen without a prompt must not match");
+
+    parser_tx.send(Bytes::from("done\r\nuser@host:~$ ")).await.unwrap();
+    activity.touch();
+
+    let (status, json) = request.await.unwrap();
+    assert_eq!(status, 200);
+    assert_eq!(json["matched"], "$ ");
+    assert_eq!(json["line"], "user@host:~$ ");
+    assert!(json["generation"].is_u64());
+    assert!(json["screen"]["lines"].is_array());
+}
+
+#[tokio::test]
+async fn test_wait_prompt_times_out_without_a_match() {
+    let (state, _rx, _activity, _parser_tx) = create_test_state();
+    let app = api::router(state, api::RouterConfig::default());
+    let addr = start_server(app).await;
+
+    // `pattern` overrides the session setting; `>>> $` never appears.
+    let (status, json) = http_get(
+        addr,
+        "/sessions/test/wait-prompt?timeout_ms=300&idle_ms=50&pattern=%3E%3E%3E%20%24",
+    )
+    .await;
+    assert_eq!(status, 408);
+    assert_eq!(json["error"]["code"], "prompt_timeout");
+}
+
 // ---------------------------------------------------------------------------
 // HTTP /idle activity tracking tests
 // ---------------------------------------------------------------------------
@@ -789,6 +857,7 @@ fn create_multi_session_state() -> (api::AppState, ActivityTracker, ActivityTrac
             child_exited: Arc::new(std::sync::atomic::AtomicBool::new(false)),
             input_tx,
             output_rx: broker.sender(),
+            output_mirror: broker.mirror(),
             shutdown: ShutdownCoordinator::new(),
             parser,
             overlays: OverlayStore::new(),
@@ -806,6 +875,13 @@ fn create_multi_session_state() -> (api::AppState, ActivityTracker, ActivityTrac
             visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
             screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
             cancelled: tokio_util::sync::CancellationToken::new(),
+            io_counters: wsh::session::IoCounters::default(),
+            prompt_pattern: wsh::session::PromptPattern::default(),
+            raw_log: wsh::broker::RawLog::default(),
+            logs: wsh::session_log::SessionLog::default(),
+            read_pause: wsh::session::ReadPause::default(),
+            output_ready: wsh::session::OutputReady::default(),
+            output_feed: wsh::session::OutputFeed::default(),
         };
         (session, activity, parser_tx)
     };
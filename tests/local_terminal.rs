@@ -0,0 +1,118 @@
+//! Tests for the local-terminal path of `wsh` / `wsh attach`: keystrokes
+//! read from stdin under raw mode, and Ctrl+\ double-tap detach. Runs the
+//! real stdin reader over a pipe with a simulated raw-mode guard instead of
+//! a tty.
+#![cfg(feature = "test-support")]
+
+use std::io::Write;
+use std::os::unix::io::{FromRawFd, OwnedFd};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use tempfile::TempDir;
+
+use wsh::client::Client;
+use wsh::parser::state::{Format, FormattedLine, Query, QueryResponse};
+use wsh::protocol::*;
+use wsh::session::SessionRegistry;
+use wsh::terminal::RawModeGuard;
+
+async fn start_test_server() -> (PathBuf, SessionRegistry) {
+    let sessions = SessionRegistry::new();
+    let dir = TempDir::new().unwrap();
+    let socket_path = dir.path().join("test.sock");
+    std::mem::forget(dir);
+    let path = socket_path.clone();
+    let sessions_clone = sessions.clone();
+
+    tokio::spawn(async move {
+        let cancel = tokio_util::sync::CancellationToken::new();
+        let shutdown_request = wsh::shutdown::ShutdownRequest::new();
+        wsh::server::serve(sessions_clone, &socket_path, cancel, None, shutdown_request)
+            .await
+            .unwrap();
+    });
+
+    for _ in 0..50 {
+        if path.exists() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(path.exists(), "server socket should exist at {:?}", path);
+
+    (path, sessions)
+}
+
+/// A pipe standing in for the terminal's stdin: (read end, write end).
+fn stdin_pipe() -> (std::fs::File, std::fs::File) {
+    let mut fds = [0i32; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+    (read.into(), write.into())
+}
+
+#[tokio::test]
+async fn test_stdin_reaches_session_and_ctrl_backslash_detaches() {
+    let (path, sessions) = start_test_server().await;
+    let mut client = Client::connect(&path).await.unwrap();
+    client
+        .create_session(CreateSessionMsg {
+            name: Some("local".to_string()),
+            command: Some("cat".to_string()),
+            cwd: None,
+            env: None,
+            rows: 24,
+            cols: 80,
+            tags: vec![],
+        })
+        .await
+        .unwrap();
+    let session = sessions.get("local").unwrap();
+
+    let raw_guard = RawModeGuard::simulated();
+    assert!(RawModeGuard::is_enabled());
+    let (stdin, mut keyboard) = stdin_pipe();
+    let streaming = tokio::spawn(client.run_streaming_with(stdin, std::io::sink()));
+
+    keyboard.write_all(b"typed locally\r").unwrap();
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    loop {
+        let screen = session.parser.query(Query::Screen { format: Format::Plain }).await.unwrap();
+        let QueryResponse::Screen(screen) = screen else {
+            panic!("expected Screen response");
+        };
+        if screen
+            .lines
+            .iter()
+            .any(|line| matches!(line, FormattedLine::Plain(text) if text.contains("typed locally")))
+        {
+            break;
+        }
+        assert!(tokio::time::Instant::now() < deadline, "stdin never reached the session");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    // Two separate Ctrl+\ reads within the double-tap window detach.
+    keyboard.write_all(&[0x1c]).unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    keyboard.write_all(&[0x1c]).unwrap();
+    tokio::time::timeout(Duration::from_secs(5), streaming)
+        .await
+        .expect("streaming should end on detach")
+        .unwrap()
+        .unwrap();
+
+    // Detaching leaves the session running and the terminal restored.
+    assert!(RawModeGuard::is_enabled());
+    drop(raw_guard);
+    assert!(!RawModeGuard::is_enabled());
+    assert!(sessions.get("local").is_some(), "session should survive detach");
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
+    while session.clients() > 0 {
+        assert!(tokio::time::Instant::now() < deadline, "client never disconnected");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    std::fs::remove_file(&path).ok();
+}
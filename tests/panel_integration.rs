@@ -733,3 +733,318 @@ async fn test_panel_region_write() {
     assert_eq!(writes[1]["text"], "Row 3");
     assert_eq!(writes[1]["bold"], true);
 }
+
+#[tokio::test]
+async fn test_layout_reports_panel_rectangles() {
+    let state = create_test_state_with_size(10, 40);
+    let app = router(state.clone(), RouterConfig::default());
+
+    for (position, height, z) in [("top", 2, 0), ("bottom", 9, 0)] {
+        let body = serde_json::json!({"position": position, "height": height, "z": z});
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/sessions/test/panel")
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_string(&body).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+
+    let get_layout = || async {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/sessions/test/layout")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        json_body(response).await
+    };
+
+    // The 9-row bottom panel does not fit alongside the top panel.
+    let json = get_layout().await;
+    assert_eq!(json["rows"], 10);
+    assert_eq!(json["cols"], 40);
+    assert_eq!(
+        json["pty"],
+        serde_json::json!({"row": 2, "col": 0, "width": 40, "height": 8})
+    );
+    assert_eq!(json["panels"].as_array().unwrap().len(), 1);
+    assert_eq!(json["panels"][0]["position"], "top");
+    assert_eq!(json["panels"][0]["row"], 0);
+    assert_eq!(json["panels"][0]["height"], 2);
+    assert_eq!(json["hidden_panels"].as_array().unwrap().len(), 1);
+
+    // After the outer terminal grows, both panels fit.
+    let session = state.sessions.get("test").unwrap();
+    session.terminal_size.set(20, 60);
+    let json = get_layout().await;
+    assert_eq!(json["cols"], 60);
+    assert_eq!(
+        json["pty"],
+        serde_json::json!({"row": 2, "col": 0, "width": 60, "height": 9})
+    );
+    assert_eq!(json["panels"][1]["position"], "bottom");
+    assert_eq!(json["panels"][1]["row"], 11);
+    assert_eq!(json["panels"][1]["width"], 60);
+    assert_eq!(json["hidden_panels"], serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn test_panel_focus_not_found_and_not_focusable() {
+    let state = create_test_state();
+    let app = router(state, RouterConfig::default());
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/sessions/test/panel/missing/focus")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(json_body(response).await["error"]["code"], "panel_not_found");
+
+    let body = serde_json::json!({"position": "top", "height": 1});
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/sessions/test/panel")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_string(&body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let id = json_body(response).await["id"].as_str().unwrap().to_string();
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/sessions/test/panel/{}/focus", id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(json_body(response).await["error"]["code"], "not_focusable");
+}
+
+#[tokio::test]
+async fn test_input_routed_to_focused_command_panel() {
+    let (state, mut input_rx, _, _ptx) = common::create_test_state_with_size(24, 80);
+    let app = router(state.clone(), RouterConfig::default());
+
+    let body = serde_json::json!({"position": "bottom", "height": 3, "command": "cat"});
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/sessions/test/panel")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_string(&body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let id = json_body(response).await["id"].as_str().unwrap().to_string();
+
+    let post = |uri: String, body: Body| {
+        let app = app.clone();
+        async move {
+            app.oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(uri)
+                    .body(body)
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status()
+        }
+    };
+
+    assert_eq!(
+        post(format!("/sessions/test/panel/{}/focus", id), Body::empty()).await,
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(
+        post("/sessions/test/input".to_string(), Body::from("to-panel\n")).await,
+        StatusCode::NO_CONTENT
+    );
+
+    let session = state.sessions.get("test").unwrap();
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+    loop {
+        let text: String = session
+            .panels
+            .get(&id)
+            .unwrap()
+            .spans
+            .iter()
+            .map(|s| s.text.as_str())
+            .collect();
+        if text.contains("to-panel") {
+            break;
+        }
+        assert!(tokio::time::Instant::now() < deadline, "panel never showed input: {text:?}");
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert!(input_rx.try_recv().is_err(), "input also reached the session PTY");
+
+    // Without focus, input goes back to the session.
+    assert_eq!(
+        post("/sessions/test/input/unfocus".to_string(), Body::empty()).await,
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(
+        post("/sessions/test/input".to_string(), Body::from("to-session")).await,
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(input_rx.recv().await.unwrap(), "to-session");
+}
+
+#[tokio::test]
+async fn test_keys_routed_to_focused_command_panel() {
+    let (state, mut input_rx, _, _ptx) = common::create_test_state_with_size(24, 80);
+    let app = router(state.clone(), RouterConfig::default());
+
+    let body = serde_json::json!({"position": "bottom", "height": 3, "command": "cat"});
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/sessions/test/panel")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_string(&body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let id = json_body(response).await["id"].as_str().unwrap().to_string();
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/sessions/test/panel/{}/focus", id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/sessions/test/input/keys")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"keys": ["Home", "Enter"]}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The panel's PTY echoes the escape as `^[`.
+    let session = state.sessions.get("test").unwrap();
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+    loop {
+        let text: String = session
+            .panels
+            .get(&id)
+            .unwrap()
+            .spans
+            .iter()
+            .map(|s| s.text.as_str())
+            .collect();
+        if text.contains("^[[H") {
+            break;
+        }
+        assert!(tokio::time::Instant::now() < deadline, "panel never showed keys: {text:?}");
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert!(input_rx.try_recv().is_err(), "keys also reached the session PTY");
+}
+
+#[tokio::test]
+async fn test_panel_command_output_fills_panel() {
+    let state = create_test_state_with_size(24, 50);
+    let app = router(state, RouterConfig::default());
+
+    let create_body = serde_json::json!({
+        "position": "bottom",
+        "height": 2,
+        "command": "stty size; sleep 5"
+    });
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/sessions/test/panel")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_string(&create_body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let panel_id = json_body(response).await["id"].as_str().unwrap().to_string();
+
+    // The command runs in a PTY sized to the panel: 2 rows x terminal width.
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+    loop {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/sessions/test/panel/{}", panel_id))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let json = json_body(response).await;
+        assert_eq!(json["command"], "stty size; sleep 5");
+        let text: String = json["spans"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["text"].as_str().unwrap())
+            .collect();
+        if text.starts_with("2 50") {
+            break;
+        }
+        assert!(tokio::time::Instant::now() < deadline, "panel never filled: {text:?}");
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+}
@@ -9,6 +9,8 @@
 //! - Session isolation (input to one session does not affect another)
 //! - Error cases (duplicate names, nonexistent sessions)
 //! - Per-session endpoints work after creation
+//! - Raw output log export
+//! - Waiting for a new session to be ready
 
 use std::net::SocketAddr;
 use std::time::Duration;
@@ -477,3 +479,441 @@ async fn test_per_session_endpoints_work_after_create() {
     let body: Vec<serde_json::Value> = resp.json().await.unwrap();
     assert!(body.is_empty(), "Panel list should be empty initially");
 }
+
+// ── Test 10: Server default size applies when dimensions are omitted ──
+
+#[tokio::test]
+async fn test_create_session_uses_server_default_size() {
+    let state = AppState {
+        sessions: SessionRegistry::new(),
+        shutdown: ShutdownCoordinator::new(),
+        server_config: std::sync::Arc::new(
+            wsh::api::ServerConfig::new(false).with_default_size(40, 120),
+        ),
+        server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+    };
+    let addr = start_test_server(router(state, RouterConfig::default())).await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(format!("http://{}/sessions", addr))
+        .json(&serde_json::json!({"name": "sized"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["rows"], 40);
+    assert_eq!(body["cols"], 120);
+
+    // Explicit dimensions still win.
+    let resp = client
+        .post(format!("http://{}/sessions", addr))
+        .json(&serde_json::json!({"name": "explicit", "rows": 30, "cols": 100}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["rows"], 30);
+    assert_eq!(body["cols"], 100);
+}
+
+#[tokio::test]
+async fn test_create_session_with_latin1_decoding() {
+    let addr = start_test_server(create_empty_test_app()).await;
+    let client = reqwest::Client::new();
+
+    // printf emits the raw latin1 byte 0xE9 ('é'), which isn't valid UTF-8.
+    let resp = client
+        .post(format!("http://{}/sessions", addr))
+        .json(&serde_json::json!({
+            "name": "legacy",
+            "command": "printf 'caf\\351\\n'; sleep 5",
+            "decoding": "latin1",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let mut screen = String::new();
+    for _ in 0..40 {
+        let body: serde_json::Value = client
+            .get(format!("http://{}/sessions/legacy/screen?format=plain", addr))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        screen = body["lines"][0].as_str().unwrap_or_default().to_string();
+        if screen.contains("caf") {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(screen, "café");
+
+    let resp = client
+        .post(format!("http://{}/sessions", addr))
+        .json(&serde_json::json!({"name": "bad", "decoding": "ebcdic"}))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_client_error());
+}
+
+#[tokio::test]
+async fn test_create_session_rejects_unsafe_names() {
+    let addr = start_test_server(create_empty_test_app()).await;
+    let client = reqwest::Client::new();
+
+    let too_long = "a".repeat(65);
+    for name in ["foo/bar", "", too_long.as_str(), "has space", "..", "caf\u{e9}"] {
+        let resp = client
+            .post(format!("http://{}/sessions", addr))
+            .json(&serde_json::json!({"name": name}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 400, "name {name:?} should be rejected");
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "invalid_session_name");
+    }
+
+    let resp = client
+        .post(format!("http://{}/sessions", addr))
+        .json(&serde_json::json!({"name": "build_2-x.y"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+}
+
+#[tokio::test]
+async fn test_create_session_without_reflow_on_resize() {
+    use futures::{SinkExt, StreamExt};
+
+    let addr = start_test_server(create_empty_test_app()).await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(format!("http://{}/sessions", addr))
+        .json(&serde_json::json!({
+            "name": "rows",
+            "command": "printf 0123456789abcdef; sleep 5",
+            "cols": 10,
+            "rows": 4,
+            "reflow_on_resize": false,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let screen_lines = || async {
+        let body: serde_json::Value = client
+            .get(format!("http://{}/sessions/rows/screen?format=plain", addr))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        body["lines"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|l| l.as_str().unwrap_or_default().to_string())
+            .collect::<Vec<_>>()
+    };
+    for _ in 0..40 {
+        if screen_lines().await[1] == "abcdef" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/sessions/rows/ws/json", addr))
+        .await
+        .unwrap();
+    ws.send(tokio_tungstenite::tungstenite::Message::Text(
+        serde_json::json!({"id": 1, "method": "resize", "params": {"cols": 5, "rows": 4}})
+            .to_string()
+            .into(),
+    ))
+    .await
+    .unwrap();
+    // Wait for the resize response (skipping the connected message).
+    loop {
+        let msg = tokio::time::timeout(Duration::from_secs(2), ws.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_str(msg.to_text().unwrap()).unwrap();
+        if value["method"] == "resize" {
+            break;
+        }
+    }
+
+    // Each row was cut at the new width instead of being re-wrapped.
+    let lines = screen_lines().await;
+    assert_eq!(&lines[..2], ["01234", "abcde"]);
+}
+
+#[tokio::test]
+async fn test_create_session_with_default_format() {
+    use futures::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+
+    let addr = start_test_server(create_empty_test_app()).await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(format!("http://{}/sessions", addr))
+        .json(&serde_json::json!({
+            "name": "plain",
+            "command": "printf ready; sleep 5",
+            "default_format": "plain",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["default_format"], "plain");
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/sessions/plain/ws/json", addr))
+        .await
+        .unwrap();
+    let mut sync_for = async |params: serde_json::Value| {
+        ws.send(Message::Text(
+            serde_json::json!({"id": 1, "method": "subscribe", "params": params})
+                .to_string()
+                .into(),
+        ))
+        .await
+        .unwrap();
+        loop {
+            let msg = tokio::time::timeout(Duration::from_secs(2), ws.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            let value: serde_json::Value = serde_json::from_str(msg.to_text().unwrap()).unwrap();
+            if value["event"] == "sync" {
+                return value;
+            }
+        }
+    };
+
+    // No format named: the session's default applies.
+    let sync = sync_for(serde_json::json!({"events": ["lines"]})).await;
+    assert!(sync["screen"]["lines"][0].is_string());
+
+    // A subscriber's own format still wins.
+    let sync = sync_for(serde_json::json!({"events": ["lines"], "format": "styled"})).await;
+    assert!(sync["screen"]["lines"][0].is_array());
+}
+
+#[tokio::test]
+async fn test_session_info_counts_pty_bytes() {
+    let addr = start_test_server(create_empty_test_app()).await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(format!("http://{}/sessions", addr))
+        .json(&serde_json::json!({"name": "meter", "command": "cat"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["bytes_written"], 0);
+    assert_eq!(body["output_pressure"], 0.0);
+
+    let resp = client
+        .post(format!("http://{}/sessions/meter/input", addr))
+        .body("hello\r")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 204);
+
+    // cat echoes the input back, so output grows too.
+    let mut info = serde_json::Value::Null;
+    for _ in 0..40 {
+        info = client
+            .get(format!("http://{}/sessions/meter", addr))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if info["bytes_written"] == 6 && info["bytes_read"].as_u64().unwrap_or(0) >= 6 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(info["bytes_written"], 6);
+    assert!(info["bytes_read"].as_u64().unwrap() >= 6, "info: {info}");
+}
+
+#[tokio::test]
+async fn test_session_info_reports_journal_range() {
+    let addr = start_test_server(create_empty_test_app()).await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(format!("http://{}/sessions", addr))
+        .json(&serde_json::json!({
+            "name": "journal",
+            "command": "printf 'one\\ntwo\\nthree\\n'; sleep 5",
+            "journal_events": 2,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["journal"]["capacity"], 2);
+    assert_eq!(body["journal"]["misses"], 0);
+
+    // Only the two newest events are kept.
+    let mut info = serde_json::Value::Null;
+    for _ in 0..40 {
+        info = client
+            .get(format!("http://{}/sessions/journal", addr))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if info["journal"]["events"] == 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let journal = &info["journal"];
+    assert_eq!(journal["events"], 2, "info: {info}");
+    assert_eq!(journal["oldest_seq"].as_u64().unwrap() + 1, journal["newest_seq"].as_u64().unwrap());
+
+    let info: serde_json::Value = client
+        .patch(format!("http://{}/sessions/journal", addr))
+        .json(&serde_json::json!({"journal_events": 1, "journal_bytes": 4096}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let journal = &info["journal"];
+    assert_eq!((journal["events"].as_u64(), journal["max_bytes"].as_u64()), (Some(1), Some(4096)));
+    assert_eq!(journal["oldest_seq"], journal["newest_seq"]);
+    assert!(journal["bytes"].as_u64().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn test_raw_log_returns_output_bytes_before_interpretation() {
+    let addr = start_test_server(create_empty_test_app()).await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(format!("http://{}/sessions", addr))
+        .json(&serde_json::json!({
+            "name": "raw",
+            "command": "printf 'head \\033[31mred\\033[0m tail'; sleep 5",
+            "raw_log_bytes": 12,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["raw_log_bytes"], 12);
+
+    // Only the newest 12 bytes are kept, escape sequences included.
+    let mut bytes = Vec::new();
+    let mut headers = None;
+    for _ in 0..40 {
+        let resp = client
+            .get(format!("http://{}/sessions/raw/raw-log", addr))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        headers = Some(resp.headers().clone());
+        bytes = resp.bytes().await.unwrap().to_vec();
+        if bytes.ends_with(b"tail") {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(bytes, b"red\x1b[0m tail");
+    let headers = headers.unwrap();
+    assert_eq!(headers["content-type"], "application/octet-stream");
+    let offset: u64 = headers["x-wsh-raw-offset"].to_str().unwrap().parse().unwrap();
+    let total: u64 = headers["x-wsh-raw-total"].to_str().unwrap().parse().unwrap();
+    assert_eq!(offset + bytes.len() as u64, total);
+    assert!(offset > 0);
+
+    // Shrinking the limit drops the oldest bytes.
+    let info: serde_json::Value = client
+        .patch(format!("http://{}/sessions/raw", addr))
+        .json(&serde_json::json!({"raw_log_bytes": 4}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(info["raw_log_bytes"], 4);
+    let bytes = client
+        .get(format!("http://{}/sessions/raw/raw-log", addr))
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    assert_eq!(&bytes[..], b"tail");
+}
+
+// ── Readiness ────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn test_ready_waits_for_output_and_prompt() {
+    let app = create_empty_test_app();
+    let addr = start_test_server(app).await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(format!("http://{}/sessions", addr))
+        .json(&serde_json::json!({
+            "name": "slow",
+            "command": "sleep 0.2; echo banner; sleep 0.3; printf 'ready> '; sleep 30",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // With a prompt pattern, the banner alone isn't enough.
+    let resp = client
+        .get(format!("http://{}/sessions/slow/ready", addr))
+        .query(&[("timeout_ms", "5000"), ("format", "plain"), ("pattern", "ready> $")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["ready"], true);
+    assert_eq!(body["prompt"], "ready> ");
+    assert_eq!(body["screen"]["lines"][0], "banner");
+
+    let _ = client.delete(format!("http://{}/sessions/slow", addr)).send().await;
+}
@@ -31,6 +31,7 @@ fn create_test_state() -> (api::AppState, mpsc::Receiver<Bytes>, mpsc::Sender<By
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
         input_tx,
         output_rx: broker.sender(),
+        output_mirror: broker.mirror(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -45,6 +46,13 @@ fn create_test_state() -> (api::AppState, mpsc::Receiver<Bytes>, mpsc::Sender<By
         visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
         screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
         cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
@@ -101,6 +109,7 @@ async fn test_ws_method_get_input_mode() {
     // Read "connected" message
     let msg = recv_json(&mut rx).await;
     assert_eq!(msg["connected"], true);
+    assert_eq!(msg["protocol_version"], wsh::api::PROTOCOL_VERSION);
 
     // Send method call (no subscribe needed first!)
     tx.send(Message::Text(
@@ -186,6 +195,7 @@ async fn test_ws_subscribe_then_events() {
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
         input_tx,
         output_rx: broker.sender(),
+        output_mirror: broker.mirror(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -200,6 +210,13 @@ async fn test_ws_subscribe_then_events() {
         visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
         screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
         cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
@@ -260,6 +277,58 @@ async fn test_ws_subscribe_then_events() {
     assert!(found_line, "should receive line events after subscribing");
 }
 
+#[tokio::test]
+async fn test_ws_coalesces_progress_line_rewrites() {
+    let (state, _rx, parser_tx) = create_test_state();
+    let app = api::router(state, api::RouterConfig::default());
+    let addr = start_server(app).await;
+
+    let (ws, _) = connect_async(format!("ws://{}/sessions/test/ws/json", addr))
+        .await
+        .unwrap();
+    let (mut tx, mut rx) = ws.split();
+    let _ = recv_json(&mut rx).await; // connected
+
+    tx.send(Message::Text(
+        serde_json::json!({
+            "method": "subscribe",
+            "params": {"events": ["lines"], "format": "plain", "line_coalesce_ms": 300}
+        })
+        .to_string()
+        .into(),
+    ))
+    .await
+    .unwrap();
+    assert_eq!(recv_json(&mut rx).await["method"], "subscribe");
+    assert_eq!(recv_json(&mut rx).await["event"], "sync");
+
+    // A progress bar redrawing one line with \r.
+    for pct in 0..=100 {
+        parser_tx
+            .send(Bytes::from(format!("\rDownloading {pct:3}%")))
+            .await
+            .unwrap();
+    }
+
+    let mut lines = Vec::new();
+    loop {
+        let json = recv_json(&mut rx).await;
+        if json["event"] != "line" || json["index"] != 0 {
+            continue;
+        }
+        let text = json["line"].to_string();
+        lines.push(text.clone());
+        if text.contains("100%") {
+            break;
+        }
+    }
+    assert!(
+        lines.len() < 10,
+        "expected rewrites to be coalesced, got {} line events",
+        lines.len()
+    );
+}
+
 #[tokio::test]
 async fn test_ws_unknown_method() {
     let (state, _rx, _parser_tx) = create_test_state();
@@ -322,6 +391,7 @@ async fn test_ws_methods_interleaved_with_events() {
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
         input_tx,
         output_rx: broker.sender(),
+        output_mirror: broker.mirror(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -336,6 +406,13 @@ async fn test_ws_methods_interleaved_with_events() {
         visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
         screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
         cancelled: tokio_util::sync::CancellationToken::new(),
+        io_counters: wsh::session::IoCounters::default(),
+        prompt_pattern: wsh::session::PromptPattern::default(),
+        raw_log: wsh::broker::RawLog::default(),
+        logs: wsh::session_log::SessionLog::default(),
+        read_pause: wsh::session::ReadPause::default(),
+        output_ready: wsh::session::OutputReady::default(),
+        output_feed: wsh::session::OutputFeed::default(),
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
@@ -404,3 +481,342 @@ async fn test_ws_methods_interleaved_with_events() {
         "should receive method response even while events are streaming"
     );
 }
+
+#[tokio::test]
+async fn test_ws_resume_since_replays_missed_events() {
+    let (state, _rx, parser_tx) = create_test_state();
+    let app = api::router(state, api::RouterConfig::default());
+    let addr = start_server(app).await;
+
+    let subscribe = serde_json::json!({
+        "method": "subscribe",
+        "params": {"events": ["lines", "cursor"], "format": "plain"}
+    })
+    .to_string();
+
+    // First connection: subscribe and note the last seq received.
+    let (ws, _) = connect_async(format!("ws://{}/sessions/test/ws/json", addr))
+        .await
+        .unwrap();
+    let (mut tx, mut rx) = ws.split();
+    let _ = recv_json(&mut rx).await; // connected
+    tx.send(Message::Text(subscribe.clone().into())).await.unwrap();
+    assert_eq!(recv_json(&mut rx).await["method"], "subscribe");
+    assert_eq!(recv_json(&mut rx).await["event"], "sync");
+
+    parser_tx.send(Bytes::from("one\r\n")).await.unwrap();
+    let mut last_seq = 0;
+    while let Ok(Some(Ok(Message::Text(text)))) =
+        tokio::time::timeout(Duration::from_millis(300), rx.next()).await
+    {
+        let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+        last_seq = last_seq.max(json["seq"].as_u64().unwrap_or(0));
+    }
+    assert!(last_seq > 0, "should have received parser events");
+    drop((tx, rx));
+
+    // Output produced while disconnected.
+    parser_tx.send(Bytes::from("two\r\n")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // Reconnect with since: missed events are replayed instead of a sync.
+    let (ws, _) = connect_async(format!(
+        "ws://{}/sessions/test/ws/json?since={}",
+        addr, last_seq
+    ))
+    .await
+    .unwrap();
+    let (mut tx, mut rx) = ws.split();
+    let _ = recv_json(&mut rx).await; // connected
+    tx.send(Message::Text(subscribe.clone().into())).await.unwrap();
+    assert_eq!(recv_json(&mut rx).await["method"], "subscribe");
+
+    let mut replayed_lines = Vec::new();
+    let resumed = loop {
+        let msg = recv_json(&mut rx).await;
+        assert_ne!(msg["event"], "sync", "should not fall back to sync");
+        if msg["type"] == "resumed" {
+            break msg;
+        }
+        assert!(msg["seq"].as_u64().unwrap() > last_seq);
+        if msg["event"] == "line" {
+            replayed_lines.push(msg["line"].clone());
+        }
+    };
+    assert_eq!(resumed["since"], last_seq);
+    // Line events are always styled: [{"text": "two"}].
+    assert!(replayed_lines.contains(&serde_json::json!([{"text": "two"}])));
+
+    // A seq the journal never issued falls back to a full sync.
+    let (ws, _) = connect_async(format!("ws://{}/sessions/test/ws/json?since=999999", addr))
+        .await
+        .unwrap();
+    let (mut tx, mut rx) = ws.split();
+    let _ = recv_json(&mut rx).await; // connected
+    tx.send(Message::Text(subscribe.into())).await.unwrap();
+    assert_eq!(recv_json(&mut rx).await["method"], "subscribe");
+    assert_eq!(recv_json(&mut rx).await["event"], "sync");
+}
+
+#[tokio::test]
+async fn test_ws_msgpack_subprotocol_sends_binary_frames() {
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+    let (state, _rx, parser_tx) = create_test_state();
+    let app = api::router(state, api::RouterConfig::default());
+    let addr = start_server(app).await;
+
+    let mut request = format!("ws://{}/sessions/test/ws/json", addr)
+        .into_client_request()
+        .unwrap();
+    request
+        .headers_mut()
+        .insert("Sec-WebSocket-Protocol", "wsh.msgpack".parse().unwrap());
+    let (ws, response) = connect_async(request).await.unwrap();
+    assert_eq!(
+        response.headers().get("sec-websocket-protocol").unwrap(),
+        "wsh.msgpack"
+    );
+    let (mut tx, mut rx) = ws.split();
+
+    async fn recv_msgpack(
+        rx: &mut futures::stream::SplitStream<
+            tokio_tungstenite::WebSocketStream<
+                tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+            >,
+        >,
+    ) -> serde_json::Value {
+        let msg = tokio::time::timeout(Duration::from_secs(2), rx.next())
+            .await
+            .expect("timed out")
+            .unwrap()
+            .unwrap();
+        match msg {
+            Message::Binary(bytes) => rmp_serde::from_slice(&bytes).unwrap(),
+            other => panic!("expected binary frame, got {:?}", other),
+        }
+    }
+
+    assert_eq!(recv_msgpack(&mut rx).await["connected"], true);
+
+    // Requests stay JSON text; responses and events come back as MessagePack.
+    let subscribe = serde_json::json!({
+        "method": "subscribe",
+        "params": {"events": ["lines"], "format": "plain"}
+    });
+    tx.send(Message::Text(subscribe.to_string().into()))
+        .await
+        .unwrap();
+    assert_eq!(recv_msgpack(&mut rx).await["method"], "subscribe");
+    assert_eq!(recv_msgpack(&mut rx).await["event"], "sync");
+
+    parser_tx.send(Bytes::from("hello\r\n")).await.unwrap();
+    let line = loop {
+        let msg = recv_msgpack(&mut rx).await;
+        if msg["event"] == "line" {
+            break msg;
+        }
+    };
+    assert_eq!(line["index"], 0);
+    assert!(line["seq"].as_u64().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn test_ws_without_subprotocol_defaults_to_json() {
+    let (state, _rx, _parser_tx) = create_test_state();
+    let app = api::router(state, api::RouterConfig::default());
+    let addr = start_server(app).await;
+
+    let (ws, response) = connect_async(format!("ws://{}/sessions/test/ws/json", addr))
+        .await
+        .unwrap();
+    assert!(response.headers().get("sec-websocket-protocol").is_none());
+    let (_tx, mut rx) = ws.split();
+    assert_eq!(recv_json(&mut rx).await["connected"], true);
+}
+
+#[tokio::test]
+async fn test_ws_input_subscriber_sees_mode_change_from_http() {
+    let (state, _rx, _parser_tx) = create_test_state();
+    let app = api::router(state, api::RouterConfig::default());
+    let addr = start_server(app).await;
+
+    let (ws, _) = connect_async(format!("ws://{}/sessions/test/ws/json", addr))
+        .await
+        .unwrap();
+    let (mut tx, mut rx) = ws.split();
+    let _ = recv_json(&mut rx).await; // connected
+
+    tx.send(Message::Text(
+        serde_json::json!({"id": 1, "method": "subscribe", "params": {"events": ["input"]}})
+            .to_string()
+            .into(),
+    ))
+    .await
+    .unwrap();
+    let resp = recv_json(&mut rx).await;
+    assert_eq!(resp["method"], "subscribe");
+
+    // Another client captures input over HTTP.
+    let client = reqwest::Client::new();
+    let resp = client
+        .post(format!("http://{}/sessions/test/input/capture", addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 204);
+
+    // Skip the initial sync pushed on subscribe.
+    let mut event = recv_json(&mut rx).await;
+    while event["event"] == "sync" {
+        event = recv_json(&mut rx).await;
+    }
+    assert_eq!(event["event"], "mode");
+    assert_eq!(event["mode"], "capture");
+
+    client
+        .post(format!("http://{}/sessions/test/input/release", addr))
+        .send()
+        .await
+        .unwrap();
+    let event = recv_json(&mut rx).await;
+    assert_eq!(event["event"], "mode");
+    assert_eq!(event["mode"], "passthrough");
+}
+
+#[tokio::test]
+async fn test_ws_input_subscriber_sees_panel_focus_change() {
+    let (state, _rx, _parser_tx) = create_test_state();
+    let app = api::router(state, api::RouterConfig::default());
+    let addr = start_server(app).await;
+
+    let (ws, _) = connect_async(format!("ws://{}/sessions/test/ws/json", addr))
+        .await
+        .unwrap();
+    let (mut tx, mut rx) = ws.split();
+    let _ = recv_json(&mut rx).await; // connected
+
+    tx.send(Message::Text(
+        serde_json::json!({"id": 1, "method": "subscribe", "params": {"events": ["input"]}})
+            .to_string()
+            .into(),
+    ))
+    .await
+    .unwrap();
+    let resp = recv_json(&mut rx).await;
+    assert_eq!(resp["method"], "subscribe");
+
+    let client = reqwest::Client::new();
+    let resp = client
+        .post(format!("http://{}/sessions/test/panel", addr))
+        .json(&serde_json::json!({"position": "bottom", "height": 1, "focusable": true}))
+        .send()
+        .await
+        .unwrap();
+    let panel_id = resp.json::<serde_json::Value>().await.unwrap()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let resp = client
+        .post(format!("http://{}/sessions/test/panel/{}/focus", addr, panel_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 204);
+
+    // Skip the initial sync and the resize reset from the panel's layout.
+    let mut event = recv_json(&mut rx).await;
+    while event["event"] != "focus" {
+        event = recv_json(&mut rx).await;
+    }
+    assert_eq!(event["focused"], panel_id.as_str());
+
+    // Deleting the focused panel hands input back to the session.
+    client
+        .delete(format!("http://{}/sessions/test/panel/{}", addr, panel_id))
+        .send()
+        .await
+        .unwrap();
+    let mut event = recv_json(&mut rx).await;
+    while event["event"] != "focus" {
+        event = recv_json(&mut rx).await;
+    }
+    assert!(event["focused"].is_null());
+}
+
+#[tokio::test]
+async fn test_ws_subscribe_heartbeat() {
+    let (state, _rx, _parser_tx) = create_test_state();
+    let app = api::router(state, api::RouterConfig::default());
+    let addr = start_server(app).await;
+
+    let (ws, _) = connect_async(format!("ws://{}/sessions/test/ws/json", addr))
+        .await
+        .unwrap();
+    let (mut tx, mut rx) = ws.split();
+    let _ = recv_json(&mut rx).await; // connected
+
+    tx.send(Message::Text(
+        serde_json::json!({"id": 1, "method": "subscribe", "params": {"events": [], "heartbeat_ms": 100}})
+            .to_string()
+            .into(),
+    ))
+    .await
+    .unwrap();
+    let resp = recv_json(&mut rx).await;
+    assert_eq!(resp["method"], "subscribe");
+
+    // Nothing is happening in the session, yet heartbeats keep arriving.
+    let mut beats = Vec::new();
+    while beats.len() < 2 {
+        let event = recv_json(&mut rx).await;
+        if event["event"] == "heartbeat" {
+            beats.push(event);
+        }
+    }
+    for beat in &beats {
+        assert!(beat["seq"].is_u64());
+        assert!(beat["server_time_ms"].as_u64().unwrap() > 0);
+    }
+    assert!(beats[1]["server_time_ms"].as_u64() >= beats[0]["server_time_ms"].as_u64());
+}
+
+#[tokio::test]
+async fn test_ws_subscribe_initial_lines() {
+    let (state, _rx, parser_tx) = create_test_state();
+    let parser = state.sessions.get("test").unwrap().parser.clone();
+    parser_tx.send(Bytes::from("first\r\nsecond")).await.unwrap();
+    parser.barrier().await.unwrap();
+    let app = api::router(state, api::RouterConfig::default());
+    let addr = start_server(app).await;
+
+    let (ws, _) = connect_async(format!("ws://{}/sessions/test/ws/json", addr))
+        .await
+        .unwrap();
+    let (mut tx, mut rx) = ws.split();
+    let _ = recv_json(&mut rx).await; // connected
+
+    tx.send(Message::Text(
+        serde_json::json!({"id": 1, "method": "subscribe", "params": {"events": ["lines"], "format": "plain", "initial_lines": true}})
+            .to_string()
+            .into(),
+    ))
+    .await
+    .unwrap();
+    let resp = recv_json(&mut rx).await;
+    assert_eq!(resp["method"], "subscribe");
+    assert_eq!(recv_json(&mut rx).await["event"], "sync");
+
+    // One line event per screen row, in order, right after the sync.
+    for row in 0..24 {
+        let event = recv_json(&mut rx).await;
+        assert_eq!(event["event"], "line");
+        assert_eq!(event["index"], row);
+        let expected = match row {
+            0 => "first",
+            1 => "second",
+            _ => "",
+        };
+        assert_eq!(event["line"], expected);
+    }
+}
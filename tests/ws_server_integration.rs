@@ -90,6 +90,7 @@ async fn connect_server_ws(
     // Consume the connected message
     let msg = recv_json(&mut rx).await;
     assert_eq!(msg["connected"], true);
+    assert_eq!(msg["protocol_version"], wsh::api::PROTOCOL_VERSION);
 
     (tx, rx)
 }
@@ -760,6 +761,28 @@ async fn test_server_ws_kill_nonexistent() {
     assert_eq!(resp["error"]["code"], "session_not_found");
 }
 
+// ── Test: shutdown close frame ──────────────────────────────────
+
+#[tokio::test]
+async fn test_server_ws_close_frame_carries_retry_after() {
+    let state = create_empty_state();
+    let shutdown = state.shutdown.clone();
+    let app = api::router(state, api::RouterConfig::default());
+    let addr = start_server(app).await;
+
+    let (_tx, mut rx) = connect_server_ws(addr).await;
+    shutdown.shutdown_with_retry_after(Duration::from_millis(1500));
+
+    let frame = loop {
+        match tokio::time::timeout(Duration::from_secs(5), rx.next()).await {
+            Ok(Some(Ok(Message::Close(frame)))) => break frame.expect("close frame should have a reason"),
+            Ok(Some(Ok(_))) => continue,
+            other => panic!("expected close frame, got {:?}", other),
+        }
+    };
+    assert_eq!(frame.reason.as_str(), "server shutting down; retry_after_ms=1500");
+}
+
 // ── Test: malformed request ─────────────────────────────────────
 
 #[tokio::test]
@@ -782,6 +805,7 @@ async fn test_server_ws_malformed_request() {
 // ── Test: subscribe with activity events on server-level WS ────
 
 #[tokio::test]
+#[allow(clippy::single_match)]
 async fn test_server_ws_subscribe_activity_events() {
     let state = create_empty_state();
     let app = api::router(state, api::RouterConfig::default());